@@ -1,4 +1,4 @@
-import init, { get_graph, get_relation, add_relation, append_from_dsl, Sex, Person } from '../kin-wasm/kin'
+import init, { get_graph, load_graph, get_layout, get_relation, add_relation, append_from_dsl, Sex, Person } from '../kin-wasm/kin'
 
 export interface KinWasmPersonNode {
     id: number
@@ -26,11 +26,33 @@ export interface Relation {
     id: number
 
 }
+//where a person is in the family tree, with depth as the generation and width across it
+export interface KinLocation {
+    id: number,
+    depth: number,
+    width: number
+}
+//mirrors RelationKind of the wasm module, whose values are passed to add_relation
 export enum RelationKind {
-    Parent,
-    Child,
-    RP,
-    Sibling
+    Parent = 0,
+    Child = 1,
+    RP = 2,
+    Sibling = 3,
+    AdoptiveParent = 4,
+    AdoptiveChild = 5,
+    FosterParent = 6,
+    FosterChild = 7,
+    Guardian = 8,
+    Ward = 9,
+    Spouse = 10,
+}
+//the kind of a relation from its name in get_graph
+function parse_relation_kind(kind: string): RelationKind {
+    var relation = RelationKind[kind as keyof typeof RelationKind]
+    if (relation == undefined) {
+        throw new Error("Unknown relation kind " + kind)
+    }
+    return relation
 }
 class KinWasmGraph {
     nodes: KinWasmPersonNode[] | undefined
@@ -79,22 +101,7 @@ export class KinGraph {
 
                 var node = kin_wasm_graph.nodes[i]
                 var relations = kin_wasm_graph.nodes[i].relations.map((r) => {
-                    var relation;
-                    switch (r.kind) {
-                        case "Parent":
-                            relation = RelationKind.Parent
-                            break;
-                        case "Child":
-                            relation = RelationKind.Child
-                            break;
-                        case "RP":
-                            relation = RelationKind.RP
-                            break;
-                        case "Sibling":
-                            relation = RelationKind.Sibling
-                            break;
-                    }
-                    return { kind: relation!, id: r.id }
+                    return { kind: parse_relation_kind(r.kind), id: r.id }
 
                 })
                 this.nodes.push({ id: node.id, sex: node.sex == "Male" ? KinSex.Male : KinSex.Female, name: node.name, relations: relations })
@@ -107,22 +114,7 @@ export class KinGraph {
                 var our_node = this.nodes[i]
                 //update the relations
                 var relations = node.relations.map((r) => {
-                    var relation;
-                    switch (r.kind) {
-                        case "Parent":
-                            relation = RelationKind.Parent
-                            break;
-                        case "Child":
-                            relation = RelationKind.Child
-                            break;
-                        case "RP":
-                            relation = RelationKind.RP
-                            break;
-                        case "Sibling":
-                            relation = RelationKind.Sibling
-                            break;
-                    }
-                    return { kind: relation!, id: r.id }
+                    return { kind: parse_relation_kind(r.kind), id: r.id }
 
                 })
                 our_node.relations = relations
@@ -141,9 +133,21 @@ export class KinGraph {
         this.merge_from_kin_wasm_graph(wasm_graph)
 
     }
+    //replace the graph with one saved from get_graph, throwing if it is not a valid graph
+    load(json: string) {
+        load_graph(json)
+        //none of our persons are kept, so start from an empty graph
+        this.nodes = []
+        var wasm_graph = new KinWasmGraph(get_graph())
+        this.merge_from_kin_wasm_graph(wasm_graph)
+    }
     query(dsl: string) {
         return append_from_dsl(dsl)
     }
+    layout(): Map<number, KinLocation> {
+        var nodes: KinLocation[] = JSON.parse(get_layout()).nodes
+        return new Map(nodes.map((l) => [l.id, l] as [number, KinLocation]))
+    }
     get_relation(p1: PersonNode, p2: PersonNode) {
         var person1 = Person.new_with_id(p1.sex, p1.id)
         var person2 = Person.new_with_id(p2.sex, p2.id)
@@ -12,6 +12,15 @@ const COLORS = {
 
 }
 const SCALE = 5;
+const NODE_WIDTH = 100
+const NODE_HEIGHT = 50
+//the distance between the left sides of two persons next to each other in a row
+const PLACE_WIDTH = 140
+//the distance between the tops of two rows
+const ROW_HEIGHT = 120
+const NODE_MARGIN = 50
+//the relations drawn as a link down from the parent to the child
+const PARENT_KINDS = [RelationKind.Parent, RelationKind.AdoptiveParent, RelationKind.FosterParent, RelationKind.Guardian]
 export class KinGraphView {
     app: PIXI.Application;
     kin_graph: KinGraph;
@@ -85,18 +94,42 @@ export class KinGraphView {
 
         })
         this.app.stage.addChild(grid)
-        const co = drawNode({ id: 0, name: "John", sex: "M", relations: [] })
-        co.position.set(100, 100)
-        const co2 = drawNode({
-            id: 1, name: "Izy", sex: "F", relations: []
-        })
-        co2.position.set(200, 200)
-        this.app.stage.addChild(co2)
-        this.app.stage.addChild(co)
-        //draw a link between the two nodes
-        var link = new VerticalNodeLink([150, 150], [200, 200])
-        link.initDraw()
-        this.app.stage.addChild(link.graphics)
+        this.render_nodes()
+    }
+    //draw each person where the layout of the graph puts them, with a link from each parent
+    //to each of their children
+    render_nodes() {
+        var layout = this.kin_graph.layout()
+        var nodes = this.kin_graph.nodes ?? []
+        for (const node of nodes) {
+            var location = layout.get(node.id)
+            if (location == undefined) {
+                continue
+            }
+            var pixi_node = drawNode({ id: node.id, name: node.name ?? "", sex: node.sex == KinSex.Male ? "M" : "F", relations: [] })
+            //the width counts two for each place in a row, so that children can be centered under a couple
+            pixi_node.position.set(NODE_MARGIN + location.width * PLACE_WIDTH / 2, NODE_MARGIN + location.depth * ROW_HEIGHT)
+            this.pixi_nodes.set(node.id, pixi_node)
+        }
+        for (const node of nodes) {
+            var parent = this.pixi_nodes.get(node.id)
+            if (parent == undefined) {
+                continue
+            }
+            for (const relation of node.relations.filter((r) => PARENT_KINDS.includes(r.kind))) {
+                var child = this.pixi_nodes.get(relation.id)
+                if (child == undefined) {
+                    continue
+                }
+                //from the middle of the bottom of the parent to the middle of the top of the child
+                var link = new VerticalNodeLink([parent.x + NODE_WIDTH / 2, parent.y + NODE_HEIGHT], [child.x + NODE_WIDTH / 2, child.y])
+                link.initDraw()
+                this.app.stage.addChild(link.graphics)
+            }
+        }
+        for (const pixi_node of this.pixi_nodes.values()) {
+            this.app.stage.addChild(pixi_node)
+        }
     }
 
 }
@@ -114,7 +147,7 @@ function drawNode(node: KinWasmPersonNode) {
     var node_container = new PIXI.Container()
     var node_graphics = new PIXI.Graphics()
     node_graphics.beginFill(0x000000)
-    node_graphics.drawRoundedRect(0, 0, 100, 50, 10)
+    node_graphics.drawRoundedRect(0, 0, NODE_WIDTH, NODE_HEIGHT, 10)
     node_graphics.endFill()
     var name = new PIXI.Text(node.name, { fontSize: 12, fill: 0xFFFFFF })
     name.position.set(10, 10)
@@ -0,0 +1,270 @@
+//! Typed attributes that can be attached to persons and relations, such as an occupation,
+//! notes or tags.
+use crate::calendar::Calendar;
+use crate::date::GenDate;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+
+///The attributes of a person or relation, by name
+pub type Attributes = BTreeMap<String, AttrValue>;
+
+///The value of an attribute
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "lowercase")]
+pub enum AttrValue {
+    Str(String),
+    Number(f64),
+    Date(GenDate),
+    Bool(bool),
+    List(Vec<AttrValue>),
+}
+impl AttrValue {
+    //orders values of different types
+    fn rank(&self) -> u8 {
+        match self {
+            AttrValue::Str(_) => 0,
+            AttrValue::Number(_) => 1,
+            AttrValue::Date(_) => 2,
+            AttrValue::Bool(_) => 3,
+            AttrValue::List(_) => 4,
+        }
+    }
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            AttrValue::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+    ///Parses a value as written in the DSL: `true` or `false`, a number, `date(...)` with a date
+    /// in the given calendar, a list in square brackets, or otherwise a string, which may be quoted
+    pub fn parse(s: &str, calendar: &dyn Calendar) -> anyhow::Result<AttrValue> {
+        let s = s.trim();
+        if let Some(inner) = s.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            if inner.trim().is_empty() {
+                return Ok(AttrValue::List(Vec::new()));
+            }
+            return split_list(inner)
+                .into_iter()
+                .map(|item| AttrValue::parse(item, calendar))
+                .collect::<anyhow::Result<_>>()
+                .map(AttrValue::List);
+        }
+        if let Some(date) = s.strip_prefix("date(").and_then(|s| s.strip_suffix(')')) {
+            return Ok(AttrValue::Date(calendar.parse(date)?));
+        }
+        if let Some(quoted) = s.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+            return Ok(AttrValue::Str(quoted.to_string()));
+        }
+        let value = match s {
+            "true" => AttrValue::Bool(true),
+            "false" => AttrValue::Bool(false),
+            _ => match s.parse::<f64>() {
+                Ok(n) if n.is_finite() => AttrValue::Number(n),
+                _ => AttrValue::Str(s.to_string()),
+            },
+        };
+        Ok(value)
+    }
+    ///Writes the value the way [AttrValue::parse] reads it, quoting strings that would otherwise
+    /// be read as another type
+    pub fn format(&self, calendar: &dyn Calendar) -> String {
+        match self {
+            AttrValue::Str(s) => {
+                let read_back = AttrValue::parse(s, calendar).ok();
+                if s.contains(',') || read_back.as_ref().and_then(AttrValue::as_str) != Some(s) {
+                    format!("\"{}\"", s)
+                } else {
+                    s.clone()
+                }
+            }
+            AttrValue::Date(d) => format!("date({})", calendar.format(d)),
+            AttrValue::List(items) => {
+                let items = items.iter().map(|item| item.format(calendar));
+                format!("[{}]", items.collect::<Vec<_>>().join(", "))
+            }
+            value => value.to_string(),
+        }
+    }
+}
+//adds an attribute, making a list of the values of a key that is given more than once
+pub(crate) fn add_attr(attributes: &mut Attributes, key: String, value: AttrValue) {
+    match attributes.get_mut(&key) {
+        Some(AttrValue::List(items)) => items.push(value),
+        Some(existing) => *existing = AttrValue::List(vec![existing.clone(), value]),
+        None => {
+            attributes.insert(key, value);
+        }
+    }
+}
+///Splits the items of a list on the commas that are not inside a nested list or quotes
+fn split_list(s: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let (mut depth, mut quoted, mut start) = (0, false, 0);
+    for (i, c) in s.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '[' | '(' if !quoted => depth += 1,
+            ']' | ')' if !quoted => depth -= 1,
+            ',' if !quoted && depth == 0 => {
+                items.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&s[start..]);
+    items
+}
+impl std::fmt::Display for AttrValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AttrValue::Str(s) => write!(f, "{}", s),
+            AttrValue::Number(n) => write!(f, "{}", n),
+            AttrValue::Date(d) => write!(f, "{}", d),
+            AttrValue::Bool(b) => write!(f, "{}", b),
+            AttrValue::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+impl From<&str> for AttrValue {
+    fn from(s: &str) -> Self {
+        AttrValue::Str(s.to_string())
+    }
+}
+impl From<String> for AttrValue {
+    fn from(s: String) -> Self {
+        AttrValue::Str(s)
+    }
+}
+impl From<f64> for AttrValue {
+    fn from(n: f64) -> Self {
+        AttrValue::Number(n)
+    }
+}
+impl From<GenDate> for AttrValue {
+    fn from(d: GenDate) -> Self {
+        AttrValue::Date(d)
+    }
+}
+impl From<bool> for AttrValue {
+    fn from(b: bool) -> Self {
+        AttrValue::Bool(b)
+    }
+}
+impl<T: Into<AttrValue>> From<Vec<T>> for AttrValue {
+    fn from(items: Vec<T>) -> Self {
+        AttrValue::List(items.into_iter().map(Into::into).collect())
+    }
+}
+//numbers are compared by their total order, so that persons holding attributes can still be
+//hashed and sorted
+impl Ord for AttrValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (AttrValue::Str(a), AttrValue::Str(b)) => a.cmp(b),
+            (AttrValue::Number(a), AttrValue::Number(b)) => a.total_cmp(b),
+            (AttrValue::Date(a), AttrValue::Date(b)) => a.cmp(b),
+            (AttrValue::Bool(a), AttrValue::Bool(b)) => a.cmp(b),
+            (AttrValue::List(a), AttrValue::List(b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+impl PartialOrd for AttrValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl PartialEq for AttrValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl Eq for AttrValue {}
+impl Hash for AttrValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.rank().hash(state);
+        match self {
+            AttrValue::Str(s) => s.hash(state),
+            AttrValue::Number(n) => n.to_bits().hash(state),
+            AttrValue::Date(d) => d.hash(state),
+            AttrValue::Bool(b) => b.hash(state),
+            AttrValue::List(items) => items.hash(state),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calendar::Gregorian;
+    use crate::date::Date;
+
+    #[test]
+    fn parse_values() {
+        let parse = |s: &str| AttrValue::parse(s, &Gregorian).unwrap();
+        assert_eq!(parse("smith"), "smith".into());
+        assert_eq!(parse("\"true\""), "true".into());
+        assert_eq!(parse("true"), true.into());
+        assert_eq!(parse("-3.5"), (-3.5).into());
+        assert_eq!(
+            parse("date(abt 1850)"),
+            GenDate::About(Date::year(1850)).into()
+        );
+        assert_eq!(
+            parse("[hero, \"a, b\", [1, 2]]"),
+            AttrValue::List(vec!["hero".into(), "a, b".into(), vec![1.0, 2.0].into()])
+        );
+        assert!(AttrValue::parse("date(someday)", &Gregorian).is_err());
+        assert_eq!(parse("[hero, [1, 2]]").to_string(), "[hero, [1, 2]]");
+    }
+    #[test]
+    fn format_values() {
+        let values: Vec<AttrValue> = vec![
+            "smith".into(),
+            "true".into(),
+            "42".into(),
+            "a, b".into(),
+            "[x]".into(),
+            (-3.5).into(),
+            false.into(),
+            GenDate::About(Date::year(1850)).into(),
+            AttrValue::List(vec!["hero".into(), "a, b".into(), vec![1.0, 2.0].into()]),
+        ];
+        for value in values {
+            let text = value.format(&Gregorian);
+            assert_eq!(
+                AttrValue::parse(&text, &Gregorian).unwrap(),
+                value,
+                "{}",
+                text
+            );
+        }
+        assert_eq!(AttrValue::from("42").format(&Gregorian), "\"42\"");
+    }
+    #[test]
+    fn serde_round_trip() {
+        let value = AttrValue::List(vec![
+            "x".into(),
+            2.0.into(),
+            GenDate::Before(Date::year(1900)).into(),
+        ]);
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(
+            json,
+            r#"{"type":"list","value":[{"type":"str","value":"x"},{"type":"number","value":2.0},{"type":"date","value":"bef 1900"}]}"#
+        );
+        assert_eq!(serde_json::from_str::<AttrValue>(&json).unwrap(), value);
+    }
+}
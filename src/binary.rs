@@ -0,0 +1,656 @@
+//! A compact binary format for a whole [KinGraph], for graphs too large for JSON.
+//!
+//! A file is laid out as:
+//! - the magic bytes `KINB` and the [BINARY_VERSION], as a varint
+//! - the string table: every name, attribute key and other string once, in the order first used
+//! - the persons, in the order they were added, referring to strings by their index in the table
+//! - for each person, the relations written from them (see [GraphDocument](crate::GraphDocument)),
+//!   sorted by the index of the other person, which is stored as the difference from the
+//!   previous one
+//! - from version 2, the calendar of the graph when it is a [CustomCalendar](crate::CustomCalendar)
+//! - the CRC-32 of everything before it, as 4 little endian bytes
+//!
+//! Numbers are LEB128 varints, with signed ones zigzag encoded. Both saving and loading stream,
+//! so the file is never held in memory as a whole: persons and relations are added to the graph
+//! as they are read, with the same checks as [KinGraph::from_document], and the checksum is
+//! checked once the graph is complete.
+use crate::serialize::RelationRecord;
+use crate::{
+    AttrValue, Attributes, CustomCalendar, CustomMonth, Date, Era, GenDate, KinGraph, Kind,
+    LeapRule, MaritalStatus, Parentage, Partnership, Person, PersonName, Sex,
+};
+use anyhow::{bail, Result};
+use petgraph::visit::EdgeRef;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+
+///The version of the binary format that is written
+pub const BINARY_VERSION: u32 = 2;
+const MAGIC: &[u8; 4] = b"KINB";
+
+//flags of a relation
+const HAS_PARENTAGE: u8 = 1;
+const HAS_PARTNERSHIP: u8 = 2;
+const HAS_ATTRIBUTES: u8 = 4;
+
+impl KinGraph {
+    ///Writes the graph in the binary format. The graph is read twice, first to collect the
+    /// strings and then to write it.
+    pub fn write_binary<W: Write>(&self, writer: W) -> Result<()> {
+        let mut enc = Encoder {
+            out: CrcWriter::new(io::sink()),
+            strings: HashMap::new(),
+            table: Vec::new(),
+        };
+        self.encode(&mut enc)?;
+        let mut enc = Encoder {
+            out: CrcWriter::new(BufWriter::new(writer)),
+            strings: enc.strings,
+            table: enc.table,
+        };
+        enc.out.write_all(MAGIC)?;
+        enc.uint(BINARY_VERSION as u64)?;
+        enc.uint(enc.table.len() as u64)?;
+        for i in 0..enc.table.len() {
+            let s = enc.table[i];
+            enc.uint(s.len() as u64)?;
+            enc.out.write_all(s.as_bytes())?;
+        }
+        self.encode(&mut enc)?;
+        let crc = enc.out.crc;
+        let mut out = enc.out.inner;
+        out.write_all(&crc.to_le_bytes())?;
+        out.flush()?;
+        Ok(())
+    }
+    ///Reads a graph written by [KinGraph::write_binary]. The reader is buffered, so it may read
+    /// past the end of the graph.
+    pub fn read_binary<R: Read>(reader: R) -> Result<KinGraph> {
+        let mut dec = Decoder {
+            inp: CrcReader::new(BufReader::new(reader)),
+            strings: Vec::new(),
+        };
+        let mut magic = [0; 4];
+        dec.inp.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            bail!("Not a binary kin graph");
+        }
+        let version = dec.uint()?;
+        if !(1..=BINARY_VERSION as u64).contains(&version) {
+            bail!(
+                "Unsupported binary graph version {}, expected {}",
+                version,
+                BINARY_VERSION
+            );
+        }
+        for _ in 0..dec.uint()? {
+            let len = dec.uint()?;
+            let mut bytes = Vec::new();
+            (&mut dec.inp).take(len).read_to_end(&mut bytes)?;
+            if bytes.len() as u64 != len {
+                bail!("Binary graph ends within its string table");
+            }
+            dec.strings.push(String::from_utf8(bytes)?);
+        }
+        //each person and relation is added to the graph as soon as it is read, checked as when
+        //reading a document
+        let mut kg = KinGraph::new();
+        for _ in 0..dec.uint()? {
+            kg.read_person(&dec.person()?)?;
+        }
+        for from in 0..kg.graph.node_count() {
+            let mut to = 0usize;
+            for _ in 0..dec.uint()? {
+                to = to
+                    .checked_add(dec.uint()? as usize)
+                    .ok_or_else(|| anyhow::anyhow!("Relation of {} to a missing person", from))?;
+                kg.read_relation(dec.relation(from, to)?)?;
+            }
+        }
+        if version > 1 {
+            if let Some(calendar) = dec.calendar()? {
+                calendar.validate()?;
+                kg.set_calendar(Box::new(calendar));
+            }
+        }
+        let crc = dec.inp.crc;
+        let mut stored = [0; 4];
+        dec.inp.inner.read_exact(&mut stored)?;
+        if u32::from_le_bytes(stored) != crc {
+            bail!("Binary graph is corrupt, its checksum does not match");
+        }
+        Ok(kg)
+    }
+    fn encode<'a, W: Write>(&'a self, enc: &mut Encoder<'a, W>) -> Result<()> {
+        enc.uint(self.graph.node_count() as u64)?;
+        for p in self.graph.node_weights() {
+            enc.person(p)?;
+        }
+        for from in self.graph.node_indices() {
+            let mut written = self
+                .graph
+                .edges(from)
+                .filter(|e| e.weight().is_written_from(from.index(), e.target().index()))
+                .map(|e| (e.target(), *e.weight()))
+                .collect::<Vec<_>>();
+            written.sort_by_key(|(to, kind)| (*to, *kind as u8));
+            enc.uint(written.len() as u64)?;
+            let mut prev = 0;
+            for (to, kind) in written {
+                enc.uint((to.index() - prev) as u64)?;
+                prev = to.index();
+                let parentage = match kind {
+                    Kind::Parent => self.parentage.get(&(from, to)),
+                    _ => None,
+                };
+                let partnership = match kind {
+                    Kind::Spouse => self.partnership_of(from, to),
+                    _ => None,
+                };
+                let attributes = self.relation_attrs_of(from, to, kind);
+                let flags = parentage.map_or(0, |_| HAS_PARENTAGE)
+                    | partnership.map_or(0, |_| HAS_PARTNERSHIP)
+                    | attributes.map_or(0, |_| HAS_ATTRIBUTES);
+                enc.out.write_all(&[kind as u8, flags])?;
+                if let Some(parentage) = parentage {
+                    enc.byte(parentage.biological as u8 | (parentage.legal as u8) << 1)?;
+                }
+                if let Some(partnership) = partnership {
+                    enc.byte(partnership.status as u8)?;
+                    enc.opt_date(partnership.start.as_ref())?;
+                    enc.opt_date(partnership.end.as_ref())?;
+                }
+                if let Some(attributes) = attributes {
+                    enc.attributes(attributes)?;
+                }
+            }
+        }
+        enc.calendar(self.calendar.as_custom())?;
+        Ok(())
+    }
+}
+
+struct Encoder<'a, W: Write> {
+    out: CrcWriter<W>,
+    strings: HashMap<&'a str, u64>,
+    table: Vec<&'a str>,
+}
+impl<'a, W: Write> Encoder<'a, W> {
+    fn byte(&mut self, b: u8) -> io::Result<()> {
+        self.out.write_all(&[b])
+    }
+    fn uint(&mut self, mut n: u64) -> io::Result<()> {
+        let mut buf = [0; 10];
+        let mut len = 0;
+        loop {
+            let b = (n & 0x7f) as u8;
+            n >>= 7;
+            if n == 0 {
+                buf[len] = b;
+                len += 1;
+                break;
+            }
+            buf[len] = b | 0x80;
+            len += 1;
+        }
+        self.out.write_all(&buf[..len])
+    }
+    fn int(&mut self, n: i64) -> io::Result<()> {
+        self.uint(((n << 1) ^ (n >> 63)) as u64)
+    }
+    //the index of the string in the table, adding it if it is new
+    fn index(&mut self, s: &'a str) -> u64 {
+        let next = self.table.len() as u64;
+        let i = *self.strings.entry(s).or_insert(next);
+        if i == next {
+            self.table.push(s);
+        }
+        i
+    }
+    fn str(&mut self, s: &'a str) -> io::Result<()> {
+        let i = self.index(s);
+        self.uint(i)
+    }
+    //0 for none, otherwise one more than the index of the string
+    fn opt_str(&mut self, s: Option<&'a String>) -> io::Result<()> {
+        let i = s.map_or(0, |s| self.index(s) + 1);
+        self.uint(i)
+    }
+    fn strs(&mut self, strs: &'a [String]) -> io::Result<()> {
+        self.uint(strs.len() as u64)?;
+        strs.iter().try_for_each(|s| self.str(s))
+    }
+    fn date(&mut self, date: &GenDate) -> io::Result<()> {
+        let (tag, dates) = match date {
+            GenDate::Exact(d) => (0, [Some(d), None]),
+            GenDate::About(d) => (1, [Some(d), None]),
+            GenDate::Calculated(d) => (2, [Some(d), None]),
+            GenDate::Estimated(d) => (3, [Some(d), None]),
+            GenDate::Before(d) => (4, [Some(d), None]),
+            GenDate::After(d) => (5, [Some(d), None]),
+            GenDate::Between(from, to) => (6, [Some(from), Some(to)]),
+        };
+        self.byte(tag)?;
+        for d in dates.into_iter().flatten() {
+            self.int((d.year as i64) << 1 | d.dual as i64)?;
+            self.uint(d.month.map_or(0, |m| m as u64 + 1))?;
+            self.uint(d.day.map_or(0, |d| d as u64 + 1))?;
+        }
+        Ok(())
+    }
+    fn opt_date(&mut self, date: Option<&GenDate>) -> io::Result<()> {
+        match date {
+            None => self.byte(0),
+            Some(date) => {
+                self.byte(1)?;
+                self.date(date)
+            }
+        }
+    }
+    fn value(&mut self, value: &'a AttrValue) -> io::Result<()> {
+        match value {
+            AttrValue::Str(s) => {
+                self.byte(0)?;
+                self.str(s)
+            }
+            AttrValue::Number(n) => {
+                self.byte(1)?;
+                self.out.write_all(&n.to_le_bytes())
+            }
+            AttrValue::Date(d) => {
+                self.byte(2)?;
+                self.date(d)
+            }
+            AttrValue::Bool(b) => self.out.write_all(&[3, *b as u8]),
+            AttrValue::List(items) => {
+                self.byte(4)?;
+                self.uint(items.len() as u64)?;
+                items.iter().try_for_each(|item| self.value(item))
+            }
+        }
+    }
+    fn attributes(&mut self, attributes: &'a Attributes) -> io::Result<()> {
+        self.uint(attributes.len() as u64)?;
+        for (key, value) in attributes {
+            self.str(key)?;
+            self.value(value)?;
+        }
+        Ok(())
+    }
+    fn person(&mut self, p: &'a Person) -> io::Result<()> {
+        self.uint(p.id as u64)?;
+        self.str(&p.name)?;
+        self.byte(p.sex as u8 | (p.is_shadow as u8) << 1)?;
+        self.opt_date(p.birth.as_ref())?;
+        self.opt_date(p.death.as_ref())?;
+        self.attributes(&p.attributes)?;
+        self.uint(p.refs.len() as u64)?;
+        for (system, id) in &p.refs {
+            self.str(system)?;
+            self.str(id)?;
+        }
+        self.uint(p.names.len() as u64)?;
+        for name in &p.names {
+            self.strs(&name.given)?;
+            self.opt_str(name.surname.as_ref())?;
+            self.opt_str(name.birth_surname.as_ref())?;
+            self.strs(&name.nicknames)?;
+            self.strs(&name.titles)?;
+        }
+        Ok(())
+    }
+    fn calendar(&mut self, calendar: Option<&'a CustomCalendar>) -> io::Result<()> {
+        let Some(calendar) = calendar else {
+            return self.byte(0);
+        };
+        self.byte(1)?;
+        self.str(&calendar.name)?;
+        self.uint(calendar.months.len() as u64)?;
+        for month in &calendar.months {
+            self.str(&month.name)?;
+            self.byte(month.days)?;
+        }
+        match calendar.leap {
+            None => self.byte(0)?,
+            Some(leap) => {
+                self.byte(1)?;
+                self.uint(leap.every as u64)?;
+                self.byte(leap.month)?;
+            }
+        }
+        self.uint(calendar.eras.len() as u64)?;
+        for era in &calendar.eras {
+            self.str(&era.name)?;
+            self.int(era.start as i64)?;
+            self.byte(era.backwards as u8)?;
+        }
+        self.int(calendar.epoch)
+    }
+}
+
+struct Decoder<R: Read> {
+    inp: CrcReader<R>,
+    strings: Vec<String>,
+}
+impl<R: Read> Decoder<R> {
+    fn byte(&mut self) -> io::Result<u8> {
+        let mut b = [0];
+        self.inp.read_exact(&mut b)?;
+        Ok(b[0])
+    }
+    fn uint(&mut self) -> Result<u64> {
+        let mut n = 0u64;
+        for shift in (0..64).step_by(7) {
+            let b = self.byte()?;
+            n |= ((b & 0x7f) as u64) << shift;
+            if b & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+        bail!("Binary graph has a number that is too long")
+    }
+    fn int(&mut self) -> Result<i64> {
+        let n = self.uint()?;
+        Ok((n >> 1) as i64 ^ -((n & 1) as i64))
+    }
+    fn str(&mut self) -> Result<String> {
+        let i = self.uint()?;
+        match self.strings.get(i as usize) {
+            Some(s) => Ok(s.clone()),
+            None => bail!("Binary graph refers to missing string {}", i),
+        }
+    }
+    fn opt_str(&mut self) -> Result<Option<String>> {
+        match self.uint()? {
+            0 => Ok(None),
+            i => match self.strings.get(i as usize - 1) {
+                Some(s) => Ok(Some(s.clone())),
+                None => bail!("Binary graph refers to missing string {}", i - 1),
+            },
+        }
+    }
+    fn strs(&mut self) -> Result<Vec<String>> {
+        (0..self.uint()?).map(|_| self.str()).collect()
+    }
+    fn small(&mut self, what: &str) -> Result<Option<u8>> {
+        match self.uint()? {
+            0 => Ok(None),
+            n => match u8::try_from(n - 1) {
+                Ok(n) => Ok(Some(n)),
+                Err(_) => bail!("Binary graph has a date with {} {}", what, n - 1),
+            },
+        }
+    }
+    fn plain_date(&mut self) -> Result<Date> {
+        let year = self.int()?;
+        Ok(Date {
+            year: i32::try_from(year >> 1)?,
+            dual: year & 1 == 1,
+            month: self.small("month")?,
+            day: self.small("day")?,
+        })
+    }
+    fn date(&mut self) -> Result<GenDate> {
+        let tag = self.byte()?;
+        let d = self.plain_date()?;
+        Ok(match tag {
+            0 => GenDate::Exact(d),
+            1 => GenDate::About(d),
+            2 => GenDate::Calculated(d),
+            3 => GenDate::Estimated(d),
+            4 => GenDate::Before(d),
+            5 => GenDate::After(d),
+            6 => GenDate::Between(d, self.plain_date()?),
+            _ => bail!("Binary graph has an unknown kind of date {}", tag),
+        })
+    }
+    fn opt_date(&mut self) -> Result<Option<GenDate>> {
+        match self.byte()? {
+            0 => Ok(None),
+            _ => self.date().map(Some),
+        }
+    }
+    fn value(&mut self) -> Result<AttrValue> {
+        Ok(match self.byte()? {
+            0 => AttrValue::Str(self.str()?),
+            1 => {
+                let mut n = [0; 8];
+                self.inp.read_exact(&mut n)?;
+                AttrValue::Number(f64::from_le_bytes(n))
+            }
+            2 => AttrValue::Date(self.date()?),
+            3 => AttrValue::Bool(self.byte()? != 0),
+            4 => AttrValue::List(
+                (0..self.uint()?)
+                    .map(|_| self.value())
+                    .collect::<Result<_>>()?,
+            ),
+            tag => bail!("Binary graph has an unknown kind of attribute {}", tag),
+        })
+    }
+    fn attributes(&mut self) -> Result<Attributes> {
+        (0..self.uint()?)
+            .map(|_| Ok((self.str()?, self.value()?)))
+            .collect()
+    }
+    fn person(&mut self) -> Result<Person> {
+        let id = self.uint()? as usize;
+        let name = self.str()?;
+        let flags = self.byte()?;
+        let sex = if flags & 1 == 0 {
+            Sex::Male
+        } else {
+            Sex::Female
+        };
+        let birth = self.opt_date()?;
+        let death = self.opt_date()?;
+        let attributes = self.attributes()?;
+        let refs = (0..self.uint()?)
+            .map(|_| Ok((self.str()?, self.str()?)))
+            .collect::<Result<BTreeMap<_, _>>>()?;
+        let names = (0..self.uint()?)
+            .map(|_| {
+                Ok(PersonName {
+                    given: self.strs()?,
+                    surname: self.opt_str()?,
+                    birth_surname: self.opt_str()?,
+                    nicknames: self.strs()?,
+                    titles: self.strs()?,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Person {
+            id,
+            name,
+            sex,
+            is_shadow: flags & 2 != 0,
+            birth,
+            death,
+            attributes,
+            refs,
+            names,
+        })
+    }
+    fn relation(&mut self, from: usize, to: usize) -> Result<RelationRecord> {
+        let kind = match self.byte()? {
+            k if k == Kind::Parent as u8 => Kind::Parent,
+            k if k == Kind::Sibling as u8 => Kind::Sibling,
+            k if k == Kind::RP as u8 => Kind::RP,
+            k if k == Kind::AdoptiveParent as u8 => Kind::AdoptiveParent,
+            k if k == Kind::FosterParent as u8 => Kind::FosterParent,
+            k if k == Kind::Guardian as u8 => Kind::Guardian,
+            k if k == Kind::Spouse as u8 => Kind::Spouse,
+            k => bail!("Binary graph has an unknown kind of relation {}", k),
+        };
+        let flags = self.byte()?;
+        let parentage = if flags & HAS_PARENTAGE != 0 {
+            let b = self.byte()?;
+            Some(Parentage {
+                biological: b & 1 != 0,
+                legal: b & 2 != 0,
+            })
+        } else {
+            None
+        };
+        let partnership = if flags & HAS_PARTNERSHIP != 0 {
+            let status = match self.byte()? {
+                s if s == MaritalStatus::Married as u8 => MaritalStatus::Married,
+                s if s == MaritalStatus::Partnered as u8 => MaritalStatus::Partnered,
+                s if s == MaritalStatus::Divorced as u8 => MaritalStatus::Divorced,
+                s if s == MaritalStatus::Widowed as u8 => MaritalStatus::Widowed,
+                s => bail!("Binary graph has an unknown marital status {}", s),
+            };
+            Some(Partnership {
+                status,
+                start: self.opt_date()?,
+                end: self.opt_date()?,
+            })
+        } else {
+            None
+        };
+        let attributes = if flags & HAS_ATTRIBUTES != 0 {
+            self.attributes()?
+        } else {
+            Attributes::new()
+        };
+        Ok(RelationRecord {
+            from,
+            to,
+            kind,
+            parentage,
+            partnership,
+            attributes,
+        })
+    }
+    //the calendar, which is checked once it has been read
+    fn calendar(&mut self) -> Result<Option<CustomCalendar>> {
+        if self.byte()? == 0 {
+            return Ok(None);
+        }
+        let name = self.str()?;
+        let months = (0..self.uint()?)
+            .map(|_| {
+                Ok(CustomMonth {
+                    name: self.str()?,
+                    days: self.byte()?,
+                })
+            })
+            .collect::<Result<_>>()?;
+        let leap = match self.byte()? {
+            0 => None,
+            _ => Some(LeapRule {
+                every: u32::try_from(self.uint()?)?,
+                month: self.byte()?,
+            }),
+        };
+        let eras = (0..self.uint()?)
+            .map(|_| {
+                Ok(Era {
+                    name: self.str()?,
+                    start: i32::try_from(self.int()?)?,
+                    backwards: self.byte()? != 0,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Some(CustomCalendar {
+            name,
+            months,
+            leap,
+            eras,
+            epoch: self.int()?,
+        }))
+    }
+}
+
+//the CRC-32 (IEEE) lookup table
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xEDB8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+};
+fn crc32_update(crc: u32, bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!crc, |c, b| {
+        CRC_TABLE[((c ^ *b as u32) & 0xff) as usize] ^ (c >> 8)
+    })
+}
+struct CrcWriter<W> {
+    inner: W,
+    crc: u32,
+}
+impl<W> CrcWriter<W> {
+    fn new(inner: W) -> Self {
+        CrcWriter { inner, crc: 0 }
+    }
+}
+impl<W: Write> Write for CrcWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.crc = crc32_update(self.crc, &buf[..n]);
+        Ok(n)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+struct CrcReader<R> {
+    inner: R,
+    crc: u32,
+}
+impl<R> CrcReader<R> {
+    fn new(inner: R) -> Self {
+        CrcReader { inner, crc: 0 }
+    }
+}
+impl<R: Read> Read for CrcReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.crc = crc32_update(self.crc, &buf[..n]);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksum_and_numbers() {
+        assert_eq!(crc32_update(0, b"123456789"), 0xCBF4_3926);
+        assert_eq!(
+            crc32_update(crc32_update(0, b"1234"), b"56789"),
+            0xCBF4_3926
+        );
+        let mut enc = Encoder {
+            out: CrcWriter::new(Vec::new()),
+            strings: HashMap::new(),
+            table: Vec::new(),
+        };
+        for n in [0, -1, 63, -64, 300, i64::MAX, i64::MIN] {
+            enc.int(n).unwrap();
+        }
+        enc.uint(u64::MAX).unwrap();
+        assert_eq!(enc.out.inner[..4], [0, 1, 126, 127]);
+        let mut dec = Decoder {
+            inp: CrcReader::new(&enc.out.inner[..]),
+            strings: Vec::new(),
+        };
+        for n in [0, -1, 63, -64, 300, i64::MAX, i64::MIN] {
+            assert_eq!(dec.int().unwrap(), n);
+        }
+        assert_eq!(dec.uint().unwrap(), u64::MAX);
+    }
+}
@@ -0,0 +1,622 @@
+//! Calendars that [Date]s are interpreted in.
+//!
+//! A [Date] is only a year, month and day; its calendar decides how long the months are, what
+//! they are called and how years are numbered. The [Gregorian] calendar is the default, and
+//! [CustomCalendar] describes invented calendars with their own months, leap days and eras.
+use crate::date::{Date, GenDate, APPROX_YEARS, MAX_YEAR};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+///The Gregorian names of the months, as written in dates
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+///A way of counting days. Only the month lengths and names are required, everything else
+/// has a default built on top of them.
+pub trait Calendar {
+    ///The name of the calendar
+    fn name(&self) -> &str;
+    fn months_in_year(&self, year: i32) -> u8;
+    fn days_in_month(&self, year: i32, month: u8) -> u8;
+    ///The name of the month (starting from 1) as it is written in dates
+    fn month_name(&self, month: u8) -> String;
+    ///The ordinal of the first day of year 0. Calendars whose epochs are set relative to each
+    /// other can convert dates through [Calendar::to_ordinal].
+    fn epoch(&self) -> i64 {
+        0
+    }
+    ///The calendar as a [CustomCalendar], if it is one, which is how it is saved along with a
+    /// graph
+    fn as_custom(&self) -> Option<&CustomCalendar> {
+        None
+    }
+    fn days_in_year(&self, year: i32) -> i64 {
+        (1..=self.months_in_year(year))
+            .map(|m| self.days_in_month(year, m) as i64)
+            .sum()
+    }
+    ///The day number of the (first day of the) date
+    fn to_ordinal(&self, date: Date) -> i64 {
+        let d = date.earliest();
+        let mut days = self.epoch();
+        if d.year >= 0 {
+            days += (0..d.year).map(|y| self.days_in_year(y)).sum::<i64>();
+        } else {
+            days -= (d.year..0).map(|y| self.days_in_year(y)).sum::<i64>();
+        }
+        days += (1..d.month.unwrap())
+            .map(|m| self.days_in_month(d.year, m) as i64)
+            .sum::<i64>();
+        days + d.day.unwrap() as i64 - 1
+    }
+    ///The full date of the day number
+    fn ordinal_to_date(&self, ordinal: i64) -> Date {
+        let mut days = ordinal - self.epoch();
+        let mut year = 0;
+        while days < 0 {
+            year -= 1;
+            days += self.days_in_year(year);
+        }
+        while days >= self.days_in_year(year) {
+            days -= self.days_in_year(year);
+            year += 1;
+        }
+        let mut month = 1;
+        while days >= self.days_in_month(year, month) as i64 {
+            days -= self.days_in_month(year, month) as i64;
+            month += 1;
+        }
+        Date::ymd(year, month, days as u8 + 1)
+    }
+    ///Writes a year, which is dual dated (e.g. 1710/11) if `dual` is set
+    fn format_year(&self, year: i32, dual: bool) -> String {
+        if dual {
+            format!("{}/{:02}", year - 1, year.rem_euclid(100))
+        } else {
+            year.to_string()
+        }
+    }
+    ///Parses a year, which may be dual dated such as 1710/11
+    fn parse_year(&self, y: &str) -> anyhow::Result<Date> {
+        match y.split_once('/') {
+            Some((old, new)) => {
+                let old: i32 = old.parse()?;
+                //the new style year is only ever abbreviated, so can't be longer than the old one
+                let modulus = (new.len() <= old.unsigned_abs().to_string().len())
+                    .then(|| 10_i32.checked_pow(new.len() as u32))
+                    .flatten();
+                let (Some(new_year), Some(modulus)) = (old.checked_add(1), modulus) else {
+                    anyhow::bail!("Invalid dual year {:?}", y);
+                };
+                if new.parse::<i32>()? != new_year.rem_euclid(modulus) {
+                    anyhow::bail!("Invalid dual year {:?}", y);
+                }
+                Ok(Date {
+                    dual: true,
+                    ..Date::year(new_year)
+                })
+            }
+            None => Ok(Date::year(y.parse()?)),
+        }
+    }
+    ///Parses a month name, allowing for longer forms such as "March" for "Mar"
+    fn parse_month(&self, m: &str, year: i32) -> anyhow::Result<u8> {
+        let lower = m.to_lowercase();
+        let names = (1..=self.months_in_year(year))
+            .map(|i| (i, self.month_name(i).to_lowercase()))
+            .collect::<Vec<_>>();
+        names
+            .iter()
+            .find(|(_, name)| *name == lower)
+            .or_else(|| names.iter().find(|(_, name)| lower.starts_with(name)))
+            .map(|(i, _)| *i)
+            .ok_or_else(|| anyhow::anyhow!("Unknown month {:?}", m))
+    }
+    fn format_date(&self, date: &Date) -> String {
+        let mut s = String::new();
+        if let Some(m) = date.month {
+            if let Some(d) = date.day {
+                s += &format!("{} ", d);
+            }
+            s += &format!("{} ", self.month_name(m));
+        }
+        s + &self.format_year(date.year, date.dual)
+    }
+    ///Parses "1850", "Mar 1850", "12 Mar 1850", "12 Mar 1710/11" or the ISO forms "1850-03" and "1850-03-12".
+    /// The year is whatever [Calendar::parse_year] accepts, and may be more than one word.
+    fn parse_date(&self, s: &str) -> anyhow::Result<Date> {
+        let s = s.trim();
+        let words = s.split_whitespace().collect::<Vec<_>>();
+        //a leading '-' is the sign of the year rather than a separator
+        if words.len() == 1 && s.chars().skip(1).any(|c| c == '-') {
+            let mut parts = s.splitn(3, '-');
+            let year = self.parse_year(parts.next().unwrap())?;
+            let month = parts.next().map(str::parse::<u8>).transpose()?;
+            let day = parts.next().map(str::parse::<u8>).transpose()?;
+            return self.validate(Date { month, day, ..year });
+        }
+        //the year is the last one or two words
+        let (year, rest) = (1..=words.len().min(2))
+            .find_map(|n| {
+                let (rest, year) = words.split_at(words.len() - n);
+                self.parse_year(&year.join(" ")).ok().map(|y| (y, rest))
+            })
+            .ok_or_else(|| anyhow::anyhow!("Invalid date {:?}", s))?;
+        let date = match rest {
+            [] => year,
+            [m] => Date {
+                month: Some(self.parse_month(m, year.year)?),
+                ..year
+            },
+            [d, m] => Date {
+                day: Some(d.parse()?),
+                month: Some(self.parse_month(m, year.year)?),
+                ..year
+            },
+            _ => anyhow::bail!("Invalid date {:?}", s),
+        };
+        self.validate(date)
+    }
+    ///Checks that the year is within [MAX_YEAR] and that the month and day exist in the calendar
+    fn validate(&self, date: Date) -> anyhow::Result<Date> {
+        if !(-MAX_YEAR..=MAX_YEAR).contains(&date.year) {
+            anyhow::bail!("Year {} is out of range", date.year);
+        }
+        if let Some(m) = date.month {
+            if m == 0 || m > self.months_in_year(date.year) {
+                anyhow::bail!("Invalid month {} in date", m);
+            }
+            if let Some(d) = date.day {
+                if d == 0 || d > self.days_in_month(date.year, m) {
+                    anyhow::bail!("Invalid day {} in date", d);
+                }
+            }
+        }
+        Ok(date)
+    }
+    fn format(&self, date: &GenDate) -> String {
+        let d = |d: &Date| self.format_date(d);
+        match date {
+            GenDate::Exact(a) => d(a),
+            GenDate::About(a) => format!("abt {}", d(a)),
+            GenDate::Calculated(a) => format!("cal {}", d(a)),
+            GenDate::Estimated(a) => format!("est {}", d(a)),
+            GenDate::Before(a) => format!("bef {}", d(a)),
+            GenDate::After(a) => format!("aft {}", d(a)),
+            GenDate::Between(a, b) => format!("bet {} and {}", d(a), d(b)),
+        }
+    }
+    ///Parses a date with an optional (case insensitive) qualifier, such as "abt 1850",
+    /// "bef 1900", "between 1820 and 1825" or "est Mar 1710/11"
+    fn parse(&self, s: &str) -> anyhow::Result<GenDate> {
+        let s = s.trim();
+        let (qualifier, rest) = match s.split_once(char::is_whitespace) {
+            Some((q, rest)) => (q.to_lowercase(), rest.trim()),
+            None => (String::new(), s),
+        };
+        let date = match qualifier.as_str() {
+            "abt" | "about" | "circa" | "ca" | "c." => GenDate::About(self.parse_date(rest)?),
+            "cal" | "calculated" => GenDate::Calculated(self.parse_date(rest)?),
+            "est" | "estimated" => GenDate::Estimated(self.parse_date(rest)?),
+            "bef" | "before" => GenDate::Before(self.parse_date(rest)?),
+            "aft" | "after" => GenDate::After(self.parse_date(rest)?),
+            "bet" | "between" => {
+                //found in the text itself, as lowercasing can change where it is
+                let and = rest
+                    .char_indices()
+                    .map(|(i, _)| i)
+                    .find(|i| {
+                        rest.get(*i..i + 5)
+                            .is_some_and(|w| w.eq_ignore_ascii_case(" and "))
+                    })
+                    .ok_or_else(|| anyhow::anyhow!("Expected 'and' in {:?}", s))?;
+                let a = self.parse_date(&rest[..and])?;
+                let b = self.parse_date(&rest[and + 5..])?;
+                if b < a {
+                    anyhow::bail!("Date range {:?} ends before it starts", s);
+                }
+                GenDate::Between(a, b)
+            }
+            _ => GenDate::Exact(self.parse_date(s)?),
+        };
+        Ok(date)
+    }
+    ///The last day of the (possibly partial) date
+    fn latest(&self, date: &Date) -> Date {
+        let month = date.month.unwrap_or_else(|| self.months_in_year(date.year));
+        let day = date
+            .day
+            .unwrap_or_else(|| self.days_in_month(date.year, month));
+        Date::ymd(date.year, month, day)
+    }
+    ///The earliest and latest days the date could be, where None is unbounded
+    fn range(&self, date: &GenDate) -> (Option<Date>, Option<Date>) {
+        let approx = |d: &Date, years: i32| Date {
+            year: d.year.saturating_add(years),
+            ..*d
+        };
+        match date {
+            GenDate::Exact(d) | GenDate::Calculated(d) => {
+                (Some(d.earliest()), Some(self.latest(d)))
+            }
+            GenDate::About(d) | GenDate::Estimated(d) => (
+                Some(approx(d, -APPROX_YEARS).earliest()),
+                Some(self.latest(&approx(d, APPROX_YEARS))),
+            ),
+            GenDate::Before(d) => (None, Some(self.ordinal_to_date(self.to_ordinal(*d) - 1))),
+            GenDate::After(d) => (
+                Some(self.ordinal_to_date(self.to_ordinal(self.latest(d)) + 1)),
+                None,
+            ),
+            GenDate::Between(a, b) => (Some(a.earliest()), Some(self.latest(b))),
+        }
+    }
+    ///Orders dates by when they were written for, so that "bef 1900" sorts before "1900"
+    /// and "1900" before "aft 1900"
+    fn compare(&self, a: &GenDate, b: &GenDate) -> Ordering {
+        a.date()
+            .earliest()
+            .cmp(&b.date().earliest())
+            .then(a.rank().cmp(&b.rank()))
+            .then_with(|| self.range(a).cmp(&self.range(b)))
+            .then_with(|| self.format(a).cmp(&self.format(b)))
+    }
+    ///The number of whole years between the two full dates
+    fn years_between(&self, from: Date, to: Date) -> i32 {
+        let (from, to) = (from.earliest(), to.earliest());
+        let years = to.year - from.year;
+        if (to.month, to.day) < (from.month, from.day) {
+            years - 1
+        } else {
+            years
+        }
+    }
+}
+
+///Converts a date from one calendar to another through their ordinals
+pub fn convert(date: Date, from: &dyn Calendar, to: &dyn Calendar) -> Date {
+    to.ordinal_to_date(from.to_ordinal(date))
+}
+
+///The proleptic Gregorian calendar, with day 0 being 1 Jan 1970
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Gregorian;
+impl Gregorian {
+    pub fn is_leap_year(year: i32) -> bool {
+        (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+    }
+}
+impl Calendar for Gregorian {
+    fn name(&self) -> &str {
+        "Gregorian"
+    }
+    fn months_in_year(&self, _year: i32) -> u8 {
+        12
+    }
+    fn days_in_month(&self, year: i32, month: u8) -> u8 {
+        match month {
+            2 if Gregorian::is_leap_year(year) => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        }
+    }
+    fn month_name(&self, month: u8) -> String {
+        match MONTHS.get((month as usize).wrapping_sub(1)) {
+            Some(name) => name.to_string(),
+            None => month.to_string(),
+        }
+    }
+    fn days_in_year(&self, year: i32) -> i64 {
+        if Gregorian::is_leap_year(year) {
+            366
+        } else {
+            365
+        }
+    }
+    //Howard Hinnant's days_from_civil
+    fn to_ordinal(&self, date: Date) -> i64 {
+        let d = date.earliest();
+        let (m, day) = (d.month.unwrap() as i64, d.day.unwrap() as i64);
+        let y = d.year as i64 - (m <= 2) as i64;
+        let era = y.div_euclid(400);
+        let yoe = y - era * 400;
+        let doy = (153 * ((m + 9) % 12) + 2) / 5 + day - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146097 + doe - 719468
+    }
+    //and its inverse, civil_from_days
+    fn ordinal_to_date(&self, ordinal: i64) -> Date {
+        let z = ordinal + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + (month <= 2) as i64;
+        Date::ymd(year as i32, month as u8, day as u8)
+    }
+}
+
+///A month of a [CustomCalendar]
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct CustomMonth {
+    pub name: String,
+    pub days: u8,
+}
+///Adds a day to one month every so many years
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct LeapRule {
+    ///Years divisible by this are leap years
+    pub every: u32,
+    ///The month (starting from 1) that gains the day
+    pub month: u8,
+}
+///A named span of years, such as "AE" (After the Exile). Years in an era count up from its start,
+/// unless it is `backwards`, in which case they count down from it, like years BC.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Era {
+    pub name: String,
+    ///The year, in the calendar's own numbering, that is year 1 of the era
+    pub start: i32,
+    #[serde(default)]
+    pub backwards: bool,
+}
+///An invented calendar, such as one from a fictional world. It is checked with
+/// [CustomCalendar::validate] when it is made or deserialized.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(try_from = "CustomCalendarFields")]
+pub struct CustomCalendar {
+    pub name: String,
+    pub months: Vec<CustomMonth>,
+    #[serde(default)]
+    pub leap: Option<LeapRule>,
+    #[serde(default)]
+    pub eras: Vec<Era>,
+    ///The ordinal of the first day of year 0, see [Calendar::epoch]
+    #[serde(default)]
+    pub epoch: i64,
+}
+//the fields of a calendar as they are deserialized, before they are checked
+#[derive(Deserialize)]
+struct CustomCalendarFields {
+    name: String,
+    months: Vec<CustomMonth>,
+    #[serde(default)]
+    leap: Option<LeapRule>,
+    #[serde(default)]
+    eras: Vec<Era>,
+    #[serde(default)]
+    epoch: i64,
+}
+impl TryFrom<CustomCalendarFields> for CustomCalendar {
+    type Error = anyhow::Error;
+    fn try_from(fields: CustomCalendarFields) -> anyhow::Result<Self> {
+        let calendar = CustomCalendar {
+            name: fields.name,
+            months: fields.months,
+            leap: fields.leap,
+            eras: fields.eras,
+            epoch: fields.epoch,
+        };
+        calendar.validate()?;
+        Ok(calendar)
+    }
+}
+impl CustomCalendar {
+    ///A calendar with the given month names and lengths, which must be a valid one
+    pub fn new(name: &str, months: &[(&str, u8)]) -> anyhow::Result<Self> {
+        let calendar = CustomCalendar {
+            name: name.to_string(),
+            months: months
+                .iter()
+                .map(|(name, days)| CustomMonth {
+                    name: name.to_string(),
+                    days: *days,
+                })
+                .collect(),
+            leap: None,
+            eras: Vec::new(),
+            epoch: 0,
+        };
+        calendar.validate()?;
+        Ok(calendar)
+    }
+    pub fn with_leap_rule(mut self, every: u32, month: u8) -> anyhow::Result<Self> {
+        self.leap = Some(LeapRule { every, month });
+        self.validate()?;
+        Ok(self)
+    }
+    ///Checks that every year has at least one month, that no month is empty or too long to
+    /// count its days, and that the leap rule names a month and a number of years, as days could
+    /// not be counted otherwise
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.months.is_empty() || self.months.len() > u8::MAX as usize {
+            anyhow::bail!("Calendar {} must have 1 to 255 months", self.name);
+        }
+        if let Some(month) = self.months.iter().find(|m| m.days == 0) {
+            anyhow::bail!("Month {} of calendar {} has no days", month.name, self.name);
+        }
+        if let Some(leap) = self.leap {
+            let month = self.months.get((leap.month as usize).wrapping_sub(1));
+            if leap.every == 0 || month.is_none_or(|m| m.days == u8::MAX) {
+                anyhow::bail!("Invalid leap rule for calendar {}", self.name);
+            }
+        }
+        Ok(())
+    }
+    pub fn with_era(mut self, name: &str, start: i32, backwards: bool) -> Self {
+        self.eras.push(Era {
+            name: name.to_string(),
+            start,
+            backwards,
+        });
+        self
+    }
+    pub fn with_epoch(mut self, epoch: i64) -> Self {
+        self.epoch = epoch;
+        self
+    }
+    ///The era the year is written in and the year within it
+    fn era_of(&self, year: i32) -> Option<(&Era, i32)> {
+        let forward = self
+            .eras
+            .iter()
+            .filter(|e| !e.backwards && year >= e.start)
+            .max_by_key(|e| e.start)
+            .map(|e| (e, year - e.start + 1));
+        forward.or_else(|| {
+            self.eras
+                .iter()
+                .filter(|e| e.backwards && year <= e.start)
+                .min_by_key(|e| e.start)
+                .map(|e| (e, e.start - year + 1))
+        })
+    }
+}
+impl Calendar for CustomCalendar {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn as_custom(&self) -> Option<&CustomCalendar> {
+        Some(self)
+    }
+    fn months_in_year(&self, _year: i32) -> u8 {
+        self.months.len() as u8
+    }
+    fn days_in_month(&self, year: i32, month: u8) -> u8 {
+        let days = self
+            .months
+            .get((month as usize).wrapping_sub(1))
+            .map_or(0, |m| m.days);
+        match self.leap {
+            Some(leap) if leap.month == month && year.rem_euclid(leap.every as i32) == 0 => {
+                days + 1
+            }
+            _ => days,
+        }
+    }
+    fn month_name(&self, month: u8) -> String {
+        match self.months.get((month as usize).wrapping_sub(1)) {
+            Some(m) => m.name.clone(),
+            None => month.to_string(),
+        }
+    }
+    fn epoch(&self) -> i64 {
+        self.epoch
+    }
+    fn format_year(&self, year: i32, _dual: bool) -> String {
+        match self.era_of(year) {
+            Some((era, n)) => format!("{} {}", n, era.name),
+            None => year.to_string(),
+        }
+    }
+    ///Parses a year followed by the name of its era, or a bare year in the calendar's own numbering
+    fn parse_year(&self, y: &str) -> anyhow::Result<Date> {
+        let Some((n, name)) = y.split_once(' ') else {
+            return Ok(Date::year(y.parse()?));
+        };
+        let n: i32 = n.parse()?;
+        let era = self
+            .eras
+            .iter()
+            .find(|e| e.name.eq_ignore_ascii_case(name.trim()))
+            .ok_or_else(|| anyhow::anyhow!("Unknown era {:?}", name))?;
+        if n < 1 {
+            anyhow::bail!("Invalid year {} of era {}", n, era.name);
+        }
+        let year = if era.backwards {
+            era.start.checked_sub(n - 1)
+        } else {
+            era.start.checked_add(n - 1)
+        };
+        let year =
+            year.ok_or_else(|| anyhow::anyhow!("Year {} of era {} is out of range", n, era.name))?;
+        Ok(Date::year(year))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shire() -> CustomCalendar {
+        //twelve months of thirty days, with a leap day every fourth year
+        let months = [
+            "Afteryule",
+            "Solmath",
+            "Rethe",
+            "Astron",
+            "Thrimidge",
+            "Forelithe",
+            "Afterlithe",
+            "Wedmath",
+            "Halimath",
+            "Winterfilth",
+            "Blotmath",
+            "Foreyule",
+        ];
+        let months = months.map(|m| (m, 30));
+        CustomCalendar::new("Shire", &months)
+            .unwrap()
+            .with_leap_rule(4, 6)
+            .unwrap()
+            .with_era("SR", 1601, false)
+            .with_era("BSR", 1600, true)
+    }
+    #[test]
+    fn gregorian_ordinals() {
+        let g = Gregorian;
+        assert_eq!(g.to_ordinal(Date::ymd(1970, 1, 1)), 0);
+        assert_eq!(g.to_ordinal(Date::ymd(2000, 3, 1)), 11017);
+        for ordinal in [-800_000, -1, 0, 59, 11017, 400_000] {
+            assert_eq!(g.to_ordinal(g.ordinal_to_date(ordinal)), ordinal);
+        }
+        assert_eq!(
+            g.years_between(Date::ymd(1850, 3, 12), Date::ymd(1900, 3, 11)),
+            49
+        );
+    }
+    #[test]
+    fn custom_calendar() {
+        let shire = shire();
+        assert_eq!(shire.days_in_year(1604), 361);
+        assert_eq!(shire.days_in_year(1605), 360);
+        let d = shire.parse_date("2 Halimath 1341 SR").unwrap();
+        assert_eq!(d, Date::ymd(2941, 9, 2));
+        assert_eq!(shire.format_date(&d), "2 Halimath 1341 SR");
+        assert_eq!(shire.format_date(&Date::year(1500)), "101 BSR");
+        assert_eq!(shire.parse_date("101 BSR").unwrap(), Date::year(1500));
+        assert!(shire.parse_date("31 Solmath 1341 SR").is_err());
+        assert!(shire.parse_date("1 Mar 1341 SR").is_err());
+        assert!(shire.parse_date("2147483647 SR").is_err());
+        assert!(shire.parse("abt 998400 SR").is_ok());
+        assert!(shire.parse("abt 998401 SR").is_err());
+        for ordinal in [-1000, 0, 1_000_000] {
+            assert_eq!(shire.to_ordinal(shire.ordinal_to_date(ordinal)), ordinal);
+        }
+        //the day after the last of a month is the first of the next
+        let after: GenDate = shire.parse("aft 30 Halimath 1341 SR").unwrap();
+        assert_eq!(shire.range(&after).0, Some(Date::ymd(2941, 10, 1)));
+        let converted = convert(Date::ymd(1970, 1, 1), &Gregorian, &shire.with_epoch(10));
+        assert_eq!(converted, Date::ymd(-1, 12, 21));
+    }
+    #[test]
+    fn invalid_custom_calendars() {
+        assert!(CustomCalendar::new("Empty", &[]).is_err());
+        assert!(CustomCalendar::new("Short", &[("One", 30), ("Two", 0)]).is_err());
+        let calendar = CustomCalendar::new("Long", &[("One", 255)]).unwrap();
+        assert!(calendar.clone().with_leap_rule(4, 1).is_err());
+        assert!(calendar.clone().with_leap_rule(0, 1).is_err());
+        assert!(calendar.with_leap_rule(4, 2).is_err());
+        let json = r#"{"name": "Empty", "months": []}"#;
+        assert!(serde_json::from_str::<CustomCalendar>(json).is_err());
+        let json = r#"{"name": "Short", "months": [{"name": "One", "days": 0}]}"#;
+        assert!(serde_json::from_str::<CustomCalendar>(json).is_err());
+        let json = r#"{"name": "Moons", "months": [{"name": "One", "days": 28}]}"#;
+        assert!(serde_json::from_str::<CustomCalendar>(json).is_ok());
+    }
+}
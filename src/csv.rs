@@ -0,0 +1,363 @@
+//! A two table CSV format, for loading persons and relations from spreadsheets.
+//!
+//! The persons table has the columns `id`, `name` and `sex` (M or F), and may have `birth` and
+//! `death` columns with dates in the graph's calendar. A `ref:<system>` column holds the ids of
+//! the persons in another system, and any other column is an attribute, written as
+//! [AttrValue::parse] reads it. The relations table has the columns `from`, `to` and `kind`, with
+//! the kinds written as in the DSL (e.g. PARENT or ADOPTIVE_CHILD), and any other column is an
+//! attribute of the relation. Empty cells are left out.
+//!
+//! The ids of the persons table are kept as `csv` external refs. Partnerships and how parents are
+//! parents ([crate::Parentage]) are not part of the format.
+use crate::kin_dsl::{kind_keyword, parse_kind};
+use crate::{AttrValue, KinGraph, Person, Sex};
+use anyhow::{anyhow, bail, Context, Result};
+use petgraph::visit::EdgeRef;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::io::{Read, Write};
+
+///The external ref system that the ids of the persons table are kept under
+pub const CSV_REF: &str = "csv";
+const PERSON_COLUMNS: [&str; 5] = ["id", "name", "sex", "birth", "death"];
+const RELATION_COLUMNS: [&str; 3] = ["from", "to", "kind"];
+
+///One of the two tables of the CSV format
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CsvTable {
+    Persons,
+    Relations,
+}
+///A row that could not be read, and was left out
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct CsvError {
+    pub table: CsvTable,
+    ///The line the row starts on, counting the header as line 1
+    pub line: u64,
+    pub message: String,
+}
+impl std::fmt::Display for CsvError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let table = match self.table {
+            CsvTable::Persons => "persons",
+            CsvTable::Relations => "relations",
+        };
+        write!(f, "{} line {}: {}", table, self.line, self.message)
+    }
+}
+///The graph read from CSV tables, along with the rows that could not be read
+pub struct CsvImport {
+    pub graph: KinGraph,
+    pub errors: Vec<CsvError>,
+}
+
+//the cells of each row, along with the line it starts on
+type Rows = Vec<(u64, Vec<String>)>;
+
+//the rows of a table along with its header
+fn read_table<R: Read>(
+    reader: R,
+    table: CsvTable,
+    required: &[&str],
+    errors: &mut Vec<CsvError>,
+) -> Result<(Vec<String>, Rows)> {
+    let mut reader = ::csv::ReaderBuilder::new()
+        .trim(::csv::Trim::All)
+        .from_reader(reader);
+    let header = reader
+        .headers()?
+        .iter()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>();
+    for column in required {
+        if !header.iter().any(|h| h == column) {
+            bail!("The {:?} table has no {:?} column", table, column);
+        }
+    }
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        match record {
+            Ok(record) => {
+                let line = record.position().map_or(0, |p| p.line());
+                rows.push((line, record.iter().map(str::to_string).collect()));
+            }
+            Err(e) => errors.push(CsvError {
+                table,
+                line: e.position().map_or(0, |p| p.line()),
+                message: e.to_string(),
+            }),
+        }
+    }
+    Ok((header, rows))
+}
+
+impl KinGraph {
+    ///Reads persons and relations from the two CSV tables. Rows that can not be read are left
+    /// out and reported with their line, while a table without one of its required columns is an
+    /// error.
+    pub fn from_csv<P: Read, R: Read>(persons: P, relations: R) -> Result<CsvImport> {
+        let mut errors = Vec::new();
+        let (header, rows) = read_table(persons, CsvTable::Persons, &["id", "sex"], &mut errors)?;
+        let mut kg = KinGraph::new();
+        let mut ids = HashMap::new();
+        for (line, row) in rows {
+            let cells = header
+                .iter()
+                .map(String::as_str)
+                .zip(row.iter().map(String::as_str));
+            if let Err(e) = kg.csv_person(cells, &mut ids) {
+                errors.push(CsvError {
+                    table: CsvTable::Persons,
+                    line,
+                    message: format!("{:#}", e),
+                });
+            }
+        }
+        let (header, rows) = read_table(
+            relations,
+            CsvTable::Relations,
+            &RELATION_COLUMNS,
+            &mut errors,
+        )?;
+        for (line, row) in rows {
+            let cells = header
+                .iter()
+                .map(String::as_str)
+                .zip(row.iter().map(String::as_str));
+            if let Err(e) = kg.csv_relation(cells, &ids) {
+                errors.push(CsvError {
+                    table: CsvTable::Relations,
+                    line,
+                    message: format!("{:#}", e),
+                });
+            }
+        }
+        errors.sort_by_key(|e| (e.table == CsvTable::Relations, e.line));
+        Ok(CsvImport { graph: kg, errors })
+    }
+    //adds the person of a row, after checking all of it so that a bad row adds nothing
+    fn csv_person<'a>(
+        &mut self,
+        cells: impl Iterator<Item = (&'a str, &'a str)>,
+        ids: &mut HashMap<String, Person>,
+    ) -> Result<()> {
+        let (mut id, mut name, mut sex) = ("", crate::names::UNKNOWN_NAME, None);
+        let (mut birth, mut death) = (None, None);
+        let (mut refs, mut attributes) = (Vec::new(), Vec::new());
+        for (column, cell) in cells.filter(|(_, cell)| !cell.is_empty()) {
+            match column {
+                "id" => id = cell,
+                "name" => name = cell,
+                "sex" => {
+                    sex = Some(match cell.to_uppercase().as_str() {
+                        "M" | "MALE" => Sex::Male,
+                        "F" | "FEMALE" => Sex::Female,
+                        _ => bail!("Invalid sex {:?}", cell),
+                    })
+                }
+                "birth" => birth = Some(self.parse_date(cell).context("Invalid birth")?),
+                "death" => death = Some(self.parse_date(cell).context("Invalid death")?),
+                _ => match column.strip_prefix("ref:") {
+                    Some(system) => {
+                        if self.find_by_ref(system, cell).is_some() {
+                            bail!("Another person has the {} ref {:?}", system, cell);
+                        }
+                        refs.push((system, cell));
+                    }
+                    None => {
+                        let value = AttrValue::parse(cell, self.calendar())
+                            .with_context(|| format!("Invalid {:?}", column))?;
+                        attributes.push((column, value));
+                    }
+                },
+            }
+        }
+        if id.is_empty() {
+            bail!("Missing id");
+        }
+        if ids.contains_key(id) {
+            bail!("Another person has the id {:?}", id);
+        }
+        let sex = sex.ok_or_else(|| anyhow!("Missing sex"))?;
+        let p = self.np_with_name(sex, name.to_string());
+        self.set_birth(&p, birth)?;
+        self.set_death(&p, death)?;
+        for (key, value) in attributes {
+            self.set_attr(&p, key, value)?;
+        }
+        for (system, value) in refs {
+            self.set_external_ref(&p, system, value)?;
+        }
+        self.set_external_ref(&p, CSV_REF, id)?;
+        ids.insert(id.to_string(), p);
+        Ok(())
+    }
+    //adds the relation of a row, along with its attributes
+    fn csv_relation<'a>(
+        &mut self,
+        cells: impl Iterator<Item = (&'a str, &'a str)>,
+        ids: &HashMap<String, Person>,
+    ) -> Result<()> {
+        let (mut from, mut to, mut kind) = (None, None, None);
+        let mut attributes = Vec::new();
+        let person = |id: &str| {
+            ids.get(id)
+                .ok_or_else(|| anyhow!("Unknown person {:?}", id))
+        };
+        for (column, cell) in cells.filter(|(_, cell)| !cell.is_empty()) {
+            match column {
+                "from" => from = Some(person(cell)?),
+                "to" => to = Some(person(cell)?),
+                "kind" => {
+                    let parsed = parse_kind(&cell.to_uppercase());
+                    kind = Some(parsed.map_err(|_| anyhow!("Invalid kind {:?}", cell))?);
+                }
+                _ => {
+                    let value = AttrValue::parse(cell, self.calendar())
+                        .with_context(|| format!("Invalid {:?}", column))?;
+                    attributes.push((column, value));
+                }
+            }
+        }
+        let missing = |column| anyhow!("Missing {}", column);
+        let (from, to) = (from.ok_or(missing("from"))?, to.ok_or(missing("to"))?);
+        let kind = kind.ok_or(missing("kind"))?;
+        self.add_relation(from, to, kind)?;
+        for (key, value) in attributes {
+            self.set_relation_attr(from, to, kind, key, value)?;
+        }
+        Ok(())
+    }
+    ///Writes the persons and relations of the graph as the two CSV tables. Each relation is
+    /// written once, from the parent, guardian or (for symmetric kinds) the earlier person, along
+    /// with its attributes.
+    pub fn to_csv<P: Write, R: Write>(&self, persons: P, relations: R) -> Result<()> {
+        let mut used = HashSet::new();
+        let mut ids = HashMap::new();
+        for n in self.graph.node_indices() {
+            let read_with = self.graph[n].external_ref(CSV_REF);
+            if let Some(id) = read_with.filter(|id| used.insert(id.to_string())) {
+                ids.insert(n, id.to_string());
+            }
+        }
+        let mut next = 0;
+        for n in self.graph.node_indices() {
+            while !ids.contains_key(&n) {
+                next += 1;
+                if used.insert(next.to_string()) {
+                    ids.insert(n, next.to_string());
+                }
+            }
+        }
+        let persons_of = || self.graph.node_weights();
+        let systems = persons_of()
+            .flat_map(|p| p.refs.keys())
+            .filter(|s| *s != CSV_REF)
+            .collect::<BTreeSet<_>>();
+        let keys = persons_of()
+            .flat_map(|p| p.attributes.keys())
+            .filter(|k| !PERSON_COLUMNS.contains(&k.as_str()) && !k.starts_with("ref:"))
+            .collect::<BTreeSet<_>>();
+        let mut writer = ::csv::Writer::from_writer(persons);
+        let header = PERSON_COLUMNS.iter().map(|c| c.to_string());
+        let header = header.chain(systems.iter().map(|s| format!("ref:{}", s)));
+        writer.write_record(header.chain(keys.iter().map(|k| k.to_string())))?;
+        for n in self.graph.node_indices() {
+            let p = &self.graph[n];
+            let sex = match p.sex {
+                Sex::Male => "M",
+                Sex::Female => "F",
+            };
+            let date = |d: Option<_>| d.map_or(String::new(), |d| self.format_date(&d));
+            let mut row = vec![ids[&n].clone(), p.name.clone(), sex.into()];
+            row.extend([date(p.birth), date(p.death)]);
+            row.extend(
+                systems
+                    .iter()
+                    .map(|s| p.external_ref(s).unwrap_or_default().into()),
+            );
+            let value = |k: &&String| {
+                p.attr(k)
+                    .map_or(String::new(), |v| v.format(self.calendar()))
+            };
+            row.extend(keys.iter().map(value));
+            writer.write_record(row)?;
+        }
+        writer.flush()?;
+
+        let keys = self
+            .relation_attrs
+            .values()
+            .flat_map(|attrs| attrs.keys())
+            .filter(|k| !RELATION_COLUMNS.contains(&k.as_str()))
+            .collect::<BTreeSet<_>>();
+        let mut writer = ::csv::Writer::from_writer(relations);
+        let header = RELATION_COLUMNS.iter().map(|c| c.to_string());
+        writer.write_record(header.chain(keys.iter().map(|k| k.to_string())))?;
+        for e in self.graph.edge_references() {
+            let (from, to, kind) = (e.source(), e.target(), *e.weight());
+            if !kind.is_written_from(from.index(), to.index()) {
+                continue;
+            }
+            let attributes = self.relation_attrs_of(from, to, kind);
+            let mut row = vec![
+                ids[&from].clone(),
+                ids[&to].clone(),
+                kind_keyword(kind).into(),
+            ];
+            row.extend(keys.iter().map(|k| {
+                let value = attributes.and_then(|attrs| attrs.get(*k));
+                value.map_or(String::new(), |v| v.format(self.calendar()))
+            }));
+            writer.write_record(row)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn row_errors() {
+        let persons = "\
+id,name,sex,birth,occupation
+1,Mike,M,abt 1850,smith
+2,Izy,X,,
+3,Ann,F,someday,
+1,Bob,M,,
+,Nobody,M,,
+4,Tom,M
+5,Sue,female,,\"[a, b]\"
+";
+        let relations = "\
+from,to,kind,note
+1,5,PARENT,date(aft 1870)
+1,9,PARENT,
+5,1,COUSIN,
+5,1,parent,
+";
+        let CsvImport { graph, errors } =
+            KinGraph::from_csv(persons.as_bytes(), relations.as_bytes()).unwrap();
+        let errors = errors.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+        assert_eq!(errors.len(), 8, "{:?}", errors);
+        for (error, expected) in errors.iter().zip([
+            "persons line 3: Invalid sex \"X\"",
+            "persons line 4: Invalid birth",
+            "persons line 5: Another person has the id \"1\"",
+            "persons line 6: Missing id",
+            "persons line 7: ",
+            "relations line 3: Unknown person \"9\"",
+            "relations line 4: Invalid kind \"COUSIN\"",
+            "relations line 5: ",
+        ]) {
+            assert!(error.starts_with(expected), "{:?}", errors);
+        }
+        assert_eq!(graph.graph.node_count(), 2);
+        let sue = graph.find_by_ref(CSV_REF, "5").unwrap();
+        assert_eq!(sue.attr("occupation"), Some(&vec!["a", "b"].into()));
+        let header = "id,name";
+        assert!(KinGraph::from_csv(header.as_bytes(), relations.as_bytes()).is_err());
+    }
+}
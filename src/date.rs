@@ -0,0 +1,314 @@
+//! Dates that can be attached to persons and the relations between them.
+//!
+//! Genealogical data is rarely exact, so besides plain (possibly partial) [Date]s there is
+//! [GenDate], which can be approximate ("abt 1850"), bounded ("bef 1900"), ranged
+//! ("bet 1820 and 1825") or dual dated ("1710/11").
+//!
+//! How long months are and what they are called depends on the [Calendar]; the `Display` and
+//! `FromStr` impls use the [Gregorian] one.
+use crate::calendar::{Calendar, Gregorian};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+///How many years either side of an approximate date it may actually have been
+pub const APPROX_YEARS: i32 = 5;
+///The latest year, and (negated) the earliest, that a date can be parsed with, so that the days
+/// around it can always be counted
+pub const MAX_YEAR: i32 = 1_000_000;
+
+///A calendar date. Only the year is required, so that partially known dates
+/// (e.g. only the year of a marriage) can still be recorded.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+pub struct Date {
+    pub year: i32,
+    pub month: Option<u8>,
+    pub day: Option<u8>,
+    ///The year was written in dual style (e.g. 1710/11), in which case `year` is the later,
+    /// new style, year
+    #[serde(default)]
+    pub dual: bool,
+}
+impl Date {
+    ///A date with a known year, month and day
+    pub fn ymd(year: i32, month: u8, day: u8) -> Self {
+        Date {
+            year,
+            month: Some(month),
+            day: Some(day),
+            dual: false,
+        }
+    }
+    ///A date of which only the year and month are known
+    pub fn ym(year: i32, month: u8) -> Self {
+        Date {
+            year,
+            month: Some(month),
+            day: None,
+            dual: false,
+        }
+    }
+    ///A date of which only the year is known
+    pub fn year(year: i32) -> Self {
+        Date {
+            year,
+            month: None,
+            day: None,
+            dual: false,
+        }
+    }
+    ///The first day this (possibly partial) date could be
+    pub fn earliest(&self) -> Date {
+        Date::ymd(self.year, self.month.unwrap_or(1), self.day.unwrap_or(1))
+    }
+}
+impl std::fmt::Display for Date {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Gregorian.format_date(self))
+    }
+}
+impl FromStr for Date {
+    type Err = anyhow::Error;
+    ///Parses a Gregorian date, see [Calendar::parse_date]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Gregorian.parse_date(s)
+    }
+}
+///Reads and writes dates of any calendar by month number, for serialization
+struct Lenient;
+impl Calendar for Lenient {
+    fn name(&self) -> &str {
+        "Lenient"
+    }
+    fn months_in_year(&self, _year: i32) -> u8 {
+        u8::MAX
+    }
+    fn days_in_month(&self, _year: i32, _month: u8) -> u8 {
+        u8::MAX
+    }
+    fn month_name(&self, month: u8) -> String {
+        Gregorian.month_name(month)
+    }
+}
+
+///A genealogical date, which can be qualified with how certain it is
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum GenDate {
+    Exact(Date),
+    ///About, give or take [APPROX_YEARS]
+    About(Date),
+    ///Calculated from other data, e.g. an age at death
+    Calculated(Date),
+    ///Estimated, give or take [APPROX_YEARS]
+    Estimated(Date),
+    Before(Date),
+    After(Date),
+    ///Some time between the two dates, inclusive
+    Between(Date, Date),
+}
+impl From<Date> for GenDate {
+    fn from(d: Date) -> Self {
+        GenDate::Exact(d)
+    }
+}
+impl GenDate {
+    ///The earliest and latest days this date could be in the calendar, where None is unbounded
+    pub fn range(&self, calendar: &dyn Calendar) -> (Option<Date>, Option<Date>) {
+        calendar.range(self)
+    }
+    ///The date that was written, used for sorting. For a range this is its start.
+    pub fn date(&self) -> Date {
+        match self {
+            GenDate::Exact(d)
+            | GenDate::About(d)
+            | GenDate::Calculated(d)
+            | GenDate::Estimated(d)
+            | GenDate::Before(d)
+            | GenDate::After(d)
+            | GenDate::Between(d, _) => *d,
+        }
+    }
+    ///The date with each of the days it is written with changed, e.g. converted to another
+    /// calendar
+    pub fn map(self, f: impl Fn(Date) -> Date) -> GenDate {
+        match self {
+            GenDate::Exact(d) => GenDate::Exact(f(d)),
+            GenDate::About(d) => GenDate::About(f(d)),
+            GenDate::Calculated(d) => GenDate::Calculated(f(d)),
+            GenDate::Estimated(d) => GenDate::Estimated(f(d)),
+            GenDate::Before(d) => GenDate::Before(f(d)),
+            GenDate::After(d) => GenDate::After(f(d)),
+            GenDate::Between(a, b) => GenDate::Between(f(a), f(b)),
+        }
+    }
+    ///Whether the two dates could be the same day
+    pub fn overlaps(&self, other: &GenDate, calendar: &dyn Calendar) -> bool {
+        let (s1, e1) = self.range(calendar);
+        let (s2, e2) = other.range(calendar);
+        let starts_before_other_ends = match (s1, e2) {
+            (Some(s), Some(e)) => s <= e,
+            _ => true,
+        };
+        let ends_after_other_starts = match (e1, s2) {
+            (Some(e), Some(s)) => e >= s,
+            _ => true,
+        };
+        starts_before_other_ends && ends_after_other_starts
+    }
+    ///Whether this date is certainly after the (possibly partial) date
+    pub fn is_after(&self, date: Date, calendar: &dyn Calendar) -> bool {
+        self.range(calendar)
+            .0
+            .is_some_and(|s| s > calendar.latest(&date))
+    }
+    ///Whether this date was certainly on or before the end of the (possibly partial) date
+    pub fn is_on_or_before(&self, date: Date, calendar: &dyn Calendar) -> bool {
+        self.range(calendar)
+            .1
+            .is_some_and(|e| e <= calendar.latest(&date))
+    }
+    //orders qualifiers that share a date, so that "bef 1900" sorts before "1900"
+    pub(crate) fn rank(&self) -> u8 {
+        match self {
+            GenDate::Before(_) => 0,
+            GenDate::Between(..) => 1,
+            GenDate::About(_) | GenDate::Estimated(_) | GenDate::Calculated(_) => 2,
+            GenDate::Exact(_) => 3,
+            GenDate::After(_) => 4,
+        }
+    }
+}
+///Orders Gregorian dates, see [Calendar::compare] for other calendars
+impl Ord for GenDate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        Gregorian.compare(self, other)
+    }
+}
+impl PartialOrd for GenDate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl std::fmt::Display for GenDate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Gregorian.format(self))
+    }
+}
+impl FromStr for GenDate {
+    type Err = anyhow::Error;
+    ///Parses a Gregorian date, see [Calendar::parse]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Gregorian.parse(s)
+    }
+}
+///Serialized as text, with the months of calendars other than the Gregorian one written as
+/// the Gregorian month of the same number, or the number itself past the twelfth
+impl Serialize for GenDate {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&Lenient.format(self))
+    }
+}
+impl<'de> Deserialize<'de> for GenDate {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Lenient.parse(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_format() {
+        for s in [
+            "1850",
+            "Mar 1850",
+            "12 Mar 1850",
+            "abt 1850",
+            "cal 1 Jan 1900",
+            "est 1777",
+            "bef 1900",
+            "aft Jun 1901",
+            "bet 1820 and 1825",
+            "1710/11",
+            "3 Feb 1710/11",
+        ] {
+            let d: GenDate = s.parse().unwrap();
+            assert_eq!(d.to_string(), s);
+        }
+        let d: GenDate = "Between 1820 AND 1825".parse().unwrap();
+        assert_eq!(d, GenDate::Between(Date::year(1820), Date::year(1825)));
+        let d: GenDate = "1850-03-12".parse().unwrap();
+        assert_eq!(d, GenDate::Exact(Date::ymd(1850, 3, 12)));
+        assert_eq!("1710/11".parse::<Date>().unwrap().year, 1711);
+        assert!("1710/13".parse::<GenDate>().is_err());
+        assert!("31 Feb 1850".parse::<GenDate>().is_err());
+        assert!("bet 1830 and 1825".parse::<GenDate>().is_err());
+        //years too far out to count the days around, and dual year suffixes too long to compare
+        for s in [
+            "abt 2147483647",
+            "aft -2147483648",
+            "bef 1000001",
+            "1710/00000000000",
+            "2147483646/47",
+            "-1000000/00000001",
+        ] {
+            assert!(s.parse::<GenDate>().is_err(), "{}", s);
+        }
+        assert_eq!("-1000000".parse::<Date>().unwrap().year, -MAX_YEAR);
+        //text that changes length when lowercased, or starts with more than one byte
+        for s in ["é", "İ 1850", "BETWEEN İ1820 AND 1825", "bet 1820 ánd 1825"] {
+            assert!(s.parse::<GenDate>().is_err());
+        }
+    }
+    #[test]
+    fn ordering_and_overlap() {
+        let mut dates = ["aft 1850", "1850", "bef 1850", "abt 1849", "Jan 1850"]
+            .iter()
+            .map(|s| s.parse::<GenDate>().unwrap())
+            .collect::<Vec<_>>();
+        dates.sort();
+        let sorted = dates.iter().map(|d| d.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            sorted,
+            ["abt 1849", "bef 1850", "Jan 1850", "1850", "aft 1850"]
+        );
+
+        let abt: GenDate = "abt 1850".parse().unwrap();
+        let bef: GenDate = "bef 1846".parse().unwrap();
+        let bet: GenDate = "bet 1820 and 1825".parse().unwrap();
+        let g = &Gregorian;
+        assert!(abt.overlaps(&bef, g));
+        assert!(!abt.overlaps(&bet, g));
+        assert!(!GenDate::from(Date::year(1850)).overlaps(&"aft 1850".parse().unwrap(), g));
+        assert!(bet.is_on_or_before(Date::year(1825), g));
+        assert!(!abt.is_after(Date::year(1846), g));
+        assert!(abt.is_after(Date::year(1844), g));
+
+        //the furthest years that can be parsed, and ones made directly that are further out
+        let far: GenDate = "abt 1000000".parse().unwrap();
+        let before: GenDate = "bef -1000000".parse().unwrap();
+        assert!(before < far);
+        let after: GenDate = "aft 1000000".parse().unwrap();
+        assert_eq!(after.range(g).0, Some(Date::ymd(MAX_YEAR + 1, 1, 1)));
+        assert!(!before.overlaps(&far, g));
+        let max = GenDate::About(Date::year(i32::MAX));
+        let min = GenDate::Estimated(Date::year(i32::MIN));
+        assert_eq!(max.range(g).1.unwrap().year, i32::MAX);
+        assert!(min < max);
+        assert!(max.overlaps(&max, g));
+    }
+    #[test]
+    fn serde_round_trip() {
+        let d: GenDate = "bet Mar 1710/11 and 1712".parse().unwrap();
+        let json = serde_json::to_string(&d).unwrap();
+        assert_eq!(json, "\"bet Mar 1710/11 and 1712\"");
+        assert_eq!(serde_json::from_str::<GenDate>(&json).unwrap(), d);
+        //a thirteenth month of some other calendar
+        let d = GenDate::About(Date::ymd(340, 13, 40));
+        let json = serde_json::to_string(&d).unwrap();
+        assert_eq!(json, "\"abt 40 13 340\"");
+        assert_eq!(serde_json::from_str::<GenDate>(&json).unwrap(), d);
+    }
+}
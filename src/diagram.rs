@@ -0,0 +1,203 @@
+//! Export of the graph as Mermaid flowcharts and PlantUML diagrams, for documentation.
+//!
+//! Both draw each relation once, from the parent (or guardian) to the child, with men as
+//! rectangles and women as rounded shapes. Shadow persons are dashed and the focus person, if
+//! there is one, is bold. Each node id is `n` followed by the index of the person.
+use crate::{KinError, KinGraph, Kind, Nd, QueryOptions, Sex};
+use anyhow::Result;
+use petgraph::visit::EdgeRef;
+use petgraph::Direction::Outgoing;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+
+///Options for [KinGraph::to_mermaid] and [KinGraph::to_plantuml]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DiagramOptions {
+    ///The index of the person the diagram is drawn around
+    pub focus: Option<usize>,
+    ///Only draw the persons within this many relations of the focus person. Without a focus
+    /// everyone is drawn.
+    pub depth: Option<usize>,
+    ///Add the birth and death dates of each person to their label
+    pub dates: bool,
+    ///Add the canonical relationships of each person to the focus person to their label, named
+    /// with these options
+    pub relationships: Option<QueryOptions>,
+}
+
+//a person to draw, with the lines of their label
+struct DiagramNode {
+    n: Nd,
+    lines: Vec<String>,
+    shadow: bool,
+    focus: bool,
+}
+//the persons to draw, and the relations between them, each once
+struct Diagram {
+    nodes: Vec<DiagramNode>,
+    relations: Vec<(Nd, Nd, Kind)>,
+}
+
+impl KinGraph {
+    fn diagram(&self, opts: &DiagramOptions) -> Result<Diagram> {
+        let focus = match opts.focus {
+            Some(i) if i >= self.graph.node_count() => {
+                return Err(KinError::MissingNode { id: i as u32 }.into())
+            }
+            focus => focus.map(Nd::new),
+        };
+        //how many relations away from the focus each person related to them is
+        let mut distance = HashMap::new();
+        if let Some(focus) = focus {
+            distance.insert(focus, 0);
+            let mut queue = VecDeque::from([focus]);
+            while let Some(n) = queue.pop_front() {
+                let d = distance[&n] + 1;
+                if opts.depth.is_some_and(|depth| d > depth) {
+                    continue;
+                }
+                for e in self.graph.edges_directed(n, Outgoing) {
+                    if let Entry::Vacant(entry) = distance.entry(e.target()) {
+                        entry.insert(d);
+                        queue.push_back(e.target());
+                    }
+                }
+            }
+        }
+        let included = |n: &Nd| opts.depth.is_none() || focus.is_none() || distance.contains_key(n);
+        let mut nodes = Vec::new();
+        for n in self.graph.node_indices().filter(included) {
+            let p = &self.graph[n];
+            let name = p.primary_name().map(|name| name.to_string());
+            let mut lines = vec![name.unwrap_or_else(|| p.name.clone())];
+            if let Some(life) = self.life_dates(p).filter(|_| opts.dates) {
+                lines.push(life);
+            }
+            if let (Some(query), Some(f)) = (&opts.relationships, focus) {
+                //persons who are not related to the focus have no relationship to name
+                if n != f && distance.contains_key(&n) {
+                    let states = self.get_canonical_relationships_with(p, &self.graph[f], query)?;
+                    let names = states
+                        .iter()
+                        .map(|s| s.print_canonical_name())
+                        .collect::<BTreeSet<_>>();
+                    if !names.is_empty() {
+                        lines.push(names.into_iter().collect::<Vec<_>>().join(", "));
+                    }
+                }
+            }
+            nodes.push(DiagramNode {
+                n,
+                lines,
+                shadow: p.is_shadow,
+                focus: Some(n) == focus,
+            });
+        }
+        let relations = self
+            .graph
+            .edge_references()
+            .map(|e| (e.source(), e.target(), *e.weight()))
+            .filter(|(a, b, kind)| {
+                kind.is_written_from(a.index(), b.index()) && included(a) && included(b)
+            })
+            .collect();
+        Ok(Diagram { nodes, relations })
+    }
+    ///Writes the graph as a top-down Mermaid flowchart. Parents point to their children, and
+    /// adoptive, foster and guardian relations are dotted arrows labelled with their kind.
+    /// Siblings are joined by dotted lines, reproductive partners by lines and spouses by thick
+    /// lines.
+    pub fn to_mermaid(&self, opts: &DiagramOptions) -> Result<String> {
+        let Diagram { nodes, relations } = self.diagram(opts)?;
+        let mut out = String::from("flowchart TD\n");
+        for node in &nodes {
+            let label = node
+                .lines
+                .iter()
+                .map(|line| line.replace('"', "#quot;").replace('\n', "<br>"))
+                .collect::<Vec<_>>()
+                .join("<br>");
+            let (open, close) = match self.graph[node.n].sex {
+                Sex::Male => ("[", "]"),
+                Sex::Female => ("([", "])"),
+            };
+            out += &format!("    n{}{}\"{}\"{}\n", node.n.index(), open, label, close);
+        }
+        for (a, b, kind) in relations {
+            let link = match kind {
+                Kind::Parent => "-->",
+                Kind::AdoptiveParent => "-.->|adoptive|",
+                Kind::FosterParent => "-.->|foster|",
+                Kind::Guardian => "-.->|guardian|",
+                Kind::Sibling => "-.-",
+                Kind::RP => "---",
+                Kind::Spouse => "===",
+                //the inverse kinds are never written
+                _ => continue,
+            };
+            out += &format!("    n{} {} n{}\n", a.index(), link, b.index());
+        }
+        let ids = |of: fn(&DiagramNode) -> bool| {
+            let of = nodes.iter().filter(|n| of(n));
+            of.map(|n| format!("n{}", n.n.index())).collect::<Vec<_>>()
+        };
+        for (class, style, of) in [
+            ("shadow", "stroke-dasharray: 5 5", ids(|n| n.shadow)),
+            ("focus", "stroke-width: 3px", ids(|n| n.focus)),
+        ] {
+            if !of.is_empty() {
+                out += &format!("    classDef {} {}\n", class, style);
+                out += &format!("    class {} {}\n", of.join(","), class);
+            }
+        }
+        Ok(out)
+    }
+    ///Writes the graph as a PlantUML diagram, with men as rectangles and women as use case
+    /// ellipses. Parents point to their children, and adoptive, foster and guardian relations
+    /// are dotted or dashed arrows labelled with their kind. Siblings are joined by dotted lines,
+    /// reproductive partners by lines and spouses by bold lines.
+    pub fn to_plantuml(&self, opts: &DiagramOptions) -> Result<String> {
+        let Diagram { nodes, relations } = self.diagram(opts)?;
+        let mut out = String::from("@startuml\n");
+        for node in &nodes {
+            let label = node
+                .lines
+                .iter()
+                .map(|line| line.replace('"', "<U+0022>").replace('\n', "\\n"))
+                .collect::<Vec<_>>()
+                .join("\\n");
+            let element = match self.graph[node.n].sex {
+                Sex::Male => "rectangle",
+                Sex::Female => "usecase",
+            };
+            let style = match (node.shadow, node.focus) {
+                (true, true) => " #line.dashed;line.bold",
+                (true, false) => " #line.dashed",
+                (false, true) => " #line.bold",
+                (false, false) => "",
+            };
+            out += &format!(
+                "{} \"{}\" as n{}{}\n",
+                element,
+                label,
+                node.n.index(),
+                style
+            );
+        }
+        for (a, b, kind) in relations {
+            let (link, label) = match kind {
+                Kind::Parent => ("-->", ""),
+                Kind::AdoptiveParent => ("..>", " : adoptive"),
+                Kind::FosterParent => ("~~>", " : foster"),
+                Kind::Guardian => ("~~>", " : guardian"),
+                Kind::Sibling => ("..", ""),
+                Kind::RP => ("--", ""),
+                Kind::Spouse => ("==", ""),
+                //the inverse kinds are never written
+                _ => continue,
+            };
+            out += &format!("n{} {} n{}{}\n", a.index(), link, b.index(), label);
+        }
+        Ok(out + "@enduml\n")
+    }
+}
@@ -0,0 +1,400 @@
+//! Geometry of circular fan charts of ancestors and radial charts of descendants, and their
+//! rendering as SVG.
+//!
+//! Both charts are rings around a focus person, who is drawn in the middle, with one ring for
+//! each generation out from them. In a fan chart each person is given a sector of a ring, and
+//! their father and mother split the sector beyond it between them, so that the place of every
+//! ancestor is fixed. A parent who is not known is given an empty sector, beyond which nothing is
+//! drawn. In a radial chart the sector of a person is split among their children, from the first
+//! born, in proportion to how many descendants each of them leads to on the outer ring.
+//!
+//! A person who is reached more than once, as in pedigree collapse, has a sector for each way
+//! they are reached, and all but the first point back to the first one.
+//!
+//! Angles are in degrees, clockwise from the top, and points are relative to the middle of the
+//! chart with y going down, as in SVG and the HTML canvas.
+use crate::svg::{num, MARGIN};
+use crate::xml::Element;
+use crate::{KinGraph, Nd, Person, Sex};
+use anyhow::Result;
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+///Options for [KinGraph::fan_chart], [KinGraph::fan_svg], [KinGraph::radial_chart] and
+/// [KinGraph::radial_svg]
+#[derive(Clone, Debug)]
+pub struct FanOptions {
+    ///How many generations away from the focus person are drawn
+    pub generations: usize,
+    ///The angle the chart spreads over, 360 for a full circle and 180 for a half fan
+    pub sweep: f64,
+    ///Show the birth and death dates of each person
+    pub dates: bool,
+    pub font_family: String,
+    ///The font size in pixels, which the line height and padding of the labels follow
+    pub font_size: f64,
+    ///The radius of the circle the focus person is drawn in
+    pub center_radius: f64,
+    pub ring_width: f64,
+}
+impl Default for FanOptions {
+    fn default() -> Self {
+        FanOptions {
+            generations: 4,
+            sweep: 360.0,
+            dates: true,
+            font_family: "sans-serif".into(),
+            font_size: 12.0,
+            center_radius: 60.0,
+            ring_width: 80.0,
+        }
+    }
+}
+
+///The place of one person in a fan or radial chart
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct FanSector {
+    ///The index of the person, or None for an ancestor who is not known
+    pub person: Option<usize>,
+    ///How many generations away from the focus person the sector is
+    pub generation: usize,
+    ///The place of the sector in its ring, counted clockwise. In a fan chart this is the place
+    /// the ancestor would have if every ancestor was known, fathers on even places.
+    pub position: usize,
+    pub start_angle: f64,
+    pub end_angle: f64,
+    pub inner_radius: f64,
+    pub outer_radius: f64,
+    ///The middle of the label
+    pub label_x: f64,
+    pub label_y: f64,
+    ///How far the label is turned clockwise, so that it is never upside down
+    pub label_rotation: f64,
+    ///How long a line of the label may be
+    pub label_length: f64,
+    ///How much room there is for the lines of the label, side by side
+    pub label_height: f64,
+    ///The index of the first sector of the same person, when this one is a repeat of it
+    pub repeat_of: Option<usize>,
+}
+
+//the point at a radius and angle from the middle of the chart
+fn polar(radius: f64, angle: f64) -> (f64, f64) {
+    let radians = angle * PI / 180.0;
+    (radius * radians.sin(), -radius * radians.cos())
+}
+
+//turns an angle by half a turn when text at it would be upside down
+fn upright(angle: f64) -> f64 {
+    let angle = (angle + 180.0).rem_euclid(360.0) - 180.0;
+    match angle {
+        a if a > 90.0 => a - 180.0,
+        a if a < -90.0 => a + 180.0,
+        a => a,
+    }
+}
+
+impl FanSector {
+    fn new(
+        person: Option<Nd>,
+        generation: usize,
+        position: usize,
+        (start_angle, end_angle): (f64, f64),
+        opts: &FanOptions,
+    ) -> Self {
+        let (inner_radius, outer_radius) = match generation {
+            0 => (0.0, opts.center_radius),
+            g => {
+                let inner = opts.center_radius + (g - 1) as f64 * opts.ring_width;
+                (inner, inner + opts.ring_width)
+            }
+        };
+        let middle = (start_angle + end_angle) / 2.0;
+        let span = end_angle - start_angle;
+        //the focus person of a full circle is labelled in the very middle
+        let radius = match generation == 0 && span >= 360.0 {
+            true => 0.0,
+            false => (inner_radius + outer_radius) / 2.0,
+        };
+        let (label_x, label_y) = polar(radius, middle);
+        let arc = match span >= 360.0 {
+            true => 2.0 * outer_radius,
+            false => radius * span * PI / 180.0,
+        };
+        let depth = outer_radius - inner_radius;
+        //labels run along the ring when there is room, and out from the middle otherwise
+        let (label_rotation, label_length, label_height) = match arc >= depth {
+            true => (upright(middle), arc, depth),
+            false => (upright(middle - 90.0), depth, arc),
+        };
+        FanSector {
+            person: person.map(|n| n.index()),
+            generation,
+            position,
+            start_angle,
+            end_angle,
+            inner_radius,
+            outer_radius,
+            label_x,
+            label_y,
+            label_rotation,
+            label_length,
+            label_height,
+            repeat_of: None,
+        }
+    }
+    //the SVG path of the sector
+    fn path(&self) -> String {
+        let span = self.end_angle - self.start_angle;
+        let large = (span > 180.0) as u8;
+        let point = |radius, angle| {
+            let (x, y) = polar(radius, angle);
+            format!("{} {}", num(x), num(y))
+        };
+        let (outer, inner) = (num(self.outer_radius), num(self.inner_radius));
+        let mut d = format!(
+            "M {} A {} {} 0 {} 1 {}",
+            point(self.outer_radius, self.start_angle),
+            outer,
+            outer,
+            large,
+            point(self.outer_radius, self.end_angle)
+        );
+        d += &match self.inner_radius > 0.0 {
+            true => format!(
+                " L {} A {} {} 0 {} 0 {}",
+                point(self.inner_radius, self.end_angle),
+                inner,
+                inner,
+                large,
+                point(self.inner_radius, self.start_angle)
+            ),
+            false => " L 0 0".to_string(),
+        };
+        d + " Z"
+    }
+}
+
+//points back each sector of a person who has been reached before to their first one
+fn mark_repeats(sectors: &mut [FanSector]) {
+    let mut first = HashMap::new();
+    for (i, sector) in sectors.iter_mut().enumerate() {
+        if let Some(person) = sector.person {
+            let first = *first.entry(person).or_insert(i);
+            sector.repeat_of = Some(first).filter(|first| *first != i);
+        }
+    }
+}
+
+impl KinGraph {
+    //the father and mother of a person, each of which may not be known. Parents of the same sex
+    //take both places in turn.
+    fn fan_parents(&self, n: Nd) -> [Option<Nd>; 2] {
+        let mut places = [None, None];
+        for parent in self.chart_parents(n) {
+            let place = (self.graph[parent].sex != Sex::Male) as usize;
+            if places[place].is_none() {
+                places[place] = Some(parent);
+            } else if places[1 - place].is_none() {
+                places[1 - place] = Some(parent);
+            }
+        }
+        places
+    }
+    ///The sectors of a fan chart of the ancestors of a person, generation by generation,
+    /// fathers before mothers
+    pub fn fan_chart(&self, p: &Person, opts: &FanOptions) -> Result<Vec<FanSector>> {
+        let focus = self.node(p)?;
+        let whole = (-opts.sweep / 2.0, opts.sweep / 2.0);
+        let mut sectors = vec![FanSector::new(Some(focus), 0, 0, whole, opts)];
+        //the known persons of the last generation, by place
+        let mut known = vec![(0, focus)];
+        for generation in 1..=opts.generations {
+            let step = opts.sweep / 2f64.powi(generation as i32);
+            let mut next = Vec::new();
+            for (position, n) in known {
+                for (i, parent) in self.fan_parents(n).into_iter().enumerate() {
+                    let position = 2 * position + i;
+                    let start = whole.0 + position as f64 * step;
+                    let angles = (start, start + step);
+                    sectors.push(FanSector::new(parent, generation, position, angles, opts));
+                    next.extend(parent.map(|parent| (position, parent)));
+                }
+            }
+            known = next;
+        }
+        mark_repeats(&mut sectors);
+        Ok(sectors)
+    }
+    //how many descendants of a person there are in the outer ring, counting the person when the
+    //line ends with them
+    fn radial_leaves(&self, n: Nd, generations: usize) -> usize {
+        let children = match generations {
+            0 => Vec::new(),
+            _ => self.chart_children(n),
+        };
+        match children.is_empty() {
+            true => 1,
+            false => children
+                .into_iter()
+                .map(|c| self.radial_leaves(c, generations - 1))
+                .sum(),
+        }
+    }
+    //adds the sectors of the descendants of n, which is in the given generation and sector
+    fn add_radial(
+        &self,
+        n: Nd,
+        generation: usize,
+        (start, end): (f64, f64),
+        opts: &FanOptions,
+        sectors: &mut Vec<FanSector>,
+    ) {
+        let generations = opts.generations - generation;
+        if generations == 0 {
+            return;
+        }
+        let children = self.chart_children(n);
+        let total = self.radial_leaves(n, generations) as f64;
+        let mut from = start;
+        for c in children {
+            let to = from + (end - start) * self.radial_leaves(c, generations - 1) as f64 / total;
+            sectors.push(FanSector::new(Some(c), generation + 1, 0, (from, to), opts));
+            self.add_radial(c, generation + 1, (from, to), opts, sectors);
+            from = to;
+        }
+    }
+    ///The sectors of a radial chart of the descendants of a person, each generation from the
+    /// first born clockwise
+    pub fn radial_chart(&self, p: &Person, opts: &FanOptions) -> Result<Vec<FanSector>> {
+        let focus = self.node(p)?;
+        let whole = (-opts.sweep / 2.0, opts.sweep / 2.0);
+        let mut sectors = vec![FanSector::new(Some(focus), 0, 0, whole, opts)];
+        self.add_radial(focus, 0, whole, opts, &mut sectors);
+        //the sectors are added down each line, clockwise, but are listed generation by generation
+        sectors.sort_by_key(|s| s.generation);
+        let mut positions = HashMap::new();
+        for s in &mut sectors {
+            let position = positions.entry(s.generation).or_insert(0);
+            s.position = *position;
+            *position += 1;
+        }
+        mark_repeats(&mut sectors);
+        Ok(sectors)
+    }
+    ///Draws the ancestors of a person as an SVG fan chart
+    pub fn fan_svg(&self, p: &Person, opts: &FanOptions) -> Result<String> {
+        Ok(self.sectors_svg(&self.fan_chart(p, opts)?, opts))
+    }
+    ///Draws the descendants of a person as an SVG radial chart
+    pub fn radial_svg(&self, p: &Person, opts: &FanOptions) -> Result<String> {
+        Ok(self.sectors_svg(&self.radial_chart(p, opts)?, opts))
+    }
+    //the lines of the label of a person
+    fn sector_lines(&self, p: &Person, opts: &FanOptions) -> Vec<String> {
+        let name = p.primary_name().map(|name| name.to_string());
+        let mut lines = vec![name.unwrap_or_else(|| p.name.clone())];
+        if let Some(life) = self.life_dates(p).filter(|_| opts.dates) {
+            lines.push(life);
+        }
+        lines
+    }
+    //draws the sectors of a chart with their labels, cut to fit
+    fn sectors_svg(&self, sectors: &[FanSector], opts: &FanOptions) -> String {
+        //the corners of the sectors, and the outer ring where it passes the top, right, bottom
+        //and left of the chart, bound the drawing
+        let (mut left, mut top, mut right, mut bottom) = (0.0f64, 0.0f64, 0.0f64, 0.0f64);
+        for s in sectors {
+            let mut points = vec![];
+            for angle in [s.start_angle, s.end_angle] {
+                points.push(polar(s.inner_radius, angle));
+                points.push(polar(s.outer_radius, angle));
+            }
+            let quarters =
+                (s.start_angle / 90.0).ceil() as i32..=(s.end_angle / 90.0).floor() as i32;
+            points.extend(quarters.map(|q| polar(s.outer_radius, q as f64 * 90.0)));
+            for (x, y) in points {
+                (left, top, right, bottom) = (left.min(x), top.min(y), right.max(x), bottom.max(y));
+            }
+        }
+        let (width, height) = (right - left + 2.0 * MARGIN, bottom - top + 2.0 * MARGIN);
+        let mut svg = Element::new("svg")
+            .with_attr("xmlns", "http://www.w3.org/2000/svg")
+            .with_attr("width", num(width))
+            .with_attr("height", num(height))
+            .with_attr("viewBox", format!("0 0 {} {}", num(width), num(height)))
+            .with_attr("font-family", opts.font_family.as_str())
+            .with_attr("font-size", num(opts.font_size));
+        let mut chart = Element::new("g").with_attr(
+            "transform",
+            format!("translate({} {})", num(MARGIN - left), num(MARGIN - top)),
+        );
+        let line_height = opts.font_size * 1.25;
+        let padding = opts.font_size / 2.0;
+        for s in sectors {
+            let p = s.person.map(|i| self.px(i));
+            let mut shape = match s.end_angle - s.start_angle >= 360.0 {
+                true => Element::new("circle")
+                    .with_attr("cx", "0")
+                    .with_attr("cy", "0")
+                    .with_attr("r", num(s.outer_radius)),
+                false => Element::new("path").with_attr("d", s.path()),
+            };
+            shape = match p.map(|p| p.sex) {
+                Some(Sex::Male) => shape.with_attr("fill", "#dbe8f6"),
+                Some(Sex::Female) => shape.with_attr("fill", "#f6dbe8"),
+                None => shape.with_attr("fill", "#f4f4f4"),
+            };
+            shape = shape.with_attr("stroke", "#333");
+            if s.repeat_of.is_some() {
+                shape = shape.with_attr("fill-opacity", "0.5");
+            }
+            if p.is_none_or(|p| p.is_shadow) {
+                shape = shape.with_attr("stroke-dasharray", "4 2");
+            }
+            if s.generation == 0 {
+                shape = shape.with_attr("stroke-width", "2");
+            }
+            let mut group = Element::new("g").with(shape);
+            let Some(p) = p else {
+                chart = chart.with(group);
+                continue;
+            };
+            //roughly how many characters fit on a line, and how many lines fit
+            let fit = ((s.label_length - 2.0 * padding) / (opts.font_size * 0.6)).max(1.0);
+            let rows = ((s.label_height - padding) / line_height).max(1.0) as usize;
+            let lines = self.sector_lines(p, opts);
+            let lines = &lines[..rows.min(lines.len())];
+            let rotate = format!(
+                "rotate({} {} {})",
+                num(s.label_rotation),
+                num(s.label_x),
+                num(s.label_y)
+            );
+            for (i, line) in lines.iter().enumerate() {
+                let text = match line.chars().count() > fit as usize {
+                    true => line.chars().take(fit as usize - 1).collect::<String>() + "…",
+                    false => line.clone(),
+                };
+                //the lines are centered on the label point
+                let offset = (i as f64 - (lines.len() - 1) as f64 / 2.0) * line_height;
+                let mut text = Element::text("text", &text)
+                    .with_attr("x", num(s.label_x))
+                    .with_attr("y", num(s.label_y + offset + opts.font_size * 0.35))
+                    .with_attr("text-anchor", "middle");
+                if s.label_rotation != 0.0 {
+                    text = text.with_attr("transform", rotate.as_str());
+                }
+                if i == 0 {
+                    text = text.with_attr("font-weight", "bold");
+                }
+                group = group.with(text);
+            }
+            chart = chart.with(group);
+        }
+        svg = svg.with(chart);
+        let mut out = String::new();
+        svg.write(&mut out, 0);
+        out
+    }
+}
@@ -0,0 +1,1208 @@
+//! Import and export of GEDCOM files.
+//!
+//! On import (of GEDCOM 5.5.1), INDI records become persons, with their names, sex and dates of
+//! birth and death. FAM records become the [Kind::Parent] edges to their children, with the
+//! pedigree of a child (PEDI) picking an adoptive or foster kind, and a [Kind::RP] edge between the
+//! couple, who are also made spouses if the family has a marriage. The xref of each record is kept
+//! as a `gedcom` external ref, and other REFNs with a TYPE as refs of that system.
+//!
+//! Tags that have no place in the graph are kept as attributes, keyed by their path below the
+//! record (e.g. `BIRT.PLAC`). Records that can not be imported are skipped with a [GedcomWarning]
+//! rather than failing the whole import.
+//!
+//! Export writes either GEDCOM 5.5.1 or 7.0, writing attributes with tag path keys back as those
+//! tags, and others as a FACT with their key as its TYPE. [validate_gedcom] checks a file against
+//! the structural rules of either version.
+use crate::{
+    convert, AttrValue, Attributes, Calendar, Date, GenDate, Gregorian, KinGraph, Kind,
+    MaritalStatus, Nd, Partnership, Person, PersonName, Sex,
+};
+use anyhow::{bail, Result};
+use petgraph::visit::EdgeRef;
+use petgraph::Direction;
+use std::collections::{HashMap, HashSet};
+
+///The external ref system that the xrefs of records are kept under
+pub const GEDCOM_REF: &str = "gedcom";
+
+///A problem with a GEDCOM file that was skipped over
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct GedcomWarning {
+    ///The line of the file the problem is on, counting from 1
+    pub line: usize,
+    pub message: String,
+}
+impl std::fmt::Display for GedcomWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+///A graph read from a GEDCOM file, along with what could not be read
+pub struct GedcomImport {
+    pub graph: KinGraph,
+    pub warnings: Vec<GedcomWarning>,
+}
+
+//a line of the file, along with the lines nested below it
+struct Record<'a> {
+    line: usize,
+    xref: Option<&'a str>,
+    tag: &'a str,
+    value: String,
+    children: Vec<Record<'a>>,
+}
+impl<'a> Record<'a> {
+    fn child(&self, tag: &str) -> Option<&Record<'a>> {
+        self.children.iter().find(|c| c.tag == tag)
+    }
+}
+
+//splits a line into its level, xref, tag and value
+fn parse_line(line: &str) -> Option<(usize, Option<&str>, &str, &str)> {
+    let line = line.trim_start();
+    let (level, rest) = line.split_once(' ')?;
+    let level = level.parse().ok()?;
+    let (xref, rest) = match rest.strip_prefix('@') {
+        Some(_) => {
+            let (xref, rest) = rest.split_once(' ')?;
+            (Some(xref.trim_matches('@')), rest)
+        }
+        None => (None, rest),
+    };
+    let (tag, value) = rest.split_once(' ').unwrap_or((rest, ""));
+    if tag.is_empty() {
+        return None;
+    }
+    Some((level, xref, tag, value))
+}
+
+//builds the tree of records, joining continuation lines onto the values they continue
+fn parse_records<'a>(text: &'a str, warnings: &mut Vec<GedcomWarning>) -> Vec<Record<'a>> {
+    let mut roots = Vec::new();
+    let mut stack: Vec<Record> = Vec::new();
+    let close = |stack: &mut Vec<Record<'a>>, roots: &mut Vec<Record<'a>>| {
+        let record = stack.pop().unwrap();
+        match stack.last_mut() {
+            Some(parent) => parent.children.push(record),
+            None => roots.push(record),
+        }
+    };
+    for (i, line) in text.trim_start_matches('\u{feff}').lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let warn = |message: String| GedcomWarning {
+            line: i + 1,
+            message,
+        };
+        let Some((level, xref, tag, value)) = parse_line(line) else {
+            warnings.push(warn(format!("Malformed line {:?}", line.trim())));
+            continue;
+        };
+        if level > stack.len() {
+            warnings.push(warn(format!(
+                "{} is nested more than one level deeper",
+                tag
+            )));
+            continue;
+        }
+        while stack.len() > level {
+            close(&mut stack, &mut roots);
+        }
+        if tag == "CONC" || tag == "CONT" {
+            match stack.last_mut() {
+                Some(parent) => {
+                    if tag == "CONT" {
+                        parent.value.push('\n');
+                    }
+                    parent.value.push_str(value);
+                }
+                None => warnings.push(warn(format!("{} continues no line", tag))),
+            }
+            continue;
+        }
+        stack.push(Record {
+            line: i + 1,
+            xref,
+            tag,
+            value: value.to_string(),
+            children: Vec::new(),
+        });
+    }
+    while !stack.is_empty() {
+        close(&mut stack, &mut roots);
+    }
+    roots
+}
+
+//parses a GEDCOM date value, which may carry a calendar escape or an interpretation
+fn parse_date(value: &str, calendar: &dyn Calendar) -> Result<GenDate> {
+    let mut value = value.trim();
+    if let Some(rest) = value.strip_prefix("@#DGREGORIAN@") {
+        value = rest.trim();
+    } else if value.starts_with("@#") {
+        bail!("Unsupported calendar in date {:?}", value);
+    }
+    if let Some(rest) = value.strip_prefix("INT ") {
+        value = rest.split('(').next().unwrap_or_default().trim();
+    }
+    if value.starts_with('(') || value.starts_with("FROM ") || value.starts_with("TO ") {
+        bail!("Unsupported date {:?}", value);
+    }
+    calendar.parse(value)
+}
+
+//reads a DATE line, preferring its phrase if that is the same date, dual dated
+fn date_of(record: &Record, calendar: &dyn Calendar) -> Result<GenDate> {
+    let date = parse_date(&record.value, calendar)?;
+    let phrase = record
+        .child("PHRASE")
+        .and_then(|p| parse_date(&p.value, calendar).ok());
+    Ok(phrase
+        .filter(|p| p.map(|d| Date { dual: false, ..d }) == date)
+        .unwrap_or(date))
+}
+//keeps a tag and everything below it as attributes, keyed by path
+fn keep_as_attributes(record: &Record, prefix: &str, attributes: &mut Attributes) {
+    let key = match prefix {
+        "" => record.tag.to_string(),
+        _ => format!("{}.{}", prefix, record.tag),
+    };
+    if !record.value.is_empty() || record.children.is_empty() {
+        let value = text_value(&record.value);
+        match attributes.get_mut(&key) {
+            Some(AttrValue::List(items)) => items.push(value),
+            Some(existing) => *existing = AttrValue::List(vec![existing.clone(), value]),
+            None => {
+                attributes.insert(key.clone(), value);
+            }
+        }
+    }
+    for child in &record.children {
+        keep_as_attributes(child, &key, attributes);
+    }
+}
+
+//the value of a line as an attribute, where a line without a value only says the tag applies
+fn text_value(value: &str) -> AttrValue {
+    match value {
+        "" => AttrValue::Bool(true),
+        //a leading @ is doubled so that it is not read as a pointer
+        value => AttrValue::Str(
+            value
+                .strip_prefix('@')
+                .filter(|v| v.starts_with('@'))
+                .unwrap_or(value)
+                .into(),
+        ),
+    }
+}
+//a FACT (or EVEN) with nothing but a TYPE, which is how attributes that are not tags are written
+fn typed_fact(record: &Record) -> Option<(String, AttrValue)> {
+    match record.children.as_slice() {
+        [kind] if kind.tag == "TYPE" => Some((kind.value.clone(), text_value(&record.value))),
+        _ => None,
+    }
+}
+//reads a NAME line, along with the name pieces nested below it
+fn parse_name(record: &Record, attributes: &mut Attributes) -> Result<PersonName> {
+    let mut name: PersonName = record.value.parse()?;
+    for piece in &record.children {
+        let words = || piece.value.split_whitespace().map(str::to_string);
+        match piece.tag {
+            "GIVN" => name.given = words().collect(),
+            "SURN" => name.surname = Some(piece.value.trim().to_string()),
+            "NICK" => name.nicknames = piece.value.split(',').map(|n| n.trim().into()).collect(),
+            "NPFX" => name.titles = words().collect(),
+            _ => keep_as_attributes(piece, "NAME", attributes),
+        }
+    }
+    Ok(name)
+}
+
+impl KinGraph {
+    ///Reads a GEDCOM 5.5.1 file, skipping the records that can not be read. Dates are
+    /// read in the calendar of a new graph, which is Gregorian.
+    pub fn from_gedcom(text: &str) -> GedcomImport {
+        let mut warnings = Vec::new();
+        let mut kg = KinGraph::new();
+        let records = parse_records(text, &mut warnings);
+        match records.first() {
+            Some(head) if head.tag == "HEAD" => {}
+            first => warnings.push(GedcomWarning {
+                line: first.map_or(1, |r| r.line),
+                message: "The file does not start with a HEAD record".into(),
+            }),
+        }
+        //the sex of persons that do not give it can be told from the families they head
+        let mut roles = HashMap::new();
+        for fam in records.iter().filter(|r| r.tag == "FAM") {
+            for role in &fam.children {
+                match role.tag {
+                    "HUSB" => roles.insert(role.value.trim_matches('@'), Sex::Male),
+                    "WIFE" => roles.insert(role.value.trim_matches('@'), Sex::Female),
+                    _ => None,
+                };
+            }
+        }
+        let mut persons = HashMap::new();
+        //the kind of parent the parents of a family are to a child, by child and family
+        let mut pedigrees = HashMap::new();
+        for indi in records.iter().filter(|r| r.tag == "INDI") {
+            let mut warn =
+                |line: usize, message: String| warnings.push(GedcomWarning { line, message });
+            let mut attributes = Attributes::new();
+            let mut names = Vec::new();
+            let (mut sex, mut birth, mut death) = (None, None, None);
+            let (mut refs, mut shadow) = (Vec::new(), false);
+            for fact in &indi.children {
+                match fact.tag {
+                    "NAME" => match parse_name(fact, &mut attributes) {
+                        Ok(name) => names.push(name),
+                        Err(e) => warn(fact.line, e.to_string()),
+                    },
+                    "SEX" => match fact.value.trim() {
+                        "M" => sex = Some(Sex::Male),
+                        "F" => sex = Some(Sex::Female),
+                        _ => keep_as_attributes(fact, "", &mut attributes),
+                    },
+                    "BIRT" | "DEAT" => {
+                        for detail in &fact.children {
+                            let date = match detail.tag {
+                                "DATE" => date_of(detail, kg.calendar()),
+                                _ => {
+                                    keep_as_attributes(detail, fact.tag, &mut attributes);
+                                    continue;
+                                }
+                            };
+                            match date {
+                                Ok(date) if fact.tag == "BIRT" => birth = Some(date),
+                                Ok(date) => death = Some(date),
+                                Err(e) => {
+                                    warn(detail.line, e.to_string());
+                                    keep_as_attributes(detail, fact.tag, &mut attributes);
+                                }
+                            }
+                        }
+                    }
+                    "FAMC" => {
+                        let kind = match fact.child("PEDI").map(|p| p.value.to_lowercase()) {
+                            None => Kind::Parent,
+                            Some(pedi) => match pedi.as_str() {
+                                "birth" => Kind::Parent,
+                                "adopted" => Kind::AdoptiveParent,
+                                "foster" => Kind::FosterParent,
+                                _ => {
+                                    warn(fact.line, format!("Unknown pedigree {:?}", pedi));
+                                    Kind::Parent
+                                }
+                            },
+                        };
+                        if let Some(xref) = indi.xref {
+                            pedigrees.insert((xref, fact.value.trim_matches('@')), kind);
+                        }
+                    }
+                    //families are linked from the FAM records
+                    "FAMS" => {}
+                    "REFN" => match fact.child("TYPE") {
+                        Some(system) if fact.children.len() == 1 => {
+                            refs.push((fact.line, &system.value, &fact.value))
+                        }
+                        _ => keep_as_attributes(fact, "", &mut attributes),
+                    },
+                    "FACT" if typed_fact(fact).is_some() => {
+                        let (key, value) = typed_fact(fact).unwrap();
+                        attributes.insert(key, value);
+                    }
+                    "_SHADOW" => shadow = fact.value == "Y",
+                    _ => keep_as_attributes(fact, "", &mut attributes),
+                }
+            }
+            let sex = match sex.or_else(|| indi.xref.and_then(|x| roles.get(x).copied())) {
+                Some(sex) => sex,
+                None => {
+                    warn(indi.line, "Unknown sex, imported as female".into());
+                    Sex::Female
+                }
+            };
+            let short = names
+                .first()
+                .map_or(crate::names::UNKNOWN_NAME.into(), PersonName::to_string);
+            let p = kg.np_with_name(sex, short);
+            for name in names {
+                kg.add_alternate_name(&p, name).unwrap();
+            }
+            kg.set_birth(&p, birth).unwrap();
+            kg.set_death(&p, death).unwrap();
+            for (key, value) in attributes {
+                kg.set_attr(&p, &key, value).unwrap();
+            }
+            for (line, system, id) in refs {
+                if let Err(e) = kg.set_external_ref(&p, system, id) {
+                    warn(line, e.to_string());
+                }
+            }
+            let px = kg.idx(&p).unwrap();
+            kg.graph[px].is_shadow = shadow;
+            match indi.xref {
+                Some(xref) => {
+                    if let Err(e) = kg.set_external_ref(&p, GEDCOM_REF, xref) {
+                        warn(indi.line, e.to_string());
+                    }
+                    persons.insert(xref, p);
+                }
+                None => warn(indi.line, "Individual has no xref".into()),
+            }
+        }
+        for fam in records.iter().filter(|r| r.tag == "FAM") {
+            kg.add_family(fam, &persons, &pedigrees, &mut warnings);
+        }
+        for record in &records {
+            match record.tag {
+                "HEAD" | "TRLR" | "INDI" | "FAM" | "SUBM" | "SUBN" | "SOUR" | "REPO" | "NOTE"
+                | "OBJE" => {}
+                tag => warnings.push(GedcomWarning {
+                    line: record.line,
+                    message: format!("Ignoring unknown record {}", tag),
+                }),
+            }
+        }
+        GedcomImport {
+            graph: kg,
+            warnings,
+        }
+    }
+    //adds the relations of a FAM record
+    fn add_family(
+        &mut self,
+        fam: &Record,
+        persons: &HashMap<&str, Person>,
+        pedigrees: &HashMap<(&str, &str), Kind>,
+        warnings: &mut Vec<GedcomWarning>,
+    ) {
+        let mut warn =
+            |line: usize, message: String| warnings.push(GedcomWarning { line, message });
+        let fam_xref = fam.xref.unwrap_or_default();
+        let mut parents: Vec<&Person> = Vec::new();
+        let mut children = Vec::new();
+        let mut partnership = None;
+        let mut attributes = Attributes::new();
+        for fact in &fam.children {
+            let member = || {
+                let xref = fact.value.trim_matches('@');
+                persons.get(xref).map(|p| (xref, p))
+            };
+            match fact.tag {
+                "HUSB" | "WIFE" | "CHIL" => match member() {
+                    Some((_, p)) if fact.tag != "CHIL" => {
+                        if parents.len() == 2 {
+                            warn(fact.line, "Family has more than two partners".into());
+                        } else {
+                            parents.push(p);
+                        }
+                    }
+                    Some((xref, p)) => children.push((fact.line, xref, p)),
+                    None => warn(
+                        fact.line,
+                        format!("Family refers to missing individual {}", fact.value),
+                    ),
+                },
+                "MARR" | "DIV" => {
+                    let partnership = partnership.get_or_insert_with(Partnership::default);
+                    if fact.tag == "DIV" {
+                        partnership.status = MaritalStatus::Divorced;
+                    }
+                    for detail in &fact.children {
+                        let date = match detail.tag {
+                            "DATE" => date_of(detail, self.calendar()),
+                            _ => {
+                                keep_as_attributes(detail, fact.tag, &mut attributes);
+                                continue;
+                            }
+                        };
+                        match date {
+                            Ok(date) if fact.tag == "MARR" => partnership.start = Some(date),
+                            Ok(date) => partnership.end = Some(date),
+                            Err(e) => {
+                                warn(detail.line, e.to_string());
+                                keep_as_attributes(detail, fact.tag, &mut attributes);
+                            }
+                        }
+                    }
+                }
+                "FACT" | "EVEN" if typed_fact(fact).is_some() => {
+                    let (key, value) = typed_fact(fact).unwrap();
+                    attributes.insert(key, value);
+                }
+                _ => keep_as_attributes(fact, "", &mut attributes),
+            }
+        }
+        for (line, xref, child) in children {
+            let kind = pedigrees
+                .get(&(xref, fam_xref))
+                .copied()
+                .unwrap_or(Kind::Parent);
+            for parent in &parents {
+                if let Err(e) = self.add_relation(parent, child, kind) {
+                    warn(line, e.to_string());
+                }
+            }
+        }
+        let &[p1, p2] = parents.as_slice() else {
+            if partnership.is_some() || !attributes.is_empty() {
+                warn(
+                    fam.line,
+                    "Family without a couple, its facts are ignored".into(),
+                );
+            }
+            return;
+        };
+        let mut relation = self.add_relation(p1, p2, Kind::RP);
+        if let Some(partnership) = partnership {
+            relation = relation.and_then(|_| self.add_partnership(p1, p2, partnership));
+        }
+        for (key, value) in attributes {
+            relation = relation.and_then(|_| self.set_relation_attr(p1, p2, Kind::RP, &key, value));
+        }
+        if let Err(e) = relation {
+            warn(fam.line, e.to_string());
+        }
+    }
+}
+
+///The version of GEDCOM to write, or to validate a file against
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum GedcomVersion {
+    #[default]
+    V551,
+    V70,
+}
+impl GedcomVersion {
+    fn number(&self) -> &'static str {
+        match self {
+            GedcomVersion::V551 => "5.5.1",
+            GedcomVersion::V70 => "7.0",
+        }
+    }
+    fn is_tag(&self, tag: &str) -> bool {
+        match self {
+            GedcomVersion::V551 => {
+                (1..=31).contains(&tag.len())
+                    && tag.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            }
+            //extension tags start with an underscore, standard ones with a capital
+            GedcomVersion::V70 => {
+                let rest = tag.strip_prefix('_').unwrap_or(tag);
+                (tag.starts_with('_') || rest.starts_with(|c: char| c.is_ascii_uppercase()))
+                    && rest
+                        .chars()
+                        .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+                    && !rest.is_empty()
+            }
+        }
+    }
+    fn is_xref(&self, xref: &str) -> bool {
+        match self {
+            GedcomVersion::V551 => {
+                (1..=20).contains(&xref.len())
+                    && xref.starts_with(|c: char| c.is_ascii_alphanumeric())
+                    && xref.chars().all(|c| c.is_ascii_graphic() && c != '@')
+            }
+            GedcomVersion::V70 => {
+                !xref.is_empty()
+                    && xref != "VOID"
+                    && xref
+                        .chars()
+                        .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+            }
+        }
+    }
+}
+///Options for [KinGraph::to_gedcom]
+#[derive(Clone, Copy, Default, Debug)]
+pub struct GedcomOptions {
+    pub version: GedcomVersion,
+    ///Leave shadow persons out, rather than marking them with a `_SHADOW` tag
+    pub drop_shadows: bool,
+}
+
+//the longest line GEDCOM 5.5.1 allows
+const MAX_LINE: usize = 255;
+
+//a line to write, along with the lines nested below it
+struct Node {
+    xref: Option<String>,
+    tag: String,
+    value: String,
+    children: Vec<Node>,
+}
+impl Node {
+    fn new(tag: &str, value: impl Into<String>) -> Node {
+        Node {
+            xref: None,
+            tag: tag.to_string(),
+            value: value.into(),
+            children: Vec::new(),
+        }
+    }
+    fn record(xref: &str, tag: &str) -> Node {
+        Node {
+            xref: Some(xref.to_string()),
+            ..Node::new(tag, "")
+        }
+    }
+    //a line of text, doubling a leading @ so that it is not read as a pointer
+    fn text(tag: &str, value: &str) -> Node {
+        match value.starts_with('@') {
+            true => Node::new(tag, format!("@{}", value)),
+            false => Node::new(tag, value),
+        }
+    }
+    fn pointer(tag: &str, xref: &str) -> Node {
+        Node::new(tag, format!("@{}@", xref))
+    }
+    fn with(mut self, child: Node) -> Node {
+        self.children.push(child);
+        self
+    }
+    fn write(&self, out: &mut String, level: usize, version: GedcomVersion) {
+        let head = match &self.xref {
+            Some(xref) => format!("{} @{}@ {}", level, xref, self.tag),
+            None => format!("{} {}", level, self.tag),
+        };
+        let mut lines = self.value.split('\n');
+        write_line(
+            out,
+            head,
+            lines.next().unwrap_or_default(),
+            level + 1,
+            version,
+        );
+        for line in lines {
+            write_line(out, format!("{} CONT", level + 1), line, level + 1, version);
+        }
+        for child in &self.children {
+            child.write(out, level + 1, version);
+        }
+    }
+}
+//writes a line, continuing values that are too long for GEDCOM 5.5.1 on CONC lines
+fn write_line(
+    out: &mut String,
+    mut head: String,
+    mut value: &str,
+    level: usize,
+    version: GedcomVersion,
+) {
+    loop {
+        let max = MAX_LINE - head.len() - 1;
+        let split = match version {
+            GedcomVersion::V551 if value.len() > max => conc_split(value, max),
+            _ => value.len(),
+        };
+        out.push_str(&head);
+        if split > 0 {
+            out.push(' ');
+            out.push_str(&value[..split]);
+        }
+        out.push('\n');
+        value = &value[split..];
+        if value.is_empty() {
+            return;
+        }
+        head = format!("{} CONC", level);
+    }
+}
+//where to split a value that is longer than max, avoiding splitting next to a space, which
+//some readers drop
+fn conc_split(value: &str, max: usize) -> usize {
+    let boundaries = || (1..=max).rev().filter(|i| value.is_char_boundary(*i));
+    boundaries()
+        .find(|i| !value[..*i].ends_with(' ') && !value[*i..].starts_with(' '))
+        .or_else(|| boundaries().next())
+        .unwrap_or(max)
+}
+//the nodes of a name, and of the surname it was born with, if it differs
+fn name_nodes(name: &PersonName, version: GedcomVersion) -> Vec<Node> {
+    let value = |surname: Option<&String>| {
+        let mut value = name.given.join(" ");
+        if let Some(surname) = surname {
+            if !value.is_empty() {
+                value.push(' ');
+            }
+            value += &format!("/{}/", surname);
+        }
+        value
+    };
+    let mut node = Node::text("NAME", &value(name.surname.as_ref()));
+    if !name.titles.is_empty() {
+        node.children
+            .push(Node::text("NPFX", &name.titles.join(" ")));
+    }
+    if !name.nicknames.is_empty() {
+        node.children
+            .push(Node::text("NICK", &name.nicknames.join(", ")));
+    }
+    let mut nodes = vec![node];
+    if let Some(birth) = &name.birth_surname {
+        let kind = match version {
+            GedcomVersion::V551 => "birth",
+            GedcomVersion::V70 => "BIRTH",
+        };
+        nodes.push(Node::text("NAME", &value(Some(birth))).with(Node::new("TYPE", kind)));
+    }
+    nodes
+}
+//writes attributes whose keys are tag paths (such as those read from GEDCOM) as those tags, and
+//others as a fact (or event) of their own type
+fn attribute_nodes(node: &mut Node, attributes: &Attributes, fact: &str) {
+    let is_tag = |t: &str| t.len() <= 31 && GedcomVersion::V70.is_tag(t);
+    for (key, value) in attributes {
+        let values = match value {
+            AttrValue::List(items) => items.iter().collect(),
+            value => vec![value],
+        };
+        let text = |value: &AttrValue| match value {
+            AttrValue::Bool(true) => String::new(),
+            value => value.to_string(),
+        };
+        if !key.split('.').all(is_tag) {
+            for value in values {
+                node.children
+                    .push(Node::text(fact, &text(value)).with(Node::text("TYPE", key)));
+            }
+            continue;
+        }
+        let tags = key.split('.').collect::<Vec<_>>();
+        let mut parent = &mut *node;
+        for tag in &tags[..tags.len() - 1] {
+            let i = match parent.children.iter().rposition(|c| c.tag == *tag) {
+                Some(i) => i,
+                None => {
+                    parent.children.push(Node::new(tag, ""));
+                    parent.children.len() - 1
+                }
+            };
+            parent = &mut parent.children[i];
+        }
+        let tag = tags[tags.len() - 1];
+        for value in values {
+            //the value of a structure made for an earlier key, e.g. BIRT for BIRT.PLAC
+            let made = parent
+                .children
+                .iter_mut()
+                .rfind(|c| c.tag == tag && c.value.is_empty() && !c.children.is_empty());
+            match made {
+                Some(made) => made.value = Node::text(tag, &text(value)).value,
+                None => parent.children.push(Node::text(tag, &text(value))),
+            }
+        }
+    }
+}
+
+//a family to write, as a FAM record or in other formats with families
+pub(crate) struct Family<'a> {
+    pub(crate) parents: Vec<Nd>,
+    //the children, with the kind of parent the parents are to them
+    pub(crate) children: Vec<(Nd, Kind)>,
+    pub(crate) partnership: Option<&'a Partnership>,
+}
+
+impl KinGraph {
+    ///Writes the graph as GEDCOM. Each couple with children, and each pair of spouses, becomes a
+    /// FAM record, as does each single parent. Adoptive and foster parents are told apart by the
+    /// pedigree (PEDI) of the child. Dates of other calendars are converted to Gregorian ones.
+    ///
+    /// How a parent is a parent ([crate::Parentage]), guardians and siblings who share no parents are not
+    /// written, as GEDCOM has no families for them.
+    pub fn to_gedcom(&self, options: &GedcomOptions) -> String {
+        let version = options.version;
+        let included = |n: &Nd| !(options.drop_shadows && self.graph[*n].is_shadow);
+        let nodes = self
+            .graph
+            .node_indices()
+            .filter(included)
+            .collect::<Vec<_>>();
+        //keep the xrefs persons were read with, where they can be
+        let mut used = HashSet::from(["SUBM".to_string()]);
+        let mut xrefs = HashMap::new();
+        for n in &nodes {
+            let read_with = self.graph[*n].external_ref(GEDCOM_REF);
+            if let Some(xref) =
+                read_with.filter(|x| version.is_xref(x) && used.insert(x.to_string()))
+            {
+                xrefs.insert(*n, xref.to_string());
+            }
+        }
+        let mut next = HashMap::new();
+        let mut fresh = |prefix: &'static str| loop {
+            let n = next.entry(prefix).or_insert(0);
+            *n += 1;
+            let xref = format!("{}{}", prefix, n);
+            if used.insert(xref.clone()) {
+                return xref;
+            }
+        };
+        for n in &nodes {
+            if !xrefs.contains_key(n) {
+                xrefs.insert(*n, fresh("I"));
+            }
+        }
+        let families = self.families(&nodes, included);
+        let fam_xrefs = families.iter().map(|_| fresh("F")).collect::<Vec<_>>();
+        let mut famc: HashMap<Nd, Vec<(usize, Kind)>> = HashMap::new();
+        let mut fams: HashMap<Nd, Vec<usize>> = HashMap::new();
+        for (i, family) in families.iter().enumerate() {
+            for p in &family.parents {
+                fams.entry(*p).or_default().push(i);
+            }
+            for (c, kind) in &family.children {
+                famc.entry(*c).or_default().push((i, *kind));
+            }
+        }
+
+        let mut records = Vec::new();
+        let mut gedc = Node::new("GEDC", "").with(Node::new("VERS", version.number()));
+        let mut head = Node::new("HEAD", "");
+        if version == GedcomVersion::V551 {
+            gedc = gedc.with(Node::new("FORM", "LINEAGE-LINKED"));
+            head = head
+                .with(Node::new("SOUR", "KIN"))
+                .with(gedc)
+                .with(Node::new("CHAR", "UTF-8"))
+                .with(Node::pointer("SUBM", "SUBM"));
+            records.push(head);
+            records.push(Node::record("SUBM", "SUBM").with(Node::new("NAME", "kin")));
+        } else {
+            records.push(head.with(gedc).with(Node::new("SOUR", "KIN")));
+        }
+        for n in &nodes {
+            let p = &self.graph[*n];
+            let mut indi = Node::record(&xrefs[n], "INDI");
+            if p.names.is_empty() && p.name != crate::names::UNKNOWN_NAME {
+                indi.children.push(Node::text("NAME", &p.name));
+            }
+            for name in &p.names {
+                indi.children.extend(name_nodes(name, version));
+            }
+            let sex = match p.sex {
+                Sex::Male => "M",
+                Sex::Female => "F",
+            };
+            indi.children.push(Node::new("SEX", sex));
+            for (tag, date) in [("BIRT", &p.birth), ("DEAT", &p.death)] {
+                if let Some(date) = date {
+                    indi.children
+                        .push(Node::new(tag, "").with(self.gedcom_date(date, version)));
+                }
+            }
+            attribute_nodes(&mut indi, &p.attributes, "FACT");
+            for (system, id) in p.refs.iter().filter(|(system, _)| *system != GEDCOM_REF) {
+                indi.children
+                    .push(Node::text("REFN", id).with(Node::text("TYPE", system)));
+            }
+            if p.is_shadow {
+                indi.children.push(Node::new("_SHADOW", "Y"));
+            }
+            for (i, kind) in famc.get(n).into_iter().flatten() {
+                let pedi = match (kind, version) {
+                    (Kind::AdoptiveParent, GedcomVersion::V551) => Some("adopted"),
+                    (Kind::AdoptiveParent, GedcomVersion::V70) => Some("ADOPTED"),
+                    (Kind::FosterParent, GedcomVersion::V551) => Some("foster"),
+                    (Kind::FosterParent, GedcomVersion::V70) => Some("FOSTER"),
+                    _ => None,
+                };
+                let mut link = Node::pointer("FAMC", &fam_xrefs[*i]);
+                if let Some(pedi) = pedi {
+                    link = link.with(Node::new("PEDI", pedi));
+                }
+                indi.children.push(link);
+            }
+            for i in fams.get(n).into_iter().flatten() {
+                indi.children.push(Node::pointer("FAMS", &fam_xrefs[*i]));
+            }
+            records.push(indi);
+        }
+        for (family, xref) in families.iter().zip(&fam_xrefs) {
+            let mut fam = Node::record(xref, "FAM");
+            //the husband is the man of a couple, or else the first
+            let mut parents = family.parents.clone();
+            parents.sort_by_key(|p| self.graph[*p].sex != Sex::Male);
+            let roles = match parents.as_slice() {
+                [p] if self.graph[*p].sex == Sex::Female => vec![("WIFE", p)],
+                [p] => vec![("HUSB", p)],
+                [p1, p2, ..] => vec![("HUSB", p1), ("WIFE", p2)],
+                [] => vec![],
+            };
+            for (role, p) in roles {
+                fam.children.push(Node::pointer(role, &xrefs[p]));
+            }
+            for (c, _) in &family.children {
+                fam.children.push(Node::pointer("CHIL", &xrefs[c]));
+            }
+            if let Some(partnership) = family.partnership {
+                let mut events = Vec::new();
+                match partnership.status {
+                    MaritalStatus::Partnered => {}
+                    MaritalStatus::Divorced => {
+                        events.push(("MARR", partnership.start));
+                        events.push(("DIV", partnership.end));
+                    }
+                    _ => events.push(("MARR", partnership.start)),
+                }
+                for (tag, date) in events {
+                    let mut event = Node::new(tag, "");
+                    match date {
+                        Some(date) => event = event.with(self.gedcom_date(&date, version)),
+                        None => event.value = "Y".into(),
+                    }
+                    fam.children.push(event);
+                }
+            }
+            //the facts of a family are those of the couple, as partners and as spouses
+            if let [p1, p2] = family.parents.as_slice() {
+                for kind in [Kind::RP, Kind::Spouse] {
+                    if let Some(attributes) = self.relation_attrs_of(*p1, *p2, kind) {
+                        let fact = match version {
+                            GedcomVersion::V551 => "EVEN",
+                            GedcomVersion::V70 => "FACT",
+                        };
+                        attribute_nodes(&mut fam, attributes, fact);
+                    }
+                }
+            }
+            records.push(fam);
+        }
+        records.push(Node::new("TRLR", ""));
+        let mut out = String::new();
+        for record in &records {
+            record.write(&mut out, 0, version);
+        }
+        out
+    }
+    //groups the parents of each child into couples, and makes a family of each couple (or single
+    //parent), and of each pair of spouses
+    pub(crate) fn families(&self, nodes: &[Nd], included: impl Fn(&Nd) -> bool) -> Vec<Family<'_>> {
+        let mut families: Vec<Family> = Vec::new();
+        let mut by_parents = HashMap::new();
+        let mut family_of = |mut parents: Vec<Nd>, families: &mut Vec<Family>| {
+            parents.sort();
+            *by_parents.entry(parents.clone()).or_insert_with(|| {
+                families.push(Family {
+                    parents,
+                    children: Vec::new(),
+                    partnership: None,
+                });
+                families.len() - 1
+            })
+        };
+        let are_partners = |p1: Nd, p2: Nd| {
+            self.graph
+                .edges_connecting(p1, p2)
+                .any(|e| matches!(e.weight(), Kind::RP | Kind::Spouse))
+        };
+        for c in nodes {
+            for kind in [Kind::Parent, Kind::AdoptiveParent, Kind::FosterParent] {
+                let mut parents = self
+                    .graph
+                    .edges_directed(*c, Direction::Incoming)
+                    .filter(|e| *e.weight() == kind && included(&e.source()))
+                    .map(|e| e.source())
+                    .collect::<Vec<_>>();
+                while !parents.is_empty() {
+                    let p = parents.remove(0);
+                    let mut couple = vec![p];
+                    if let Some(i) = parents.iter().position(|q| are_partners(p, *q)) {
+                        couple.push(parents.remove(i));
+                    }
+                    let i = family_of(couple, &mut families);
+                    families[i].children.push((*c, kind));
+                }
+            }
+        }
+        for e in self.graph.edge_references() {
+            let (p1, p2) = (e.source(), e.target());
+            if *e.weight() == Kind::Spouse && p1 < p2 && included(&p1) && included(&p2) {
+                let i = family_of(vec![p1, p2], &mut families);
+                families[i].partnership = self.partnership_of(p1, p2);
+            }
+        }
+        families
+    }
+    //a DATE line, with the date in the Gregorian calendar
+    fn gedcom_date(&self, date: &GenDate, version: GedcomVersion) -> Node {
+        let mut date = *date;
+        if self.calendar.name() != Gregorian.name() {
+            date = date.map(|d| convert(d, self.calendar(), &Gregorian));
+        }
+        let text = Gregorian.format(&date).to_uppercase();
+        let dual = date.map(|d| Date { dual: false, ..d });
+        match version {
+            //dual years are not part of GEDCOM 7.0, so they are kept as a phrase
+            GedcomVersion::V70 if dual != date => {
+                Node::new("DATE", Gregorian.format(&dual).to_uppercase())
+                    .with(Node::text("PHRASE", &text))
+            }
+            _ => Node::new("DATE", text),
+        }
+    }
+}
+
+///Checks a GEDCOM file against the structural rules of the given version: the layout of its
+/// lines, tags and xrefs, the header it must start with, that pointers point to records of the
+/// right kind, and that families and the individuals in them link to each other.
+pub fn validate_gedcom(text: &str, version: GedcomVersion) -> Vec<GedcomWarning> {
+    let mut warnings = Vec::new();
+    let mut warn = |line: usize, message: String| warnings.push(GedcomWarning { line, message });
+    for (i, line) in text.trim_start_matches('\u{feff}').lines().enumerate() {
+        if line.trim().is_empty() {
+            warn(i + 1, "Empty line".into());
+            continue;
+        }
+        if version == GedcomVersion::V551 && line.chars().count() > MAX_LINE {
+            warn(
+                i + 1,
+                format!("Line is longer than {} characters", MAX_LINE),
+            );
+        }
+        let Some((_, xref, tag, _)) = parse_line(line) else {
+            continue;
+        };
+        if !version.is_tag(tag) {
+            warn(i + 1, format!("Invalid tag {:?}", tag));
+        }
+        if let Some(xref) = xref.filter(|x| !version.is_xref(x)) {
+            warn(i + 1, format!("Invalid xref @{}@", xref));
+        }
+        if version == GedcomVersion::V70 && (tag == "CONC" || tag == "CHAR") {
+            warn(i + 1, format!("{} is not part of GEDCOM 7.0", tag));
+        }
+    }
+    let mut structure = Vec::new();
+    let records = parse_records(text, &mut structure);
+    warnings.extend(structure);
+    let mut warn = |line: usize, message: String| warnings.push(GedcomWarning { line, message });
+
+    match records.first() {
+        Some(head) if head.tag == "HEAD" => {
+            let gedc = head.child("GEDC");
+            match gedc.and_then(|g| g.child("VERS")) {
+                Some(vers) if vers.value.starts_with(version.number()) => {}
+                _ => warn(
+                    head.line,
+                    format!("HEAD has no GEDC.VERS {}", version.number()),
+                ),
+            }
+            if version == GedcomVersion::V551 {
+                if gedc.and_then(|g| g.child("FORM")).map(|f| f.value.as_str())
+                    != Some("LINEAGE-LINKED")
+                {
+                    warn(head.line, "HEAD has no GEDC.FORM LINEAGE-LINKED".into());
+                }
+                for tag in ["SOUR", "SUBM", "CHAR"] {
+                    if head.child(tag).is_none() {
+                        warn(head.line, format!("HEAD has no {}", tag));
+                    }
+                }
+            }
+        }
+        first => warn(
+            first.map_or(1, |r| r.line),
+            "The file does not start with HEAD".into(),
+        ),
+    }
+    for (i, record) in records.iter().enumerate() {
+        if record.tag == "TRLR" && i + 1 != records.len() {
+            warn(record.line, "TRLR is not the last record".into());
+        }
+    }
+    if records.last().map(|r| r.tag) != Some("TRLR") {
+        warn(
+            records.last().map_or(1, |r| r.line),
+            "The file does not end with TRLR".into(),
+        );
+    }
+
+    let mut kinds = HashMap::new();
+    for record in &records {
+        if let Some(xref) = record.xref {
+            if kinds.insert(xref, record.tag).is_some() {
+                warn(
+                    record.line,
+                    format!("Xref @{}@ is used more than once", xref),
+                );
+            }
+        }
+    }
+    //every pointer, by the line it is on, and what it points from and to
+    let mut pointers = Vec::new();
+    fn walk<'a>(
+        record: &'a Record<'a>,
+        top: &'a Record<'a>,
+        out: &mut Vec<(&'a Record<'a>, &'a Record<'a>)>,
+    ) {
+        out.push((top, record));
+        for child in &record.children {
+            walk(child, top, out);
+        }
+    }
+    for record in &records {
+        walk(record, record, &mut pointers);
+    }
+    let pointer_of = |r: &Record| -> Option<String> {
+        let inner = r.value.strip_prefix('@')?.strip_suffix('@')?;
+        (!inner.is_empty() && !inner.contains('@') && !inner.starts_with('#'))
+            .then(|| inner.to_string())
+    };
+    let mut links = HashSet::new();
+    for (top, record) in &pointers {
+        if record.xref.is_some() && !std::ptr::eq(*top, *record) {
+            warn(record.line, "Only records can have an xref".into());
+        }
+        let Some(target) = pointer_of(record) else {
+            continue;
+        };
+        if version == GedcomVersion::V70 && target == "VOID" {
+            continue;
+        }
+        let expected = match (top.tag, record.tag) {
+            ("INDI", "FAMC" | "FAMS") => Some("FAM"),
+            ("FAM", "HUSB" | "WIFE" | "CHIL") => Some("INDI"),
+            _ => None,
+        };
+        match kinds.get(target.as_str()) {
+            None => warn(
+                record.line,
+                format!("Pointer to missing record @{}@", target),
+            ),
+            Some(kind) if expected.is_some_and(|e| e != *kind) => warn(
+                record.line,
+                format!("{} points to a {} record", record.tag, kind),
+            ),
+            _ => {
+                let top_xref = top.xref.unwrap_or_default().to_string();
+                links.insert((top_xref, record.tag, target));
+            }
+        }
+    }
+    for record in &records {
+        let count = |tag: &str| record.children.iter().filter(|c| c.tag == tag).count();
+        match record.tag {
+            "INDI" => {
+                if count("SEX") > 1 {
+                    warn(record.line, "Individual has more than one SEX".into());
+                }
+                let sexes: &[&str] = match version {
+                    GedcomVersion::V551 => &["M", "F", "U"],
+                    GedcomVersion::V70 => &["M", "F", "U", "X"],
+                };
+                for sex in record.children.iter().filter(|c| c.tag == "SEX") {
+                    if !sexes.contains(&sex.value.as_str()) {
+                        warn(sex.line, format!("Invalid SEX {:?}", sex.value));
+                    }
+                }
+            }
+            "FAM" => {
+                for role in ["HUSB", "WIFE"] {
+                    if count(role) > 1 {
+                        warn(record.line, format!("Family has more than one {}", role));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    //families and their members must point to each other
+    for (from, tag, to) in &links {
+        let back: &[&str] = match *tag {
+            "FAMS" => &["HUSB", "WIFE"],
+            "FAMC" => &["CHIL"],
+            "HUSB" | "WIFE" => &["FAMS"],
+            "CHIL" => &["FAMC"],
+            _ => continue,
+        };
+        if !back
+            .iter()
+            .any(|b| links.contains(&(to.clone(), *b, from.clone())))
+        {
+            let line = pointers
+                .iter()
+                .find(|(top, r)| {
+                    top.xref == Some(from.as_str())
+                        && r.tag == *tag
+                        && pointer_of(r).as_ref() == Some(to)
+                })
+                .map_or(1, |(_, r)| r.line);
+            warn(line, format!("@{}@ does not link back to @{}@", to, from));
+        }
+    }
+    warnings.sort_by_key(|w| w.line);
+    warnings
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Date;
+
+    #[test]
+    fn parse_lines() {
+        let mut warnings = Vec::new();
+        let text = "0 HEAD\n1 CHAR UTF-8\n0 @N1@ NOTE First\n1 CONT second\n1 CONC  line\n\
+                    3 DATE 1900\nbad\n0 TRLR";
+        let records = parse_records(text, &mut warnings);
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].children[0].value, "UTF-8");
+        assert_eq!(records[1].xref, Some("N1"));
+        assert_eq!(records[1].value, "First\nsecond line");
+        assert_eq!(warnings.iter().map(|w| w.line).collect::<Vec<_>>(), [6, 7]);
+    }
+    #[test]
+    fn parse_dates() {
+        let parse = |s: &str| parse_date(s, &crate::Gregorian);
+        assert_eq!(parse("12 MAR 1850").unwrap(), Date::ymd(1850, 3, 12).into());
+        assert_eq!(
+            parse("BET 1820 AND 1825").unwrap(),
+            GenDate::Between(Date::year(1820), Date::year(1825))
+        );
+        assert_eq!(
+            parse("@#DGREGORIAN@ ABT 1700").unwrap(),
+            GenDate::About(Date::year(1700))
+        );
+        assert_eq!(
+            parse("INT 1901 (from the census)").unwrap(),
+            Date::year(1901).into()
+        );
+        assert!(parse("@#DJULIAN@ 1700").is_err());
+        assert!(parse("FROM 1900 TO 1910").is_err());
+        assert!(parse("(unknown)").is_err());
+    }
+    #[test]
+    fn validate_structure() {
+        let lines = |text: &str, version| {
+            validate_gedcom(text, version)
+                .iter()
+                .map(|w| w.line)
+                .collect::<Vec<_>>()
+        };
+        let v551 = "0 HEAD\n1 SOUR X\n1 GEDC\n2 VERS 5.5.1\n2 FORM LINEAGE-LINKED\n1 CHAR UTF-8\n\
+                    1 SUBM @U@\n0 @U@ SUBM\n0 @I1@ INDI\n1 SEX M\n1 FAMS @F1@\n0 @F1@ FAM\n\
+                    1 HUSB @I1@\n0 TRLR";
+        assert!(lines(v551, GedcomVersion::V551).is_empty());
+        //7.0 has no CHAR, and does not need a submitter
+        assert_eq!(lines(v551, GedcomVersion::V70), [1, 6]);
+        let v70 = v551
+            .replace("2 VERS 5.5.1", "2 VERS 7.0")
+            .replace("1 CHAR UTF-8\n", "");
+        assert!(lines(&v70, GedcomVersion::V70).is_empty());
+        let broken = v70
+            .replace("1 SEX M", "1 SEX Q\n1 sex M\n2 @X@ NOTE")
+            .replace("1 HUSB @I1@", "1 HUSB @F1@\n1 WIFE @I9@\n1 CHIL @I1@");
+        assert_eq!(
+            lines(&broken, GedcomVersion::V70),
+            [9, 10, 11, 12, 14, 15, 16]
+        );
+        let long = v551.replace("1 SEX M", &format!("1 NOTE {}", "x".repeat(300)));
+        assert_eq!(lines(&long, GedcomVersion::V551), [10]);
+        assert_eq!(lines("0 @I1@ INDI", GedcomVersion::V551), [1, 1]);
+    }
+}
@@ -0,0 +1,707 @@
+//! Conversion to and from the JSON serialization of GEDCOM X.
+//!
+//! Persons keep their id as a `gedcomx` external ref, and their identifiers as refs of the
+//! identifier type. Birth and death facts become the dates of the person, and other facts become
+//! attributes, keyed by the fact type without the `http://gedcomx.org/` prefix (or the text of a
+//! `data:,` type). The place of a fact is kept as the attribute `<key>.place`.
+//!
+//! A ParentChild relationship is a [Kind::Parent] edge, or an adoptive, foster or guardian one
+//! when it has the fact of that type. StepParent and SociologicalParent facts make the parentage
+//! legal only, and a BiologicalParent fact is written for parentage that is biological only,
+//! although it is read back as the default parentage. A Couple relationship is a [Kind::RP]
+//! edge, and a marriage fact on it also makes the couple spouses. Other facts of relationships
+//! become relation attributes.
+use crate::attributes::add_attr;
+use crate::{
+    convert, AttrValue, Attributes, Calendar, Date, GenDate, Gregorian, KinGraph, Kind,
+    MaritalStatus, Parentage, Partnership, PersonName, Sex,
+};
+use anyhow::{anyhow, bail, Result};
+use petgraph::visit::EdgeRef;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+///The external ref system that the ids of GEDCOM X persons are kept under
+pub const GEDCOMX_REF: &str = "gedcomx";
+const GX: &str = "http://gedcomx.org/";
+
+#[derive(Default, Serialize, Deserialize)]
+struct Document {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    persons: Vec<GxPerson>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    relationships: Vec<Relationship>,
+}
+#[derive(Serialize, Deserialize)]
+struct GxPerson {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    gender: Option<Typed>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    names: Vec<Name>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    facts: Vec<Fact>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    identifiers: BTreeMap<String, Vec<String>>,
+}
+#[derive(Serialize, Deserialize)]
+struct Typed {
+    #[serde(rename = "type")]
+    kind: String,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Name {
+    #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
+    kind: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    preferred: Option<bool>,
+    #[serde(default)]
+    name_forms: Vec<NameForm>,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NameForm {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    full_text: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    parts: Vec<NamePart>,
+}
+#[derive(Serialize, Deserialize)]
+struct NamePart {
+    #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
+    kind: Option<String>,
+    value: String,
+}
+#[derive(Serialize, Deserialize)]
+struct Fact {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    date: Option<GxDate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    place: Option<PlaceReference>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    value: Option<String>,
+}
+impl Fact {
+    fn new(kind: String) -> Fact {
+        Fact {
+            kind,
+            date: None,
+            place: None,
+            value: None,
+        }
+    }
+}
+#[derive(Serialize, Deserialize)]
+struct GxDate {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    original: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    formal: Option<String>,
+}
+#[derive(Serialize, Deserialize)]
+struct PlaceReference {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    original: Option<String>,
+}
+#[derive(Serialize, Deserialize)]
+struct Relationship {
+    #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
+    kind: Option<String>,
+    person1: ResourceReference,
+    person2: ResourceReference,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    facts: Vec<Fact>,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ResourceReference {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    resource: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    resource_id: Option<String>,
+}
+impl ResourceReference {
+    fn to(id: &str) -> Self {
+        ResourceReference {
+            resource: Some(format!("#{}", id)),
+            resource_id: None,
+        }
+    }
+    //the id of the person in the same document it refers to
+    fn id(&self) -> Option<&str> {
+        self.resource_id
+            .as_deref()
+            .or_else(|| self.resource.as_deref()?.strip_prefix('#'))
+    }
+}
+
+//the key of an attribute for a fact type
+fn type_key(kind: &str) -> String {
+    if let Some(key) = kind.strip_prefix(GX) {
+        return key.to_string();
+    }
+    match kind.strip_prefix("data:,") {
+        Some(key) => percent_decode(key),
+        None => kind.to_string(),
+    }
+}
+//the fact type for the key of an attribute
+fn key_type(key: &str) -> String {
+    if key.contains(':') {
+        key.to_string()
+    } else if key.starts_with(|c: char| c.is_ascii_uppercase())
+        && key.chars().all(|c| c.is_ascii_alphanumeric())
+    {
+        format!("{}{}", GX, key)
+    } else {
+        format!("data:,{}", percent_encode(key))
+    }
+}
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            b => format!("%{:02X}", b),
+        })
+        .collect()
+}
+fn percent_decode(s: &str) -> String {
+    let mut bytes = Vec::new();
+    let mut rest = s.as_bytes();
+    while let Some((&b, after)) = rest.split_first() {
+        let hex = after.get(..2).and_then(|h| std::str::from_utf8(h).ok());
+        match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+            Some(decoded) if b == b'%' => {
+                bytes.push(decoded);
+                rest = &after[2..];
+            }
+            _ => {
+                bytes.push(b);
+                rest = after;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+///Reads a GEDCOM X formal date, such as `+1850-03-12`, `A+1850` (approximately), `+1820/+1825`
+/// (a range), `/+1900` (before) or `+1900/` (after)
+fn parse_formal(formal: &str) -> Result<GenDate> {
+    let simple = |s: &str| -> Result<Date> {
+        let s = s.split('T').next().unwrap_or_default();
+        let (sign, rest) = match s.split_at_checked(1) {
+            Some(("+", rest)) => (1, rest),
+            Some(("-", rest)) => (-1, rest),
+            _ => bail!("Formal date {:?} has no sign", formal),
+        };
+        let mut parts = rest.split('-');
+        let year: i32 = parts.next().unwrap_or_default().parse()?;
+        let month = parts.next().map(str::parse::<u8>).transpose()?;
+        let day = parts.next().map(str::parse::<u8>).transpose()?;
+        Ok(Date {
+            year: sign * year,
+            month,
+            day,
+            dual: false,
+        })
+    };
+    let (approximate, range) = match formal.strip_prefix('A') {
+        Some(rest) => (true, rest),
+        None => (false, formal),
+    };
+    let date = match range.split_once('/') {
+        None if approximate => GenDate::About(simple(range)?),
+        None => GenDate::Exact(simple(range)?),
+        Some((_, end)) if end.starts_with('P') => {
+            bail!("Durations are not supported: {:?}", formal)
+        }
+        Some(("", end)) => GenDate::Before(simple(end)?),
+        Some((start, "")) => GenDate::After(simple(start)?),
+        Some((start, end)) => GenDate::Between(simple(start)?, simple(end)?),
+    };
+    Ok(date)
+}
+///Writes a (Gregorian) date as a GEDCOM X formal date, which has no way to say that a date was
+/// calculated, or to write dual years
+fn format_formal(date: &GenDate) -> String {
+    let simple = |d: &Date| {
+        let sign = if d.year < 0 { '-' } else { '+' };
+        let mut s = format!("{}{:04}", sign, d.year.abs());
+        if let Some(m) = d.month {
+            s += &format!("-{:02}", m);
+            if let Some(day) = d.day {
+                s += &format!("-{:02}", day);
+            }
+        }
+        s
+    };
+    match date {
+        GenDate::Exact(d) | GenDate::Calculated(d) => simple(d),
+        GenDate::About(d) | GenDate::Estimated(d) => format!("A{}", simple(d)),
+        GenDate::Before(d) => format!("/{}", simple(d)),
+        GenDate::After(d) => format!("{}/", simple(d)),
+        GenDate::Between(a, b) => format!("{}/{}", simple(a), simple(b)),
+    }
+}
+//reads the date of a fact, preferring the original text, which keeps what formal dates can not
+fn fact_date(date: &GxDate, calendar: &dyn Calendar) -> Result<GenDate> {
+    let original = date.original.as_deref().map(|o| calendar.parse(o));
+    match (original, &date.formal) {
+        (Some(Ok(date)), _) => Ok(date),
+        (_, Some(formal)) => parse_formal(formal),
+        (Some(Err(e)), None) => Err(e),
+        (None, None) => bail!("Date has neither an original nor a formal form"),
+    }
+}
+
+//the name of a person, from the first of its forms
+fn person_name(name: &Name) -> Option<PersonName> {
+    let form = name.name_forms.first()?;
+    let mut person_name = PersonName::default();
+    for part in &form.parts {
+        let words = part.value.split_whitespace().map(str::to_string);
+        match part.kind.as_deref().and_then(|k| k.strip_prefix(GX)) {
+            Some("Given") => person_name.given.extend(words),
+            Some("Surname") => person_name.surname = Some(part.value.clone()),
+            Some("Prefix") => person_name.titles.extend(words),
+            _ => {}
+        }
+    }
+    if form.parts.is_empty() {
+        //without parts, the last word of the name is taken to be the surname
+        let mut words = form
+            .full_text
+            .as_deref()?
+            .split_whitespace()
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        if words.len() > 1 {
+            person_name.surname = words.pop();
+        }
+        person_name.given = words;
+    }
+    Some(person_name)
+}
+fn name_of(name: &PersonName, kind: Option<&str>, preferred: bool) -> Name {
+    let mut parts = Vec::new();
+    let mut part = |kind: &str, value: String| {
+        if !value.is_empty() {
+            parts.push(NamePart {
+                kind: Some(format!("{}{}", GX, kind)),
+                value,
+            })
+        }
+    };
+    part("Prefix", name.titles.join(" "));
+    part("Given", name.given.join(" "));
+    part("Surname", name.surname.clone().unwrap_or_default());
+    Name {
+        kind: kind.map(|k| format!("{}{}", GX, k)),
+        preferred: preferred.then_some(true),
+        name_forms: vec![NameForm {
+            full_text: Some(name.to_string()),
+            parts,
+        }],
+    }
+}
+
+//keeps the place of a fact as the attribute <key>.place
+fn keep_place(fact: &Fact, attributes: &mut Attributes) {
+    if let Some(place) = fact.place.as_ref().and_then(|p| p.original.clone()) {
+        add_attr(
+            attributes,
+            format!("{}.place", type_key(&fact.kind)),
+            place.into(),
+        );
+    }
+}
+//keeps a fact as attributes, with its place under <key>.place
+fn keep_fact(fact: &Fact, attributes: &mut Attributes, calendar: &dyn Calendar) -> Result<()> {
+    let value = match (&fact.value, &fact.date) {
+        (Some(value), _) => AttrValue::Str(value.clone()),
+        (None, Some(date)) => AttrValue::Date(fact_date(date, calendar)?),
+        (None, None) => AttrValue::Bool(true),
+    };
+    keep_place(fact, attributes);
+    add_attr(attributes, type_key(&fact.kind), value);
+    Ok(())
+}
+//writes attributes as facts, putting each place on the fact it is of
+fn facts_of(attributes: &Attributes, facts: &mut Vec<Fact>, date: impl Fn(&GenDate) -> GxDate) {
+    for (key, value) in attributes {
+        if key.ends_with(".place") {
+            continue;
+        }
+        let values = match value {
+            AttrValue::List(items) => items.iter().collect(),
+            value => vec![value],
+        };
+        for value in values {
+            let mut fact = Fact::new(key_type(key));
+            match value {
+                AttrValue::Date(d) => fact.date = Some(date(d)),
+                AttrValue::Bool(true) => {}
+                value => fact.value = Some(value.to_string()),
+            }
+            facts.push(fact);
+        }
+    }
+    for (key, value) in attributes {
+        let Some(of) = key.strip_suffix(".place") else {
+            continue;
+        };
+        let kind = key_type(of);
+        let fact = match facts.iter().position(|f| f.kind == kind) {
+            Some(i) => &mut facts[i],
+            None => {
+                facts.push(Fact::new(kind));
+                facts.last_mut().unwrap()
+            }
+        };
+        fact.place = Some(PlaceReference {
+            original: Some(value.to_string()),
+        });
+    }
+}
+
+impl KinGraph {
+    ///Reads a GEDCOM X JSON document. Persons of unknown gender are read as female, keeping the
+    /// gender as the `gender` attribute. Relationships to persons that are not in the document
+    /// are an error.
+    pub fn from_gedcomx(json: &str) -> Result<KinGraph> {
+        let doc: Document = serde_json::from_str(json)?;
+        let mut kg = KinGraph::new();
+        let mut persons = HashMap::new();
+        for gx in &doc.persons {
+            let mut attributes = Attributes::new();
+            let sex = match gx.gender.as_ref().map(|g| g.kind.as_str()) {
+                Some("http://gedcomx.org/Male") => Sex::Male,
+                Some("http://gedcomx.org/Female") => Sex::Female,
+                gender => {
+                    let gender = gender.unwrap_or("http://gedcomx.org/Unknown");
+                    attributes.insert("gender".into(), gender.into());
+                    Sex::Female
+                }
+            };
+            let mut names = gx.names.iter().collect::<Vec<_>>();
+            names.sort_by_key(|n| n.preferred != Some(true));
+            let (nicknames, names): (Vec<_>, Vec<_>) = names
+                .into_iter()
+                .partition(|n| n.kind.as_deref() == Some("http://gedcomx.org/Nickname"));
+            let mut names = names
+                .iter()
+                .filter_map(|n| person_name(n))
+                .collect::<Vec<_>>();
+            if let Some(primary) = names.first_mut() {
+                let full_texts = nicknames
+                    .iter()
+                    .filter_map(|n| n.name_forms.first()?.full_text.clone());
+                primary.nicknames.extend(full_texts);
+            }
+            let (mut birth, mut death) = (None, None);
+            for fact in &gx.facts {
+                let date = fact.date.as_ref().map(|d| fact_date(d, kg.calendar()));
+                let date = match date.transpose() {
+                    Ok(date) => date,
+                    Err(e) => return Err(e.context(format!("In a fact of {:?}", gx.id))),
+                };
+                match fact.kind.as_str() {
+                    "http://gedcomx.org/Birth" if birth.is_none() && fact.value.is_none() => {
+                        birth = date;
+                        keep_place(fact, &mut attributes);
+                    }
+                    "http://gedcomx.org/Death" if death.is_none() && fact.value.is_none() => {
+                        death = date;
+                        keep_place(fact, &mut attributes);
+                    }
+                    _ => keep_fact(fact, &mut attributes, kg.calendar())?,
+                }
+            }
+            let short = names
+                .first()
+                .map_or(crate::names::UNKNOWN_NAME.into(), PersonName::to_string);
+            let p = kg.np_with_name(sex, short);
+            for name in names {
+                kg.add_alternate_name(&p, name)?;
+            }
+            kg.set_birth(&p, birth)?;
+            kg.set_death(&p, death)?;
+            for (key, value) in attributes {
+                kg.set_attr(&p, &key, value)?;
+            }
+            for (system, ids) in &gx.identifiers {
+                if let Some(id) = ids.first() {
+                    kg.set_external_ref(&p, system, id)?;
+                }
+            }
+            if let Some(id) = &gx.id {
+                kg.set_external_ref(&p, GEDCOMX_REF, id)?;
+                persons.insert(id.as_str(), p);
+            }
+        }
+        for r in &doc.relationships {
+            let person = |reference: &ResourceReference| {
+                let id = reference.id();
+                id.and_then(|id| persons.get(id))
+                    .ok_or_else(|| anyhow!("Relationship to a missing person {:?}", id))
+            };
+            let (p1, p2) = (person(&r.person1)?, person(&r.person2)?);
+            let mut attributes = Attributes::new();
+            let kind = match r.kind.as_deref() {
+                Some("http://gedcomx.org/ParentChild") => {
+                    let (mut kind, mut parentage) = (Kind::Parent, Parentage::default());
+                    for fact in &r.facts {
+                        match fact.kind.strip_prefix(GX).unwrap_or_default() {
+                            "AdoptiveParent" => kind = Kind::AdoptiveParent,
+                            "FosterParent" => kind = Kind::FosterParent,
+                            "GuardianParent" => kind = Kind::Guardian,
+                            "StepParent" | "SociologicalParent" => {
+                                parentage = Parentage::legal_only()
+                            }
+                            "BiologicalParent" => {}
+                            _ => keep_fact(fact, &mut attributes, kg.calendar())?,
+                        }
+                    }
+                    match kind {
+                        Kind::Parent => kg.add_parent_with(p1, p2, parentage)?,
+                        kind => kg.add_relation(p1, p2, kind)?,
+                    }
+                    kind
+                }
+                Some("http://gedcomx.org/Couple") => {
+                    kg.add_relation(p1, p2, Kind::RP)?;
+                    let mut partnership = None;
+                    for fact in &r.facts {
+                        let date = fact.date.as_ref().map(|d| fact_date(d, kg.calendar()));
+                        let status = match fact.kind.strip_prefix(GX).unwrap_or_default() {
+                            "Marriage" => MaritalStatus::Married,
+                            "CommonLawMarriage" => MaritalStatus::Partnered,
+                            "Divorce" => MaritalStatus::Divorced,
+                            _ => {
+                                keep_fact(fact, &mut attributes, kg.calendar())?;
+                                continue;
+                            }
+                        };
+                        keep_place(fact, &mut attributes);
+                        let partnership = partnership.get_or_insert_with(Partnership::default);
+                        if status == MaritalStatus::Divorced {
+                            partnership.end = date.transpose()?;
+                        } else {
+                            partnership.start = date.transpose()?;
+                        }
+                        if partnership.status != MaritalStatus::Divorced {
+                            partnership.status = status;
+                        }
+                    }
+                    if let Some(partnership) = partnership {
+                        kg.add_partnership(p1, p2, partnership)?;
+                    }
+                    Kind::RP
+                }
+                kind => bail!("Unsupported relationship type {:?}", kind),
+            };
+            for (key, value) in attributes {
+                kg.set_relation_attr(p1, p2, kind, &key, value)?;
+            }
+        }
+        Ok(kg)
+    }
+    ///Writes the graph as a GEDCOM X JSON document. Sibling relations have no GEDCOM X
+    /// relationship and are left out, and dates of other calendars are converted to Gregorian.
+    pub fn to_gedcomx(&self) -> Result<String> {
+        let date = |d: &GenDate| {
+            let d = match self.calendar.name() == Gregorian.name() {
+                true => *d,
+                false => d.map(|day| convert(day, self.calendar(), &Gregorian)),
+            };
+            GxDate {
+                original: Some(Gregorian.format(&d)),
+                formal: Some(format_formal(&d)),
+            }
+        };
+        let mut used = HashSet::new();
+        let mut ids = HashMap::new();
+        for n in self.graph.node_indices() {
+            let read_with = self.graph[n].external_ref(GEDCOMX_REF);
+            if let Some(id) = read_with.filter(|id| used.insert(id.to_string())) {
+                ids.insert(n, id.to_string());
+            }
+        }
+        let mut next = 0;
+        for n in self.graph.node_indices() {
+            while !ids.contains_key(&n) {
+                next += 1;
+                let id = format!("P-{}", next);
+                if used.insert(id.clone()) {
+                    ids.insert(n, id);
+                }
+            }
+        }
+        let mut doc = Document::default();
+        for n in self.graph.node_indices() {
+            let p = &self.graph[n];
+            let mut attributes = p.attributes.clone();
+            let gender = match attributes.remove("gender") {
+                Some(AttrValue::Str(gender)) => gender,
+                _ => match p.sex {
+                    Sex::Male => "http://gedcomx.org/Male".into(),
+                    Sex::Female => "http://gedcomx.org/Female".into(),
+                },
+            };
+            let mut names = Vec::new();
+            for (i, name) in p.names.iter().enumerate() {
+                names.push(name_of(name, None, i == 0));
+                if let Some(birth) = &name.birth_surname {
+                    let birth_name = PersonName {
+                        surname: Some(birth.clone()),
+                        ..name.clone()
+                    };
+                    names.push(name_of(&birth_name, Some("BirthName"), false));
+                }
+                for nickname in &name.nicknames {
+                    let nickname = PersonName::new(&[nickname], None);
+                    names.push(name_of(&nickname, Some("Nickname"), false));
+                }
+            }
+            if p.names.is_empty() && p.name != crate::names::UNKNOWN_NAME {
+                names.push(Name {
+                    kind: None,
+                    preferred: Some(true),
+                    name_forms: vec![NameForm {
+                        full_text: Some(p.name.clone()),
+                        parts: Vec::new(),
+                    }],
+                });
+            }
+            let mut facts = Vec::new();
+            for (kind, d) in [("Birth", &p.birth), ("Death", &p.death)] {
+                if let Some(d) = d {
+                    facts.push(Fact {
+                        date: Some(date(d)),
+                        ..Fact::new(format!("{}{}", GX, kind))
+                    });
+                }
+            }
+            facts_of(&attributes, &mut facts, date);
+            let identifiers = p
+                .refs
+                .iter()
+                .filter(|(system, _)| *system != GEDCOMX_REF)
+                .map(|(system, id)| (system.clone(), vec![id.clone()]))
+                .collect();
+            doc.persons.push(GxPerson {
+                id: Some(ids[&n].clone()),
+                gender: Some(Typed { kind: gender }),
+                names,
+                facts,
+                identifiers,
+            });
+        }
+        let mut couples = HashSet::new();
+        for e in self.graph.edge_references() {
+            let (p1, p2, kind) = (e.source(), e.target(), *e.weight());
+            let mut attributes = Vec::from_iter(self.relation_attrs_of(p1, p2, kind));
+            let mut facts = Vec::new();
+            let relationship = match kind {
+                Kind::Parent | Kind::AdoptiveParent | Kind::FosterParent | Kind::Guardian => {
+                    let fact = match kind {
+                        Kind::AdoptiveParent => Some("AdoptiveParent"),
+                        Kind::FosterParent => Some("FosterParent"),
+                        Kind::Guardian => Some("GuardianParent"),
+                        _ => match self.parentage_of(p1, p2) {
+                            Parentage {
+                                biological: true,
+                                legal: false,
+                            } => Some("BiologicalParent"),
+                            Parentage {
+                                biological: false,
+                                legal: true,
+                            } => Some("SociologicalParent"),
+                            _ => None,
+                        },
+                    };
+                    facts.extend(fact.map(|f| Fact::new(format!("{}{}", GX, f))));
+                    "ParentChild"
+                }
+                Kind::RP | Kind::Spouse if couples.insert(Self::pair_key(p1, p2)) => {
+                    //a couple is one relationship for both of the kinds
+                    let other = match kind {
+                        Kind::RP => Kind::Spouse,
+                        _ => Kind::RP,
+                    };
+                    attributes.extend(self.relation_attrs_of(p1, p2, other));
+                    if let Some(partnership) = self.partnership_of(p1, p2) {
+                        let mut marriage = Fact::new(format!("{}Marriage", GX));
+                        if partnership.status == MaritalStatus::Partnered {
+                            marriage.kind = format!("{}CommonLawMarriage", GX);
+                        }
+                        marriage.date = partnership.start.as_ref().map(date);
+                        facts.push(marriage);
+                        if partnership.status == MaritalStatus::Divorced {
+                            let mut divorce = Fact::new(format!("{}Divorce", GX));
+                            divorce.date = partnership.end.as_ref().map(date);
+                            facts.push(divorce);
+                        }
+                    }
+                    "Couple"
+                }
+                _ => continue,
+            };
+            for attributes in attributes {
+                facts_of(attributes, &mut facts, date);
+            }
+            doc.relationships.push(Relationship {
+                kind: Some(format!("{}{}", GX, relationship)),
+                person1: ResourceReference::to(&ids[&p1]),
+                person2: ResourceReference::to(&ids[&p2]),
+                facts,
+            });
+        }
+        Ok(serde_json::to_string_pretty(&doc)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formal_dates() {
+        let cases = [
+            ("+1850-03-12", GenDate::Exact(Date::ymd(1850, 3, 12))),
+            ("A+1850", GenDate::About(Date::year(1850))),
+            ("/+1900-02", GenDate::Before(Date::ym(1900, 2))),
+            ("+1900/", GenDate::After(Date::year(1900))),
+            (
+                "+1820/+1825",
+                GenDate::Between(Date::year(1820), Date::year(1825)),
+            ),
+            ("-0044-03-15", GenDate::Exact(Date::ymd(-44, 3, 15))),
+        ];
+        for (formal, date) in cases {
+            assert_eq!(parse_formal(formal).unwrap(), date);
+            assert_eq!(format_formal(&date), formal);
+        }
+        assert_eq!(
+            parse_formal("+1850-03-12T10:00:00").unwrap(),
+            Date::ymd(1850, 3, 12).into()
+        );
+        assert!(parse_formal("1850").is_err());
+        assert!(parse_formal("+1850/P10Y").is_err());
+    }
+    #[test]
+    fn fact_types() {
+        assert_eq!(type_key("http://gedcomx.org/Occupation"), "Occupation");
+        assert_eq!(type_key("data:,Eye%20colour"), "Eye colour");
+        assert_eq!(key_type("Eye colour"), "data:,Eye%20colour");
+        assert_eq!(key_type("Occupation"), "http://gedcomx.org/Occupation");
+        assert_eq!(key_type("urn:x:y"), "urn:x:y");
+        assert_eq!(percent_decode("%E2%9C%93 100%"), "\u{2713} 100%");
+    }
+}
@@ -0,0 +1,904 @@
+//! Reading and writing of Gramps XML, either plain or gzip compressed as in `.gramps` files.
+//!
+//! People become persons, keeping their Gramps handle as a `gramps` external ref and their
+//! Gramps ID as a `gramps_id` one, so that writing a graph that was read from Gramps gives the
+//! same handles back. A family with a father and a mother makes them RPs, and spouses when it is
+//! a married family or has a marriage event. Each child reference makes each of the parents of the
+//! family a parent of the child, or an adoptive or foster one when the relation of the child to
+//! them says so. Guardians are kept as associations (`personref`) of their wards.
+//!
+//! Birth and death events become the dates of a person. Other events become attributes keyed by
+//! the event type, holding the date of the event, or `true` when it has none. The place and
+//! description of an event are kept as `<type>.place` and `<type>.description`, as is a date that
+//! can not be read as `<type>.date`. Gramps attributes become text attributes. The events and
+//! attributes of a family, along with its handle and ID, are kept as attributes of the relation
+//! between the parents, or between a single parent and each of the children.
+use crate::attributes::add_attr;
+use crate::xml::{self, Element};
+use crate::{
+    convert, AttrValue, Attributes, Calendar, Date, GenDate, Gregorian, KinGraph, Kind,
+    MaritalStatus, Nd, Parentage, Partnership, PersonName, Sex,
+};
+use anyhow::{anyhow, Result};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use petgraph::visit::EdgeRef;
+use petgraph::Direction;
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+
+///The external ref system that the handles of Gramps people are kept under
+pub const GRAMPS_REF: &str = "gramps";
+///The external ref system that the IDs of Gramps people (such as `I0001`) are kept under
+pub const GRAMPS_ID_REF: &str = "gramps_id";
+const NAMESPACE: &str = "http://gramps-project.org/xml/1.7.1/";
+//the details of an event that are kept as attributes next to its date
+const DETAILS: [&str; 3] = ["place", "description", "date"];
+
+//reads the database element of a Gramps XML document
+fn parse_xml(text: &str) -> Result<Element> {
+    xml::parse(text)?
+        .children
+        .into_iter()
+        .find(|e| e.name == "database")
+        .ok_or_else(|| anyhow!("Not a Gramps XML document"))
+}
+
+//an event of a person or family
+#[derive(Default)]
+struct GrampsEvent {
+    kind: String,
+    date: Option<GenDate>,
+    //a date that could not be read, or is in a calendar that is not supported
+    date_text: Option<String>,
+    place: Option<String>,
+    description: Option<String>,
+}
+impl GrampsEvent {
+    fn new(kind: &str, date: Option<GenDate>) -> Self {
+        GrampsEvent {
+            kind: kind.to_string(),
+            date,
+            ..Default::default()
+        }
+    }
+    fn read(event: &Element, places: &HashMap<&str, &str>, calendar: &dyn Calendar) -> Self {
+        let (date, date_text) = match read_date(event, calendar) {
+            Ok(date) => (date, None),
+            Err(text) => (None, Some(text)),
+        };
+        GrampsEvent {
+            kind: event.child_text("type").unwrap_or("Unknown").to_string(),
+            date,
+            date_text,
+            place: event
+                .child("place")
+                .and_then(|p| places.get(p.attr("hlink")?))
+                .map(|p| p.to_string()),
+            description: event.child_text("description").map(str::to_string),
+        }
+    }
+    //keeps the event as attributes keyed by its type, along with its date unless it is kept
+    //elsewhere, such as the date of birth of a person
+    fn keep(self, attributes: &mut Attributes, with_date: bool) {
+        if with_date {
+            let value = self.date.map_or(AttrValue::Bool(true), AttrValue::Date);
+            add_attr(attributes, self.kind.clone(), value);
+        }
+        for (detail, value) in DETAILS
+            .iter()
+            .zip([self.place, self.description, self.date_text])
+        {
+            if let Some(value) = value {
+                add_attr(
+                    attributes,
+                    format!("{}.{}", self.kind, detail),
+                    value.into(),
+                );
+            }
+        }
+    }
+}
+//the type of event and the detail of it that an attribute keeps, e.g. Birth.place
+fn detail_of(key: &str) -> Option<(&str, &str)> {
+    key.rsplit_once('.').filter(|(_, d)| DETAILS.contains(d))
+}
+//splits attributes into the events they were read from, which are added to the given ones, and
+//the Gramps attributes they were read from
+fn split_attributes(
+    attributes: &Attributes,
+    events: &mut Vec<GrampsEvent>,
+    to_gregorian: &dyn Fn(&GenDate) -> GenDate,
+) -> Vec<(String, String)> {
+    let mut plain = Vec::new();
+    for (key, value) in attributes {
+        if key.starts_with("gramps.") || key == "gender" || detail_of(key).is_some() {
+            continue;
+        }
+        let values = match value {
+            AttrValue::List(items) => items.iter().collect(),
+            value => vec![value],
+        };
+        for value in values {
+            match value {
+                AttrValue::Date(d) => events.push(GrampsEvent::new(key, Some(to_gregorian(d)))),
+                AttrValue::Bool(true) => events.push(GrampsEvent::new(key, None)),
+                value => plain.push((key.clone(), value.to_string())),
+            }
+        }
+    }
+    for (key, value) in attributes {
+        let Some((kind, detail)) = detail_of(key) else {
+            continue;
+        };
+        let event = match events.iter().position(|e| e.kind == kind) {
+            Some(i) => &mut events[i],
+            None => {
+                events.push(GrampsEvent::new(kind, None));
+                events.last_mut().unwrap()
+            }
+        };
+        let value = Some(value.to_string());
+        match detail {
+            "place" => event.place = value,
+            "description" => event.description = value,
+            _ => event.date_text = value,
+        }
+    }
+    plain
+}
+
+//reads a date value such as 1850-03-12, 1850-03 or 1850, where 00 stands for an unknown part
+fn parse_val(val: &str) -> Option<Date> {
+    let (sign, rest) = match val.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, val),
+    };
+    let mut parts = rest.split('-');
+    let year: i32 = parts.next()?.parse().ok()?;
+    let mut part = || match parts.next() {
+        None => Some(None),
+        Some(p) => p.parse::<u8>().ok().map(|n| Some(n).filter(|n| *n != 0)),
+    };
+    let month = part()?;
+    let day = part()?;
+    Some(Date {
+        year: sign * year,
+        month,
+        day: month.and(day),
+        dual: false,
+    })
+}
+fn format_val(date: &Date) -> String {
+    let sign = if date.year < 0 { "-" } else { "" };
+    let mut val = format!("{}{:04}", sign, date.year.abs());
+    if let Some(month) = date.month {
+        val += &format!("-{:02}", month);
+        if let Some(day) = date.day {
+            val += &format!("-{:02}", day);
+        }
+    }
+    val
+}
+//reads the date of an event, or gives back the text of one that can not be read
+fn read_date(event: &Element, calendar: &dyn Calendar) -> Result<Option<GenDate>, String> {
+    if let Some(text) = event.child("datestr").and_then(|d| d.attr("val")) {
+        return calendar.parse(text).map(Some).map_err(|_| text.to_string());
+    }
+    let Some(element) = ["dateval", "daterange", "datespan"]
+        .iter()
+        .find_map(|name| event.child(name))
+    else {
+        return Ok(None);
+    };
+    let dual = element.attr("dualdated") == Some("1");
+    let val = |key| {
+        Some(Date {
+            dual,
+            ..parse_val(element.attr(key)?)?
+        })
+    };
+    let date = match element.name.as_str() {
+        "dateval" => val("val").map(|d| match (element.attr("quality"), element.attr("type")) {
+            (Some("estimated"), _) => GenDate::Estimated(d),
+            (Some("calculated"), _) => GenDate::Calculated(d),
+            (_, Some("about")) => GenDate::About(d),
+            (_, Some("before")) => GenDate::Before(d),
+            (_, Some("after")) => GenDate::After(d),
+            _ => GenDate::Exact(d),
+        }),
+        _ => val("start")
+            .zip(val("stop"))
+            .map(|(start, stop)| GenDate::Between(start, stop)),
+    };
+    let gregorian = element.attr("cformat").is_none_or(|c| c == "Gregorian");
+    match date {
+        Some(date) if gregorian => Ok(Some(date)),
+        _ => {
+            let described = element.attrs.iter().map(|(k, v)| format!("{}={}", k, v));
+            Err(described.collect::<Vec<_>>().join(" "))
+        }
+    }
+}
+//the element of a (Gregorian) date
+fn date_element(date: &GenDate) -> Element {
+    let element = match date {
+        GenDate::Between(start, stop) => Element::new("daterange")
+            .with_attr("start", format_val(start))
+            .with_attr("stop", format_val(stop)),
+        GenDate::Exact(d)
+        | GenDate::About(d)
+        | GenDate::Before(d)
+        | GenDate::After(d)
+        | GenDate::Estimated(d)
+        | GenDate::Calculated(d) => {
+            let element = Element::new("dateval").with_attr("val", format_val(d));
+            match date {
+                GenDate::About(_) => element.with_attr("type", "about"),
+                GenDate::Before(_) => element.with_attr("type", "before"),
+                GenDate::After(_) => element.with_attr("type", "after"),
+                GenDate::Estimated(_) => element.with_attr("quality", "estimated"),
+                GenDate::Calculated(_) => element.with_attr("quality", "calculated"),
+                _ => element,
+            }
+        }
+    };
+    let dual = date.map(|d| Date { dual: false, ..d }) != *date;
+    match dual {
+        true => element.with_attr("dualdated", "1"),
+        false => element,
+    }
+}
+
+fn read_name(name: &Element) -> PersonName {
+    let words = |tag| {
+        name.child_text(tag).map_or(Vec::new(), |t| {
+            t.split_whitespace().map(str::to_string).collect()
+        })
+    };
+    let surname = name
+        .children("surname")
+        .find(|s| s.attr("prim") != Some("0"))
+        .map(|s| s.text.clone());
+    PersonName {
+        given: words("first"),
+        surname: surname.filter(|s| !s.is_empty()),
+        birth_surname: None,
+        nicknames: name
+            .child_text("nick")
+            .map(str::to_string)
+            .into_iter()
+            .collect(),
+        titles: words("title"),
+    }
+}
+fn name_element(name: &PersonName, kind: &str, alternate: bool) -> Element {
+    let mut element = Element::new("name").with_attr("type", kind);
+    if alternate {
+        element = element.with_attr("alt", "1");
+    }
+    let parts = [
+        ("first", name.given.join(" ")),
+        ("surname", name.surname.clone().unwrap_or_default()),
+        ("title", name.titles.join(" ")),
+        ("nick", name.nicknames.first().cloned().unwrap_or_default()),
+    ];
+    for (tag, text) in parts {
+        if !text.is_empty() {
+            element = element.with(Element::text(tag, &text));
+        }
+    }
+    element
+}
+
+impl KinGraph {
+    ///Reads a Gramps XML database, either plain or gzip compressed. People of unknown gender are
+    /// read as female, keeping the gender as the `gender` attribute. Families that refer to people
+    /// who are not in the database are an error.
+    pub fn read_gramps<R: Read>(mut reader: R) -> Result<KinGraph> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let text = match bytes.starts_with(&[0x1f, 0x8b]) {
+            true => {
+                let mut text = String::new();
+                GzDecoder::new(bytes.as_slice()).read_to_string(&mut text)?;
+                text
+            }
+            false => String::from_utf8(bytes)?,
+        };
+        let doc = parse_xml(&text)?;
+        let places: HashMap<&str, &str> = doc
+            .section("places", "placeobj")
+            .filter_map(|p| {
+                let name = p.child("pname").and_then(|n| n.attr("value"));
+                Some((p.attr("handle")?, name.or(p.child_text("ptitle"))?))
+            })
+            .collect();
+        let events: HashMap<&str, &Element> = doc
+            .section("events", "event")
+            .filter_map(|e| Some((e.attr("handle")?, e)))
+            .collect();
+        let mut kg = KinGraph::new();
+        let event_of = |r: &Element, kg: &KinGraph| {
+            let handle = r.attr("hlink").unwrap_or_default();
+            let event = events
+                .get(handle)
+                .ok_or_else(|| anyhow!("Missing event {:?}", handle))?;
+            Ok::<_, anyhow::Error>(GrampsEvent::read(event, &places, kg.calendar()))
+        };
+        let mut persons = HashMap::new();
+        for person in doc.section("people", "person") {
+            let handle = person
+                .attr("handle")
+                .ok_or_else(|| anyhow!("A person has no handle"))?;
+            let mut attributes = Attributes::new();
+            let sex = match person.child_text("gender") {
+                Some("M") => Sex::Male,
+                Some("F") => Sex::Female,
+                gender => {
+                    attributes.insert("gender".into(), gender.unwrap_or("U").into());
+                    Sex::Female
+                }
+            };
+            let mut names = person.children("name").collect::<Vec<_>>();
+            names.sort_by_key(|n| n.attr("alt") == Some("1"));
+            let mut person_names = names.iter().map(|n| read_name(n)).collect::<Vec<_>>();
+            //a birth name next to a married one is the birth surname of the married name
+            if names.first().and_then(|n| n.attr("type")) == Some("Married Name") {
+                let birth = names.iter().skip(1).position(|n| {
+                    n.attr("type") == Some("Birth Name")
+                        && read_name(n).given == person_names[0].given
+                });
+                if let Some(i) = birth {
+                    let birth = person_names.remove(i + 1);
+                    person_names[0].birth_surname = birth.surname;
+                }
+            }
+            let (mut birth, mut death) = (None, None);
+            let (mut has_birth, mut has_death) = (false, false);
+            for r in person.children("eventref") {
+                if r.attr("role").is_some_and(|role| role != "Primary") {
+                    continue;
+                }
+                let event = event_of(r, &kg)?;
+                let kept_as = match event.kind.as_str() {
+                    "Birth" if !has_birth => Some((&mut birth, &mut has_birth)),
+                    "Death" if !has_death => Some((&mut death, &mut has_death)),
+                    _ => None,
+                };
+                match kept_as {
+                    Some((date, has)) => {
+                        (*date, *has) = (event.date, true);
+                        event.keep(&mut attributes, false);
+                    }
+                    None => event.keep(&mut attributes, true),
+                }
+            }
+            for a in person.children("attribute") {
+                let key = a.attr("type").unwrap_or("Unknown").to_string();
+                add_attr(
+                    &mut attributes,
+                    key,
+                    a.attr("value").unwrap_or_default().into(),
+                );
+            }
+            let short = person_names
+                .first()
+                .map_or(crate::names::UNKNOWN_NAME.into(), PersonName::to_string);
+            let p = kg.np_with_name(sex, short);
+            for name in person_names {
+                kg.add_alternate_name(&p, name)?;
+            }
+            kg.set_birth(&p, birth)?;
+            kg.set_death(&p, death)?;
+            for (key, value) in attributes {
+                kg.set_attr(&p, &key, value)?;
+            }
+            kg.set_external_ref(&p, GRAMPS_REF, handle)?;
+            if let Some(id) = person.attr("id") {
+                kg.set_external_ref(&p, GRAMPS_ID_REF, id)?;
+            }
+            persons.insert(handle, p);
+        }
+        let person_of = |r: &Element| {
+            let handle = r.attr("hlink");
+            handle
+                .and_then(|h| persons.get(h))
+                .ok_or_else(|| anyhow!("Missing person {:?}", handle))
+        };
+        for person in doc.section("people", "person") {
+            for r in person.children("personref") {
+                if r.attr("rel") == Some("Guardian") {
+                    let ward = &persons[person.attr("handle").unwrap()];
+                    kg.add_relation(person_of(r)?, ward, Kind::Guardian)?;
+                }
+            }
+        }
+        for family in doc.section("families", "family") {
+            let id = family.attr("id");
+            let in_family = |e: anyhow::Error| e.context(format!("In family {:?}", id));
+            let parent = |tag| family.child(tag).map(person_of).transpose();
+            let father = parent("father").map_err(in_family)?;
+            let mother = parent("mother").map_err(in_family)?;
+            let mut attributes = Attributes::new();
+            for (key, value) in [("gramps.handle", family.attr("handle")), ("gramps.id", id)] {
+                if let Some(value) = value {
+                    attributes.insert(key.into(), value.into());
+                }
+            }
+            let rel = family.child("rel").and_then(|r| r.attr("type"));
+            let mut partnership = match rel {
+                Some("Married") => Some(Partnership::new(MaritalStatus::Married)),
+                Some("Civil Union") => Some(Partnership::new(MaritalStatus::Partnered)),
+                _ => None,
+            };
+            for r in family.children("eventref") {
+                let event = event_of(r, &kg).map_err(in_family)?;
+                let with_date = match event.kind.as_str() {
+                    "Marriage" => {
+                        partnership.get_or_insert_with(Partnership::default).start = event.date;
+                        false
+                    }
+                    "Divorce" => {
+                        let partnership = partnership.get_or_insert_with(Partnership::default);
+                        partnership.status = MaritalStatus::Divorced;
+                        partnership.end = event.date;
+                        false
+                    }
+                    _ => true,
+                };
+                event.keep(&mut attributes, with_date);
+            }
+            for a in family.children("attribute") {
+                let key = a.attr("type").unwrap_or("Unknown").to_string();
+                add_attr(
+                    &mut attributes,
+                    key,
+                    a.attr("value").unwrap_or_default().into(),
+                );
+            }
+            let mut pairs = Vec::new();
+            if let (Some(father), Some(mother)) = (father, mother) {
+                kg.add_relation(father, mother, Kind::RP)?;
+                if let Some(partnership) = partnership {
+                    kg.add_partnership(father, mother, partnership)?;
+                }
+                pairs.push((father, mother, Kind::RP));
+            }
+            for r in family.children("childref") {
+                let child = person_of(r).map_err(in_family)?;
+                for (parent, rel) in [(father, r.attr("frel")), (mother, r.attr("mrel"))] {
+                    let Some(parent) = parent else {
+                        continue;
+                    };
+                    let kind = match rel.unwrap_or("Birth") {
+                        "Adopted" => Kind::AdoptiveParent,
+                        "Foster" => Kind::FosterParent,
+                        "Sponsored" => Kind::Guardian,
+                        "Stepchild" => {
+                            kg.add_parent_with(parent, child, Parentage::legal_only())?;
+                            Kind::Parent
+                        }
+                        "None" => continue,
+                        _ => Kind::Parent,
+                    };
+                    if rel != Some("Stepchild") {
+                        kg.add_relation(parent, child, kind)?;
+                    }
+                    if father.is_none() || mother.is_none() {
+                        pairs.push((parent, child, kind));
+                    }
+                }
+            }
+            for (p1, p2, kind) in pairs {
+                for (key, value) in &attributes {
+                    kg.set_relation_attr(p1, p2, kind, key, value.clone())?;
+                }
+            }
+        }
+        Ok(kg)
+    }
+    ///Writes the graph as a Gramps XML database, gzip compressed as in `.gramps` files when gzip
+    /// is set. Each couple with children, each pair of spouses and each single parent becomes a
+    /// family. Dates of other calendars are converted to Gregorian ones.
+    ///
+    /// Siblings who share no parents are left out, as Gramps only knows siblings through families.
+    pub fn write_gramps<W: Write>(&self, writer: W, gzip: bool) -> Result<()> {
+        let xml = self.gramps_xml();
+        if gzip {
+            let mut encoder = GzEncoder::new(writer, Compression::default());
+            encoder.write_all(xml.as_bytes())?;
+            encoder.finish()?;
+        } else {
+            let mut writer = writer;
+            writer.write_all(xml.as_bytes())?;
+        }
+        Ok(())
+    }
+    fn gramps_xml(&self) -> String {
+        let to_gregorian = |date: &GenDate| match self.calendar.name() == Gregorian.name() {
+            true => *date,
+            false => date.map(|d| convert(d, self.calendar(), &Gregorian)),
+        };
+        let nodes = self.graph.node_indices().collect::<Vec<_>>();
+        let families = self.families(&nodes, |_| true);
+        //the attributes that each family was read with, those of the couple as partners and as
+        //spouses, or those of a single parent to their first child
+        let family_attrs = families
+            .iter()
+            .map(|family| {
+                let relations = match family.parents[..] {
+                    [p1, p2] => vec![(p1, p2, Kind::RP), (p1, p2, Kind::Spouse)],
+                    _ => {
+                        let (child, kind) = family.children[0];
+                        vec![(family.parents[0], child, kind)]
+                    }
+                };
+                let mut attrs = relations
+                    .into_iter()
+                    .filter_map(|(p1, p2, kind)| self.relation_attrs_of(p1, p2, kind))
+                    .peekable();
+                attrs.peek()?;
+                Some(
+                    attrs
+                        .flatten()
+                        .map(|(k, v)| (k.clone(), v.clone()))
+                        .collect::<Attributes>(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let kept = |attrs: Option<&Attributes>, key| {
+            attrs.and_then(|a| a.get(key)?.as_str().map(str::to_string))
+        };
+        let mut used = HashSet::new();
+        for p in self.graph.node_weights() {
+            used.extend(p.external_ref(GRAMPS_REF).map(str::to_string));
+            used.extend(p.external_ref(GRAMPS_ID_REF).map(str::to_string));
+        }
+        for attrs in &family_attrs {
+            used.extend(kept(attrs.as_ref(), "gramps.handle"));
+            used.extend(kept(attrs.as_ref(), "gramps.id"));
+        }
+        let mut counters = HashMap::new();
+        let mut fresh = |prefix: char| loop {
+            let n = counters.entry(prefix).or_insert(0);
+            let id = format!("{}{:04}", prefix, n);
+            *n += 1;
+            let handle = format!("_{}", id.to_lowercase());
+            if !used.contains(&id) && !used.contains(&handle) {
+                used.insert(id.clone());
+                used.insert(handle.clone());
+                return (handle, id);
+            }
+        };
+        let mut handles = HashMap::new();
+        for n in &nodes {
+            let p = &self.graph[*n];
+            let (handle, id) = (p.external_ref(GRAMPS_REF), p.external_ref(GRAMPS_ID_REF));
+            let (handle, id) = match (handle, id) {
+                (Some(handle), Some(id)) => (handle.to_string(), id.to_string()),
+                (Some(handle), None) => (handle.to_string(), fresh('I').1),
+                _ => fresh('I'),
+            };
+            handles.insert(*n, (handle, id));
+        }
+        let family_handles = family_attrs
+            .iter()
+            .map(|attrs| {
+                match (
+                    kept(attrs.as_ref(), "gramps.handle"),
+                    kept(attrs.as_ref(), "gramps.id"),
+                ) {
+                    (Some(handle), Some(id)) => (handle, id),
+                    (Some(handle), None) => (handle, fresh('F').1),
+                    _ => fresh('F'),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut events = Element::new("events");
+        let mut places = Element::new("places");
+        let mut place_handles = HashMap::new();
+        //adds the events to the database, giving back the references to them
+        let mut add_events = |list: Vec<GrampsEvent>, role: &str| {
+            let mut refs = Vec::new();
+            for event in list {
+                let (handle, id) = fresh('E');
+                let mut element = Element::new("event")
+                    .with_attr("handle", &handle)
+                    .with_attr("id", id)
+                    .with(Element::text("type", &event.kind));
+                if let Some(date) = &event.date {
+                    element = element.with(date_element(date));
+                } else if let Some(text) = &event.date_text {
+                    element = element.with(Element::new("datestr").with_attr("val", text));
+                }
+                if let Some(place) = event.place {
+                    let place_handle = place_handles.entry(place.clone()).or_insert_with(|| {
+                        let (handle, id) = fresh('P');
+                        places.children.push(
+                            Element::new("placeobj")
+                                .with_attr("handle", &handle)
+                                .with_attr("id", id)
+                                .with_attr("type", "Unknown")
+                                .with(Element::text("ptitle", &place))
+                                .with(Element::new("pname").with_attr("value", &place)),
+                        );
+                        handle
+                    });
+                    element =
+                        element.with(Element::new("place").with_attr("hlink", &*place_handle));
+                }
+                if let Some(description) = &event.description {
+                    element = element.with(Element::text("description", description));
+                }
+                events.children.push(element);
+                refs.push(
+                    Element::new("eventref")
+                        .with_attr("hlink", handle)
+                        .with_attr("role", role),
+                );
+            }
+            refs
+        };
+
+        let mut childof: HashMap<Nd, Vec<usize>> = HashMap::new();
+        let mut parentin: HashMap<Nd, Vec<usize>> = HashMap::new();
+        for (i, family) in families.iter().enumerate() {
+            for p in &family.parents {
+                parentin.entry(*p).or_default().push(i);
+            }
+            for (c, _) in &family.children {
+                let of = childof.entry(*c).or_default();
+                if !of.contains(&i) {
+                    of.push(i);
+                }
+            }
+        }
+        let mut people = Element::new("people");
+        for n in &nodes {
+            let p = &self.graph[*n];
+            let (handle, id) = &handles[n];
+            let gender = match (p.attr("gender"), p.sex) {
+                (Some(AttrValue::Str(g)), _) if g == "U" => "U",
+                (_, Sex::Male) => "M",
+                (_, Sex::Female) => "F",
+            };
+            let mut element = Element::new("person")
+                .with_attr("handle", handle)
+                .with_attr("id", id)
+                .with(Element::text("gender", gender));
+            for (i, name) in p.names.iter().enumerate() {
+                let kind = match (i, &name.birth_surname) {
+                    (0, Some(_)) => "Married Name",
+                    (0, None) => "Birth Name",
+                    _ => "Also Known As",
+                };
+                element = element.with(name_element(name, kind, i > 0));
+                if let (0, Some(surname)) = (i, &name.birth_surname) {
+                    let birth_name = PersonName {
+                        surname: Some(surname.clone()),
+                        ..name.clone()
+                    };
+                    element = element.with(name_element(&birth_name, "Birth Name", true));
+                }
+            }
+            if p.names.is_empty() && p.name != crate::names::UNKNOWN_NAME {
+                element = element.with(Element::new("name").with(Element::text("first", &p.name)));
+            }
+            let mut person_events = Vec::new();
+            for (kind, date) in [("Birth", &p.birth), ("Death", &p.death)] {
+                if let Some(date) = date {
+                    person_events.push(GrampsEvent::new(kind, Some(to_gregorian(date))));
+                }
+            }
+            let plain = split_attributes(&p.attributes, &mut person_events, &to_gregorian);
+            element
+                .children
+                .extend(add_events(person_events, "Primary"));
+            for (key, value) in plain {
+                element = element.with(
+                    Element::new("attribute")
+                        .with_attr("type", key)
+                        .with_attr("value", value),
+                );
+            }
+            for (tag, list) in [("childof", &childof), ("parentin", &parentin)] {
+                for i in list.get(n).into_iter().flatten() {
+                    element =
+                        element.with(Element::new(tag).with_attr("hlink", &family_handles[*i].0));
+                }
+            }
+            for e in self.graph.edges_directed(*n, Direction::Incoming) {
+                if *e.weight() == Kind::Guardian {
+                    let guardian = &handles[&e.source()].0;
+                    element = element.with(
+                        Element::new("personref")
+                            .with_attr("hlink", guardian)
+                            .with_attr("rel", "Guardian"),
+                    );
+                }
+            }
+            people.children.push(element);
+        }
+
+        let mut family_elements = Element::new("families");
+        for (i, family) in families.iter().enumerate() {
+            let (handle, id) = &family_handles[i];
+            let rel = match family.partnership.map(|p| p.status) {
+                Some(MaritalStatus::Partnered) => "Civil Union",
+                Some(_) => "Married",
+                None if family.parents.len() == 2 => "Unmarried",
+                None => "Unknown",
+            };
+            let mut element = Element::new("family")
+                .with_attr("handle", handle)
+                .with_attr("id", id)
+                .with(Element::new("rel").with_attr("type", rel));
+            //the father and the mother, or the first and second parent when they are of the same sex
+            let mut parents = family.parents.clone();
+            parents.sort_by_key(|p| self.graph[*p].sex != Sex::Male);
+            let roles = match parents[..] {
+                [p] if self.graph[p].sex == Sex::Female => vec![("mother", p)],
+                [p] => vec![("father", p)],
+                _ => vec![("father", parents[0]), ("mother", parents[1])],
+            };
+            for (role, p) in &roles {
+                element = element.with(Element::new(role).with_attr("hlink", &handles[p].0));
+            }
+            let mut family_events = Vec::new();
+            let attrs = family_attrs[i].clone().unwrap_or_default();
+            if let Some(partnership) = family.partnership {
+                let has_marriage = attrs
+                    .keys()
+                    .any(|k| detail_of(k).is_some_and(|(e, _)| e == "Marriage"));
+                if partnership.start.is_some() || has_marriage {
+                    let start = partnership.start.as_ref().map(to_gregorian);
+                    family_events.push(GrampsEvent::new("Marriage", start));
+                }
+                if partnership.status == MaritalStatus::Divorced {
+                    let end = partnership.end.as_ref().map(to_gregorian);
+                    family_events.push(GrampsEvent::new("Divorce", end));
+                }
+            }
+            let plain = split_attributes(&attrs, &mut family_events, &to_gregorian);
+            element.children.extend(add_events(family_events, "Family"));
+            let mut children: Vec<(Nd, Kind)> = Vec::new();
+            for (c, kind) in &family.children {
+                if !children.iter().any(|(d, _)| d == c) {
+                    children.push((*c, *kind));
+                }
+            }
+            for (c, kind) in children {
+                let mut childref = Element::new("childref").with_attr("hlink", &handles[&c].0);
+                for (role, p) in &roles {
+                    let rel = match kind {
+                        Kind::AdoptiveParent => "Adopted",
+                        Kind::FosterParent => "Foster",
+                        _ if !self.parentage_of(*p, c).biological => "Stepchild",
+                        _ => continue,
+                    };
+                    let key = if *role == "father" { "frel" } else { "mrel" };
+                    childref = childref.with_attr(key, rel);
+                }
+                element = element.with(childref);
+            }
+            for (key, value) in plain {
+                element = element.with(
+                    Element::new("attribute")
+                        .with_attr("type", key)
+                        .with_attr("value", value),
+                );
+            }
+            family_elements.children.push(element);
+        }
+
+        let mut database = Element::new("database").with_attr("xmlns", NAMESPACE);
+        let created = Element::new("created")
+            .with_attr("date", format_val(&today()))
+            .with_attr("version", env!("CARGO_PKG_VERSION"));
+        database = database.with(Element::new("header").with(created));
+        for section in [events, people, family_elements, places] {
+            if !section.children.is_empty() {
+                database = database.with(section);
+            }
+        }
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        out.push_str("<!DOCTYPE database PUBLIC \"-//Gramps//DTD Gramps XML 1.7.1//EN\"\n");
+        out.push_str(&format!("\"{}grampsxml.dtd\">\n", NAMESPACE));
+        database.write(&mut out, 0);
+        out
+    }
+}
+//the current (Gregorian) date
+fn today() -> Date {
+    let seconds = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let epoch = Gregorian.to_ordinal(Date::ymd(1970, 1, 1));
+    Gregorian.ordinal_to_date(epoch + (seconds / 86400) as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xml_elements() {
+        let xml = r#"<?xml version="1.0"?>
+<database xmlns="http://gramps-project.org/xml/1.7.1/">
+  <people>
+    <person handle="_a&amp;b"><gender>M</gender><name><first><![CDATA[Jo & Al]]></first></name></person>
+  </people>
+</database>"#;
+        let doc = parse_xml(xml).unwrap();
+        let person = doc.section("people", "person").next().unwrap();
+        assert_eq!(person.attr("handle"), Some("_a&b"));
+        assert_eq!(person.child_text("gender"), Some("M"));
+        assert_eq!(
+            person.child("name").unwrap().child_text("first"),
+            Some("Jo & Al")
+        );
+        let mut out = String::new();
+        person.write(&mut out, 0);
+        let written = parse_xml(&format!("<database>{}</database>", out)).unwrap();
+        assert_eq!(written.children[0].attr("handle"), Some("_a&b"));
+        assert!(parse_xml("<database><people></database>").is_err());
+        assert!(parse_xml("<other/>").is_err());
+    }
+    #[test]
+    fn dates() {
+        let gregorian = Gregorian;
+        let event = |date: &str| parse_xml(&format!("<database>{}</database>", date)).unwrap();
+        let cases = [
+            (
+                r#"<dateval val="1850-03-12"/>"#,
+                Date::ymd(1850, 3, 12).into(),
+            ),
+            (
+                r#"<dateval val="1850-00-00" type="about"/>"#,
+                GenDate::About(Date::year(1850)),
+            ),
+            (
+                r#"<dateval val="1850-03" quality="estimated"/>"#,
+                GenDate::Estimated(Date::ym(1850, 3)),
+            ),
+            (
+                r#"<daterange start="1820" stop="1825"/>"#,
+                GenDate::Between(Date::year(1820), Date::year(1825)),
+            ),
+            (
+                r#"<datestr val="bef 1900"/>"#,
+                GenDate::Before(Date::year(1900)),
+            ),
+        ];
+        for (xml, date) in cases {
+            assert_eq!(
+                read_date(&event(xml), &gregorian),
+                Ok(Some(date)),
+                "{}",
+                xml
+            );
+        }
+        let julian = event(r#"<dateval val="1710-03-12" cformat="Julian"/>"#);
+        assert_eq!(
+            read_date(&julian, &gregorian),
+            Err("val=1710-03-12 cformat=Julian".into())
+        );
+        let unknown = event(r#"<datestr val="in the spring"/>"#);
+        assert_eq!(read_date(&unknown, &gregorian), Err("in the spring".into()));
+        let dual = Date {
+            dual: true,
+            ..Date::ymd(1710, 3, 12)
+        };
+        for date in [GenDate::Exact(dual), GenDate::After(Date::ym(-44, 3))] {
+            let mut xml = String::new();
+            date_element(&date).write(&mut xml, 0);
+            assert_eq!(
+                read_date(&event(&xml), &gregorian),
+                Ok(Some(date)),
+                "{}",
+                xml
+            );
+        }
+    }
+}
@@ -0,0 +1,529 @@
+//! Export of the graph as Graphviz DOT.
+//!
+//! Each relation is drawn as one edge, from the parent (or guardian) to the child, or without a
+//! direction for the symmetric kinds, and labelled with the short name of its [Kind]. Men are
+//! boxes and women ellipses, and shadow persons are dashed. Each node id is `n` followed by the
+//! index of the person.
+//!
+//! With [DotLayout::Families] the graph is drawn like a genealogy chart instead: each couple is
+//! joined through a small union node `u` followed by a number, which their shared children hang
+//! from in order of birth.
+//!
+//! [KinGraph::from_dot] reads the first kind of file back, as well as those of the earlier exporter, whose
+//! node ids are the name and index of the person, like `Mike_0`, and which drew both edges of
+//! every relation.
+use crate::{AttrValue, Attributes, Calendar, KinGraph, Kind, Sex};
+use anyhow::{anyhow, bail, Result};
+use indexmap::IndexMap;
+use petgraph::visit::EdgeRef;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{Read, Write};
+
+///How relations are drawn by [KinGraph::to_dot]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DotLayout {
+    ///One edge per relation
+    #[default]
+    Relations,
+    ///A union node per couple, with partners side by side and their children below it
+    Families,
+}
+
+///Options for [KinGraph::to_dot]
+#[derive(Clone, Debug)]
+pub struct DotOptions {
+    ///The id of the graph
+    pub graph_id: String,
+    ///Put the persons of each generation on the same row, see [KinGraph::generations]
+    pub ranks: bool,
+    ///Add the attributes of persons and relations to their labels
+    pub attributes: bool,
+    ///How relations are drawn
+    pub layout: DotLayout,
+}
+impl Default for DotOptions {
+    fn default() -> Self {
+        DotOptions {
+            graph_id: "kin".into(),
+            ranks: true,
+            attributes: true,
+            layout: DotLayout::Relations,
+        }
+    }
+}
+
+//a line break within the name or an attribute of a label, as opposed to the breaks between its
+//lines. It is written as `\l`, which Graphviz also breaks the line at.
+const LINE_BREAK: char = '\r';
+
+//a string as a quoted DOT id
+pub(crate) fn quoted(s: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => escaped += "\\\"",
+            '\\' => escaped += "\\\\",
+            '\n' => escaped += "\\n",
+            LINE_BREAK => escaped += "\\l",
+            _ => escaped.push(c),
+        }
+    }
+    escaped + "\""
+}
+
+//adds a line per attribute to a label, with the value as [AttrValue::parse] reads it
+fn with_attributes(label: &str, attrs: Option<&Attributes>, calendar: &dyn Calendar) -> String {
+    let line = |s: &str| s.replace('\n', &LINE_BREAK.to_string());
+    let mut label = line(label);
+    for (key, value) in attrs.into_iter().flatten() {
+        label += &format!("\n{}: {}", line(key), line(&value.format(calendar)));
+    }
+    label
+}
+
+//the DOT attributes that draw an edge of a kind, besides its label
+pub(crate) fn edge_style(kind: Kind) -> &'static str {
+    match kind {
+        Kind::Parent => "",
+        Kind::AdoptiveParent => ", style=dashed",
+        Kind::FosterParent => ", style=dotted",
+        Kind::Guardian => ", style=dotted, color=gray40",
+        Kind::Sibling => ", dir=none, style=dotted",
+        Kind::RP => ", dir=none, color=gray40",
+        Kind::Spouse => ", dir=none, style=bold",
+        //the inverse kinds are never written
+        _ => "",
+    }
+}
+
+//the kind of a relation from the short name it is labelled with
+fn parse_label_kind(name: &str) -> Option<Kind> {
+    let kind = match name {
+        "P" => Kind::Parent,
+        "C" => Kind::Child,
+        "S" => Kind::Sibling,
+        "R" => Kind::RP,
+        "AP" => Kind::AdoptiveParent,
+        "AC" => Kind::AdoptiveChild,
+        "FP" => Kind::FosterParent,
+        "FC" => Kind::FosterChild,
+        "G" => Kind::Guardian,
+        "W" => Kind::Ward,
+        "SP" => Kind::Spouse,
+        _ => return None,
+    };
+    Some(kind)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    //an identifier, number or quoted string, with the quotes and escapes removed
+    Id(String),
+    //`->` or `--`
+    Edge,
+    Punct(char),
+}
+
+//splits DOT into tokens, each with the line it is on
+fn tokenize(text: &str) -> Result<Vec<(usize, Token)>> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            c if c.is_whitespace() => {}
+            //comments, and preprocessor lines
+            '#' => while chars.next_if(|c| *c != '\n').is_some() {},
+            '/' if chars.next_if_eq(&'/').is_some() => {
+                while chars.next_if(|c| *c != '\n').is_some() {}
+            }
+            '/' if chars.next_if_eq(&'*').is_some() => loop {
+                match chars.next() {
+                    Some('*') if chars.next_if_eq(&'/').is_some() => break,
+                    Some('\n') => line += 1,
+                    Some(_) => {}
+                    None => bail!("Line {}: unterminated comment", line),
+                }
+            },
+            '-' if chars.next_if(|c| *c == '>' || *c == '-').is_some() => {
+                tokens.push((line, Token::Edge))
+            }
+            '"' => {
+                let start = line;
+                let mut id = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => id.push('\n'),
+                            Some('l' | 'r') => id.push(LINE_BREAK),
+                            //a line continuation
+                            Some('\n') => line += 1,
+                            Some(c @ ('"' | '\\')) => id.push(c),
+                            Some(c) => {
+                                id.push('\\');
+                                id.push(c);
+                            }
+                            None => {}
+                        },
+                        Some(c) => {
+                            line += (c == '\n') as usize;
+                            id.push(c);
+                        }
+                        None => bail!("Line {}: unterminated string", start),
+                    }
+                }
+                tokens.push((start, Token::Id(id)));
+            }
+            '{' | '}' | '[' | ']' | ';' | ',' | '=' | ':' => tokens.push((line, Token::Punct(c))),
+            c if c.is_alphanumeric() || c == '_' || c == '.' || c == '-' => {
+                let mut id = String::from(c);
+                while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || *c == '_' || *c == '.')
+                {
+                    id.push(c);
+                }
+                tokens.push((line, Token::Id(id)));
+            }
+            c => bail!("Line {}: unexpected {:?}", line, c),
+        }
+    }
+    Ok(tokens)
+}
+
+type DotAttrs = HashMap<String, String>;
+
+//the nodes and edges of a DOT graph, with the attributes they are given
+#[derive(Default)]
+struct DotGraph {
+    nodes: IndexMap<String, DotAttrs>,
+    edges: Vec<(usize, String, String, DotAttrs)>,
+}
+
+//reads the statements of a DOT graph, leaving out its subgraphs but not the nodes and edges
+//inside them. Default node and edge attributes are given to the nodes and edges after them in
+//the same subgraph, or those inside it.
+fn parse_dot(text: &str) -> Result<DotGraph> {
+    let tokens = tokenize(text)?;
+    let mut graph = DotGraph::default();
+    let mut i = 0;
+    let peek = |i: usize| tokens.get(i).map(|(_, t)| t);
+    let line = |i: usize| tokens.get(i).or(tokens.last()).map_or(1, |(l, _)| *l);
+    let id = |i: usize| match peek(i) {
+        Some(Token::Id(id)) => Ok(id.clone()),
+        _ => Err(anyhow!("Line {}: expected an id", line(i))),
+    };
+    //an attribute list, if there is one at i
+    let attrs = |i: &mut usize| -> Result<DotAttrs> {
+        let mut attrs = DotAttrs::new();
+        while peek(*i) == Some(&Token::Punct('[')) {
+            *i += 1;
+            while peek(*i) != Some(&Token::Punct(']')) {
+                let key = id(*i)?;
+                if peek(*i + 1) != Some(&Token::Punct('=')) {
+                    bail!("Line {}: expected '=' after {:?}", line(*i), key);
+                }
+                attrs.insert(key, id(*i + 2)?);
+                *i += 3;
+                if let Some(Token::Punct(';' | ',')) = peek(*i) {
+                    *i += 1;
+                }
+            }
+            *i += 1;
+        }
+        Ok(attrs)
+    };
+    if peek(i) == Some(&Token::Id("strict".into())) {
+        i += 1;
+    }
+    match peek(i) {
+        Some(Token::Id(kw)) if kw == "digraph" || kw == "graph" => i += 1,
+        _ => bail!("Line {}: expected a graph or digraph", line(i)),
+    }
+    if let Some(Token::Id(_)) = peek(i) {
+        i += 1;
+    }
+    if peek(i) != Some(&Token::Punct('{')) {
+        bail!("Line {}: expected '{{'", line(i));
+    }
+    i += 1;
+    //the default node and edge attributes of each subgraph that is open
+    let mut defaults = vec![(DotAttrs::new(), DotAttrs::new())];
+    while let Some((node_defaults, edge_defaults)) = defaults.last().cloned() {
+        let start = i;
+        match peek(i) {
+            None => bail!("Line {}: expected '}}'", line(i)),
+            Some(Token::Punct('{')) => {
+                defaults.push((node_defaults, edge_defaults));
+                i += 1;
+            }
+            Some(Token::Punct('}')) => {
+                defaults.pop();
+                i += 1;
+            }
+            Some(Token::Punct(';' | ',')) => i += 1,
+            Some(Token::Id(kw)) if kw == "subgraph" => {
+                i += 1;
+                if let Some(Token::Id(_)) = peek(i) {
+                    i += 1;
+                }
+            }
+            Some(Token::Id(kw)) if kw == "graph" || kw == "node" || kw == "edge" => {
+                let kw = kw.clone();
+                i += 1;
+                let attrs = attrs(&mut i)?;
+                let (node_defaults, edge_defaults) = defaults.last_mut().unwrap();
+                match kw.as_str() {
+                    "node" => node_defaults.extend(attrs),
+                    "edge" => edge_defaults.extend(attrs),
+                    _ => {}
+                }
+            }
+            Some(Token::Id(_)) if peek(i + 1) == Some(&Token::Punct('=')) => {
+                id(i + 2)?;
+                i += 3;
+            }
+            Some(Token::Id(_)) => {
+                let mut ends = vec![id(i)?];
+                i += 1;
+                while peek(i) == Some(&Token::Edge) {
+                    ends.push(id(i + 1)?);
+                    i += 2;
+                }
+                let attrs = attrs(&mut i)?;
+                //nodes get the defaults when they are first seen
+                for end in &ends {
+                    let node = graph.nodes.entry(end.clone());
+                    node.or_insert_with(|| node_defaults.clone());
+                }
+                if ends.len() == 1 {
+                    graph.nodes[&ends[0]].extend(attrs);
+                    continue;
+                }
+                let mut edge_attrs = edge_defaults;
+                edge_attrs.extend(attrs);
+                for pair in ends.windows(2) {
+                    let edge = (pair[0].clone(), pair[1].clone(), edge_attrs.clone());
+                    graph.edges.push((line(start), edge.0, edge.1, edge.2));
+                }
+            }
+            Some(t) => bail!("Line {}: unexpected {:?}", line(i), t),
+        }
+    }
+    Ok(graph)
+}
+
+//the index at the end of a node id, and the name before it when they are split by an underscore
+fn split_index(id: &str) -> (Option<&str>, Option<usize>) {
+    let prefix = id.trim_end_matches(|c: char| c.is_ascii_digit());
+    let index = id[prefix.len()..].parse::<usize>().ok();
+    (prefix.strip_suffix('_').filter(|_| index.is_some()), index)
+}
+
+//the name of a person, and the attributes given after it in a label
+fn read_label(label: &str, calendar: &dyn Calendar) -> Result<(String, Vec<(String, AttrValue)>)> {
+    let unbroken = |s: &str| s.replace(LINE_BREAK, "\n");
+    let mut lines = label.split('\n');
+    let name = unbroken(lines.next().unwrap_or_default());
+    let attributes = lines
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let (key, value) = line
+                .split_once(": ")
+                .ok_or_else(|| anyhow!("Invalid attribute {:?}", line))?;
+            Ok((unbroken(key), AttrValue::parse(&unbroken(value), calendar)?))
+        })
+        .collect::<Result<_>>()?;
+    Ok((name, attributes))
+}
+
+impl KinGraph {
+    ///Writes the graph as DOT, see [KinGraph::to_dot]
+    pub fn write_dot<W: Write>(&self, mut writer: W, opts: &DotOptions) -> Result<()> {
+        writer.write_all(self.to_dot(opts).as_bytes())?;
+        Ok(())
+    }
+    ///Reads a graph from DOT, as written by [KinGraph::to_dot] with [DotLayout::Relations]. Each
+    /// edge is labelled with the short name of its [Kind], and relations that are drawn in one
+    /// direction only get their inverse. Persons are added in the order of the index at the end of
+    /// their node id (as in `Mike_0` or `n0`), and named after the first line of their label, or
+    /// their id when it has no other label. Boxes are read as men, and every other shape as women.
+    /// Line breaks written as `\l` or `\r` are kept in the name or attribute they are in.
+    /// Default `node` and `edge` attributes apply as they do in Graphviz, and an edge from a node
+    /// to itself is an error.
+    pub fn from_dot(text: &str) -> Result<KinGraph> {
+        let graph = parse_dot(text)?;
+        let mut kg = KinGraph::new();
+        let mut nodes = graph.nodes.iter().enumerate().collect::<Vec<_>>();
+        nodes.sort_by_key(|(order, (id, _))| (split_index(id).1.unwrap_or(usize::MAX), *order));
+        let mut persons = HashMap::new();
+        for (_, (id, attrs)) in nodes {
+            let label = attrs.get("label").filter(|label| *label != id);
+            let (name, attributes) = match label {
+                Some(label) => read_label(label, kg.calendar())?,
+                None => (split_index(id).0.unwrap_or(id).to_string(), Vec::new()),
+            };
+            let sex = match attrs.get("shape").map(String::as_str) {
+                Some("box" | "rect" | "rectangle" | "square") => Sex::Male,
+                _ => Sex::Female,
+            };
+            let p = kg.np_with_name(sex, name);
+            for (key, value) in attributes {
+                kg.set_attr(&p, &key, value)?;
+            }
+            let px = kg.idx(&p).unwrap();
+            kg.graph[px].is_shadow = attrs.get("style").is_some_and(|s| s.contains("dashed"));
+            persons.insert(id.as_str(), p);
+        }
+        for (line, from, to, attrs) in &graph.edges {
+            let (p1, p2) = (&persons[from.as_str()], &persons[to.as_str()]);
+            let label = attrs.get("label").map_or("", String::as_str);
+            let (kind, attributes) = read_label(label, kg.calendar())?;
+            let kind = parse_label_kind(&kind)
+                .ok_or_else(|| anyhow!("Line {}: invalid kind {:?}", line, kind))?;
+            let (p1x, p2x) = (kg.idx(p1).unwrap(), kg.idx(p2).unwrap());
+            if p1x == p2x {
+                bail!("Line {}: {:?} can not be related to themselves", line, from);
+            }
+            //the other edge of the pair has already been read
+            if !kg
+                .graph
+                .edges_connecting(p1x, p2x)
+                .any(|e| *e.weight() == kind)
+            {
+                kg.add_relation(p1, p2, kind)
+                    .map_err(|e| anyhow!("Line {}: {}", line, e))?;
+            }
+            for (key, value) in attributes {
+                kg.set_relation_attr(p1, p2, kind, &key, value)?;
+            }
+        }
+        Ok(kg)
+    }
+    ///Reads a graph from DOT, see [KinGraph::from_dot]
+    pub fn read_dot<R: Read>(mut reader: R) -> Result<KinGraph> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        KinGraph::from_dot(&text)
+    }
+    ///The graph as DOT, with each relation drawn once
+    pub fn to_dot(&self, opts: &DotOptions) -> String {
+        let mut dot = format!("digraph {} {{\n", quoted(&opts.graph_id));
+        for n in self.graph.node_indices() {
+            let p = &self.graph[n];
+            let attributes = Some(&p.attributes).filter(|_| opts.attributes);
+            let label = with_attributes(&p.name, attributes, self.calendar());
+            let shape = match p.sex {
+                Sex::Male => "box",
+                Sex::Female => "ellipse",
+            };
+            let dashed = if p.is_shadow { ", style=dashed" } else { "" };
+            dot += &format!(
+                "    n{} [label={}, shape={}{}];\n",
+                n.index(),
+                quoted(&label),
+                shape,
+                dashed
+            );
+        }
+        let families = match opts.layout {
+            DotLayout::Relations => Vec::new(),
+            DotLayout::Families => {
+                let nodes = self.graph.node_indices().collect::<Vec<_>>();
+                self.families(&nodes, |_| true)
+            }
+        };
+        //the relations that are drawn through a union node
+        let mut drawn = HashSet::new();
+        let couples = families.into_iter().filter(|f| f.parents.len() == 2);
+        for (i, mut family) in couples.enumerate() {
+            let (a, b) = (family.parents[0], family.parents[1]);
+            let key = Self::pair_key(a, b);
+            let style = if family.partnership.is_some() {
+                ", style=bold"
+            } else if self
+                .graph
+                .edges_connecting(a, b)
+                .any(|e| *e.weight() == Kind::RP)
+            {
+                ""
+            } else {
+                ", style=invis"
+            };
+            drawn.extend([(key, Kind::RP), (key, Kind::Spouse)]);
+            dot += &format!(
+                "    u{} [label=\"\", shape=point, width=0.1, ordering=out];\n",
+                i
+            );
+            dot += &format!("    n{} -> u{} [dir=none{}];\n", a.index(), i, style);
+            dot += &format!("    u{} -> n{} [dir=none{}];\n", i, b.index(), style);
+            dot += &format!(
+                "    {{ rank=same; n{}; u{}; n{}; }}\n",
+                a.index(),
+                i,
+                b.index()
+            );
+            //children with a known birth come first, from the eldest
+            family
+                .children
+                .sort_by(|(c1, _), (c2, _)| self.birth_order(*c1, *c2));
+            for (j, (c, kind)) in family.children.iter().enumerate() {
+                drawn.extend([
+                    (Self::pair_key(a, *c), *kind),
+                    (Self::pair_key(b, *c), *kind),
+                ]);
+                for (c2, _) in &family.children[j + 1..] {
+                    drawn.insert((Self::pair_key(*c, *c2), Kind::Sibling));
+                }
+                dot += &match edge_style(*kind).trim_start_matches(", ") {
+                    "" => format!("    u{} -> n{};\n", i, c.index()),
+                    style => format!("    u{} -> n{} [{}];\n", i, c.index(), style),
+                };
+            }
+        }
+        for e in self.graph.edge_references() {
+            let (a, b, kind) = (e.source(), e.target(), *e.weight());
+            if !kind.is_written_from(a.index(), b.index()) {
+                continue;
+            }
+            let key = Self::pair_key(a, b);
+            if drawn.contains(&(key, kind)) {
+                continue;
+            }
+            let attributes = self
+                .relation_attrs_of(a, b, kind)
+                .filter(|_| opts.attributes);
+            let label = with_attributes(&kind.to_string(), attributes, self.calendar());
+            dot += &format!(
+                "    n{} -> n{} [label={}{}];\n",
+                a.index(),
+                b.index(),
+                quoted(&label),
+                edge_style(kind)
+            );
+        }
+        if opts.ranks {
+            let mut rows: BTreeMap<i32, Vec<usize>> = BTreeMap::new();
+            for (i, generation) in self.generations().into_iter().enumerate() {
+                rows.entry(generation).or_default().push(i);
+            }
+            for row in rows.values().filter(|row| row.len() > 1) {
+                let nodes = row.iter().map(|i| format!("n{}; ", i)).collect::<String>();
+                dot += &format!("    {{ rank=same; {}}}\n", nodes);
+            }
+        }
+        dot + "}\n"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoting() {
+        assert_eq!(quoted("Mike"), "\"Mike\"");
+        assert_eq!(quoted("a \"b\"\nc\\"), r#""a \"b\"\nc\\""#);
+        assert_eq!(quoted("a\rb"), r#""a\lb""#);
+    }
+}
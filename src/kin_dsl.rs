@@ -8,6 +8,26 @@ The relationships are in all caps, and are:
   CHILD
   RP (reproductive partner)
   SIBLING
+  SPOUSE
+  ADOPTIVE_PARENT, ADOPTIVE_CHILD
+  FOSTER_PARENT, FOSTER_CHILD
+  GUARDIAN, WARD
+Birth and death dates are given with
+{NAME} {M|F} BORN {DATE}
+{NAME} {M|F} DIED {DATE}
+where the date is written in the graph's calendar, such as "abt 1850" or "12 Mar 1710/11".
+A SPOUSE statement can be followed by the status of the partnership (MARRIED, PARTNERED, DIVORCED
+or WIDOWED, married if left out) and the dates it started and ended
+{NAME} {M|F} SPOUSE {NAME} {M|F} [{STATUS}] [FROM {DATE}] [UNTIL {DATE}]
+Attributes of persons and relations, and ids of persons in other systems, are given with
+{NAME} {M|F} ATTR {KEY} {VALUE}
+{NAME} {M|F} REL_ATTR {RELATIONSHIP} {NAME} {M|F} {KEY} {VALUE}
+{NAME} {M|F} REF {SYSTEM} {ID}
+The full name of a person, and other names they are known by, are given GEDCOM style, with the
+surname between slashes and nicknames in quotes
+{NAME} {M|F} NAME John Ronald "Tolly" /Tolkien/
+{NAME} {M|F} ALIAS {FULL NAME}
+where the value is as [AttrValue::parse] reads it, e.g. smith, 42, true, date(abt 1850) or [a, b].
 It is then followed by the label ":QUERY". All subsequent lines are queries
 of the form:
 {NAME} TO {NAME}
@@ -17,7 +37,163 @@ use std::collections::HashMap;
 
 use itertools::Itertools;
 
-use crate::{states::State, KinGraph, Person};
+use crate::{
+    states::State, AttrValue, KinGraph, Kind, MaritalStatus, Partnership, Person, PersonName,
+};
+///Parses the all caps relationship keyword of a statement
+pub(crate) fn parse_kind(rel: &str) -> anyhow::Result<Kind> {
+    let kind = match rel {
+        "PARENT" => Kind::Parent,
+        "CHILD" => Kind::Child,
+        "RP" => Kind::RP,
+        "SIBLING" => Kind::Sibling,
+        "SPOUSE" => Kind::Spouse,
+        "ADOPTIVE_PARENT" => Kind::AdoptiveParent,
+        "ADOPTIVE_CHILD" => Kind::AdoptiveChild,
+        "FOSTER_PARENT" => Kind::FosterParent,
+        "FOSTER_CHILD" => Kind::FosterChild,
+        "GUARDIAN" => Kind::Guardian,
+        "WARD" => Kind::Ward,
+        _ => anyhow::bail!("Invalid relationship"),
+    };
+    Ok(kind)
+}
+///The relationship keyword of a kind, as [parse_kind] reads it
+pub(crate) fn kind_keyword(kind: Kind) -> &'static str {
+    match kind {
+        Kind::Parent => "PARENT",
+        Kind::Child => "CHILD",
+        Kind::RP => "RP",
+        Kind::Sibling => "SIBLING",
+        Kind::Spouse => "SPOUSE",
+        Kind::AdoptiveParent => "ADOPTIVE_PARENT",
+        Kind::AdoptiveChild => "ADOPTIVE_CHILD",
+        Kind::FosterParent => "FOSTER_PARENT",
+        Kind::FosterChild => "FOSTER_CHILD",
+        Kind::Guardian => "GUARDIAN",
+        Kind::Ward => "WARD",
+    }
+}
+///Parses the all caps status keyword of a SPOUSE statement
+pub(crate) fn parse_status(status: &str) -> Option<MaritalStatus> {
+    let status = match status {
+        "MARRIED" => MaritalStatus::Married,
+        "PARTNERED" => MaritalStatus::Partnered,
+        "DIVORCED" => MaritalStatus::Divorced,
+        "WIDOWED" => MaritalStatus::Widowed,
+        _ => return None,
+    };
+    Some(status)
+}
+///Parses the words after the persons of a SPOUSE statement, [{STATUS}] [FROM {DATE}] [UNTIL {DATE}]
+fn parse_partnership(words: &[&str], kg: &KinGraph) -> anyhow::Result<Partnership> {
+    let mut partnership = Partnership::default();
+    let mut words = words;
+    if let Some(status) = words.first().and_then(|w| parse_status(w)) {
+        partnership.status = status;
+        words = &words[1..];
+    }
+    let (start, end) = match words.iter().position(|w| *w == "UNTIL") {
+        Some(i) => (&words[..i], Some(&words[i + 1..])),
+        None => (words, None),
+    };
+    match start.split_first() {
+        None => {}
+        Some((&"FROM", date)) => partnership.start = Some(kg.parse_date(&date.join(" "))?),
+        Some(_) => anyhow::bail!("Expected a status, FROM or UNTIL in {:?}", words.join(" ")),
+    }
+    if let Some(date) = end {
+        partnership.end = Some(kg.parse_date(&date.join(" "))?);
+    }
+    Ok(partnership)
+}
+///Applies a single statement to the graph, adding any persons it mentions that are not
+/// already in persons
+fn apply_statement<'a>(
+    line: &'a str,
+    persons: &mut HashMap<&'a str, Person>,
+    kg: &mut KinGraph,
+) -> anyhow::Result<()> {
+    let line = line.trim();
+    let mut words = line.split_whitespace();
+    let missing = |what| anyhow::anyhow!("Missing {} in {:?}", what, line);
+    let name = words.next().ok_or_else(|| missing("name"))?;
+    let sex = parse_sex(words.next().ok_or_else(|| missing("sex"))?)?;
+    let p_id = match persons.get(name) {
+        Some(id) => id.clone(),
+        None => {
+            let person = kg.np_with_name(sex, name.to_string());
+            persons.insert(name, person.clone());
+            person
+        }
+    };
+    let rel = words.next().ok_or_else(|| missing("relationship"))?;
+    if rel == "ATTR" {
+        let key = words.next().ok_or_else(|| missing("key"))?;
+        let value = AttrValue::parse(&words.join(" "), kg.calendar())?;
+        kg.set_attr(&p_id, key, value)?;
+        return Ok(());
+    }
+    if rel == "NAME" || rel == "ALIAS" {
+        let name: PersonName = words.join(" ").parse()?;
+        if rel == "NAME" {
+            kg.set_name(&p_id, name)?;
+        } else {
+            kg.add_alternate_name(&p_id, name)?;
+        }
+        return Ok(());
+    }
+    if rel == "REF" {
+        let system = words.next().ok_or_else(|| missing("system"))?;
+        let id = words.next().ok_or_else(|| missing("id"))?;
+        kg.set_external_ref(&p_id, system, id)?;
+        return Ok(());
+    }
+    if rel == "BORN" || rel == "DIED" {
+        let date = kg.parse_date(&words.join(" "))?;
+        if rel == "BORN" {
+            kg.set_birth(&p_id, Some(date))?;
+        } else {
+            kg.set_death(&p_id, Some(date))?;
+        }
+        return Ok(());
+    }
+    //the attribute of a relation is given along with the relationship it is set on
+    let (rel, is_attr) = match rel {
+        "REL_ATTR" => (words.next().ok_or_else(|| missing("relationship"))?, true),
+        rel => (rel, false),
+    };
+    let name2 = words.next().ok_or_else(|| missing("name"))?;
+    let sex2 = parse_sex(words.next().ok_or_else(|| missing("sex"))?)?;
+    let p_id2 = match persons.get(name2) {
+        Some(id) => id.clone(),
+        None => {
+            let person = kg.np_with_name(sex2, name2.to_string());
+            persons.insert(name2, person.clone());
+            person
+        }
+    };
+    let kind = parse_kind(rel)?;
+    if is_attr {
+        let key = words.next().ok_or_else(|| missing("key"))?;
+        let value = AttrValue::parse(&words.join(" "), kg.calendar())?;
+        return kg.set_relation_attr(&p_id, &p_id2, kind, key, value);
+    }
+    if kind == Kind::Spouse {
+        let partnership = parse_partnership(&words.collect_vec(), kg)?;
+        return kg.add_partnership(&p_id, &p_id2, partnership);
+    }
+    //add relationship
+    kg.add_relation(&p_id, &p_id2, kind)?;
+    Ok(())
+}
+fn parse_sex(sex: &str) -> anyhow::Result<crate::Sex> {
+    match sex {
+        "M" => Ok(crate::Sex::Male),
+        "F" => Ok(crate::Sex::Female),
+        _ => anyhow::bail!("Invalid sex"),
+    }
+}
 ///Takes a string representing kin dsl and processes it. If there are any statements, adds the relationships to the graph, and if there are any queries, returns the results of the queries
 pub fn query_kin(string: &str, kg: &mut KinGraph) -> anyhow::Result<Vec<Box<dyn State>>> {
     //first trim the string
@@ -42,48 +218,7 @@ pub fn query_kin(string: &str, kg: &mut KinGraph) -> anyhow::Result<Vec<Box<dyn
         }
     }
     for line in statements {
-        let line = line.trim();
-        let mut words = line.split_whitespace();
-        let name = words.next().unwrap();
-        let sex = words.next().unwrap();
-        let sex = match sex {
-            "M" => crate::Sex::Male,
-            "F" => crate::Sex::Female,
-            _ => anyhow::bail!("Invalid sex"),
-        };
-        let p_id = match persons.get(name) {
-            Some(id) => id.clone(),
-            None => {
-                let person = kg.np_with_name(sex, name.to_string());
-                persons.insert(name, person.clone());
-                person
-            }
-        };
-        let rel = words.next().unwrap();
-        let rel = match rel {
-            "PARENT" => crate::Kind::Parent,
-            "CHILD" => crate::Kind::Child,
-            "RP" => crate::Kind::RP,
-            "SIBLING" => crate::Kind::Sibling,
-            _ => anyhow::bail!("Invalid relationship"),
-        };
-        let name2 = words.next().unwrap();
-        let sex2 = words.next().unwrap();
-        let sex2 = match sex2 {
-            "M" => crate::Sex::Male,
-            "F" => crate::Sex::Female,
-            _ => anyhow::bail!("Invalid sex"),
-        };
-        let p_id2 = match persons.get(name2) {
-            Some(id) => id.clone(),
-            None => {
-                let person = kg.np_with_name(sex2, name2.to_string());
-                persons.insert(name2, person.clone());
-                person
-            }
-        };
-        //add relationship
-        kg.add_relation(&p_id, &p_id2, rel)?;
+        apply_statement(line, &mut persons, kg)?;
     }
     let queries = queries
         .iter()
@@ -132,48 +267,7 @@ pub fn parse_relations_from_dsl(string: &str, kg: &mut KinGraph) -> Result<(), a
         }
     }
     for line in statements {
-        let line = line.trim();
-        let mut words = line.split_whitespace();
-        let name = words.next().unwrap();
-        let sex = words.next().unwrap();
-        let sex = match sex {
-            "M" => crate::Sex::Male,
-            "F" => crate::Sex::Female,
-            _ => anyhow::bail!("Invalid sex"),
-        };
-        let p_id = match persons.get(name) {
-            Some(id) => id.clone(),
-            None => {
-                let person = kg.np_with_name(sex, name.to_string());
-                persons.insert(name, person.clone());
-                person
-            }
-        };
-        let rel = words.next().unwrap();
-        let rel = match rel {
-            "PARENT" => crate::Kind::Parent,
-            "CHILD" => crate::Kind::Child,
-            "RP" => crate::Kind::RP,
-            "SIBLING" => crate::Kind::Sibling,
-            _ => anyhow::bail!("Invalid relationship"),
-        };
-        let name2 = words.next().unwrap();
-        let sex2 = words.next().unwrap();
-        let sex2 = match sex2 {
-            "M" => crate::Sex::Male,
-            "F" => crate::Sex::Female,
-            _ => anyhow::bail!("Invalid sex"),
-        };
-        let p_id2 = match persons.get(name2) {
-            Some(id) => id.clone(),
-            None => {
-                let person = kg.np_with_name(sex2, name2.to_string());
-                persons.insert(name2, person.clone());
-                person
-            }
-        };
-        //add relationship
-        kg.add_relation(&p_id, &p_id2, rel)?;
+        apply_statement(line, &mut persons, kg)?;
     }
     Ok(())
 }
@@ -197,7 +291,7 @@ mod tests {
 
         // let res = query_kin(dsl, &mut kg).unwrap();
         // let mut file = std::fs::File::create("grandchild_rp.dot").unwrap();
-        // crate::render_to(&mut file, &kg);
+        // kg.write_dot(&mut file, &crate::DotOptions::default()).unwrap();
         // println!("RP and grandchild {:?}", res);
         //represnt half-sibling relationship
         // let half_sib_dsl = r#"
@@ -212,7 +306,7 @@ mod tests {
         // info!("Looking for half sib relationship");
         // let res = query_kin(half_sib_dsl, &mut kg).unwrap();
         // let mut file = std::fs::File::create("half_sib.dot").unwrap();
-        // crate::render_to(&mut file, &kg);
+        // kg.write_dot(&mut file, &crate::DotOptions::default()).unwrap();
         // info!(relationships = ?res, "Half sibling relationship");
 
         //incest child
@@ -228,4 +322,170 @@ mod tests {
         //     println!("{}", r);
         // }
     }
+    #[test]
+    fn life_dates_dsl() {
+        let dsl = r#"
+        Mike M CHILD Izy F
+        Mike M BORN abt 1850
+        Izy F BORN 12 Mar 1820
+        Izy F DIED bet 1890 and 1895
+        :QUERY
+        Mike TO Izy
+        "#;
+        let mut kg = KinGraph::new();
+        let res = query_kin(dsl, &mut kg).unwrap();
+        assert_eq!(res.len(), 1);
+        let named = |name: &str| {
+            kg.graph
+                .node_weights()
+                .find(|p| p.name == name)
+                .unwrap()
+                .clone()
+        };
+        let (mike, izy) = (named("Mike"), named("Izy"));
+        assert_eq!(mike.birth(), Some("abt 1850".parse().unwrap()));
+        assert_eq!(izy.birth(), Some("12 Mar 1820".parse().unwrap()));
+        assert_eq!(izy.death().unwrap().to_string(), "bet 1890 and 1895");
+        let bad = "Mike M BORN sometime";
+        assert!(parse_relations_from_dsl(bad, &mut KinGraph::new()).is_err());
+    }
+    #[test]
+    fn partnership_dsl() {
+        let dsl = r#"
+        Ann F SPOUSE Bob M
+        Ann F SPOUSE Cal M PARTNERED FROM abt 1850
+        Dot F SPOUSE Cal M WIDOWED UNTIL bet 1890 and 1895
+        Dot F SPOUSE Eli M FROM 1880 UNTIL 1885
+        "#;
+        let mut kg = KinGraph::new();
+        parse_relations_from_dsl(dsl, &mut kg).unwrap();
+        let (ann, bob, cal) = (kg.px(0).clone(), kg.px(1).clone(), kg.px(2).clone());
+        let (dot, eli) = (kg.px(3).clone(), kg.px(4).clone());
+        assert_eq!(
+            kg.get_partnership(&ann, &bob),
+            Some(&Partnership::default())
+        );
+        let partnership = kg.get_partnership(&ann, &cal).unwrap();
+        assert_eq!(partnership.status, MaritalStatus::Partnered);
+        assert_eq!(partnership.start, Some("abt 1850".parse().unwrap()));
+        assert_eq!(partnership.end, None);
+        let partnership = kg.get_partnership(&cal, &dot).unwrap();
+        assert_eq!(partnership.status, MaritalStatus::Widowed);
+        assert_eq!(partnership.end, Some("bet 1890 and 1895".parse().unwrap()));
+        let partnership = kg.get_partnership(&dot, &eli).unwrap();
+        assert_eq!(partnership.status, MaritalStatus::Married);
+        assert_eq!(partnership.start, Some("1880".parse().unwrap()));
+        assert_eq!(partnership.end, Some("1885".parse().unwrap()));
+        for bad in [
+            "Ann F SPOUSE Bob M ENGAGED",
+            "Ann F SPOUSE Bob M 1850",
+            "Ann F SPOUSE Bob M FROM sometime",
+            "Ann F SPOUSE Bob M UNTIL",
+        ] {
+            assert!(parse_relations_from_dsl(bad, &mut KinGraph::new()).is_err());
+        }
+    }
+    #[test]
+    fn formatted_dates_dsl() {
+        use crate::{Calendar, CustomCalendar, Date, GenDate, Gregorian};
+        //the dates the DSL reads back from how the graph's calendar writes them
+        let round_trip = |calendar: Box<dyn Calendar>, date: GenDate| {
+            let mut kg = KinGraph::new();
+            kg.set_calendar(calendar);
+            let written = kg.format_date(&date);
+            let dsl = format!(
+                "Ann F BORN {0}\nAnn F SPOUSE Bob M DIVORCED FROM {0} UNTIL {0}",
+                written
+            );
+            parse_relations_from_dsl(&dsl, &mut kg).unwrap();
+            let partnership = kg.get_partnership(kg.px(0), kg.px(1)).unwrap();
+            assert_eq!(partnership.start, Some(date), "{}", written);
+            assert_eq!(partnership.end, Some(date), "{}", written);
+            assert_eq!(kg.px(0).birth(), Some(date), "{}", written);
+        };
+        let dual = Date {
+            dual: true,
+            ..Date::ymd(1711, 2, 3)
+        };
+        for date in [
+            GenDate::Exact(Date::ymd(1850, 3, 12)),
+            GenDate::About(Date::ym(1850, 3)),
+            GenDate::Calculated(Date::year(1900)),
+            GenDate::Estimated(Date::year(1777)),
+            GenDate::Before(dual),
+            GenDate::After(Date::year(1901)),
+            GenDate::Between(Date::year(1820), Date::ymd(1825, 12, 31)),
+        ] {
+            round_trip(Box::new(Gregorian), date);
+        }
+        //thirteen months of 28 days, counted from the founding of the city
+        let months = (1..=13).map(|i| format!("Moon{}", i)).collect::<Vec<_>>();
+        let months = months.iter().map(|m| (m.as_str(), 28)).collect::<Vec<_>>();
+        let lunar = CustomCalendar::new("Lunar", &months)
+            .unwrap()
+            .with_era("AF", 1, false);
+        for date in [
+            GenDate::Exact(Date::ymd(320, 2, 3)),
+            GenDate::About(Date::year(318)),
+            GenDate::Before(Date::ym(340, 13)),
+            GenDate::Between(Date::year(300), Date::ymd(360, 13, 28)),
+        ] {
+            round_trip(Box::new(lunar.clone()), date);
+        }
+    }
+    #[test]
+    fn attributes_dsl() {
+        let dsl = r#"
+        Mike M CHILD Izy F
+        Mike M ATTR occupation blacksmith
+        Mike M ATTR tags [hero, "the brave"]
+        Mike M REF novella char-17
+        Mike M REL_ATTR CHILD Izy F note date(aft 1850)
+        "#;
+        let mut kg = KinGraph::new();
+        parse_relations_from_dsl(dsl, &mut kg).unwrap();
+        let mike = kg.find_by_ref("novella", "char-17").unwrap().clone();
+        let izy = kg.px(1).clone();
+        assert_eq!(mike.attr("occupation"), Some(&"blacksmith".into()));
+        assert_eq!(mike.attr("tags"), Some(&vec!["hero", "the brave"].into()));
+        let note = kg
+            .relation_attrs(&izy, &mike, Kind::Parent)
+            .unwrap()
+            .get("note");
+        assert_eq!(note.unwrap().to_string(), "aft 1850");
+        assert_eq!(kg.relation_attrs(&izy, &mike, Kind::Guardian), None);
+        let bad = "Mike M REL_ATTR CHILD Bob M note x";
+        assert!(parse_relations_from_dsl(bad, &mut KinGraph::new()).is_err());
+        let wrong_kind = "Mike M CHILD Izy F\nMike M REL_ATTR WARD Izy F note x";
+        assert!(parse_relations_from_dsl(wrong_kind, &mut KinGraph::new()).is_err());
+        //statements cut short are errors rather than panics
+        for short in [
+            "Mike M ATTR",
+            "Mike M REF novella",
+            "Mike M REL_ATTR",
+            "Mike M REL_ATTR CHILD Izy F",
+        ] {
+            assert!(parse_relations_from_dsl(short, &mut KinGraph::new()).is_err());
+        }
+    }
+    #[test]
+    fn names_dsl() {
+        let dsl = r#"
+        Jr M CHILD Sr M
+        Jr M NAME John "Johnny" /Smith/
+        Jr M ALIAS Jack /Smith/
+        Sr M NAME John /Smith/
+        :QUERY
+        Jr TO Sr
+        "#;
+        let mut kg = KinGraph::new();
+        query_kin(dsl, &mut kg).unwrap();
+        let found = kg.find_by_name("john smith");
+        assert_eq!(found.len(), 2);
+        let jr = kg.find_by_name("Jack")[0];
+        assert_eq!(jr.name(), "Jr");
+        assert_eq!(jr.primary_name().unwrap().to_string(), "John Smith");
+        assert_eq!(jr.alternate_names()[0].given, ["Jack"]);
+        assert_eq!(kg.find_by_name("johnny").len(), 1);
+    }
 }
@@ -2,6 +2,7 @@ use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::cell::RefCell;
+use std::collections::BTreeMap;
 use wasm_bindgen::prelude::*;
 
 thread_local! {
@@ -10,10 +11,29 @@ thread_local! {
 #[derive(Deserialize, Serialize)]
 pub struct KinWasmGraph {
     nodes: Vec<PersonNode>,
+    ///The calendar that the dates are written in, if it is not the Gregorian one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    calendar: Option<CustomCalendar>,
 }
 impl KinWasmGraph {
     pub fn new(nodes: Vec<PersonNode>) -> Self {
-        Self { nodes }
+        Self {
+            nodes,
+            calendar: None,
+        }
+    }
+    pub fn with_calendar(mut self, calendar: Option<CustomCalendar>) -> Self {
+        self.calendar = calendar;
+        self
+    }
+    pub fn nodes(&self) -> &[PersonNode] {
+        &self.nodes
+    }
+    pub fn calendar(&self) -> Option<&CustomCalendar> {
+        self.calendar.as_ref()
+    }
+    pub fn into_nodes(self) -> Vec<PersonNode> {
+        self.nodes
     }
 }
 #[derive(Deserialize, Serialize)]
@@ -21,8 +41,34 @@ pub struct PersonNode {
     pub id: u32,
     pub sex: Sex,
     pub name: String,
+    ///If this is a shadow person added by the sanitizer
+    #[serde(default)]
+    pub is_shadow: bool,
+    #[serde(default)]
+    pub birth: Option<GenDate>,
+    #[serde(default)]
+    pub death: Option<GenDate>,
+    #[serde(default, skip_serializing_if = "Attributes::is_empty")]
+    pub attributes: Attributes,
+    ///Ids of the person in other systems, by system
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub refs: BTreeMap<String, String>,
+    ///The primary name first, followed by any alternate names
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub names: Vec<PersonName>,
     //vec of ids
     relations: Vec<Relation>,
+    ///The attributes of relations, by the id of the other person and the kind of the relation
+    /// to them
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub relation_attributes: BTreeMap<u32, BTreeMap<RelationKind, Attributes>>,
+    ///How the person is a parent of their children, by the id of the child, where it has been
+    /// recorded
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub parentage: BTreeMap<u32, Parentage>,
+    ///The partnerships of the person, by the id of the spouse
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub partnerships: BTreeMap<u32, Partnership>,
 }
 
 impl PersonNode {
@@ -32,6 +78,23 @@ impl PersonNode {
     pub fn get_id(&self) -> u32 {
         self.id
     }
+    pub fn relations(&self) -> &[Relation] {
+        &self.relations
+    }
+}
+///The person of a node, with the node id as their id
+impl From<PersonNode> for Person {
+    fn from(node: PersonNode) -> Self {
+        Person {
+            is_shadow: node.is_shadow,
+            birth: node.birth,
+            death: node.death,
+            attributes: node.attributes,
+            refs: node.refs,
+            names: node.names,
+            ..Person::new_with_id(node.sex, node.id as usize, node.name)
+        }
+    }
 }
 impl PersonNode {
     pub fn new(id: u32, sex: Sex, name: String, relations: Vec<Relation>) -> Self {
@@ -40,8 +103,52 @@ impl PersonNode {
             id,
             relations,
             name,
+            is_shadow: false,
+            birth: None,
+            death: None,
+            attributes: Attributes::new(),
+            refs: BTreeMap::new(),
+            names: Vec::new(),
+            relation_attributes: BTreeMap::new(),
+            parentage: BTreeMap::new(),
+            partnerships: BTreeMap::new(),
         }
     }
+    pub fn with_shadow(mut self, is_shadow: bool) -> Self {
+        self.is_shadow = is_shadow;
+        self
+    }
+    ///Set the birth and death dates of the node
+    pub fn with_life_dates(mut self, birth: Option<GenDate>, death: Option<GenDate>) -> Self {
+        self.birth = birth;
+        self.death = death;
+        self
+    }
+    pub fn with_attributes(
+        mut self,
+        attributes: Attributes,
+        refs: BTreeMap<String, String>,
+        relation_attributes: BTreeMap<u32, BTreeMap<RelationKind, Attributes>>,
+    ) -> Self {
+        self.attributes = attributes;
+        self.refs = refs;
+        self.relation_attributes = relation_attributes;
+        self
+    }
+    pub fn with_names(mut self, names: Vec<PersonName>) -> Self {
+        self.names = names;
+        self
+    }
+    ///Set how the node is a parent of each child, and its partnership with each spouse
+    pub fn with_family(
+        mut self,
+        parentage: BTreeMap<u32, Parentage>,
+        partnerships: BTreeMap<u32, Partnership>,
+    ) -> Self {
+        self.parentage = parentage;
+        self.partnerships = partnerships;
+        self
+    }
 }
 #[wasm_bindgen]
 #[derive(Deserialize, Serialize, Copy, Clone)]
@@ -51,14 +158,58 @@ pub struct Relation {
 }
 #[repr(C)]
 #[wasm_bindgen]
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum RelationKind {
     Parent = 0,
     Child = 1,
     RP = 2,
     Sibling = 3,
+    AdoptiveParent = 4,
+    AdoptiveChild = 5,
+    FosterParent = 6,
+    FosterChild = 7,
+    Guardian = 8,
+    Ward = 9,
+    Spouse = 10,
+}
+impl From<RelationKind> for Kind {
+    fn from(kind: RelationKind) -> Self {
+        match kind {
+            RelationKind::Parent => Kind::Parent,
+            RelationKind::Child => Kind::Child,
+            RelationKind::RP => Kind::RP,
+            RelationKind::Sibling => Kind::Sibling,
+            RelationKind::AdoptiveParent => Kind::AdoptiveParent,
+            RelationKind::AdoptiveChild => Kind::AdoptiveChild,
+            RelationKind::FosterParent => Kind::FosterParent,
+            RelationKind::FosterChild => Kind::FosterChild,
+            RelationKind::Guardian => Kind::Guardian,
+            RelationKind::Ward => Kind::Ward,
+            RelationKind::Spouse => Kind::Spouse,
+        }
+    }
+}
+impl From<Kind> for RelationKind {
+    fn from(kind: Kind) -> Self {
+        match kind {
+            Kind::Parent => RelationKind::Parent,
+            Kind::Child => RelationKind::Child,
+            Kind::RP => RelationKind::RP,
+            Kind::Sibling => RelationKind::Sibling,
+            Kind::AdoptiveParent => RelationKind::AdoptiveParent,
+            Kind::AdoptiveChild => RelationKind::AdoptiveChild,
+            Kind::FosterParent => RelationKind::FosterParent,
+            Kind::FosterChild => RelationKind::FosterChild,
+            Kind::Guardian => RelationKind::Guardian,
+            Kind::Ward => RelationKind::Ward,
+            Kind::Spouse => RelationKind::Spouse,
+        }
+    }
 }
-use crate::{kin_dsl, KinGraph, Kind, Person, Sex};
+use crate::{
+    kin_dsl, AttrValue, Attributes, CustomCalendar, FanOptions, FanSector, GenDate, KinGraph, Kind,
+    MaritalStatus, NameMatch, Parentage, Partnership, Person, PersonName, Sex,
+};
 #[wasm_bindgen]
 extern "C" {
     pub fn alert(s: &str);
@@ -74,21 +225,170 @@ pub fn get_graph() -> String {
     })
 }
 #[wasm_bindgen]
+///Replace the current graph with one in the JSON layout of [get_graph]
+pub fn load_graph(json: &str) -> Result<(), JsValue> {
+    let to_js = |e: anyhow::Error| JsValue::from_str(&format!("{:?}", e));
+    let graph: KinWasmGraph = serde_json::from_str(json).map_err(|e| to_js(e.into()))?;
+    let graph = KinGraph::from_wasm_graph(graph).map_err(to_js)?;
+    KINGRAPH_INSTANCE.with(|kg| *kg.borrow_mut() = graph);
+    Ok(())
+}
+#[wasm_bindgen]
 ///Add a relationship to the graph
 pub fn add_relation(p1: &Person, p2: &Person, kind: RelationKind) -> Result<(), JsValue> {
-    let kind = match kind {
-        RelationKind::Parent => Kind::Parent,
-        RelationKind::Child => Kind::Child,
-        RelationKind::RP => Kind::RP,
-        RelationKind::Sibling => Kind::Sibling,
-    };
     KINGRAPH_INSTANCE.with(|kg| {
         let mut kg = kg.borrow_mut();
-        kg.add_relation(p1, p2, kind)
+        kg.add_relation(p1, p2, kind.into())
+            .map_err(|e| JsValue::from_str(&format!("{:?}", e)))
+    })
+}
+#[wasm_bindgen]
+///Make two persons spouses, or replace their partnership, with the given status and the dates it
+/// started and ended, given as genealogical date strings in the graph's calendar
+pub fn set_partnership(
+    p1: &Person,
+    p2: &Person,
+    status: MaritalStatus,
+    start: Option<String>,
+    end: Option<String>,
+) -> Result<(), JsValue> {
+    KINGRAPH_INSTANCE.with(|kg| {
+        let mut kg = kg.borrow_mut();
+        let parse = |d: Option<String>| {
+            d.map(|d| kg.parse_date(&d))
+                .transpose()
+                .map_err(|e| JsValue::from_str(&format!("{:?}", e)))
+        };
+        let partnership = Partnership {
+            status,
+            start: parse(start)?,
+            end: parse(end)?,
+        };
+        kg.add_partnership(p1, p2, partnership)
+            .map_err(|e| JsValue::from_str(&format!("{:?}", e)))
+    })
+}
+#[wasm_bindgen]
+///Set the birth and death dates of a person, given as genealogical date strings such as "abt 1850"
+/// in the graph's calendar
+pub fn set_life_dates(
+    p: &Person,
+    birth: Option<String>,
+    death: Option<String>,
+) -> Result<(), JsValue> {
+    KINGRAPH_INSTANCE.with(|kg| {
+        let mut kg = kg.borrow_mut();
+        let parse = |d: Option<String>| {
+            d.map(|d| kg.parse_date(&d))
+                .transpose()
+                .map_err(|e| JsValue::from_str(&format!("{:?}", e)))
+        };
+        let birth = parse(birth)?;
+        let death = parse(death)?;
+        kg.set_birth(p, birth)
+            .and_then(|_| kg.set_death(p, death))
             .map_err(|e| JsValue::from_str(&format!("{:?}", e)))
     })
 }
 #[wasm_bindgen]
+///Use a custom calendar, given as the JSON of a [CustomCalendar], for the dates of the graph
+pub fn set_calendar(json: &str) -> Result<(), JsValue> {
+    let calendar: CustomCalendar =
+        serde_json::from_str(json).map_err(|e| JsValue::from_str(&format!("{:?}", e)))?;
+    KINGRAPH_INSTANCE.with(|kg| {
+        kg.borrow_mut().set_calendar(Box::new(calendar));
+    });
+    Ok(())
+}
+#[wasm_bindgen]
+///Set an attribute of a person. The value is written as in the DSL, e.g. "42" or "[a, b]".
+pub fn set_attribute(p: &Person, key: &str, value: &str) -> Result<(), JsValue> {
+    KINGRAPH_INSTANCE.with(|kg| {
+        let mut kg = kg.borrow_mut();
+        AttrValue::parse(value, kg.calendar())
+            .and_then(|value| kg.set_attr(p, key, value))
+            .map_err(|e| JsValue::from_str(&format!("{:?}", e)))
+    })
+}
+#[wasm_bindgen]
+///Record the id of a person in another system
+pub fn set_external_ref(p: &Person, system: &str, id: &str) -> Result<(), JsValue> {
+    KINGRAPH_INSTANCE.with(|kg| {
+        kg.borrow_mut()
+            .set_external_ref(p, system, id)
+            .map_err(|e| JsValue::from_str(&format!("{:?}", e)))
+    })
+}
+#[wasm_bindgen]
+///Find a person by their id in another system
+pub fn find_by_ref(system: &str, id: &str) -> Option<Person> {
+    KINGRAPH_INSTANCE.with(|kg| kg.borrow().find_by_ref(system, id).cloned())
+}
+#[wasm_bindgen]
+///Set the primary name of a person, written GEDCOM style, e.g. `John "Johnny" /Smith/`
+pub fn set_name(p: &Person, name: &str) -> Result<(), JsValue> {
+    let name: PersonName = name
+        .parse()
+        .map_err(|e| JsValue::from_str(&format!("{:?}", e)))?;
+    KINGRAPH_INSTANCE.with(|kg| {
+        kg.borrow_mut()
+            .set_name(p, name)
+            .map_err(|e| JsValue::from_str(&format!("{:?}", e)))
+    })
+}
+#[wasm_bindgen]
+///Search the names of persons, returning the node ids (as in [get_graph]) of those found,
+/// best matches first
+pub fn search_names(query: &str, mode: NameMatch) -> Vec<u32> {
+    KINGRAPH_INSTANCE.with(|kg| {
+        let kg = kg.borrow();
+        kg.search_names(query, mode)
+            .iter()
+            .map(|p| kg.idx(p).unwrap().index() as u32)
+            .collect()
+    })
+}
+#[wasm_bindgen]
+///Lay the graph out as a family tree, returning the depth and width (see [crate::Location]) of each
+/// person by node id, as JSON
+pub fn get_layout() -> String {
+    KINGRAPH_INSTANCE.with(|kg| {
+        let mut kg = kg.borrow_mut();
+        kg.layout();
+        let nodes = kg
+            .graph
+            .node_indices()
+            .filter_map(|n| {
+                let location = kg.location(&kg.graph[n])?;
+                Some(json!({
+                    "id": n.index(),
+                    "depth": location.depth(),
+                    "width": location.width(),
+                }))
+            })
+            .collect_vec();
+        json!({ "nodes": nodes }).to_string()
+    })
+}
+#[wasm_bindgen]
+///Lay out a fan chart of the ancestors of a person, or a radial chart of their descendants, over
+/// the given number of generations, returning its [FanSector]s as JSON
+pub fn get_fan_chart(p: &Person, generations: usize, descendants: bool) -> Result<String, JsValue> {
+    KINGRAPH_INSTANCE.with(|kg| {
+        let kg = kg.borrow();
+        let opts = FanOptions {
+            generations,
+            ..FanOptions::default()
+        };
+        let sectors: Vec<FanSector> = match descendants {
+            true => kg.radial_chart(p, &opts),
+            false => kg.fan_chart(p, &opts),
+        }
+        .map_err(|e| JsValue::from_str(&format!("{:?}", e)))?;
+        Ok(json!(sectors).to_string())
+    })
+}
+#[wasm_bindgen]
 pub fn get_relation(p1: &Person, p2: &Person) -> Result<String, JsValue> {
     KINGRAPH_INSTANCE.with(|kg| {
         let kg = kg.borrow();
@@ -100,6 +400,25 @@ pub fn get_relation(p1: &Person, p2: &Person) -> Result<String, JsValue> {
     })
 }
 #[wasm_bindgen]
+///The relationships between two persons as they were on a date, written in the graph's calendar,
+/// as JSON along with whether each of them was alive then
+pub fn get_relation_as_of(p1: &Person, p2: &Person, date: &str) -> Result<String, JsValue> {
+    KINGRAPH_INSTANCE.with(|kg| {
+        let kg = kg.borrow();
+        let to_js = |e: anyhow::Error| JsValue::from_str(&format!("{:?}", e));
+        let date = kg.calendar().parse_date(date).map_err(to_js)?;
+        let res = kg
+            .get_canonical_relationships_as_of(p1, p2, date)
+            .map_err(to_js)?;
+        let json = json!({
+            "relationships": res.relationships.iter().map(|r| r.to_string()).collect_vec(),
+            "p1_status": res.p1_status,
+            "p2_status": res.p2_status,
+        });
+        Ok(json.to_string())
+    })
+}
+#[wasm_bindgen]
 pub struct QueryResult {
     results: Vec<String>,
 }
@@ -0,0 +1,301 @@
+//! Layout of the graph as a family tree, giving each person a [Location].
+//!
+//! Persons are put in rows by generation (see [KinGraph::generations]), with parents above their
+//! children. Partners and spouses are kept next to each other in their row, men first, and a
+//! person with more than one partner is put between them. The rows are then reordered with
+//! barycenter sweeps, keeping the order that crosses the fewest lines between parents and
+//! children. Last, each row is moved across so that children are centered under their parents
+//! and parents over their children, as far as the persons beside them allow.
+use crate::{KinGraph, Kind, Location, Nd, Person, Sex};
+use petgraph::visit::EdgeRef;
+use petgraph::Direction::Outgoing;
+use std::collections::{BTreeMap, HashSet, VecDeque};
+
+//the width between two persons next to each other in a row, which makes the middle of a couple
+//a whole number
+const SPACING: f64 = 2.0;
+//how many times the rows are reordered, alternately from the top and from the bottom
+const SWEEPS: usize = 8;
+//how many times the rows are moved under their parents and then over their children
+const PASSES: usize = 4;
+
+//partners that are kept next to each other in a row, in order
+type Block = Vec<Nd>;
+
+//the nondecreasing values that are closest to the targets, each given with how much it counts,
+//found by pooling neighbouring values that are out of order
+fn isotonic(targets: &[(f64, f64)]) -> Vec<f64> {
+    //the mean, weight and size of each pool
+    let mut pools: Vec<(f64, f64, usize)> = Vec::new();
+    for &(target, weight) in targets {
+        pools.push((target, weight, 1));
+        while pools.len() > 1 && pools[pools.len() - 2].0 > pools[pools.len() - 1].0 {
+            let (m2, w2, n2) = pools.pop().unwrap();
+            let (m1, w1, n1) = pools.pop().unwrap();
+            pools.push(((m1 * w1 + m2 * w2) / (w1 + w2), w1 + w2, n1 + n2));
+        }
+    }
+    pools
+        .into_iter()
+        .flat_map(|(mean, _, n)| std::iter::repeat_n(mean, n))
+        .collect()
+}
+
+//the place of each person across their row, by index
+fn row_positions(rows: &[Vec<Block>], count: usize) -> Vec<usize> {
+    let mut positions = vec![0; count];
+    for row in rows {
+        for (i, n) in row.iter().flatten().enumerate() {
+            positions[n.index()] = i;
+        }
+    }
+    positions
+}
+
+impl KinGraph {
+    ///Lays the graph out as a family tree and returns the [Location] of each person by node,
+    /// which is kept until persons or relations are added. Each generation is a row, from the earliest at depth 0
+    /// down, and the width of a person is how far across their row they are, two for each
+    /// place, so that a child can be centered under a couple.
+    pub fn layout(&mut self) -> &BTreeMap<Nd, Location> {
+        if self.depth_map.is_none() {
+            self.depth_map = Some(self.compute_layout());
+        }
+        self.depth_map.as_ref().unwrap()
+    }
+    ///Where the person is in the layout of the graph, if it has been laid out since it last
+    /// changed
+    pub fn location(&self, p: &Person) -> Option<Location> {
+        self.depth_map.as_ref()?.get(&self.idx(p)?).copied()
+    }
+    fn compute_layout(&self) -> BTreeMap<Nd, Location> {
+        let generations = self.generations();
+        let mut rows = self.layout_rows(&generations);
+        self.order_rows(&mut rows, &generations);
+        let x = self.place_rows(&rows);
+        let left = x.iter().copied().fold(f64::INFINITY, f64::min);
+        self.graph
+            .node_indices()
+            .map(|n| {
+                let location = Location {
+                    d: generations[n.index()],
+                    w: (x[n.index()] - left).round() as i32,
+                };
+                (n, location)
+            })
+            .collect()
+    }
+    //the persons a generation up (parents) or down (children) by any kind of parenthood
+    fn vertical(&self, n: Nd, up: bool) -> Vec<Nd> {
+        self.graph
+            .edges_directed(n, Outgoing)
+            .filter(|e| match e.weight() {
+                Kind::Child | Kind::AdoptiveChild | Kind::FosterChild | Kind::Ward => up,
+                Kind::Parent | Kind::AdoptiveParent | Kind::FosterParent | Kind::Guardian => !up,
+                Kind::Sibling | Kind::RP | Kind::Spouse => false,
+            })
+            .map(|e| e.target())
+            .collect()
+    }
+    //the partners and spouses of a person in their own generation
+    fn row_partners(&self, n: Nd, generations: &[i32]) -> Vec<Nd> {
+        let mut partners = self
+            .graph
+            .edges_directed(n, Outgoing)
+            .filter(|e| matches!(e.weight(), Kind::RP | Kind::Spouse))
+            .map(|e| e.target())
+            .filter(|m| generations[m.index()] == generations[n.index()])
+            .collect::<Vec<_>>();
+        partners.sort();
+        partners.dedup();
+        partners
+    }
+    //the persons joined to n by partners in its row, walked from one end so that a person with
+    //two partners is between them
+    fn partner_block(&self, n: Nd, generations: &[i32]) -> Block {
+        let mut members = vec![n];
+        let mut i = 0;
+        while i < members.len() {
+            for m in self.row_partners(members[i], generations) {
+                if !members.contains(&m) {
+                    members.push(m);
+                }
+            }
+            i += 1;
+        }
+        let start = members.iter().copied().min_by_key(|m| {
+            let partners = self.row_partners(*m, generations).len();
+            (partners, self.graph[*m].sex != Sex::Male, *m)
+        });
+        let mut block = Vec::new();
+        let mut stack = Vec::from_iter(start);
+        while let Some(m) = stack.pop() {
+            if block.contains(&m) {
+                continue;
+            }
+            block.push(m);
+            for partner in self.row_partners(m, generations).into_iter().rev() {
+                if !block.contains(&partner) {
+                    stack.push(partner);
+                }
+            }
+        }
+        block
+    }
+    //the blocks of each generation, in the order they are reached family by family
+    fn layout_rows(&self, generations: &[i32]) -> Vec<Vec<Block>> {
+        let count = generations.iter().max().map_or(0, |g| g + 1) as usize;
+        let mut rows = vec![Vec::new(); count];
+        let mut placed = HashSet::new();
+        let mut seen = HashSet::new();
+        for start in self.graph.node_indices() {
+            if !seen.insert(start) {
+                continue;
+            }
+            let mut queue = VecDeque::from([start]);
+            while let Some(n) = queue.pop_front() {
+                if !placed.contains(&n) {
+                    let block = self.partner_block(n, generations);
+                    placed.extend(block.iter().copied());
+                    rows[generations[n.index()] as usize].push(block);
+                }
+                for e in self.graph.edges_directed(n, Outgoing) {
+                    if seen.insert(e.target()) {
+                        queue.push_back(e.target());
+                    }
+                }
+            }
+        }
+        rows
+    }
+    //the number of pairs of lines from parents to children in the next row that cross
+    fn crossings(&self, rows: &[Vec<Block>], generations: &[i32]) -> usize {
+        let positions = row_positions(rows, generations.len());
+        let mut crossings = 0;
+        for row in rows {
+            let mut lines = Vec::new();
+            for n in row.iter().flatten() {
+                for c in self.vertical(*n, false) {
+                    if generations[c.index()] == generations[n.index()] + 1 {
+                        lines.push((positions[n.index()], positions[c.index()]));
+                    }
+                }
+            }
+            for (i, (a1, b1)) in lines.iter().enumerate() {
+                crossings += lines[i + 1..]
+                    .iter()
+                    .filter(|(a2, b2)| (a1 < a2 && b1 > b2) || (a1 > a2 && b1 < b2))
+                    .count();
+            }
+        }
+        crossings
+    }
+    //reorders the blocks of each row by the mean place of their parents, going down, or of their
+    //children, going up, and keeps the order with the fewest crossings
+    fn order_rows(&self, rows: &mut Vec<Vec<Block>>, generations: &[i32]) {
+        let mut best = rows.clone();
+        let mut fewest = self.crossings(rows, generations);
+        for sweep in 0..SWEEPS {
+            let down = sweep % 2 == 0;
+            let order = match down {
+                true => (1..rows.len()).collect::<Vec<_>>(),
+                false => (0..rows.len().saturating_sub(1)).rev().collect(),
+            };
+            for r in order {
+                let positions = row_positions(rows, generations.len());
+                let mut keyed = rows[r]
+                    .drain(..)
+                    .map(|mut block| {
+                        let mean = |m: Nd| {
+                            let near = self.vertical(m, down);
+                            let sum = near
+                                .iter()
+                                .map(|v| positions[v.index()] as f64)
+                                .sum::<f64>();
+                            Some(sum / near.len() as f64).filter(|_| !near.is_empty())
+                        };
+                        //partners face the side their own families are on
+                        if let (Some(first), Some(last)) =
+                            (mean(block[0]), mean(block[block.len() - 1]))
+                        {
+                            if first > last {
+                                block.reverse();
+                            }
+                        }
+                        let near = block.iter().flat_map(|m| self.vertical(*m, down));
+                        let near = near
+                            .map(|v| positions[v.index()] as f64)
+                            .collect::<Vec<_>>();
+                        //blocks with no one to follow stay where they are
+                        let key = match near.is_empty() {
+                            true => positions[block[0].index()] as f64,
+                            false => near.iter().sum::<f64>() / near.len() as f64,
+                        };
+                        (key, block)
+                    })
+                    .collect::<Vec<_>>();
+                keyed.sort_by(|a, b| a.0.total_cmp(&b.0));
+                rows[r] = keyed.into_iter().map(|(_, block)| block).collect();
+            }
+            let crossings = self.crossings(rows, generations);
+            if crossings < fewest {
+                fewest = crossings;
+                best = rows.clone();
+            }
+        }
+        *rows = best;
+    }
+    //the place of each person across the rows, by index, starting packed to the left and then
+    //moved under their parents and over their children, ending under their parents
+    fn place_rows(&self, rows: &[Vec<Block>]) -> Vec<f64> {
+        let mut x = vec![0.0; self.graph.node_count()];
+        for row in rows {
+            for (i, n) in row.iter().flatten().enumerate() {
+                x[n.index()] = i as f64 * SPACING;
+            }
+        }
+        for pass in 0..=2 * PASSES {
+            let down = pass % 2 == 0;
+            let order = match down {
+                true => (1..rows.len()).collect::<Vec<_>>(),
+                false => (0..rows.len().saturating_sub(1)).rev().collect(),
+            };
+            for r in order {
+                self.place_row(&rows[r], &mut x, down);
+            }
+        }
+        x
+    }
+    //moves the blocks of a row as near as they can be to the middle of their parents, going down,
+    //or of their children, going up, keeping them in order and apart
+    fn place_row(&self, row: &[Block], x: &mut [f64], down: bool) {
+        let mut targets = Vec::new();
+        //where the block would start if the row was packed
+        let mut packed = 0.0;
+        for block in row {
+            let (mut sum, mut weight) = (0.0, 0.0);
+            for (i, m) in block.iter().enumerate() {
+                let near = self.vertical(*m, down);
+                if !near.is_empty() {
+                    let middle = near.iter().map(|v| x[v.index()]).sum::<f64>() / near.len() as f64;
+                    sum += middle - i as f64 * SPACING;
+                    weight += 1.0;
+                }
+            }
+            //blocks with no one to follow try to stay where they are
+            let (start, weight) = match weight > 0.0 {
+                true => (sum / weight, weight),
+                false => (x[block[0].index()], 1.0),
+            };
+            targets.push((start - packed, weight));
+            packed += block.len() as f64 * SPACING;
+        }
+        let mut packed = 0.0;
+        for (block, start) in row.iter().zip(isotonic(&targets)) {
+            for (i, m) in block.iter().enumerate() {
+                x[m.index()] = start + packed + i as f64 * SPACING;
+            }
+            packed += block.len() as f64 * SPACING;
+        }
+    }
+}
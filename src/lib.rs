@@ -3,9 +3,45 @@
 pub const PPRIME: usize = 2_000_003;
 pub const CPRIME: usize = 2_000_029;
 pub const RPRIME: usize = 2_000_039;
+mod attributes;
+mod binary;
+mod calendar;
+mod csv;
+mod date;
+mod diagram;
+mod fan;
+mod gedcom;
+mod gedcomx;
+mod gramps;
+mod graphviz;
 mod kin_dsl;
 mod kin_wasm;
+mod layout;
+mod linked_data;
+mod names;
+mod network;
+mod serialize;
 mod states;
+mod svg;
+mod xml;
+pub use attributes::{AttrValue, Attributes};
+pub use binary::BINARY_VERSION;
+pub use calendar::{convert, Calendar, CustomCalendar, CustomMonth, Era, Gregorian, LeapRule};
+pub use csv::{CsvError, CsvImport, CsvTable, CSV_REF};
+pub use date::{Date, GenDate};
+pub use diagram::DiagramOptions;
+pub use fan::{FanOptions, FanSector};
+pub use gedcom::{
+    validate_gedcom, GedcomImport, GedcomOptions, GedcomVersion, GedcomWarning, GEDCOM_REF,
+};
+pub use gedcomx::GEDCOMX_REF;
+pub use gramps::{GRAMPS_ID_REF, GRAMPS_REF};
+pub use graphviz::{DotLayout, DotOptions};
+pub use linked_data::LinkedDataOptions;
+pub use names::{NameIndex, NameMatch, PersonName};
+pub use network::NetworkOptions;
+pub use serialize::{GraphDocument, RelationRecord, SCHEMA_VERSION};
+pub use svg::ChartOptions;
 
 mod uuid {
 
@@ -35,9 +71,6 @@ use std::{
 };
 use thiserror::Error;
 use wasm_bindgen::prelude::wasm_bindgen;
-fn render_to<W: std::io::Write>(output: &mut W, graph: &KinGraph) {
-    dot::render(graph, output).unwrap();
-}
 
 pub enum ETransitionStates {
     ///Initial parent
@@ -77,6 +110,16 @@ pub enum KinError {
     SameSexError { p1: usize, p2: usize },
     #[error("Parent not added. Person {p:?} already has two parents")]
     AlreadyTwoParents { p: usize },
+    #[error("Person {p1:?} and Person {p2:?} are not related")]
+    NoRelation { p1: usize, p2: usize },
+    #[error("The {system:?} reference {id:?} already belongs to another person")]
+    DuplicateRef { system: String, id: String },
+    #[error("Node {id:?} appears more than once")]
+    DuplicateNode { id: u32 },
+    #[error("Node {id:?} is related to, but does not exist")]
+    MissingNode { id: u32 },
+    #[error("Node {p1:?} is related to node {p2:?}, but not the other way around")]
+    AsymmetricRelation { p1: usize, p2: usize },
 
     #[error("An unknown error occured")]
     Unknown,
@@ -85,7 +128,7 @@ pub enum KinError {
 ///Describes the possible fundamental types of relationships (that is, all others
 /// can be represented as a combination of these).
 ///
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 
 pub enum Kind {
     Parent = 1,
@@ -95,6 +138,18 @@ pub enum Kind {
     Sibling = 2,
     //Reproductive partner, symmetric
     RP = 4,
+    ///Adoptive parent, the inverse of [Kind::AdoptiveChild]
+    AdoptiveParent = 5,
+    AdoptiveChild = 6,
+    ///Foster parent, the inverse of [Kind::FosterChild]
+    FosterParent = 7,
+    FosterChild = 8,
+    ///Legal guardian, the inverse of [Kind::Ward]
+    Guardian = 9,
+    Ward = 10,
+    ///Spouse or partner, symmetric. Unlike [Kind::RP] this says nothing about children,
+    /// and is what in-law relationships are derived from
+    Spouse = 11,
 }
 
 impl Kind {
@@ -106,6 +161,9 @@ impl Kind {
             Kind::Sibling => 1,
             //Experiment with zero length repat
             Kind::RP => 0,
+            Kind::Spouse => 0,
+            Kind::AdoptiveParent | Kind::FosterParent | Kind::Guardian => 1,
+            Kind::AdoptiveChild | Kind::FosterChild | Kind::Ward => 3,
         }
     }
     fn get_cost(&self) -> u32 {
@@ -114,14 +172,7 @@ impl Kind {
             Kind::Child => 1,
             Kind::Sibling => 1,
             Kind::RP => 1,
-        }
-    }
-    fn is_blood_kind(&self) -> bool {
-        match self {
-            Kind::Parent => true,
-            Kind::Child => true,
-            Kind::Sibling => true,
-            Kind::RP => false,
+            _ => 1,
         }
     }
     fn get_prime(&self) -> usize {
@@ -129,7 +180,8 @@ impl Kind {
             Kind::Parent => PPRIME,
             Kind::Child => CPRIME,
             Kind::Sibling => 0,
-            Kind::RP => RPRIME,
+            //custodial relations break a blood path just like an RP edge does
+            _ => RPRIME,
         }
     }
     fn get_inverse(&self) -> Kind {
@@ -138,8 +190,27 @@ impl Kind {
             Kind::Child => Kind::Parent,
             Kind::Sibling => Kind::Sibling,
             Kind::RP => Kind::RP,
+            Kind::AdoptiveParent => Kind::AdoptiveChild,
+            Kind::AdoptiveChild => Kind::AdoptiveParent,
+            Kind::FosterParent => Kind::FosterChild,
+            Kind::FosterChild => Kind::FosterParent,
+            Kind::Guardian => Kind::Ward,
+            Kind::Ward => Kind::Guardian,
+            Kind::Spouse => Kind::Spouse,
         }
     }
+    ///Whether this kind is one of the adoptive, foster or guardian relations
+    pub fn is_custodial(&self) -> bool {
+        matches!(
+            self,
+            Kind::AdoptiveParent
+                | Kind::AdoptiveChild
+                | Kind::FosterParent
+                | Kind::FosterChild
+                | Kind::Guardian
+                | Kind::Ward
+        )
+    }
     fn into_base_state(self, sex: Sex) -> Box<dyn State> {
         match self {
             Kind::Parent => Box::new(NParentState { n: 0, sex }),
@@ -149,6 +220,11 @@ impl Kind {
                 sex,
             }),
             Kind::RP => Box::new(RPState { sex }),
+            Kind::Spouse => Box::new(SpouseState {
+                sex,
+                status: MaritalStatus::Married,
+            }),
+            _ => Box::new(CustodialState { kind: self, sex }),
         }
     }
 }
@@ -159,6 +235,13 @@ impl std::fmt::Display for Kind {
             Kind::Child => write!(f, "C"),
             Kind::Sibling => write!(f, "S"),
             Kind::RP => write!(f, "R"),
+            Kind::AdoptiveParent => write!(f, "AP"),
+            Kind::AdoptiveChild => write!(f, "AC"),
+            Kind::FosterParent => write!(f, "FP"),
+            Kind::FosterChild => write!(f, "FC"),
+            Kind::Guardian => write!(f, "G"),
+            Kind::Ward => write!(f, "W"),
+            Kind::Spouse => write!(f, "SP"),
         }
     }
 }
@@ -176,6 +259,14 @@ pub struct Location {
     w: i32,
 }
 impl Location {
+    ///The generation of the person, counting down from 0 for the earliest
+    pub fn depth(&self) -> i32 {
+        self.d
+    }
+    ///How far across their generation the person is
+    pub fn width(&self) -> i32 {
+        self.w
+    }
     fn dot(&self, other: Location) -> i32 {
         self.d * other.w + self.w * other.d
     }
@@ -215,14 +306,29 @@ pub enum Sex {
     Female,
 }
 
-#[derive(Hash, PartialEq, Eq, Clone, PartialOrd, Ord, Debug)]
+#[derive(
+    Hash, PartialEq, Eq, Clone, PartialOrd, Ord, Debug, serde::Serialize, serde::Deserialize,
+)]
 #[wasm_bindgen]
 pub struct Person {
     id: usize,
     name: String,
     sex: Sex,
     //If this is a shadow person added by the sanitizer
+    #[serde(default)]
     is_shadow: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    birth: Option<GenDate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    death: Option<GenDate>,
+    #[serde(default, skip_serializing_if = "Attributes::is_empty")]
+    attributes: Attributes,
+    //ids of the person in other systems, by system
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    refs: BTreeMap<String, String>,
+    //the primary name first, followed by any alternate names
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    names: Vec<PersonName>,
 }
 #[wasm_bindgen]
 impl Person {
@@ -234,7 +340,12 @@ impl Person {
             id: id as usize,
             sex,
             is_shadow: false,
-            name: String::from("unkn"),
+            name: String::from(names::UNKNOWN_NAME),
+            birth: None,
+            death: None,
+            attributes: Attributes::new(),
+            refs: BTreeMap::new(),
+            names: Vec::new(),
         }
     }
     pub fn new_with_id(sex: Sex, id: usize, name: String) -> Self {
@@ -243,6 +354,11 @@ impl Person {
             sex,
             is_shadow: false,
             name,
+            birth: None,
+            death: None,
+            attributes: Attributes::new(),
+            refs: BTreeMap::new(),
+            names: Vec::new(),
         }
     }
     pub fn get_id(&self) -> usize {
@@ -255,6 +371,66 @@ impl Person {
         self.is_shadow
     }
 }
+impl Person {
+    ///The short name the person was added with, which the DSL and DOT output identify them by
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn primary_name(&self) -> Option<&PersonName> {
+        self.names.first()
+    }
+    ///Other names the person is known by, such as a married name or an alias
+    pub fn alternate_names(&self) -> &[PersonName] {
+        self.names.get(1..).unwrap_or_default()
+    }
+    ///The words the person can be found by in a [NameIndex]
+    fn name_words(&self) -> impl Iterator<Item = &str> {
+        let short = Some(self.name.as_str()).filter(|n| *n != names::UNKNOWN_NAME);
+        short
+            .into_iter()
+            .chain(self.names.iter().flat_map(|n| n.words()))
+    }
+    pub fn birth(&self) -> Option<GenDate> {
+        self.birth
+    }
+    pub fn death(&self) -> Option<GenDate> {
+        self.death
+    }
+    pub fn attr(&self, key: &str) -> Option<&AttrValue> {
+        self.attributes.get(key)
+    }
+    pub fn attributes(&self) -> &Attributes {
+        &self.attributes
+    }
+    ///The id of the person in another system, such as a character id
+    pub fn external_ref(&self, system: &str) -> Option<&str> {
+        self.refs.get(system).map(|id| id.as_str())
+    }
+    pub fn external_refs(&self) -> &BTreeMap<String, String> {
+        &self.refs
+    }
+    ///Whether the person had been born and was still alive on the given date. Unknown
+    /// or uncertain dates are assumed to not get in the way.
+    pub fn life_status(&self, date: Date, calendar: &dyn Calendar) -> LifeStatus {
+        if self.birth.is_some_and(|b| b.is_after(date, calendar)) {
+            LifeStatus::Unborn
+        } else if self
+            .death
+            .is_some_and(|d| d.is_on_or_before(date, calendar))
+        {
+            LifeStatus::Deceased
+        } else {
+            LifeStatus::Living
+        }
+    }
+}
+///Where a person is in their life on some date
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, serde::Serialize)]
+pub enum LifeStatus {
+    Unborn,
+    Living,
+    Deceased,
+}
 impl From<Person> for NodeIndex<usize> {
     fn from(p: Person) -> Self {
         NodeIndex::from(p.id as usize)
@@ -265,53 +441,172 @@ impl From<&Person> for NodeIndex<usize> {
         NodeIndex::from(p.id as usize)
     }
 }
-///Represents the general directed graph
-pub struct KinGraph {
-    graph: DiGraph<Person, Kind, usize>,
-    //a map between a node index and the id of the person. This is probably
-    //not necessary since it appears that DiGraph already increments directly,
-    //but I have no absoulte proof of this, so...
-    id_indx: HashMap<usize, NodeIndex<usize>>,
-    depth_map: Option<BTreeMap<Person, Location>>,
+///Options that change how relationships are named by [KinGraph::get_canonical_relationships_with]
+#[derive(Clone, Copy, Default, Debug)]
+pub struct QueryOptions {
+    ///Treat adoptive parents and children as if they were blood relations, so that an
+    /// adoptive mother is simply reported as "mother"
+    pub adoptive_as_blood: bool,
+    ///Which parent/child edges are followed when naming the relationship
+    pub parentage: ParentageView,
+    ///Name the relationship as it was on the given date, so that partnerships that had
+    /// not started are ignored, and in-laws through a divorce become former in-laws
+    pub as_of: Option<Date>,
 }
-
-impl<'a> dot::Labeller<'a, Nd, petgraph::graph::EdgeReference<'a, Kind, usize>> for KinGraph {
-    fn graph_id(&'a self) -> dot::Id<'a> {
-        dot::Id::new("Test").unwrap()
-    }
-
-    fn node_id(&'a self, n: &Nd) -> dot::Id<'a> {
-        dot::Id::new(format!(
-            "{}_{}",
-            self.graph.node_weight(*n).unwrap().name.clone(),
-            n.index()
-        ))
-        .unwrap()
-    }
-    fn edge_label(
-        &'a self,
-        e: &petgraph::graph::EdgeReference<'a, Kind, usize>,
-    ) -> dot::LabelText<'a> {
-        dot::LabelText::LabelStr(format!("{}", e.weight()).into())
+///The relationships between two persons on some date, see [KinGraph::get_canonical_relationships_as_of]
+#[derive(Debug)]
+pub struct AsOfRelationships {
+    pub relationships: Vec<Box<dyn State>>,
+    pub p1_status: LifeStatus,
+    pub p2_status: LifeStatus,
+}
+///Describes how a parent is the parent of a child. A sperm donor is only a biological parent,
+/// while a step-parent who has legally taken the role is only a legal one.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Parentage {
+    ///The parent is a genetic parent of the child
+    pub biological: bool,
+    ///The parent is a legal or social parent of the child
+    pub legal: bool,
+}
+impl Default for Parentage {
+    fn default() -> Self {
+        Parentage {
+            biological: true,
+            legal: true,
+        }
     }
 }
-impl<'a> dot::GraphWalk<'a, Nd, petgraph::graph::EdgeReference<'a, Kind, usize>> for KinGraph {
-    fn nodes(&'a self) -> dot::Nodes<'a, Nd> {
-        self.graph.node_indices().collect()
+impl Parentage {
+    ///A genetic parent with no social role, such as a donor
+    pub fn biological_only() -> Self {
+        Parentage {
+            biological: true,
+            legal: false,
+        }
     }
-
-    fn edges(&'a self) -> dot::Edges<'a, petgraph::graph::EdgeReference<'a, Kind, usize>> {
-        self.graph.edge_references().collect()
+    ///A legal or social parent that is not a genetic one
+    pub fn legal_only() -> Self {
+        Parentage {
+            biological: false,
+            legal: true,
+        }
     }
-
-    fn source(&'a self, edge: &petgraph::graph::EdgeReference<'a, Kind, usize>) -> Nd {
-        edge.source()
+}
+///The state of a partnership between two spouses
+#[wasm_bindgen]
+#[derive(
+    Clone, Copy, PartialEq, Eq, Hash, Default, Debug, serde::Serialize, serde::Deserialize,
+)]
+pub enum MaritalStatus {
+    #[default]
+    Married,
+    ///An unmarried but committed partnership
+    Partnered,
+    Divorced,
+    ///The partnership ended with the death of one of the spouses
+    Widowed,
+}
+///Describes a [Kind::Spouse] relation between two persons
+#[derive(Clone, PartialEq, Eq, Hash, Default, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Partnership {
+    pub status: MaritalStatus,
+    ///When the partnership began, if known
+    pub start: Option<GenDate>,
+    ///When the partnership ended, if it has and it is known
+    pub end: Option<GenDate>,
+}
+impl Partnership {
+    pub fn new(status: MaritalStatus) -> Self {
+        Partnership {
+            status,
+            ..Default::default()
+        }
     }
-
-    fn target(&'a self, edge: &petgraph::graph::EdgeReference<'a, Kind, usize>) -> Nd {
-        edge.target()
+    ///The status of the partnership on the given date, given the dates of death of the two spouses,
+    /// or None if it had not yet started.
+    pub fn status_as_of(
+        &self,
+        date: Date,
+        deaths: [Option<GenDate>; 2],
+        calendar: &dyn Calendar,
+    ) -> Option<MaritalStatus> {
+        if self.start.is_some_and(|s| s.is_after(date, calendar)) {
+            return None;
+        }
+        let first_death = deaths
+            .iter()
+            .flatten()
+            .min_by(|a, b| calendar.compare(a, b))
+            .copied();
+        //a divorce that happened before either died takes precedence
+        let divorced = match (self.status, self.end) {
+            (MaritalStatus::Divorced, Some(end)) => {
+                end.is_on_or_before(date, calendar)
+                    && first_death.is_none_or(|d| calendar.compare(&end, &d).is_le())
+            }
+            //divorced at some unknown point
+            (MaritalStatus::Divorced, None) => true,
+            _ => false,
+        };
+        if divorced {
+            return Some(MaritalStatus::Divorced);
+        }
+        let widowed = first_death.is_some_and(|d| d.is_on_or_before(date, calendar))
+            || (self.status == MaritalStatus::Widowed
+                && self.end.is_none_or(|e| e.is_on_or_before(date, calendar)));
+        if widowed {
+            return Some(MaritalStatus::Widowed);
+        }
+        match self.status {
+            MaritalStatus::Partnered => Some(MaritalStatus::Partnered),
+            _ => Some(MaritalStatus::Married),
+        }
     }
 }
+///Selects which kind of parentage is considered when naming relationships
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum ParentageView {
+    ///Follow every parent/child edge
+    #[default]
+    Any,
+    ///Only follow biological parentage
+    Biological,
+    ///Only follow legal/social parentage
+    Legal,
+}
+impl ParentageView {
+    fn includes(&self, parentage: Parentage) -> bool {
+        match self {
+            ParentageView::Any => true,
+            ParentageView::Biological => parentage.biological,
+            ParentageView::Legal => parentage.legal,
+        }
+    }
+}
+///Represents the general directed graph
+pub struct KinGraph {
+    graph: DiGraph<Person, Kind, usize>,
+    //a map between a node index and the id of the person. This is probably
+    //not necessary since it appears that DiGraph already increments directly,
+    //but I have no absoulte proof of this, so...
+    id_indx: HashMap<usize, NodeIndex<usize>>,
+    //the location of each person in the last layout of the graph, cleared when the graph changes
+    depth_map: Option<BTreeMap<Nd, Location>>,
+    //how each parent is the parent of their child, keyed by (parent, child). Edges
+    //that are missing from the map are both biological and legal
+    parentage: HashMap<(Nd, Nd), Parentage>,
+    //the partnership of each pair of spouses, keyed by the ordered pair
+    partnerships: HashMap<(Nd, Nd), Partnership>,
+    //the calendar dates are interpreted in
+    calendar: Box<dyn Calendar>,
+    //the attributes of each relation, keyed by the ordered pair and the kind of the relation
+    //from the first of the pair, see [KinGraph::relation_key]
+    relation_attrs: HashMap<((Nd, Nd), Kind), Attributes>,
+    //index of the external references of persons, keyed by (system, id)
+    ref_index: HashMap<(String, String), Nd>,
+    name_index: NameIndex<Nd>,
+}
 
 impl KinGraph {
     fn new() -> Self {
@@ -319,16 +614,208 @@ impl KinGraph {
             graph: Graph::default(),
             id_indx: HashMap::new(),
             depth_map: None,
+            parentage: HashMap::new(),
+            partnerships: HashMap::new(),
+            calendar: Box::new(Gregorian),
+            relation_attrs: HashMap::new(),
+            ref_index: HashMap::new(),
+            name_index: NameIndex::new(),
         }
     }
     ///Get's the person with the given index, based upon the order in which it was added to the graph.
     pub fn px(&self, ix: usize) -> &Person {
         &self.graph[NodeIndex::from(ix)]
     }
+    ///The calendar that dates are interpreted in, Gregorian unless set otherwise
+    pub fn calendar(&self) -> &dyn Calendar {
+        self.calendar.as_ref()
+    }
+    ///Sets the calendar that dates are interpreted in. Dates already in the graph are kept
+    /// as they are, not converted.
+    pub fn set_calendar(&mut self, calendar: Box<dyn Calendar>) {
+        self.calendar = calendar;
+    }
+    ///Parses a date written in the graph's calendar
+    pub fn parse_date(&self, s: &str) -> Result<GenDate> {
+        self.calendar.parse(s)
+    }
+    ///Writes a date in the graph's calendar
+    pub fn format_date(&self, date: &GenDate) -> String {
+        self.calendar.format(date)
+    }
+    //the birth and death dates of a person for a label, such as "1850 – 1900" or "b. 1850"
+    fn life_dates(&self, p: &Person) -> Option<String> {
+        let date = |d: &Option<GenDate>| d.as_ref().map(|d| self.format_date(d));
+        match (date(&p.birth), date(&p.death)) {
+            (Some(b), Some(d)) => Some(format!("{} – {}", b, d)),
+            (Some(b), None) => Some(format!("b. {}", b)),
+            (None, Some(d)) => Some(format!("d. {}", d)),
+            (None, None) => None,
+        }
+    }
+    ///The youngest and, if the birth date is bounded, oldest the person could have been on the
+    /// date, or None if their birth is unknown or they may not have been born yet
+    pub fn age_as_of(&self, p: &Person, date: Date) -> Option<(i32, Option<i32>)> {
+        let birth = self.graph[self.idx(p)?].birth?;
+        let (earliest_birth, latest_birth) = birth.range(self.calendar());
+        let latest_birth = latest_birth?;
+        if latest_birth > date.earliest() {
+            return None;
+        }
+        let youngest = self.calendar.years_between(latest_birth, date.earliest());
+        let oldest =
+            earliest_birth.map(|b| self.calendar.years_between(b, self.calendar.latest(&date)));
+        Some((youngest, oldest))
+    }
+    ///Persons with a known birth date, from the first born to the last
+    pub fn by_birth(&self) -> Vec<&Person> {
+        let mut persons = self
+            .graph
+            .node_weights()
+            .filter(|p| p.birth.is_some())
+            .collect::<Vec<_>>();
+        persons.sort_by(|a, b| {
+            self.calendar
+                .compare(a.birth.as_ref().unwrap(), b.birth.as_ref().unwrap())
+        });
+        persons
+    }
+    ///Sets an attribute of a person, replacing any previous value
+    pub fn set_attr(&mut self, p: &Person, key: &str, value: AttrValue) -> Result<()> {
+        let px = self.node(p)?;
+        self.graph[px].attributes.insert(key.to_string(), value);
+        Ok(())
+    }
+    pub fn remove_attr(&mut self, p: &Person, key: &str) -> Option<AttrValue> {
+        let px = self.idx(p)?;
+        self.graph[px].attributes.remove(key)
+    }
+    ///Gets an attribute of a person as it is in the graph
+    pub fn get_attr(&self, p: &Person, key: &str) -> Option<&AttrValue> {
+        self.graph[self.idx(p)?].attributes.get(key)
+    }
+    ///Records the id of the person in another system. Each id may only belong to one person.
+    pub fn set_external_ref(&mut self, p: &Person, system: &str, id: &str) -> Result<()> {
+        let px = self.node(p)?;
+        let key = (system.to_string(), id.to_string());
+        if let Some(other) = self.ref_index.get(&key) {
+            if *other != px {
+                return Err(KinError::DuplicateRef {
+                    system: system.to_string(),
+                    id: id.to_string(),
+                }
+                .into());
+            }
+        }
+        if let Some(old) = self.graph[px]
+            .refs
+            .insert(system.to_string(), id.to_string())
+        {
+            self.ref_index.remove(&(system.to_string(), old));
+        }
+        self.ref_index.insert(key, px);
+        Ok(())
+    }
+    ///Finds the person with the given id in another system
+    pub fn find_by_ref(&self, system: &str, id: &str) -> Option<&Person> {
+        let px = self.ref_index.get(&(system.to_string(), id.to_string()))?;
+        Some(&self.graph[*px])
+    }
+    ///Sets an attribute of the relation of the given kind from p1 to p2, which is shared by
+    /// both directions of the relation but not by other relations between them
+    pub fn set_relation_attr(
+        &mut self,
+        p1: &Person,
+        p2: &Person,
+        kind: Kind,
+        key: &str,
+        value: AttrValue,
+    ) -> Result<()> {
+        let p1x = self.node(p1)?;
+        let p2x = self.node(p2)?;
+        if !self
+            .graph
+            .edges_connecting(p1x, p2x)
+            .any(|e| *e.weight() == kind)
+        {
+            return Err(KinError::NoRelation {
+                p1: p1x.index(),
+                p2: p2x.index(),
+            }
+            .into());
+        }
+        self.relation_attrs
+            .entry(Self::relation_key(p1x, p2x, kind))
+            .or_default()
+            .insert(key.to_string(), value);
+        Ok(())
+    }
+    ///The attributes of the relation of the given kind from p1 to p2, if it has any
+    pub fn relation_attrs(&self, p1: &Person, p2: &Person, kind: Kind) -> Option<&Attributes> {
+        self.relation_attrs_of(self.idx(p1)?, self.idx(p2)?, kind)
+    }
+    fn relation_attrs_of(&self, p1: Nd, p2: Nd, kind: Kind) -> Option<&Attributes> {
+        self.relation_attrs.get(&Self::relation_key(p1, p2, kind))
+    }
+    ///Sets the primary name of a person, keeping their alternate names
+    pub fn set_name(&mut self, p: &Person, name: PersonName) -> Result<()> {
+        self.update_names(p, |names| {
+            if names.is_empty() {
+                names.push(name);
+            } else {
+                names[0] = name;
+            }
+        })
+    }
+    ///Adds another name a person is known by. If they have no primary name yet, it becomes it.
+    pub fn add_alternate_name(&mut self, p: &Person, name: PersonName) -> Result<()> {
+        self.update_names(p, |names| names.push(name))
+    }
+    //changes the names of a person, keeping the name index up to date
+    fn update_names(
+        &mut self,
+        p: &Person,
+        update: impl FnOnce(&mut Vec<PersonName>),
+    ) -> Result<()> {
+        let px = self.node(p)?;
+        let person = &mut self.graph[px];
+        self.name_index
+            .remove(px, person.name_words().collect::<Vec<_>>());
+        update(&mut person.names);
+        self.name_index
+            .insert(px, person.name_words().collect::<Vec<_>>());
+        Ok(())
+    }
+    ///The persons who have a name with every word of the query, ignoring case
+    pub fn find_by_name(&self, query: &str) -> Vec<&Person> {
+        self.search_names(query, NameMatch::Exact)
+    }
+    ///Searches the names of persons (including their short names), best matches first
+    pub fn search_names(&self, query: &str, mode: NameMatch) -> Vec<&Person> {
+        self.name_index
+            .search(query, mode)
+            .into_iter()
+            .map(|px| &self.graph[px])
+            .collect()
+    }
+    ///Sets (or clears) the date a person was born
+    pub fn set_birth(&mut self, p: &Person, date: Option<GenDate>) -> Result<()> {
+        let px = self.node(p)?;
+        self.graph[px].birth = date;
+        Ok(())
+    }
+    ///Sets (or clears) the date a person died
+    pub fn set_death(&mut self, p: &Person, date: Option<GenDate>) -> Result<()> {
+        let px = self.node(p)?;
+        self.graph[px].death = date;
+        Ok(())
+    }
     fn add_person(&mut self, p: &Person) {
         let id = p.id;
+        self.depth_map = None;
         let idx = self.graph.add_node(p.clone());
         self.id_indx.insert(id, idx);
+        self.name_index.insert(idx, p.name_words());
     }
     fn add_persons(&mut self, ps: &[&Person]) {
         for p in ps {
@@ -338,30 +825,27 @@ impl KinGraph {
     ///Adds a new person to the graph.
     fn np(&mut self, sex: Sex) -> Person {
         let p = Person::new(sex);
-        let id = p.id;
-        let idx = self.graph.add_node(p.clone());
-        self.id_indx.insert(id, idx);
+        self.add_person(&p);
         p
     }
     fn np_with_name(&mut self, sex: Sex, name: String) -> Person {
-        let p = Person {
-            id: 0,
-            name,
-            sex,
-            is_shadow: false,
-        };
+        let p = Person::new_with_id(sex, 0, name);
         //let id = p.id;
+        self.depth_map = None;
         let idx = self.graph.add_node(p);
         let person_mut = self.graph.node_weight_mut(idx).unwrap();
         person_mut.id = idx.index();
         self.id_indx.insert(idx.index(), idx);
-        person_mut.clone()
+        self.name_index
+            .insert(idx, self.graph[idx].name_words().collect::<Vec<_>>());
+        self.graph[idx].clone()
     }
     ///Adds kind between p1->p2, and kind^-1 (inverse kind) between p2->p1. If such an edge already exists between these nodes,
     /// it exits silently, not adding the edge
     fn add_edges(&mut self, p1: NodeIndex<usize>, p2: NodeIndex<usize>, kind: Kind) {
         let mut e = self.graph.edges_connecting(p1, p2);
         if !e.any(|e| *e.weight() == kind) && p1 != p2 {
+            self.depth_map = None;
             match kind {
                 Kind::Parent => {
                     self.graph.add_edge(p1, p2, Kind::Parent);
@@ -379,6 +863,10 @@ impl KinGraph {
                     self.graph.add_edge(p2, p1, Kind::RP);
                     self.graph.add_edge(p1, p2, Kind::RP)
                 }
+                _ => {
+                    self.graph.add_edge(p1, p2, kind);
+                    self.graph.add_edge(p2, p1, kind.get_inverse())
+                }
             };
         }
     }
@@ -390,6 +878,8 @@ impl KinGraph {
             Kind::Child => self.add_parent(p2, p1)?,
             Kind::RP => self.add_repat(p1, p2)?,
             Kind::Sibling => self.add_sibling(p1, p2)?,
+            Kind::Spouse => self.add_partnership(p1, p2, Partnership::default())?,
+            _ => self.add_custodial(p1, p2, kind)?,
         };
         Ok(())
     }
@@ -397,26 +887,59 @@ impl KinGraph {
         let mut persons = Vec::new();
         for p in self.graph.node_indices() {
             let mut relations = Vec::new();
+            let mut relation_attributes = BTreeMap::<u32, BTreeMap<_, _>>::new();
+            let (mut parentage, mut partnerships) = (BTreeMap::new(), BTreeMap::new());
             for e in self.graph.edges_directed(p, Direction::Outgoing) {
+                let id = e.target().index() as u32;
+                match e.weight() {
+                    Kind::Parent => {
+                        if let Some(pg) = self.parentage.get(&(p, e.target())) {
+                            parentage.insert(id, *pg);
+                        }
+                    }
+                    Kind::Spouse => {
+                        if let Some(partnership) = self.partnership_of(p, e.target()) {
+                            partnerships.insert(id, partnership.clone());
+                        }
+                    }
+                    _ => {}
+                }
+                if let Some(attrs) = self.relation_attrs_of(p, e.target(), *e.weight()) {
+                    relation_attributes
+                        .entry(e.target().index() as u32)
+                        .or_default()
+                        .insert((*e.weight()).into(), attrs.clone());
+                }
                 relations.push(kin_wasm::Relation {
                     id: e.target().index() as u32,
-                    kind: match e.weight() {
-                        Kind::Parent => kin_wasm::RelationKind::Parent,
-                        Kind::Child => kin_wasm::RelationKind::Child,
-                        Kind::RP => kin_wasm::RelationKind::RP,
-                        Kind::Sibling => kin_wasm::RelationKind::Sibling,
-                    },
+                    kind: (*e.weight()).into(),
                 });
             }
             let person_at = &self.graph[p];
-            persons.push(kin_wasm::PersonNode::new(
-                p.index() as u32,
-                person_at.sex,
-                person_at.name.clone(),
-                relations,
-            ));
+            persons.push(
+                kin_wasm::PersonNode::new(
+                    p.index() as u32,
+                    person_at.sex,
+                    person_at.name.clone(),
+                    relations,
+                )
+                .with_life_dates(person_at.birth, person_at.death)
+                .with_attributes(
+                    person_at.attributes.clone(),
+                    person_at.refs.clone(),
+                    relation_attributes,
+                )
+                .with_names(person_at.names.clone())
+                .with_shadow(person_at.is_shadow)
+                .with_family(parentage, partnerships),
+            );
         }
-        kin_wasm::KinWasmGraph::new(persons)
+        kin_wasm::KinWasmGraph::new(persons).with_calendar(self.calendar.as_custom().cloned())
+    }
+    ///Reads a graph from the layout of [KinGraph::as_wasm_graph], keeping the node ids as
+    /// person ids. Every relation must be listed by both of the nodes it is between.
+    pub fn from_wasm_graph(graph: kin_wasm::KinWasmGraph) -> Result<KinGraph> {
+        KinGraph::from_document(serialize::migrate_v1(graph)?)
     }
     ///Make c a child of both p1, and p2.
     pub fn make_child(&mut self, c: &Person, p1: &Person, p2: &Person) -> Result<()> {
@@ -426,56 +949,278 @@ impl KinGraph {
     }
 
     fn add_parent(&mut self, p: &Person, c: &Person) -> Result<()> {
-        let px = self.idx(p).unwrap();
-        let cx = self.idx(c).unwrap();
-        //get the number of parents the child has
-        let parents = self
-            .graph
-            .edges_directed(cx, Direction::Outgoing)
-            .filter(|e| *e.weight() == Kind::Child)
-            .collect::<Vec<_>>();
+        self.add_parent_with(p, c, Parentage::default())
+    }
+    ///Makes p a parent of c, recording whether p is a biological parent, a legal/social parent or both.
+    /// A child can have at most two biological and two legal parents.
+    pub fn add_parent_with(&mut self, p: &Person, c: &Person, parentage: Parentage) -> Result<()> {
+        let px = self.node(p)?;
+        let cx = self.node(c)?;
+        self.check_parent(px, cx, parentage)?;
+        if parentage.biological {
+            //We don't have to check if we already are an RP because add edge takes care of that already
+            //make ourselves the RP of the other biological parent
+            for other_p in self.bio_parents_of(cx).into_iter().filter(|q| *q != px) {
+                self.add_edges(px, other_p, Kind::RP);
+            }
+        }
+        //make ourselves the parent
+        self.add_edges(px, cx, Kind::Parent);
+        self.parentage.insert((px, cx), parentage);
 
-        let plen = parents.len();
+        Ok(())
+    }
+    ///Checks that p can be made a parent of c with the given parentage, see
+    /// [KinGraph::add_parent_with]
+    fn check_parent(&self, px: Nd, cx: Nd, parentage: Parentage) -> Result<()> {
+        if px == cx {
+            return Err(KinError::SelfCycle { index: px.index() }.into());
+        }
         //make sure p1 is not already the child of p2
-        if self.is_parent(cx, px) {
+        if self.is_parent(cx, px) || !(parentage.biological || parentage.legal) {
             return Err(KinError::InvalidRelation {
                 p1: px.index(),
                 p2: cx.index(),
             }
             .into());
         }
-        if plen >= 2 {
+        //get how the child's other parents are parents
+        let parents = self
+            .parents_of(cx)
+            .into_iter()
+            .filter(|q| *q != px)
+            .map(|q| self.parentage_of(q, cx))
+            .collect::<Vec<_>>();
+        let bio_len = parents.iter().filter(|pg| pg.biological).count();
+        let legal_len = parents.iter().filter(|pg| pg.legal).count();
+        if (parentage.biological && bio_len >= 2) || (parentage.legal && legal_len >= 2) {
             //don't add too many parents
             return Err(KinError::AlreadyTwoParents { p: cx.index() }.into());
-        } else {
-            if plen == 1 {
-                //We don't have to check if we already are an RP because add edge takes care of that already
-                //make ourselves the RP of the other parent
-                let other_p = parents[0].target();
-                self.add_edges(px, other_p, Kind::RP);
-            }
-            //make ourselves the parent
-            self.add_edges(px, cx, Kind::Parent);
         }
-
         Ok(())
     }
+    ///Gets how p is a parent of c, or None if p is not a parent of c
+    pub fn get_parentage(&self, p: &Person, c: &Person) -> Option<Parentage> {
+        let px = self.idx(p)?;
+        let cx = self.idx(c)?;
+        if self.is_parent(px, cx) {
+            Some(self.parentage_of(px, cx))
+        } else {
+            None
+        }
+    }
+    fn parentage_of(&self, p: Nd, c: Nd) -> Parentage {
+        self.parentage.get(&(p, c)).copied().unwrap_or_default()
+    }
+    ///Whether the edge from a to b of the given kind is a biological one. Only parent/child edges
+    /// can be non-biological
+    fn is_biological_edge(&self, a: Nd, b: Nd, kind: Kind) -> bool {
+        match kind {
+            Kind::Parent => self.parentage_of(a, b).biological,
+            Kind::Child => self.parentage_of(b, a).biological,
+            _ => true,
+        }
+    }
+    ///Gets only the biological parents of a person
+    fn bio_parents_of(&self, p: Nd) -> Vec<Nd> {
+        self.parents_of(p)
+            .into_iter()
+            .filter(|q| self.parentage_of(*q, p).biological)
+            .collect()
+    }
 
     fn add_sibling(&mut self, p1: &Person, p2: &Person) -> Result<()> {
-        self.add_edges(self.idx(p1).unwrap(), self.idx(p2).unwrap(), Kind::Sibling);
+        self.add_edges(self.node(p1)?, self.node(p2)?, Kind::Sibling);
         Ok(())
     }
     fn add_repat(&mut self, p1: &Person, p2: &Person) -> Result<()> {
-        self.add_edges(self.idx(p1).unwrap(), self.idx(p2).unwrap(), Kind::RP);
+        self.add_edges(self.node(p1)?, self.node(p2)?, Kind::RP);
+        Ok(())
+    }
+    ///Makes p1 and p2 spouses (or partners), described by the given partnership. If they already are,
+    /// the partnership is replaced.
+    pub fn add_partnership(
+        &mut self,
+        p1: &Person,
+        p2: &Person,
+        partnership: Partnership,
+    ) -> Result<()> {
+        let p1x = self.node(p1)?;
+        let p2x = self.node(p2)?;
+        if p1x == p2x {
+            return Err(KinError::SelfCycle { index: p1x.index() }.into());
+        }
+        self.add_edges(p1x, p2x, Kind::Spouse);
+        self.partnerships
+            .insert(Self::pair_key(p1x, p2x), partnership);
+        Ok(())
+    }
+    ///Gets the partnership between two spouses, if they are spouses
+    pub fn get_partnership(&self, p1: &Person, p2: &Person) -> Option<&Partnership> {
+        self.partnership_of(self.idx(p1)?, self.idx(p2)?)
+    }
+    fn partnership_of(&self, p1: Nd, p2: Nd) -> Option<&Partnership> {
+        self.partnerships.get(&Self::pair_key(p1, p2))
+    }
+    //key for relations that are symmetric, so that both directions share an entry
+    fn pair_key(p1: Nd, p2: Nd) -> (Nd, Nd) {
+        if p1 <= p2 {
+            (p1, p2)
+        } else {
+            (p2, p1)
+        }
+    }
+    //key for a relation of the kind from p1 to p2, so that both directions share an entry: the
+    //ordered pair, and the kind of the relation from the first of the pair to the second
+    fn relation_key(p1: Nd, p2: Nd, kind: Kind) -> ((Nd, Nd), Kind) {
+        if p1 <= p2 {
+            ((p1, p2), kind)
+        } else {
+            ((p2, p1), kind.get_inverse())
+        }
+    }
+    ///Adds an adoptive, foster or guardian relation, where p1 is the one doing the caring when
+    /// kind is one of the parent-like kinds.
+    fn add_custodial(&mut self, p1: &Person, p2: &Person, kind: Kind) -> Result<()> {
+        let p1x = self.node(p1)?;
+        let p2x = self.node(p2)?;
+        self.check_custodial(p1x, p2x, kind)?;
+        self.add_edges(p1x, p2x, kind);
+        Ok(())
+    }
+    ///Checks that an adoptive, foster or guardian relation of the kind can be added from p1 to p2
+    fn check_custodial(&self, p1x: Nd, p2x: Nd, kind: Kind) -> Result<()> {
+        if p1x == p2x {
+            return Err(KinError::SelfCycle { index: p1x.index() }.into());
+        }
+        //p2 can't already hold the inverse role towards p1
+        if self
+            .graph
+            .edges_connecting(p2x, p1x)
+            .any(|e| *e.weight() == kind)
+        {
+            return Err(KinError::InvalidRelation {
+                p1: p1x.index(),
+                p2: p2x.index(),
+            }
+            .into());
+        }
         Ok(())
     }
+    ///Gets the (legal or biological) parents of a person
+    fn parents_of(&self, p: Nd) -> Vec<Nd> {
+        self.graph
+            .edges_directed(p, Outgoing)
+            .filter(|e| *e.weight() == Kind::Child)
+            .map(|e| e.target())
+            .collect()
+    }
+    ///Gets the (legal or biological) children of a person
+    fn children_of(&self, p: Nd) -> Vec<Nd> {
+        self.graph
+            .edges_directed(p, Outgoing)
+            .filter(|e| *e.weight() == Kind::Parent)
+            .map(|e| e.target())
+            .collect()
+    }
+    //orders two persons by birth, with those whose birth is not known after the others
+    fn birth_order(&self, a: Nd, b: Nd) -> std::cmp::Ordering {
+        match (&self.graph[a].birth, &self.graph[b].birth) {
+            (Some(a), Some(b)) => self.calendar.compare(a, b),
+            (a, b) => b.is_some().cmp(&a.is_some()),
+        }
+    }
+    ///The generation of each person by index, counting down from parents to children. Every
+    /// child is in a later generation than each of their parents, and the earliest generation of
+    /// each group of related persons is 0. Partners, siblings and spouses share a generation where
+    /// that keeps them above their own children, which with pedigree collapse it may not. Adoptive,
+    /// foster and guardian relations count as parent ones.
+    pub fn generations(&self) -> Vec<i32> {
+        let children = |n: Nd| {
+            self.graph
+                .edges_directed(n, Outgoing)
+                .filter(|e| {
+                    matches!(
+                        e.weight(),
+                        Kind::Parent | Kind::AdoptiveParent | Kind::FosterParent | Kind::Guardian
+                    )
+                })
+                .map(|e| e.target())
+                .collect::<Vec<_>>()
+        };
+        //the longest path down to each person from someone without parents
+        let mut generations = vec![0; self.graph.node_count()];
+        let mut parents = vec![0; self.graph.node_count()];
+        for n in self.graph.node_indices() {
+            for c in children(n) {
+                parents[c.index()] += 1;
+            }
+        }
+        let roots = self
+            .graph
+            .node_indices()
+            .filter(|n| parents[n.index()] == 0)
+            .collect::<Vec<_>>();
+        let mut queue = VecDeque::from(roots.clone());
+        while let Some(n) = queue.pop_front() {
+            for c in children(n) {
+                generations[c.index()] = generations[c.index()].max(generations[n.index()] + 1);
+                parents[c.index()] -= 1;
+                if parents[c.index()] == 0 {
+                    queue.push_back(c);
+                }
+            }
+        }
+        //the generation after which no child of the person is
+        let latest = |n: Nd, generations: &[i32]| {
+            children(n)
+                .iter()
+                .map(|c| generations[c.index()] - 1)
+                .min()
+                .unwrap_or(i32::MAX)
+        };
+        //persons without parents are drawn down to their children, who are already placed
+        for n in roots {
+            let below = latest(n, &generations);
+            if below != i32::MAX {
+                generations[n.index()] = below;
+            }
+        }
+        //partners and siblings move down to each other as long as their children stay below them,
+        //which can only happen so many times
+        let mut moved = true;
+        while moved {
+            moved = false;
+            for e in self.graph.edge_references() {
+                if !matches!(e.weight(), Kind::Sibling | Kind::RP | Kind::Spouse) {
+                    continue;
+                }
+                let (n, m) = (e.source(), e.target());
+                let to = generations[m.index()];
+                if generations[n.index()] < to && to <= latest(n, &generations) {
+                    generations[n.index()] = to;
+                    moved = true;
+                }
+            }
+        }
+        generations
+    }
     ///Calculates relationship between two persons.
     pub fn get_canonical_relationships(
         &self,
         p1: &Person,
         p2: &Person,
     ) -> Result<Vec<Box<dyn State>>> {
-        if p1 == p2 {
+        self.get_canonical_relationships_with(p1, p2, &QueryOptions::default())
+    }
+    ///Calculates relationship between two persons, using the given [QueryOptions].
+    pub fn get_canonical_relationships_with(
+        &self,
+        p1: &Person,
+        p2: &Person,
+        opts: &QueryOptions,
+    ) -> Result<Vec<Box<dyn State>>> {
+        if p1.id == p2.id {
             return Ok(vec![Box::new(StopState {})]);
         }
         let paths = self.find_all_paths(p1, p2)?;
@@ -483,9 +1228,46 @@ impl KinGraph {
             println!("No paths found between {:?} and {:?}", p1, p2);
             return Ok(vec![Box::new(StopState {})]);
         }
+        let p1x = self.node(p1)?;
+        let p2x = self.node(p2)?;
         let mut names = HashSet::new();
         for p in paths {
-            names.insert(self.calculate_cr_single_path(self.idx(p1).unwrap(), &p)?);
+            if !self.path_in_view(p2x, &p, opts.parentage) {
+                continue;
+            }
+            //the status, on the as of date, of every partnership the path goes through
+            let mut statuses = Vec::new();
+            if let Some(date) = opts.as_of {
+                statuses = self.path_partnership_statuses(p2x, &p, date);
+                if statuses.iter().any(|s| s.is_none()) {
+                    continue;
+                }
+            }
+            let p = if opts.adoptive_as_blood {
+                p.into_iter()
+                    .map(|(n, k)| match k {
+                        Kind::AdoptiveParent => (n, Kind::Parent),
+                        Kind::AdoptiveChild => (n, Kind::Child),
+                        _ => (n, k),
+                    })
+                    .collect()
+            } else {
+                p
+            };
+            let mut state = self.calculate_cr_single_path(p1x, p2x, &p)?;
+            if let Some(spouse) = state.get_any().downcast_ref::<SpouseState>() {
+                if let [Some(status)] = statuses[..] {
+                    state = Box::new(SpouseState {
+                        sex: spouse.sex,
+                        status,
+                    });
+                }
+            } else if statuses.contains(&Some(MaritalStatus::Divorced))
+                && !state.get_any().is::<StopState>()
+            {
+                state = Box::new(FormerState { inner: state });
+            }
+            names.insert(state);
         }
         let names = names
             .into_iter()
@@ -495,8 +1277,60 @@ impl KinGraph {
         Ok(names)
     }
 
+    ///Calculates the relationships between two persons as they were on the given date, along with
+    /// whether each of them was alive on that date.
+    pub fn get_canonical_relationships_as_of(
+        &self,
+        p1: &Person,
+        p2: &Person,
+        date: Date,
+    ) -> Result<AsOfRelationships> {
+        let opts = QueryOptions {
+            as_of: Some(date),
+            ..Default::default()
+        };
+        Ok(AsOfRelationships {
+            relationships: self.get_canonical_relationships_with(p1, p2, &opts)?,
+            p1_status: self.graph[self.node(p1)?].life_status(date, self.calendar()),
+            p2_status: self.graph[self.node(p2)?].life_status(date, self.calendar()),
+        })
+    }
+    ///Gets the status on the given date of each partnership along the path (ending at p2)
+    fn path_partnership_statuses(
+        &self,
+        p2: Nd,
+        path: &[(Nd, Kind)],
+        date: Date,
+    ) -> Vec<Option<MaritalStatus>> {
+        path.iter()
+            .enumerate()
+            .filter(|(_, (_, kind))| *kind == Kind::Spouse)
+            .filter_map(|(i, (source, _))| {
+                let target = path.get(i + 1).map(|n| n.0).unwrap_or(p2);
+                let partnership = self.partnership_of(*source, target)?;
+                let deaths = [self.graph[*source].death, self.graph[target].death];
+                Some(partnership.status_as_of(date, deaths, self.calendar()))
+            })
+            .collect()
+    }
+    ///Whether every parent/child edge of the path (ending at p2) is included in the view
+    fn path_in_view(&self, p2: Nd, path: &[(Nd, Kind)], view: ParentageView) -> bool {
+        path.iter().enumerate().all(|(i, (source, kind))| {
+            let target = path.get(i + 1).map(|n| n.0).unwrap_or(p2);
+            match kind {
+                Kind::Parent => view.includes(self.parentage_of(*source, target)),
+                Kind::Child => view.includes(self.parentage_of(target, *source)),
+                _ => true,
+            }
+        })
+    }
     ///Calculates canonical relationship given a kind path.
-    fn calculate_cr_single_path(&self, p1: Nd, path: &Vec<(Nd, Kind)>) -> Result<Box<dyn State>> {
+    fn calculate_cr_single_path(
+        &self,
+        p1: Nd,
+        p2: Nd,
+        path: &Vec<(Nd, Kind)>,
+    ) -> Result<Box<dyn State>> {
         let mut sm = StateMachine::new();
         let mut cur_idx = p1;
 
@@ -507,7 +1341,7 @@ impl KinGraph {
                 return Ok(sm.get_current_state());
             }
         }
-        Ok(sm.get_current_state())
+        Ok(sm.get_current_state().finalize(p1, p2, self))
     }
 
     ///Finds whether a person is related by blood to another
@@ -532,6 +1366,10 @@ impl KinGraph {
                         return acc + RPRIME;
                     };
                     let edge1 = edge1.unwrap();
+                    //a legal-only parent does not make a blood path
+                    if !self.is_biological_edge(edge1.source(), edge1.target(), *edge1.weight()) {
+                        return acc + RPRIME;
+                    }
                     return acc + edge1.weight().get_prime();
                 });
 
@@ -541,10 +1379,62 @@ impl KinGraph {
         println!("Result {:?}", res);
         res
     }
-    fn is_repart(&self, p1: Nd, p2: Nd) -> bool {
-        self.graph
-            .edges_connecting(p1, p2)
-            .any(|e| *e.weight() == Kind::RP)
+    ///Calculates Wright's coefficient of relationship between two persons, following only
+    /// biological parentage. Parents and children, or full siblings, have a coefficient of 0.5.
+    pub fn relationship_coefficient(&self, p1: &Person, p2: &Person) -> f64 {
+        let p1x = self.idx(p1).unwrap();
+        let p2x = self.idx(p2).unwrap();
+        let mut memo = HashMap::new();
+        let kinship = self.kinship(p1x, p2x, &mut memo);
+        let f1 = self.inbreeding(p1x, &mut memo);
+        let f2 = self.inbreeding(p2x, &mut memo);
+        2.0 * kinship / ((1.0 + f1) * (1.0 + f2)).sqrt()
+    }
+    ///The inbreeding coefficient of a person, which is the kinship of their biological parents
+    fn inbreeding(&self, p: Nd, memo: &mut HashMap<(Nd, Nd), f64>) -> f64 {
+        match self.bio_parents_of(p)[..] {
+            [a, b] => self.kinship(a, b, memo),
+            _ => 0.0,
+        }
+    }
+    ///The kinship coefficient: the probability that an allele picked at random from each of the
+    /// two persons is identical by descent.
+    fn kinship(&self, a: Nd, b: Nd, memo: &mut HashMap<(Nd, Nd), f64>) -> f64 {
+        if let Some(k) = memo.get(&(a, b)) {
+            return *k;
+        }
+        //guard against (ill-formed) cycles of parentage
+        memo.insert((a, b), 0.0);
+        let k = if a == b {
+            0.5 * (1.0 + self.inbreeding(a, memo))
+        } else {
+            //recurse on whichever of the two is not an ancestor of the other
+            let (x, y) = if self.is_bio_ancestor(a, b) {
+                (b, a)
+            } else {
+                (a, b)
+            };
+            self.bio_parents_of(x)
+                .into_iter()
+                .map(|q| 0.5 * self.kinship(q, y, memo))
+                .sum()
+        };
+        memo.insert((a, b), k);
+        k
+    }
+    ///Whether a is a biological ancestor of b
+    fn is_bio_ancestor(&self, a: Nd, b: Nd) -> bool {
+        let mut visited = HashSet::new();
+        let mut stack = self.bio_parents_of(b);
+        while let Some(q) = stack.pop() {
+            if q == a {
+                return true;
+            }
+            if visited.insert(q) {
+                stack.extend(self.bio_parents_of(q));
+            }
+        }
+        false
     }
     ///Finds all paths between two people, with an internal maximum of the order of the graph
     pub fn find_all_paths(
@@ -552,8 +1442,8 @@ impl KinGraph {
         p1: &Person,
         p2: &Person,
     ) -> Result<Vec<Vec<(NodeIndex<usize>, Kind)>>> {
-        let p1x = self.idx(p1).unwrap();
-        let goalx = self.idx(p2).unwrap();
+        let p1x = self.node(p1)?;
+        let goalx = self.node(p2)?;
         //make sure that p1x!=goalx
         if p1x == goalx {
             return Ok(vec![vec![]]);
@@ -698,6 +1588,12 @@ impl KinGraph {
     fn idx(&self, p: &Person) -> Option<NodeIndex<usize>> {
         self.id_indx.get(&p.id).cloned()
     }
+    ///Get NodeIndex from person, failing if they are not in the graph, such as a handle kept from
+    /// before the graph was replaced
+    fn node(&self, p: &Person) -> Result<Nd> {
+        self.idx(p)
+            .ok_or_else(|| KinError::MissingNode { id: p.id as u32 }.into())
+    }
 }
 #[cfg(test)]
 mod test_kin;
@@ -0,0 +1,393 @@
+//! Export of the graph as linked data: JSON-LD with the schema.org vocabulary, and RDF Turtle
+//! with the FOAF, BIO and RELATIONSHIP vocabularies.
+//!
+//! Relations that these vocabularies have no terms for, like foster parents and guardians, are
+//! written with terms of the [LinkedDataOptions::vocabulary] namespace, as are the canonical
+//! relationships between persons when they are asked for. Dates are converted to the Gregorian
+//! calendar, and written as typed literals when they are exact.
+use crate::{convert, Calendar, GenDate, Gregorian, KinGraph, Kind, MaritalStatus, QueryOptions};
+use crate::{Nd, Sex};
+use anyhow::Result;
+use petgraph::visit::EdgeRef;
+use petgraph::Direction::Outgoing;
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+
+const SCHEMA: &str = "https://schema.org/";
+const PREFIXES: [(&str, &str); 4] = [
+    ("bio", "http://purl.org/vocab/bio/0.1/"),
+    ("foaf", "http://xmlns.com/foaf/0.1/"),
+    ("rel", "http://purl.org/vocab/relationship/"),
+    ("xsd", "http://www.w3.org/2001/XMLSchema#"),
+];
+
+///Options for [KinGraph::to_json_ld] and [KinGraph::to_turtle]
+#[derive(Clone, Debug)]
+pub struct LinkedDataOptions {
+    ///The IRI of a person is this followed by the index of the person in the graph
+    pub base: String,
+    ///The namespace of the terms for what the standard vocabularies have none for
+    pub vocabulary: String,
+    ///Also write the canonical relationships between every two related persons, named with
+    /// these options. This finds the paths between all pairs, so is slow for large graphs.
+    pub derived: Option<QueryOptions>,
+}
+impl Default for LinkedDataOptions {
+    fn default() -> Self {
+        LinkedDataOptions {
+            base: "urn:kin:person:".into(),
+            vocabulary: "urn:kin:vocab#".into(),
+            derived: None,
+        }
+    }
+}
+
+//the term of the kin vocabulary for a relation, read as "source is <term> target"
+fn kin_term(kind: Kind) -> &'static str {
+    match kind {
+        Kind::Parent => "parentOf",
+        Kind::Child => "childOf",
+        Kind::Sibling => "siblingOf",
+        Kind::RP => "reproductivePartnerOf",
+        Kind::AdoptiveParent => "adoptiveParentOf",
+        Kind::AdoptiveChild => "adoptiveChildOf",
+        Kind::FosterParent => "fosterParentOf",
+        Kind::FosterChild => "fosterChildOf",
+        Kind::Guardian => "guardianOf",
+        Kind::Ward => "wardOf",
+        Kind::Spouse => "spouseOf",
+    }
+}
+
+//a date as a Gregorian XML Schema literal, with the datatype for exact dates
+fn xsd_date(date: &GenDate) -> (String, Option<&'static str>) {
+    match date {
+        GenDate::Exact(d) | GenDate::Calculated(d) if (1..=9999).contains(&d.year) => {
+            match (d.month, d.day) {
+                (Some(m), Some(day)) => {
+                    (format!("{:04}-{:02}-{:02}", d.year, m, day), Some("date"))
+                }
+                (Some(m), None) => (format!("{:04}-{:02}", d.year, m), Some("gYearMonth")),
+                _ => (format!("{:04}", d.year), Some("gYear")),
+            }
+        }
+        _ => (Gregorian.format(date), None),
+    }
+}
+
+//a string as a Turtle literal
+fn literal(s: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => escaped += "\\\"",
+            '\\' => escaped += "\\\\",
+            '\n' => escaped += "\\n",
+            '\r' => escaped += "\\r",
+            '\t' => escaped += "\\t",
+            _ => escaped.push(c),
+        }
+    }
+    escaped + "\""
+}
+
+//a subject with its predicates and objects as a Turtle statement
+fn statement(subject: &str, predicates: &[(String, String)]) -> String {
+    let predicates = predicates
+        .iter()
+        .map(|(p, o)| format!("{} {}", p, o))
+        .collect::<Vec<_>>();
+    format!("{} {} .\n", subject, predicates.join(" ;\n    "))
+}
+
+impl KinGraph {
+    //a date of the graph converted to the Gregorian calendar
+    fn gregorian(&self, date: GenDate) -> GenDate {
+        match self.calendar.name() == Gregorian.name() {
+            true => date,
+            false => date.map(|d| convert(d, self.calendar(), &Gregorian)),
+        }
+    }
+    //the persons that are siblings of a person, either through a sibling relation or by sharing
+    // a parent
+    fn siblings_of(&self, n: Nd) -> BTreeSet<Nd> {
+        let mut siblings = BTreeSet::new();
+        for e in self.graph.edges_directed(n, Outgoing) {
+            match e.weight() {
+                Kind::Sibling => {
+                    siblings.insert(e.target());
+                }
+                Kind::Child => siblings.extend(
+                    self.graph
+                        .edges_directed(e.target(), Outgoing)
+                        .filter(|c| *c.weight() == Kind::Parent)
+                        .map(|c| c.target()),
+                ),
+                _ => {}
+            }
+        }
+        siblings.remove(&n);
+        siblings
+    }
+    //the names of the canonical relationships from each person to each other person they are
+    // related to, as in "source is <name> of target"
+    fn derived_relationships(
+        &self,
+        opts: &QueryOptions,
+    ) -> Result<BTreeMap<(Nd, Nd), Vec<String>>> {
+        let mut derived = BTreeMap::new();
+        for mut component in petgraph::algo::tarjan_scc(&self.graph) {
+            component.sort();
+            for &a in &component {
+                for &b in component.iter().filter(|b| **b != a) {
+                    let states = self.get_canonical_relationships_with(
+                        &self.graph[a],
+                        &self.graph[b],
+                        opts,
+                    )?;
+                    let names = states
+                        .iter()
+                        .map(|s| s.print_canonical_name())
+                        .collect::<BTreeSet<_>>();
+                    if !names.is_empty() {
+                        derived.insert((a, b), names.into_iter().collect());
+                    }
+                }
+            }
+        }
+        Ok(derived)
+    }
+    ///Writes the graph as JSON-LD, with each person a schema.org `Person` linked to its `parent`,
+    /// `children`, `sibling` and `spouse`. Adoptive parents are also schema.org parents.
+    pub fn to_json_ld(&self, opts: &LinkedDataOptions) -> Result<String> {
+        let id = |n: Nd| format!("{}{}", opts.base, n.index());
+        let iri = |n: Nd| json!({ "@id": id(n) });
+        let derived = match &opts.derived {
+            Some(query) => self.derived_relationships(query)?,
+            None => BTreeMap::new(),
+        };
+        let mut persons = Vec::new();
+        for n in self.graph.node_indices() {
+            let p = &self.graph[n];
+            let mut person = Map::new();
+            person.insert("@id".into(), id(n).into());
+            person.insert("@type".into(), "Person".into());
+            person.insert("name".into(), p.name.clone().into());
+            let gender = match p.sex {
+                Sex::Male => "Male",
+                Sex::Female => "Female",
+            };
+            person.insert(
+                "gender".into(),
+                json!({ "@id": format!("schema:{}", gender) }),
+            );
+            if let Some(name) = p.primary_name() {
+                if !name.given.is_empty() {
+                    person.insert("givenName".into(), name.given.join(" ").into());
+                }
+                if let Some(surname) = &name.surname {
+                    person.insert("familyName".into(), surname.clone().into());
+                }
+            }
+            let alternate = p.alternate_names().iter().map(|n| n.to_string());
+            let nicknames = p.names.iter().flat_map(|n| n.nicknames.iter().cloned());
+            let alternate = alternate.chain(nicknames).collect::<Vec<_>>();
+            if !alternate.is_empty() {
+                person.insert("alternateName".into(), alternate.into());
+            }
+            for (key, date) in [("birthDate", p.birth), ("deathDate", p.death)] {
+                if let Some(date) = date {
+                    person.insert(key.into(), xsd_date(&self.gregorian(date)).0.into());
+                }
+            }
+            let identifiers = p.refs.iter().map(|(system, id)| {
+                json!({ "@type": "PropertyValue", "propertyID": system, "value": id })
+            });
+            let identifiers = identifiers.collect::<Vec<_>>();
+            if !identifiers.is_empty() {
+                person.insert("identifier".into(), identifiers.into());
+            }
+
+            let mut links: BTreeMap<String, Vec<Value>> = BTreeMap::new();
+            let mut edges = self.graph.edges_directed(n, Outgoing).collect::<Vec<_>>();
+            edges.sort_by_key(|e| e.target());
+            for e in edges {
+                let kind = *e.weight();
+                let key = match kind {
+                    Kind::Child | Kind::AdoptiveChild => Some("parent"),
+                    Kind::Parent | Kind::AdoptiveParent => Some("children"),
+                    Kind::Spouse => Some("spouse"),
+                    _ => None,
+                };
+                if let Some(key) = key {
+                    links.entry(key.into()).or_default().push(iri(e.target()));
+                }
+                if !matches!(
+                    kind,
+                    Kind::Parent | Kind::Child | Kind::Sibling | Kind::Spouse
+                ) {
+                    let key = format!("kin:{}", kin_term(kind));
+                    links.entry(key).or_default().push(iri(e.target()));
+                }
+            }
+            let siblings = self.siblings_of(n).into_iter().map(iri).collect::<Vec<_>>();
+            if !siblings.is_empty() {
+                links.insert("sibling".into(), siblings);
+            }
+            let relationships = derived
+                .range((n, Nd::new(0))..)
+                .take_while(|((a, _), _)| *a == n)
+                .flat_map(|((_, b), names)| {
+                    names.iter().map(move |name| {
+                        json!({ "@type": "kin:Relationship", "kin:name": name, "kin:of": iri(*b) })
+                    })
+                })
+                .collect::<Vec<_>>();
+            if !relationships.is_empty() {
+                links.insert("kin:relationship".into(), relationships);
+            }
+            person.extend(links.into_iter().map(|(k, v)| (k, v.into())));
+            persons.push(Value::Object(person));
+        }
+        let document = json!({
+            "@context": { "@vocab": SCHEMA, "schema": SCHEMA, "kin": opts.vocabulary },
+            "@graph": persons,
+        });
+        Ok(serde_json::to_string_pretty(&document)?)
+    }
+    ///Writes the graph as RDF Turtle, with each person a FOAF `Person` with BIO births, deaths,
+    /// parents and marriages. Siblings, spouses and parents who are not biological ones are
+    /// linked with the RELATIONSHIP vocabulary.
+    pub fn to_turtle(&self, opts: &LinkedDataOptions) -> Result<String> {
+        let iri = |n: Nd| format!("<{}{}>", opts.base, n.index());
+        let derived = match &opts.derived {
+            Some(query) => self.derived_relationships(query)?,
+            None => BTreeMap::new(),
+        };
+        let mut turtle = String::new();
+        for (prefix, namespace) in PREFIXES {
+            turtle += &format!("@prefix {}: <{}> .\n", prefix, namespace);
+        }
+        turtle += &format!("@prefix kin: <{}> .\n", opts.vocabulary);
+        let date_literal = |date: GenDate| match xsd_date(&self.gregorian(date)) {
+            (value, Some(datatype)) => format!("{}^^xsd:{}", literal(&value), datatype),
+            (value, None) => literal(&value),
+        };
+        for n in self.graph.node_indices() {
+            let p = &self.graph[n];
+            let mut predicates = vec![
+                ("a".to_string(), "foaf:Person".to_string()),
+                ("foaf:name".into(), literal(&p.name)),
+            ];
+            let gender = match p.sex {
+                Sex::Male => "male",
+                Sex::Female => "female",
+            };
+            predicates.push(("foaf:gender".into(), literal(gender)));
+            if let Some(name) = p.primary_name() {
+                if !name.given.is_empty() {
+                    predicates.push(("foaf:givenName".into(), literal(&name.given.join(" "))));
+                }
+                if let Some(surname) = &name.surname {
+                    predicates.push(("foaf:familyName".into(), literal(surname)));
+                }
+            }
+            for nickname in p.names.iter().flat_map(|n| &n.nicknames) {
+                predicates.push(("foaf:nick".into(), literal(nickname)));
+            }
+            for (event, date) in [("Birth", p.birth), ("Death", p.death)] {
+                if let Some(date) = date {
+                    let object = format!(
+                        "[ a bio:{} ; bio:principal {} ; bio:date {} ]",
+                        event,
+                        iri(n),
+                        date_literal(date)
+                    );
+                    predicates.push((format!("bio:{}", event.to_lowercase()), object));
+                }
+            }
+            let mut edges = self.graph.edges_directed(n, Outgoing).collect::<Vec<_>>();
+            edges.sort_by_key(|e| e.target());
+            for e in edges {
+                let biological =
+                    |parent: Nd, child: Nd| self.parentage_of(parent, child).biological;
+                let predicate = match *e.weight() {
+                    Kind::Parent if biological(n, e.target()) => "bio:child".into(),
+                    Kind::Child if biological(e.target(), n) => match self.graph[e.target()].sex {
+                        Sex::Male => "bio:father".into(),
+                        Sex::Female => "bio:mother".into(),
+                    },
+                    Kind::Parent => "rel:parentOf".into(),
+                    Kind::Child => "rel:childOf".into(),
+                    Kind::Spouse => "rel:spouseOf".into(),
+                    Kind::Sibling => continue,
+                    kind => format!("kin:{}", kin_term(kind)),
+                };
+                predicates.push((predicate, iri(e.target())));
+            }
+            for sibling in self.siblings_of(n) {
+                predicates.push(("rel:siblingOf".into(), iri(sibling)));
+            }
+            for ((_, b), names) in derived
+                .range((n, Nd::new(0))..)
+                .take_while(|((a, _), _)| *a == n)
+            {
+                for name in names {
+                    let object = format!(
+                        "[ a kin:Relationship ; kin:name {} ; kin:of {} ]",
+                        literal(name),
+                        iri(*b)
+                    );
+                    predicates.push(("kin:relationship".into(), object));
+                }
+            }
+            turtle += "\n";
+            turtle += &statement(&iri(n), &predicates);
+        }
+        //the marriages and divorces of spouses, once for each couple
+        for e in self.graph.edge_references() {
+            let (a, b) = (e.source(), e.target());
+            if *e.weight() != Kind::Spouse || a > b {
+                continue;
+            }
+            let partnership = self.partnership_of(a, b).cloned().unwrap_or_default();
+            let partners = format!("{}, {}", iri(a), iri(b));
+            let mut events = Vec::new();
+            if partnership.status != MaritalStatus::Partnered {
+                events.push(("Marriage", partnership.start));
+            }
+            if partnership.status == MaritalStatus::Divorced {
+                events.push(("Divorce", partnership.end));
+            }
+            for (event, date) in events {
+                let mut predicates = vec![
+                    ("a".to_string(), format!("bio:{}", event)),
+                    ("bio:partner".into(), partners.clone()),
+                ];
+                if let Some(date) = date {
+                    predicates.push(("bio:date".into(), date_literal(date)));
+                }
+                turtle += "\n";
+                turtle += &statement("[]", &predicates);
+            }
+        }
+        Ok(turtle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Date;
+
+    #[test]
+    fn literals() {
+        assert_eq!(literal("a \"b\"\n\\"), r#""a \"b\"\n\\""#);
+        let exact = GenDate::Exact(Date::ymd(1850, 3, 2));
+        assert_eq!(xsd_date(&exact), ("1850-03-02".into(), Some("date")));
+        let month = GenDate::Calculated(Date::ym(1850, 3));
+        assert_eq!(xsd_date(&month), ("1850-03".into(), Some("gYearMonth")));
+        let about = GenDate::About(Date::year(1850));
+        assert_eq!(xsd_date(&about), (Gregorian.format(&about), None));
+    }
+}
@@ -0,0 +1,246 @@
+//! Structured personal names, and an index for finding persons by them.
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::str::FromStr;
+use wasm_bindgen::prelude::wasm_bindgen;
+
+///The name given to persons that were not named
+pub const UNKNOWN_NAME: &str = "unkn";
+
+///A name of a person, split into its parts
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Default, Debug, Serialize, Deserialize)]
+pub struct PersonName {
+    #[serde(default)]
+    pub given: Vec<String>,
+    #[serde(default)]
+    pub surname: Option<String>,
+    ///The surname the person was born with, if it differs from their surname
+    #[serde(default)]
+    pub birth_surname: Option<String>,
+    #[serde(default)]
+    pub nicknames: Vec<String>,
+    ///Titles such as "Dr" or "Sir", written before the name
+    #[serde(default)]
+    pub titles: Vec<String>,
+}
+impl PersonName {
+    pub fn new(given: &[&str], surname: Option<&str>) -> Self {
+        PersonName {
+            given: given.iter().map(|g| g.to_string()).collect(),
+            surname: surname.map(str::to_string),
+            ..Default::default()
+        }
+    }
+    ///Every word of the name that can be searched for; titles are left out
+    pub fn words(&self) -> impl Iterator<Item = &str> {
+        self.given
+            .iter()
+            .chain(&self.surname)
+            .chain(&self.birth_surname)
+            .chain(&self.nicknames)
+            .flat_map(|part| part.split_whitespace())
+    }
+}
+impl std::fmt::Display for PersonName {
+    ///Writes the titles, given names and surname, e.g. "Dr John Ronald Tolkien"
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let parts = self
+            .titles
+            .iter()
+            .chain(&self.given)
+            .chain(&self.surname)
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        write!(f, "{}", parts.join(" "))
+    }
+}
+impl FromStr for PersonName {
+    type Err = anyhow::Error;
+    ///Parses a name in the GEDCOM style, with the surname between slashes and nicknames in
+    /// quotes, e.g. `John Ronald "Tolly" /Tolkien/`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut name = PersonName::default();
+        let mut rest = s.trim();
+        while !rest.is_empty() {
+            let (part, after) = match rest.chars().next().unwrap() {
+                delim @ ('/' | '"') => {
+                    let end = rest[1..]
+                        .find(delim)
+                        .ok_or_else(|| anyhow::anyhow!("Unclosed {} in name {:?}", delim, s))?;
+                    let part = rest[1..end + 1].trim().to_string();
+                    if delim == '/' {
+                        if name.surname.is_some() {
+                            anyhow::bail!("More than one surname in name {:?}", s);
+                        }
+                        name.surname = Some(part).filter(|p| !p.is_empty());
+                    } else {
+                        name.nicknames.push(part);
+                    }
+                    (None, &rest[end + 2..])
+                }
+                _ => {
+                    let end = rest
+                        .find(|c: char| c.is_whitespace() || c == '/' || c == '"')
+                        .unwrap_or(rest.len());
+                    (Some(&rest[..end]), &rest[end..])
+                }
+            };
+            if let Some(given) = part {
+                name.given.push(given.to_string());
+            }
+            rest = after.trim_start();
+        }
+        Ok(name)
+    }
+}
+
+///How the words of a name search are matched against names
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NameMatch {
+    Exact,
+    ///The name starts with the searched word
+    Prefix,
+    ///The name is within a small edit distance of the searched word, which allows for misspellings
+    Fuzzy,
+}
+
+///Finds persons by the words of their names. Words are case insensitive.
+#[derive(Clone, Debug)]
+pub struct NameIndex<T: Ord + Copy> {
+    words: BTreeMap<String, BTreeSet<T>>,
+}
+impl<T: Ord + Copy> Default for NameIndex<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<T: Ord + Copy> NameIndex<T> {
+    pub fn new() -> Self {
+        NameIndex {
+            words: BTreeMap::new(),
+        }
+    }
+    pub fn insert<'a>(&mut self, item: T, words: impl IntoIterator<Item = &'a str>) {
+        for word in words {
+            self.words
+                .entry(word.to_lowercase())
+                .or_default()
+                .insert(item);
+        }
+    }
+    pub fn remove<'a>(&mut self, item: T, words: impl IntoIterator<Item = &'a str>) {
+        for word in words {
+            let word = word.to_lowercase();
+            if let Some(items) = self.words.get_mut(&word) {
+                items.remove(&item);
+                if items.is_empty() {
+                    self.words.remove(&word);
+                }
+            }
+        }
+    }
+    ///The items matching every word of the query, best matches first. The score of an item is
+    /// the sum of the edit distances of its best matching words, which is 0 unless fuzzy.
+    pub fn search(&self, query: &str, mode: NameMatch) -> Vec<T> {
+        let mut scores: Option<BTreeMap<T, usize>> = None;
+        for word in query.split_whitespace() {
+            let word = word.to_lowercase();
+            let mut matches = BTreeMap::new();
+            for (item, distance) in self.match_word(&word, mode) {
+                let best = matches.entry(item).or_insert(distance);
+                *best = (*best).min(distance);
+            }
+            scores = Some(match scores {
+                None => matches,
+                Some(scores) => scores
+                    .into_iter()
+                    .filter_map(|(item, score)| matches.get(&item).map(|d| (item, score + d)))
+                    .collect(),
+            });
+        }
+        let mut results = scores.unwrap_or_default().into_iter().collect::<Vec<_>>();
+        results.sort_by_key(|(item, score)| (*score, *item));
+        results.into_iter().map(|(item, _)| item).collect()
+    }
+    //every item with a word matching the (lowercase) word, and the distance to it
+    fn match_word(&self, word: &str, mode: NameMatch) -> Vec<(T, usize)> {
+        let with_distance = |items: &BTreeSet<T>, d: usize| {
+            items.iter().map(move |item| (*item, d)).collect::<Vec<_>>()
+        };
+        match mode {
+            NameMatch::Exact => self
+                .words
+                .get(word)
+                .map(|items| with_distance(items, 0))
+                .unwrap_or_default(),
+            NameMatch::Prefix => self
+                .words
+                .range(word.to_string()..)
+                .take_while(|(w, _)| w.starts_with(word))
+                .flat_map(|(_, items)| with_distance(items, 0))
+                .collect(),
+            NameMatch::Fuzzy => {
+                let max = (word.chars().count() / 4).max(1);
+                self.words
+                    .iter()
+                    .filter_map(|(w, items)| {
+                        let d = levenshtein(word, w);
+                        (d <= max).then(|| with_distance(items, d))
+                    })
+                    .flatten()
+                    .collect()
+            }
+        }
+    }
+}
+///The number of single character insertions, deletions or substitutions between the two words
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut prev = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut row = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev[j] + (ca != *cb) as usize;
+            row.push(substitution.min(prev[j + 1] + 1).min(row[j] + 1));
+        }
+        prev = row;
+    }
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_names() {
+        let name: PersonName = r#"John Ronald "Tolly" /Tolkien/"#.parse().unwrap();
+        assert_eq!(name.given, ["John", "Ronald"]);
+        assert_eq!(name.surname.as_deref(), Some("Tolkien"));
+        assert_eq!(name.nicknames, ["Tolly"]);
+        assert_eq!(name.to_string(), "John Ronald Tolkien");
+        let name: PersonName = "/van der Berg/ Anna".parse().unwrap();
+        assert_eq!(name.surname.as_deref(), Some("van der Berg"));
+        assert_eq!(name.given, ["Anna"]);
+        assert!("John /Smith".parse::<PersonName>().is_err());
+        assert!("/A/ /B/".parse::<PersonName>().is_err());
+    }
+    #[test]
+    fn search_index() {
+        let mut index = NameIndex::new();
+        index.insert(0, ["Anna", "Berg"]);
+        index.insert(1, ["Anne", "Bergman"]);
+        index.insert(2, ["John", "Smith"]);
+        assert_eq!(index.search("anna", NameMatch::Exact), [0]);
+        assert_eq!(index.search("berg", NameMatch::Prefix), [0, 1]);
+        assert_eq!(index.search("An Berg", NameMatch::Prefix), [0, 1]);
+        assert_eq!(index.search("Ana Berg", NameMatch::Fuzzy), [0]);
+        assert_eq!(index.search("ann", NameMatch::Fuzzy), [0, 1]);
+        assert_eq!(index.search("Jon Smyth", NameMatch::Fuzzy), [2]);
+        assert!(index.search("Anna Smith", NameMatch::Exact).is_empty());
+        index.remove(0, ["Anna", "Berg"]);
+        assert_eq!(index.search("berg", NameMatch::Prefix), [1]);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+    }
+}
@@ -0,0 +1,372 @@
+//! Export of the graph for network analysis tools: GraphML (yEd), GEXF (Gephi) and Cytoscape.js
+//! elements JSON (Cytoscape).
+//!
+//! Each person is a node with the columns `name`, `sex` (`M` or `F`), `shadow`, `birth` and
+//! `death`, optionally `generation`, and one column per attribute key. Each relation is an edge,
+//! written once from the parent (or guardian) to the other person, or from the person added first
+//! for the undirected symmetric kinds. Edges have the column `kind`, holding the relationship
+//! keyword of the DSL (such as `PARENT`), and one column per relation attribute key.
+//!
+//! Attribute columns are typed as booleans or numbers when every value of the key is one, and
+//! as strings otherwise. An attribute key that is also the name of a built-in column, or of a
+//! field of the elements such as `id` or `source`, gets an `attr.` prefix.
+use crate::kin_dsl::kind_keyword;
+use crate::xml::Element;
+use crate::{AttrValue, Attributes, KinGraph, Kind, Nd, Sex};
+use anyhow::Result;
+use indexmap::IndexMap;
+use petgraph::visit::EdgeRef;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+const GRAPHML_NAMESPACE: &str = "http://graphml.graphdrawing.org/xmlns";
+const GEXF_NAMESPACE: &str = "http://gexf.net/1.3";
+
+///Options for [KinGraph::to_graphml], [KinGraph::to_gexf] and [KinGraph::to_cytoscape]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NetworkOptions {
+    ///Add the generation of each person as the `generation` column, see [KinGraph::generations]
+    pub generations: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum ColumnType {
+    Boolean,
+    Integer,
+    Double,
+    String,
+}
+impl ColumnType {
+    fn graphml(self) -> &'static str {
+        match self {
+            ColumnType::Boolean => "boolean",
+            ColumnType::Integer => "int",
+            ColumnType::Double => "double",
+            ColumnType::String => "string",
+        }
+    }
+    fn gexf(self) -> &'static str {
+        match self {
+            ColumnType::Boolean => "boolean",
+            ColumnType::Integer => "integer",
+            ColumnType::Double => "double",
+            ColumnType::String => "string",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Cell {
+    Boolean(bool),
+    Integer(i64),
+    Double(f64),
+    String(String),
+}
+impl Cell {
+    fn text(&self) -> String {
+        match self {
+            Cell::Boolean(b) => b.to_string(),
+            Cell::Integer(i) => i.to_string(),
+            Cell::Double(d) => d.to_string(),
+            Cell::String(s) => s.clone(),
+        }
+    }
+    fn json(&self) -> Value {
+        match self {
+            Cell::Boolean(b) => json!(b),
+            Cell::Integer(i) => json!(i),
+            Cell::Double(d) => json!(d),
+            Cell::String(s) => json!(s),
+        }
+    }
+}
+
+//the nodes or edges of the graph, with the type of each column and a value for some of them
+struct Table {
+    columns: IndexMap<String, ColumnType>,
+    //the column of each attribute key
+    attributes: HashMap<String, usize>,
+    rows: Vec<Vec<(usize, Cell)>>,
+}
+impl Table {
+    fn new(builtin: &[(&str, ColumnType)]) -> Self {
+        Table {
+            columns: builtin.iter().map(|(c, t)| (c.to_string(), *t)).collect(),
+            attributes: HashMap::new(),
+            rows: Vec::new(),
+        }
+    }
+    //adds a column for each attribute key, typed by the values it has in the given sets
+    fn add_attribute_columns<'a>(
+        &mut self,
+        sets: impl Iterator<Item = &'a Attributes>,
+        reserved: &[&str],
+    ) {
+        let mut types: IndexMap<&str, ColumnType> = IndexMap::new();
+        for (key, value) in sets.flatten() {
+            let kind = match value {
+                AttrValue::Bool(_) => ColumnType::Boolean,
+                AttrValue::Number(_) => ColumnType::Double,
+                _ => ColumnType::String,
+            };
+            let column = types.entry(key).or_insert(kind);
+            if *column != kind {
+                *column = ColumnType::String;
+            }
+        }
+        for (key, kind) in types {
+            let column = match self.columns.contains_key(key) || reserved.contains(&key) {
+                true => format!("attr.{}", key),
+                false => key.to_string(),
+            };
+            let (i, _) = self.columns.insert_full(column, kind);
+            self.attributes.insert(key.to_string(), i);
+        }
+    }
+}
+
+//the built-in columns of the node and edge tables
+const NODE_COLUMNS: [(&str, ColumnType); 6] = [
+    ("name", ColumnType::String),
+    ("sex", ColumnType::String),
+    ("shadow", ColumnType::Boolean),
+    ("birth", ColumnType::String),
+    ("death", ColumnType::String),
+    ("generation", ColumnType::Integer),
+];
+const EDGE_COLUMNS: [(&str, ColumnType); 1] = [("kind", ColumnType::String)];
+//the fields of Cytoscape.js elements, besides the columns
+const NODE_FIELDS: [&str; 1] = ["id"];
+const EDGE_FIELDS: [&str; 4] = ["id", "source", "target", "directed"];
+
+//an edge of the network, with whether it has a direction
+struct NetworkEdge {
+    source: Nd,
+    target: Nd,
+    directed: bool,
+}
+
+impl KinGraph {
+    //the relations, each once, along with their attributes
+    fn network_edges(&self) -> Vec<(NetworkEdge, Kind, Option<&Attributes>)> {
+        let mut edges = Vec::new();
+        for e in self.graph.edge_references() {
+            let (source, target, kind) = (e.source(), e.target(), *e.weight());
+            if !kind.is_written_from(source.index(), target.index()) {
+                continue;
+            }
+            let attributes = self.relation_attrs_of(source, target, kind);
+            let directed = !matches!(kind, Kind::Sibling | Kind::RP | Kind::Spouse);
+            let edge = NetworkEdge {
+                source,
+                target,
+                directed,
+            };
+            edges.push((edge, kind, attributes));
+        }
+        edges
+    }
+    //the cell of an attribute in a column of the given type
+    fn attribute_cell(&self, value: &AttrValue, column: ColumnType) -> Cell {
+        match (value, column) {
+            (AttrValue::Bool(b), ColumnType::Boolean) => Cell::Boolean(*b),
+            (AttrValue::Number(n), ColumnType::Double) => Cell::Double(*n),
+            (AttrValue::Str(s), _) => Cell::String(s.clone()),
+            (AttrValue::Date(d), _) => Cell::String(self.format_date(d)),
+            (value, _) => Cell::String(value.format(self.calendar())),
+        }
+    }
+    //the node and edge tables of the graph
+    fn network_tables(&self, opts: &NetworkOptions) -> (Table, Table, Vec<NetworkEdge>) {
+        let builtin = match opts.generations {
+            true => &NODE_COLUMNS[..],
+            false => &NODE_COLUMNS[..NODE_COLUMNS.len() - 1],
+        };
+        let mut nodes = Table::new(builtin);
+        nodes.add_attribute_columns(
+            self.graph.node_weights().map(|p| &p.attributes),
+            &NODE_FIELDS,
+        );
+        let generations = opts.generations.then(|| self.generations());
+        for n in self.graph.node_indices() {
+            let p = &self.graph[n];
+            let sex = match p.sex {
+                Sex::Male => "M",
+                Sex::Female => "F",
+            };
+            let mut row = vec![
+                (0, Cell::String(p.name.clone())),
+                (1, Cell::String(sex.into())),
+                (2, Cell::Boolean(p.is_shadow)),
+            ];
+            for (i, date) in [(3, &p.birth), (4, &p.death)] {
+                if let Some(date) = date {
+                    row.push((i, Cell::String(self.format_date(date))));
+                }
+            }
+            if let Some(generations) = &generations {
+                row.push((5, Cell::Integer(generations[n.index()] as i64)));
+            }
+            for (key, value) in &p.attributes {
+                let i = nodes.attributes[key];
+                row.push((i, self.attribute_cell(value, nodes.columns[i])));
+            }
+            nodes.rows.push(row);
+        }
+        let relations = self.network_edges();
+        let mut edges = Table::new(&EDGE_COLUMNS);
+        edges.add_attribute_columns(
+            relations.iter().filter_map(|(_, _, attrs)| *attrs),
+            &EDGE_FIELDS,
+        );
+        let mut network_edges = Vec::new();
+        for (edge, kind, attributes) in relations {
+            let mut row = vec![(0, Cell::String(kind_keyword(kind).into()))];
+            for (key, value) in attributes.into_iter().flatten() {
+                let i = edges.attributes[key];
+                row.push((i, self.attribute_cell(value, edges.columns[i])));
+            }
+            edges.rows.push(row);
+            network_edges.push(edge);
+        }
+        (nodes, edges, network_edges)
+    }
+    ///Writes the graph as GraphML, with a typed key for each column. The graph is directed, and
+    /// the edges of symmetric relations are marked as undirected.
+    pub fn to_graphml(&self, opts: &NetworkOptions) -> String {
+        let (nodes, edges, network_edges) = self.network_tables(opts);
+        let mut graphml = Element::new("graphml").with_attr("xmlns", GRAPHML_NAMESPACE);
+        for (prefix, target, table) in [("n", "node", &nodes), ("e", "edge", &edges)] {
+            for (i, (column, kind)) in table.columns.iter().enumerate() {
+                let key = Element::new("key")
+                    .with_attr("id", format!("{}{}", prefix, i))
+                    .with_attr("for", target)
+                    .with_attr("attr.name", column.as_str())
+                    .with_attr("attr.type", kind.graphml());
+                graphml = graphml.with(key);
+            }
+        }
+        let mut graph = Element::new("graph")
+            .with_attr("id", "kin")
+            .with_attr("edgedefault", "directed");
+        let data = |prefix: &str, row: &[(usize, Cell)], mut element: Element| {
+            for (i, cell) in row {
+                let key = format!("{}{}", prefix, i);
+                element = element.with(Element::text("data", &cell.text()).with_attr("key", key));
+            }
+            element
+        };
+        for (n, row) in nodes.rows.iter().enumerate() {
+            let node = Element::new("node").with_attr("id", format!("n{}", n));
+            graph = graph.with(data("n", row, node));
+        }
+        for (i, (edge, row)) in network_edges.iter().zip(&edges.rows).enumerate() {
+            let mut element = Element::new("edge")
+                .with_attr("id", format!("e{}", i))
+                .with_attr("source", format!("n{}", edge.source.index()))
+                .with_attr("target", format!("n{}", edge.target.index()));
+            if !edge.directed {
+                element = element.with_attr("directed", "false");
+            }
+            graph = graph.with(data("e", row, element));
+        }
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        graphml.with(graph).write(&mut out, 0);
+        out
+    }
+    ///Writes the graph as GEXF 1.3, with an attribute for each column besides the name, which
+    /// is the label of each node. The kind of each edge is also its label.
+    pub fn to_gexf(&self, opts: &NetworkOptions) -> String {
+        let (nodes, edges, network_edges) = self.network_tables(opts);
+        let mut graph = Element::new("graph")
+            .with_attr("mode", "static")
+            .with_attr("defaultedgetype", "directed");
+        for (class, table) in [("node", &nodes), ("edge", &edges)] {
+            let mut attributes = Element::new("attributes").with_attr("class", class);
+            for (i, (column, kind)) in table.columns.iter().enumerate() {
+                attributes = attributes.with(
+                    Element::new("attribute")
+                        .with_attr("id", i.to_string())
+                        .with_attr("title", column.as_str())
+                        .with_attr("type", kind.gexf()),
+                );
+            }
+            graph = graph.with(attributes);
+        }
+        let attvalues = |row: &[(usize, Cell)]| {
+            let mut attvalues = Element::new("attvalues");
+            for (i, cell) in row {
+                attvalues = attvalues.with(
+                    Element::new("attvalue")
+                        .with_attr("for", i.to_string())
+                        .with_attr("value", cell.text()),
+                );
+            }
+            attvalues
+        };
+        let mut node_elements = Element::new("nodes");
+        for (n, row) in nodes.rows.iter().enumerate() {
+            let node = Element::new("node")
+                .with_attr("id", n.to_string())
+                .with_attr("label", self.graph[Nd::new(n)].name.as_str());
+            node_elements = node_elements.with(node.with(attvalues(row)));
+        }
+        let mut edge_elements = Element::new("edges");
+        for (i, (edge, row)) in network_edges.iter().zip(&edges.rows).enumerate() {
+            let mut element = Element::new("edge")
+                .with_attr("id", i.to_string())
+                .with_attr("source", edge.source.index().to_string())
+                .with_attr("target", edge.target.index().to_string())
+                .with_attr("label", row[0].1.text());
+            if !edge.directed {
+                element = element.with_attr("type", "undirected");
+            }
+            edge_elements = edge_elements.with(element.with(attvalues(row)));
+        }
+        let gexf = Element::new("gexf")
+            .with_attr("xmlns", GEXF_NAMESPACE)
+            .with_attr("version", "1.3")
+            .with(graph.with(node_elements).with(edge_elements));
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        gexf.write(&mut out, 0);
+        out
+    }
+    ///Writes the graph as Cytoscape.js elements JSON, `{"elements": {"nodes": [...], "edges":
+    /// [...]}}`, with the columns as typed values of the `data` of each element. Edges also have
+    /// a `directed` boolean.
+    pub fn to_cytoscape(&self, opts: &NetworkOptions) -> Result<String> {
+        let (nodes, edges, network_edges) = self.network_tables(opts);
+        let data = |table: &Table, row: &[(usize, Cell)], mut data: Map<String, Value>| {
+            for (i, cell) in row {
+                let (column, _) = table.columns.get_index(*i).unwrap();
+                data.insert(column.clone(), cell.json());
+            }
+            json!({ "data": data })
+        };
+        let node_elements = nodes
+            .rows
+            .iter()
+            .enumerate()
+            .map(|(n, row)| {
+                let id = Map::from_iter([("id".to_string(), json!(format!("n{}", n)))]);
+                data(&nodes, row, id)
+            })
+            .collect::<Vec<_>>();
+        let edge_elements = network_edges
+            .iter()
+            .zip(&edges.rows)
+            .enumerate()
+            .map(|(i, (edge, row))| {
+                let fields = Map::from_iter([
+                    ("id".to_string(), json!(format!("e{}", i))),
+                    ("source".into(), json!(format!("n{}", edge.source.index()))),
+                    ("target".into(), json!(format!("n{}", edge.target.index()))),
+                    ("directed".into(), json!(edge.directed)),
+                ]);
+                data(&edges, row, fields)
+            })
+            .collect::<Vec<_>>();
+        let elements = json!({ "elements": { "nodes": node_elements, "edges": edge_elements } });
+        Ok(serde_json::to_string_pretty(&elements)?)
+    }
+}
@@ -0,0 +1,297 @@
+//! Serialization of a whole [KinGraph] through serde.
+//!
+//! A graph is written as a [GraphDocument]: its persons in the order they were added, and each
+//! relation once, from the parent (or guardian) to the other person, or from the person added
+//! first for symmetric relations. Documents carry [SCHEMA_VERSION]. Version 1 is the
+//! `{nodes: [...]}` layout of [KinWasmGraph], which is migrated when it is read, along with the
+//! parentage, partnerships and calendar it may carry.
+//!
+//! Dates are kept as they are written, along with the calendar of the graph when it is a
+//! [CustomCalendar]. Graphs without one are read back with the Gregorian calendar.
+use crate::kin_wasm::{KinWasmGraph, RelationKind};
+use crate::{Attributes, CustomCalendar, KinError, KinGraph, Kind, Parentage, Partnership, Person};
+use anyhow::{bail, Result};
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+
+///The version of [GraphDocument] that is written
+pub const SCHEMA_VERSION: u32 = 2;
+
+///A serialized [KinGraph]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct GraphDocument {
+    pub version: u32,
+    pub persons: Vec<Person>,
+    pub relations: Vec<RelationRecord>,
+    ///The calendar that the dates are written in, if it is not the Gregorian one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub calendar: Option<CustomCalendar>,
+}
+///A relation of a [GraphDocument], which stands for both directions of it
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct RelationRecord {
+    ///The index in [GraphDocument::persons] of the person the relation is from
+    pub from: usize,
+    pub to: usize,
+    pub kind: Kind,
+    ///How a parent is the parent of the child, if not both biologically and legally
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parentage: Option<Parentage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub partnership: Option<Partnership>,
+    ///The attributes of the relation
+    #[serde(default, skip_serializing_if = "Attributes::is_empty")]
+    pub attributes: Attributes,
+}
+impl Kind {
+    ///Whether a relation of this kind from p1 to p2 is the direction it is written in
+    pub(crate) fn is_written_from(&self, p1: usize, p2: usize) -> bool {
+        match self {
+            Kind::Parent | Kind::AdoptiveParent | Kind::FosterParent | Kind::Guardian => true,
+            Kind::Sibling | Kind::RP | Kind::Spouse => p1 < p2,
+            _ => false,
+        }
+    }
+}
+impl KinGraph {
+    ///Writes the graph as a [GraphDocument] of the current version
+    pub fn to_document(&self) -> GraphDocument {
+        let persons = self.graph.node_weights().cloned().collect();
+        let mut relations = Vec::new();
+        for e in self.graph.edge_references() {
+            let (from, to) = (e.source(), e.target());
+            let kind = *e.weight();
+            if !kind.is_written_from(from.index(), to.index()) {
+                continue;
+            }
+            let attributes = self.relation_attrs_of(from, to, kind).cloned();
+            relations.push(RelationRecord {
+                from: from.index(),
+                to: to.index(),
+                kind,
+                parentage: match kind {
+                    Kind::Parent => self.parentage.get(&(from, to)).copied(),
+                    _ => None,
+                },
+                partnership: match kind {
+                    Kind::Spouse => self.partnership_of(from, to).cloned(),
+                    _ => None,
+                },
+                attributes: attributes.unwrap_or_default(),
+            });
+        }
+        GraphDocument {
+            version: SCHEMA_VERSION,
+            persons,
+            relations,
+            calendar: self.calendar.as_custom().cloned(),
+        }
+    }
+    ///Reads a graph from a [GraphDocument] of the current version
+    pub fn from_document(doc: GraphDocument) -> Result<KinGraph> {
+        if doc.version != SCHEMA_VERSION {
+            bail!(
+                "Unsupported graph document version {}, expected {}",
+                doc.version,
+                SCHEMA_VERSION
+            );
+        }
+        let mut kg = KinGraph::new();
+        if let Some(calendar) = doc.calendar {
+            calendar.validate()?;
+            kg.set_calendar(Box::new(calendar));
+        }
+        for p in &doc.persons {
+            kg.read_person(p)?;
+        }
+        for r in doc.relations {
+            kg.read_relation(r)?;
+        }
+        Ok(kg)
+    }
+    ///Adds a person read from a document, checking that their id and external references are
+    /// not already taken
+    pub(crate) fn read_person(&mut self, p: &Person) -> Result<()> {
+        if self.id_indx.contains_key(&p.id) {
+            bail!("Person id {} appears more than once", p.id);
+        }
+        self.add_person(p);
+        let px = self.idx(p).unwrap();
+        for (system, id) in &p.refs {
+            if self
+                .ref_index
+                .insert((system.clone(), id.clone()), px)
+                .is_some()
+            {
+                bail!("The {:?} reference {:?} belongs to two persons", system, id);
+            }
+        }
+        Ok(())
+    }
+    ///Adds a relation read from a document between persons that have already been read, with
+    /// the same rules as adding the relation to a graph, so that a document can't hold a graph
+    /// that couldn't have been built
+    pub(crate) fn read_relation(&mut self, r: RelationRecord) -> Result<()> {
+        let count = self.graph.node_count();
+        if r.from >= count || r.to >= count {
+            bail!(
+                "Relation between {} and {} refers to a missing person",
+                r.from,
+                r.to
+            );
+        }
+        if r.from == r.to {
+            bail!("Person {} can not be related to themselves", r.from);
+        }
+        let (from, to) = (NodeIndex::new(r.from), NodeIndex::new(r.to));
+        if self
+            .graph
+            .edges_connecting(from, to)
+            .any(|e| *e.weight() == r.kind)
+        {
+            bail!(
+                "Relation between {} and {} appears more than once",
+                r.from,
+                r.to
+            );
+        }
+        match r.kind {
+            Kind::Parent => self.check_parent(from, to, r.parentage.unwrap_or_default())?,
+            Kind::Child => self.check_parent(to, from, Parentage::default())?,
+            Kind::Sibling | Kind::RP | Kind::Spouse => {}
+            kind if kind.is_written_from(0, 1) => self.check_custodial(from, to, kind)?,
+            kind => self.check_custodial(to, from, kind.get_inverse())?,
+        }
+        match (r.kind, r.parentage, r.partnership) {
+            (_, None, None) => {}
+            (Kind::Parent, Some(parentage), None) => {
+                self.parentage.insert((from, to), parentage);
+            }
+            (Kind::Spouse, None, Some(partnership)) => {
+                self.partnerships
+                    .insert(Self::pair_key(from, to), partnership);
+            }
+            _ => bail!(
+                "Relation {} between {} and {} can not have a parentage or partnership",
+                r.kind,
+                r.from,
+                r.to
+            ),
+        }
+        self.add_edges(from, to, r.kind);
+        if !r.attributes.is_empty() {
+            self.relation_attrs
+                .insert(Self::relation_key(from, to, r.kind), r.attributes);
+        }
+        Ok(())
+    }
+    ///Writes the graph as JSON, see [GraphDocument]
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+    ///Reads a graph from the JSON of any version of [GraphDocument]
+    pub fn from_json(json: &str) -> Result<KinGraph> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+///Migrates a version 1 document, keeping the nodes in order. Every relation must be listed
+/// by both of the nodes it is between.
+pub(crate) fn migrate_v1(graph: KinWasmGraph) -> Result<GraphDocument> {
+    let mut positions = HashMap::new();
+    for (i, node) in graph.nodes().iter().enumerate() {
+        if positions.insert(node.get_id(), i).is_some() {
+            return Err(KinError::DuplicateNode { id: node.get_id() }.into());
+        }
+    }
+    for node in graph.nodes() {
+        for relation in node.relations() {
+            let Some(&to) = positions.get(&relation.id) else {
+                return Err(KinError::MissingNode { id: relation.id }.into());
+            };
+            let inverse = RelationKind::from(Kind::from(relation.kind).get_inverse());
+            let listed_back = graph.nodes()[to]
+                .relations()
+                .iter()
+                .any(|r| r.id == node.get_id() && r.kind == inverse);
+            if !listed_back {
+                return Err(KinError::AsymmetricRelation {
+                    p1: node.get_id() as usize,
+                    p2: relation.id as usize,
+                }
+                .into());
+            }
+        }
+    }
+    let mut relations = Vec::new();
+    for (from, node) in graph.nodes().iter().enumerate() {
+        for relation in node.relations() {
+            let to = positions[&relation.id];
+            let kind = Kind::from(relation.kind);
+            if !kind.is_written_from(from, to) {
+                continue;
+            }
+            //the attributes may be listed by either of the nodes
+            let inverse = RelationKind::from(kind.get_inverse());
+            let attributes = node
+                .relation_attributes
+                .get(&relation.id)
+                .and_then(|attrs| attrs.get(&relation.kind))
+                .or_else(|| {
+                    let back = &graph.nodes()[to].relation_attributes;
+                    back.get(&node.get_id())?.get(&inverse)
+                })
+                .cloned()
+                .unwrap_or_default();
+            //as may the partnership, while the parentage is listed by the parent
+            let partnership = match kind {
+                Kind::Spouse => node.partnerships.get(&relation.id).or_else(|| {
+                    let back = &graph.nodes()[to].partnerships;
+                    back.get(&node.get_id())
+                }),
+                _ => None,
+            };
+            relations.push(RelationRecord {
+                from,
+                to,
+                kind,
+                parentage: match kind {
+                    Kind::Parent => node.parentage.get(&relation.id).copied(),
+                    _ => None,
+                },
+                partnership: partnership.cloned(),
+                attributes,
+            });
+        }
+    }
+    let calendar = graph.calendar().cloned();
+    let persons = graph.into_nodes().into_iter().map(Person::from).collect();
+    Ok(GraphDocument {
+        version: SCHEMA_VERSION,
+        persons,
+        relations,
+        calendar,
+    })
+}
+impl Serialize for KinGraph {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_document().serialize(serializer)
+    }
+}
+impl<'de> Deserialize<'de> for KinGraph {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        //read as a value first rather than through an untagged enum, which can't read the maps
+        //keyed by node id of version 1. Only documents of version 2 on have a version.
+        let value = serde_json::Value::deserialize(deserializer)?;
+        let doc = if value.get("version").is_some() {
+            serde_json::from_value(value).map_err(anyhow::Error::from)
+        } else {
+            serde_json::from_value(value)
+                .map_err(anyhow::Error::from)
+                .and_then(migrate_v1)
+        };
+        let doc = doc.map_err(serde::de::Error::custom)?;
+        KinGraph::from_document(doc).map_err(serde::de::Error::custom)
+    }
+}
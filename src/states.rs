@@ -10,6 +10,11 @@ pub trait State: Any {
     fn get_any(&self) -> &dyn std::any::Any;
     //get a unique hash for this state.
     fn get_hash(&self) -> u64;
+    ///Called with the two ends of the path once every edge has been consumed. States that
+    /// are only guessed from the shape of the path (step relations) check the guess here.
+    fn finalize(&self, _p1: Nd, _p2: Nd, _kg: &KinGraph) -> Box<dyn State> {
+        self.clone_box()
+    }
 }
 impl Clone for Box<dyn State> {
     fn clone(&self) -> Box<dyn State> {
@@ -37,6 +42,15 @@ impl std::cmp::PartialEq for dyn State {
     }
 }
 impl std::cmp::Eq for dyn State {}
+///Joins a (possibly empty) prefix such as "grand" with the base name, and makes it an in-law
+fn in_law_name(prefix: &str, base: &str) -> String {
+    let prefix = prefix.trim_end_matches('-');
+    if prefix.is_empty() {
+        format!("{} in law", base)
+    } else {
+        format!("{}-{} in law", prefix, base)
+    }
+}
 ///An nth level parent (Parent, grandparent, great-grandparent, etc.)
 #[derive(Hash, Eq, PartialEq, Debug)]
 pub struct NParentState {
@@ -52,7 +66,7 @@ impl State for NParentState {
                     sex: self.sex,
                 }),
                 Kind::Child => Box::new(RPState { sex: self.sex }),
-                Kind::RP => Box::new(NPinLState {
+                Kind::Spouse => Box::new(NPinLState {
                     n: 0,
                     sex: self.sex,
                 }),
@@ -60,6 +74,7 @@ impl State for NParentState {
                     n: 0,
                     sex: self.sex,
                 }),
+                _ => Box::new(StopState {}),
             };
             Some(res)
         } else {
@@ -68,11 +83,10 @@ impl State for NParentState {
                     n: self.n + 1,
                     sex: self.sex,
                 }),
-                Kind::Child => Box::new(NPinLState {
-                    n: self.n - 1,
-                    sex: self.sex,
-                }),
-                Kind::RP => Box::new(NPinLState {
+                //the other parent of a grandchild is only an in-law when married, which
+                //is found through the spouse edge instead
+                Kind::Child => Box::new(StopState {}),
+                Kind::Spouse => Box::new(NPinLState {
                     n: self.n + 1,
                     sex: self.sex,
                 }),
@@ -80,6 +94,7 @@ impl State for NParentState {
                     n: self.n,
                     sex: self.sex,
                 }),
+                _ => Box::new(StopState {}),
             };
 
             Some(res)
@@ -142,7 +157,7 @@ impl State for NPinLState {
                 sex: self.sex,
             }),
             Kind::Child => Box::new(StopState {}),
-            Kind::RP => Box::new(NPinLState {
+            Kind::Spouse => Box::new(NPinLState {
                 n: self.n,
                 sex: self.sex,
             }),
@@ -150,6 +165,7 @@ impl State for NPinLState {
                 n: self.n,
                 sex: self.sex,
             }),
+            _ => Box::new(StopState {}),
         };
         Some(res)
     }
@@ -162,9 +178,8 @@ impl State for NPinLState {
                 greats_string + "grand"
             }
         };
-        format!(
-            "{}-{} in law",
-            g,
+        in_law_name(
+            &g,
             if self.sex == Sex::Male {
                 "father"
             } else {
@@ -231,6 +246,7 @@ impl State for NChildState {
                         Box::new(StopState {})
                     }
                 }
+                _ => Box::new(StopState {}),
             };
             Some(res)
         } else {
@@ -259,14 +275,17 @@ impl State for NChildState {
                             sex: self.sex,
                         })
                     } else {
+                        //only the spouse of a parent is a step-parent
                         Box::new(StopState {})
                     }
                 }
+                Kind::Spouse => Box::new(StepChildState { sex: self.sex }),
                 Kind::Sibling => Box::new(NNeniState {
                     n: 0,
                     is_half: false,
                     sex: self.sex,
                 }),
+                _ => Box::new(StopState {}),
             };
             Some(res)
         }
@@ -314,28 +333,23 @@ pub struct NCinLState {
     pub sex: super::Sex,
 }
 impl State for NCinLState {
-    fn transition(&self, kind: (Nd, Kind, Nd), kg: &KinGraph) -> Option<Box<dyn State>> {
+    fn transition(&self, kind: (Nd, Kind, Nd), _kg: &KinGraph) -> Option<Box<dyn State>> {
         let res: Box<dyn State> = match kind.1 {
             Kind::Parent => Box::new(StopState {}),
             Kind::Child => Box::new(NCinLState {
                 n: self.n + 1,
                 sex: self.sex,
             }),
-            Kind::RP => {
-                if kg.is_repart(kind.0, kind.2) {
-                    Box::new(NCinLState {
-                        n: self.n,
-                        sex: self.sex,
-                    })
-                } else {
-                    Box::new(StopState {})
-                }
-            }
+            Kind::Spouse => Box::new(NCinLState {
+                n: self.n,
+                sex: self.sex,
+            }),
             Kind::Sibling => Box::new(NNNinLState {
                 n: self.n,
                 is_half: false,
                 sex: self.sex,
             }),
+            _ => Box::new(StopState {}),
         };
         Some(res)
     }
@@ -348,9 +362,8 @@ impl State for NCinLState {
                 greats_string + "great-"
             }
         };
-        format!(
-            "{}{} in law",
-            g,
+        in_law_name(
+            &g,
             if self.sex == Sex::Male {
                 "son"
             } else {
@@ -415,6 +428,7 @@ impl State for NCsnKState {
                 is_half: self.is_half,
                 sex: self.sex,
             }),
+            _ => Box::new(StopState {}),
         };
 
         Some(res)
@@ -479,7 +493,7 @@ impl State for SiblingState {
                 n: 0,
                 sex: self.sex,
             }),
-            Kind::RP => Box::new(SinLState {
+            Kind::Spouse => Box::new(SinLState {
                 is_half: self.is_half,
                 sex: self.sex,
             }),
@@ -487,6 +501,7 @@ impl State for SiblingState {
                 is_half: self.is_half,
                 sex: self.sex,
             }),
+            _ => Box::new(StopState {}),
         };
         Some(res)
     }
@@ -526,28 +541,71 @@ pub struct RPState {
 impl State for RPState {
     fn transition(&self, kind: (Nd, Kind, Nd), _kg: &KinGraph) -> Option<Box<dyn State>> {
         let res: Box<dyn State> = match kind.1 {
+            //a reproductive partner is not a step-parent or an in-law, only a spouse is
             Kind::Parent => Box::new(StopState {}),
+            Kind::Child => Box::new(StopState {}),
+            Kind::RP => Box::new(StopState {}),
+            Kind::Sibling => Box::new(StopState {}),
+            _ => Box::new(StopState {}),
+        };
+        Some(res)
+    }
+    fn print_canonical_name(&self) -> String {
+        match self.sex {
+            Sex::Female => "female reproductive partner",
+            Sex::Male => "male reproductive partner",
+        }
+        .to_string()
+    }
+    fn clone_box(&self) -> Box<dyn State> {
+        Box::new(RPState { sex: self.sex })
+    }
+    fn get_any(&self) -> &dyn std::any::Any {
+        self
+    }
+    fn get_hash(&self) -> u64 {
+        //hash based on name and variables
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        "RPState".hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+///Spouse or partner. The status is filled in from the partnership once the path is complete.
+pub struct SpouseState {
+    pub sex: super::Sex,
+    pub status: MaritalStatus,
+}
+impl State for SpouseState {
+    fn transition(&self, kind: (Nd, Kind, Nd), _kg: &KinGraph) -> Option<Box<dyn State>> {
+        let res: Box<dyn State> = match kind.1 {
+            Kind::Parent => Box::new(StepParentState { sex: self.sex }),
             Kind::Child => Box::new(NCinLState {
                 n: 0,
                 sex: self.sex,
             }),
-            Kind::RP => Box::new(StopState {}),
             Kind::Sibling => Box::new(SinLState {
                 is_half: false,
                 sex: self.sex,
             }),
+            _ => Box::new(StopState {}),
         };
         Some(res)
     }
     fn print_canonical_name(&self) -> String {
-        match self.sex {
-            Sex::Female => "female reproductive partner",
-            Sex::Male => "male reproductive partner",
+        let male = self.sex == Sex::Male;
+        match self.status {
+            MaritalStatus::Married => if male { "husband" } else { "wife" }.to_string(),
+            MaritalStatus::Partnered => "partner".to_string(),
+            MaritalStatus::Divorced => if male { "ex-husband" } else { "ex-wife" }.to_string(),
+            MaritalStatus::Widowed => if male { "widower" } else { "widow" }.to_string(),
         }
-        .to_string()
     }
     fn clone_box(&self) -> Box<dyn State> {
-        Box::new(RPState { sex: self.sex })
+        Box::new(SpouseState {
+            sex: self.sex,
+            status: self.status,
+        })
     }
     fn get_any(&self) -> &dyn std::any::Any {
         self
@@ -555,9 +613,19 @@ impl State for RPState {
     fn get_hash(&self) -> u64 {
         //hash based on name and variables
         let mut hasher = std::collections::hash_map::DefaultHasher::new();
-        "RPState".hash(&mut hasher);
+        "SpouseState".hash(&mut hasher);
+        self.status.hash(&mut hasher);
         hasher.finish()
     }
+    fn finalize(&self, p1: Nd, p2: Nd, kg: &KinGraph) -> Box<dyn State> {
+        match kg.partnership_of(p1, p2) {
+            Some(partnership) => Box::new(SpouseState {
+                sex: self.sex,
+                status: partnership.status,
+            }),
+            None => self.clone_box(),
+        }
+    }
 }
 
 pub struct SinLState {
@@ -578,18 +646,18 @@ impl State for SinLState {
                 is_half: self.is_half,
                 sex: self.sex,
             }),
+            _ => Box::new(StopState {}),
         };
         Some(res)
     }
     fn print_canonical_name(&self) -> String {
-        format!(
-            "{}{} in law",
+        in_law_name(
+            if self.is_half { "half" } else { "" },
             if self.sex == Sex::Male {
                 "brother"
             } else {
                 "sister"
             },
-            if self.is_half { "half-" } else { "" }
         )
     }
     fn clone_box(&self) -> Box<dyn State> {
@@ -631,6 +699,7 @@ impl State for NNeniState {
                 is_half: self.is_half,
                 sex: self.sex,
             }),
+            _ => Box::new(StopState {}),
         };
         Some(res)
     }
@@ -696,6 +765,7 @@ impl State for NNNinLState {
                 is_half: self.is_half,
                 sex: self.sex,
             }),
+            _ => Box::new(StopState {}),
         };
         Some(res)
     }
@@ -708,14 +778,13 @@ impl State for NNNinLState {
                 greats_string + "great"
             }
         };
-        format!(
-            "{}-{} in law",
-            g,
+        in_law_name(
+            &g,
             if self.sex == Sex::Male {
                 "nephew"
             } else {
                 "niece"
-            }
+            },
         )
     }
     fn clone_box(&self) -> Box<dyn State> {
@@ -757,7 +826,7 @@ impl State for NAUState {
                     is_half: self.is_half,
                     sex: self.sex,
                 }),
-                Kind::RP => Box::new(NAUinLState {
+                Kind::Spouse => Box::new(NAUinLState {
                     n: self.n,
                     is_half: self.is_half,
                     sex: self.sex,
@@ -767,6 +836,7 @@ impl State for NAUState {
                     is_half: self.is_half,
                     sex: self.sex,
                 }),
+                _ => Box::new(StopState {}),
             };
             Some(res)
         } else {
@@ -781,7 +851,7 @@ impl State for NAUState {
                     is_half: self.is_half,
                     sex: kg.px(kind.0.index()).sex,
                 }),
-                Kind::RP => Box::new(NAUinLState {
+                Kind::Spouse => Box::new(NAUinLState {
                     n: self.n,
                     is_half: self.is_half,
                     sex: self.sex,
@@ -791,6 +861,7 @@ impl State for NAUState {
                     is_half: self.is_half,
                     sex: self.sex,
                 }),
+                _ => Box::new(StopState {}),
             };
             Some(res)
         }
@@ -860,6 +931,7 @@ impl State for NAUinLState {
                 is_half: self.is_half,
                 sex: kg.px(kind.0.index()).sex,
             }),
+            _ => Box::new(StopState {}),
         };
         Some(res)
     }
@@ -872,14 +944,13 @@ impl State for NAUinLState {
                 greats_string + "great"
             }
         };
-        format!(
-            "{}-{} in law",
-            g,
+        in_law_name(
+            &g,
             if self.sex == Sex::Male {
                 "uncle"
             } else {
                 "aunt"
-            }
+            },
         )
     }
     fn clone_box(&self) -> Box<dyn State> {
@@ -901,6 +972,193 @@ impl State for NAUinLState {
         hasher.finish()
     }
 }
+///Step-parent: the partner of one of a person's parents, who is not their parent
+pub struct StepParentState {
+    pub sex: super::Sex,
+}
+impl State for StepParentState {
+    fn transition(&self, _kind: (Nd, Kind, Nd), _kg: &KinGraph) -> Option<Box<dyn State>> {
+        Some(Box::new(StopState {}))
+    }
+    fn print_canonical_name(&self) -> String {
+        match self.sex {
+            Sex::Female => "step-mother",
+            Sex::Male => "step-father",
+        }
+        .to_string()
+    }
+    fn clone_box(&self) -> Box<dyn State> {
+        Box::new(StepParentState { sex: self.sex })
+    }
+    fn get_any(&self) -> &dyn std::any::Any {
+        self
+    }
+    fn get_hash(&self) -> u64 {
+        //hash based on name and variables
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        "StepParentState".hash(&mut hasher);
+        hasher.finish()
+    }
+    fn finalize(&self, p1: Nd, p2: Nd, kg: &KinGraph) -> Box<dyn State> {
+        if kg.is_parent(p1, p2) {
+            Box::new(StopState {})
+        } else {
+            self.clone_box()
+        }
+    }
+}
+
+///Step-child: the child of a person's partner, who is not their own child
+pub struct StepChildState {
+    pub sex: super::Sex,
+}
+impl State for StepChildState {
+    fn transition(&self, kind: (Nd, Kind, Nd), _kg: &KinGraph) -> Option<Box<dyn State>> {
+        let res: Box<dyn State> = match kind.1 {
+            Kind::Parent => Box::new(StepSiblingState { sex: self.sex }),
+            _ => Box::new(StopState {}),
+        };
+        Some(res)
+    }
+    fn print_canonical_name(&self) -> String {
+        match self.sex {
+            Sex::Female => "step-daughter",
+            Sex::Male => "step-son",
+        }
+        .to_string()
+    }
+    fn clone_box(&self) -> Box<dyn State> {
+        Box::new(StepChildState { sex: self.sex })
+    }
+    fn get_any(&self) -> &dyn std::any::Any {
+        self
+    }
+    fn get_hash(&self) -> u64 {
+        //hash based on name and variables
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        "StepChildState".hash(&mut hasher);
+        hasher.finish()
+    }
+    fn finalize(&self, p1: Nd, p2: Nd, kg: &KinGraph) -> Box<dyn State> {
+        if kg.is_child(p1, p2) {
+            Box::new(StopState {})
+        } else {
+            self.clone_box()
+        }
+    }
+}
+
+///Step-sibling: the child of a step-parent, sharing no parent with the person
+pub struct StepSiblingState {
+    pub sex: super::Sex,
+}
+impl State for StepSiblingState {
+    fn transition(&self, _kind: (Nd, Kind, Nd), _kg: &KinGraph) -> Option<Box<dyn State>> {
+        Some(Box::new(StopState {}))
+    }
+    fn print_canonical_name(&self) -> String {
+        match self.sex {
+            Sex::Female => "step-sister",
+            Sex::Male => "step-brother",
+        }
+        .to_string()
+    }
+    fn clone_box(&self) -> Box<dyn State> {
+        Box::new(StepSiblingState { sex: self.sex })
+    }
+    fn get_any(&self) -> &dyn std::any::Any {
+        self
+    }
+    fn get_hash(&self) -> u64 {
+        //hash based on name and variables
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        "StepSiblingState".hash(&mut hasher);
+        hasher.finish()
+    }
+    fn finalize(&self, p1: Nd, p2: Nd, kg: &KinGraph) -> Box<dyn State> {
+        let p1_parents = kg.parents_of(p1);
+        let shares_parent = kg.parents_of(p2).iter().any(|p| p1_parents.contains(p));
+        if p1 == p2 || shares_parent {
+            Box::new(StopState {})
+        } else {
+            self.clone_box()
+        }
+    }
+}
+
+///Adoptive, foster and guardian relations. These are only named for a direct edge.
+pub struct CustodialState {
+    pub kind: Kind,
+    pub sex: super::Sex,
+}
+impl State for CustodialState {
+    fn transition(&self, _kind: (Nd, Kind, Nd), _kg: &KinGraph) -> Option<Box<dyn State>> {
+        Some(Box::new(StopState {}))
+    }
+    fn print_canonical_name(&self) -> String {
+        let parent = if self.sex == Sex::Male {
+            "father"
+        } else {
+            "mother"
+        };
+        let child = if self.sex == Sex::Male {
+            "son"
+        } else {
+            "daughter"
+        };
+        match self.kind {
+            Kind::AdoptiveParent => format!("adoptive {}", parent),
+            Kind::AdoptiveChild => format!("adoptive {}", child),
+            Kind::FosterParent => format!("foster {}", parent),
+            Kind::FosterChild => format!("foster {}", child),
+            Kind::Guardian => "guardian".to_string(),
+            _ => "ward".to_string(),
+        }
+    }
+    fn clone_box(&self) -> Box<dyn State> {
+        Box::new(CustodialState {
+            kind: self.kind,
+            sex: self.sex,
+        })
+    }
+    fn get_any(&self) -> &dyn std::any::Any {
+        self
+    }
+    fn get_hash(&self) -> u64 {
+        //hash based on name and variables
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        "CustodialState".hash(&mut hasher);
+        self.kind.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+///A relationship that ended with a divorce somewhere along the path, such as a former sister in law
+pub struct FormerState {
+    pub inner: Box<dyn State>,
+}
+impl State for FormerState {
+    fn transition(&self, _kind: (Nd, Kind, Nd), _kg: &KinGraph) -> Option<Box<dyn State>> {
+        Some(Box::new(StopState {}))
+    }
+    fn print_canonical_name(&self) -> String {
+        format!("former {}", self.inner.print_canonical_name())
+    }
+    fn clone_box(&self) -> Box<dyn State> {
+        Box::new(FormerState {
+            inner: self.inner.clone_box(),
+        })
+    }
+    fn get_any(&self) -> &dyn std::any::Any {
+        self
+    }
+    fn get_hash(&self) -> u64 {
+        //hash based on name and variables
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        "FormerState".hash(&mut hasher);
+        self.inner.get_hash().hash(&mut hasher);
+        hasher.finish()
+    }
+}
 pub struct StopState {}
 impl State for StopState {
     fn transition(&self, _kind: (Nd, Kind, Nd), _kg: &KinGraph) -> Option<Box<dyn State>> {
@@ -0,0 +1,308 @@
+//! Rendering of pedigree, descendant and hourglass charts as SVG, without Graphviz.
+//!
+//! A chart is a tree of boxes drawn out from a focus person, one generation after the other. The
+//! ancestors of a pedigree chart go from left to right, with fathers above mothers, and the
+//! descendants of a descendant chart go down the page, in order of birth, joined to their parent
+//! by drop lines. An hourglass chart draws the ancestors of the focus person above them and the
+//! descendants below. Persons who are reached more than once, as in pedigree collapse, are drawn
+//! once for each way they are reached.
+//!
+//! Boxes hold the name of the person, and optionally their dates and some attributes, each on a
+//! line of its own that is cut short when it is too long for the box. Men are drawn in blue boxes
+//! and women in pink ones, shadow persons are dashed and the focus person is bold.
+use crate::xml::Element;
+use crate::{AttrValue, KinGraph, Nd, Person, Sex};
+use anyhow::Result;
+
+///Options for [KinGraph::pedigree_svg], [KinGraph::descendants_svg] and
+/// [KinGraph::hourglass_svg]
+#[derive(Clone, Debug)]
+pub struct ChartOptions {
+    ///How many generations away from the focus person are drawn
+    pub generations: usize,
+    ///Show the birth and death dates of each person
+    pub dates: bool,
+    ///The attributes shown below the name and dates, by key
+    pub attributes: Vec<String>,
+    pub font_family: String,
+    ///The font size in pixels, which the line height and padding of the boxes follow
+    pub font_size: f64,
+    pub box_width: f64,
+    ///The space between the boxes of one generation and the next
+    pub generation_gap: f64,
+    ///The space between the boxes of one generation
+    pub sibling_gap: f64,
+}
+impl Default for ChartOptions {
+    fn default() -> Self {
+        ChartOptions {
+            generations: 4,
+            dates: true,
+            attributes: Vec::new(),
+            font_family: "sans-serif".into(),
+            font_size: 12.0,
+            box_width: 160.0,
+            generation_gap: 40.0,
+            sibling_gap: 12.0,
+        }
+    }
+}
+
+pub(crate) const MARGIN: f64 = 10.0;
+
+//a box of a chart, with the generation it is in, counted out from the focus person, and its
+//place across the generation in slots
+struct ChartBox {
+    n: Nd,
+    generation: i32,
+    slot: f64,
+}
+
+//the boxes of a chart, and the pairs of boxes joined by lines, from the one nearer the focus
+#[derive(Default)]
+struct Chart {
+    boxes: Vec<ChartBox>,
+    lines: Vec<(usize, usize)>,
+}
+impl Chart {
+    //adds the tree of n, given the persons of the next generation out from each person, and
+    //returns the slot of n. Leaves take the next free slot, and others are centered on the
+    //persons they lead to.
+    fn add_tree(
+        &mut self,
+        n: Nd,
+        generation: i32,
+        step: i32,
+        next: &dyn Fn(Nd) -> Vec<Nd>,
+        limit: usize,
+        free: &mut f64,
+    ) -> usize {
+        let i = self.boxes.len();
+        self.boxes.push(ChartBox {
+            n,
+            generation,
+            slot: 0.0,
+        });
+        let outward = match generation.unsigned_abs() as usize >= limit {
+            true => Vec::new(),
+            false => next(n),
+        };
+        let mut slots = Vec::new();
+        for m in outward {
+            let j = self.add_tree(m, generation + step, step, next, limit, free);
+            self.lines.push((i, j));
+            slots.push(self.boxes[j].slot);
+        }
+        self.boxes[i].slot = match (slots.first(), slots.last()) {
+            (Some(first), Some(last)) => (first + last) / 2.0,
+            _ => {
+                *free += 1.0;
+                *free - 1.0
+            }
+        };
+        i
+    }
+    //moves the boxes from the given one on across the chart
+    fn shift(&mut self, from: usize, by: f64) {
+        for b in &mut self.boxes[from..] {
+            b.slot += by;
+        }
+    }
+}
+
+//rounds a coordinate for writing, without a sign on zero
+pub(crate) fn num(x: f64) -> String {
+    ((x * 10.0).round() / 10.0 + 0.0).to_string()
+}
+
+impl KinGraph {
+    //the parents of a person, fathers first
+    pub(crate) fn chart_parents(&self, n: Nd) -> Vec<Nd> {
+        let mut parents = self.parents_of(n);
+        parents.sort_by_key(|p| (self.graph[*p].sex != Sex::Male, *p));
+        parents
+    }
+    //the children of a person, from the first born
+    pub(crate) fn chart_children(&self, n: Nd) -> Vec<Nd> {
+        let mut children = self.children_of(n);
+        children.sort_by(|a, b| self.birth_order(*a, *b).then(a.cmp(b)));
+        children
+    }
+    //the lines of text in the box of a person
+    fn box_lines(&self, p: &Person, opts: &ChartOptions) -> Vec<String> {
+        let name = p.primary_name().map(|name| name.to_string());
+        let mut lines = vec![name.unwrap_or_else(|| p.name.clone())];
+        if opts.dates {
+            lines.push(self.life_dates(p).unwrap_or_default());
+        }
+        for key in &opts.attributes {
+            let line = match p.attributes.get(key) {
+                Some(AttrValue::Date(d)) => format!("{}: {}", key, self.format_date(d)),
+                Some(value) => format!("{}: {}", key, value),
+                None => String::new(),
+            };
+            lines.push(line);
+        }
+        lines
+    }
+    ///Draws the ancestors of a person as an SVG pedigree chart, from left to right
+    pub fn pedigree_svg(&self, p: &Person, opts: &ChartOptions) -> Result<String> {
+        let mut chart = Chart::default();
+        let parents = |n| self.chart_parents(n);
+        let focus = self.node(p)?;
+        chart.add_tree(focus, 0, 1, &parents, opts.generations, &mut 0.0);
+        Ok(self.chart_svg(&chart, false, opts))
+    }
+    ///Draws the descendants of a person as an SVG drop-line chart, down the page
+    pub fn descendants_svg(&self, p: &Person, opts: &ChartOptions) -> Result<String> {
+        let mut chart = Chart::default();
+        let children = |n| self.chart_children(n);
+        let focus = self.node(p)?;
+        chart.add_tree(focus, 0, 1, &children, opts.generations, &mut 0.0);
+        Ok(self.chart_svg(&chart, true, opts))
+    }
+    ///Draws the ancestors of a person above them, and their descendants below them, as an SVG
+    /// hourglass chart
+    pub fn hourglass_svg(&self, p: &Person, opts: &ChartOptions) -> Result<String> {
+        let mut chart = Chart::default();
+        let parents = |n| self.chart_parents(n);
+        let children = |n| self.chart_children(n);
+        let focus = self.node(p)?;
+        chart.add_tree(focus, 0, -1, &parents, opts.generations, &mut 0.0);
+        let below = chart.boxes.len();
+        let root = chart.add_tree(focus, 0, 1, &children, opts.generations, &mut 0.0);
+        //both trees meet at the focus person, which is drawn once
+        let by = chart.boxes[0].slot - chart.boxes[root].slot;
+        chart.shift(below, by);
+        chart.boxes.remove(root);
+        for (from, to) in &mut chart.lines {
+            for i in [from, to] {
+                if *i == root {
+                    *i = 0;
+                } else if *i > root {
+                    *i -= 1;
+                }
+            }
+        }
+        Ok(self.chart_svg(&chart, true, opts))
+    }
+    //draws the boxes of a chart and the lines between them, with generations in rows when it is
+    //vertical and in columns otherwise
+    fn chart_svg(&self, chart: &Chart, vertical: bool, opts: &ChartOptions) -> String {
+        let line_height = opts.font_size * 1.25;
+        let padding = opts.font_size / 2.0;
+        let lines = 1 + opts.dates as usize + opts.attributes.len();
+        let box_height = 2.0 * padding + lines as f64 * line_height;
+        //the size of a box along and across the generations
+        let (along, across) = match vertical {
+            true => (box_height, opts.box_width),
+            false => (opts.box_width, box_height),
+        };
+        let generation_step = along + opts.generation_gap;
+        let slot_step = across + opts.sibling_gap;
+        let first = chart.boxes.iter().map(|b| b.generation).min().unwrap_or(0);
+        let first_slot = chart.boxes.iter().map(|b| b.slot).fold(0.0, f64::min);
+        //the top left corner of a box, along and across the generations
+        let corner = |b: &ChartBox| {
+            (
+                MARGIN + (b.generation - first) as f64 * generation_step,
+                MARGIN + (b.slot - first_slot) * slot_step,
+            )
+        };
+        let point = |along: f64, across: f64| match vertical {
+            true => format!("{} {}", num(across), num(along)),
+            false => format!("{} {}", num(along), num(across)),
+        };
+        let (mut width, mut height) = (0.0, 0.0);
+        for b in &chart.boxes {
+            let (a, c) = corner(b);
+            let (x, y) = match vertical {
+                true => (c, a),
+                false => (a, c),
+            };
+            width = f64::max(width, x + opts.box_width + MARGIN);
+            height = f64::max(height, y + box_height + MARGIN);
+        }
+        let mut svg = Element::new("svg")
+            .with_attr("xmlns", "http://www.w3.org/2000/svg")
+            .with_attr("width", num(width))
+            .with_attr("height", num(height))
+            .with_attr("viewBox", format!("0 0 {} {}", num(width), num(height)))
+            .with_attr("font-family", opts.font_family.as_str())
+            .with_attr("font-size", num(opts.font_size));
+        let mut paths = Element::new("g")
+            .with_attr("fill", "none")
+            .with_attr("stroke", "#555");
+        for (from, to) in &chart.lines {
+            let (a, b) = (&chart.boxes[*from], &chart.boxes[*to]);
+            let ((a_along, a_across), (b_along, b_across)) = (corner(a), corner(b));
+            //lines leave a box on the side facing the next generation
+            let outward = b.generation.abs() > a.generation.abs();
+            let forward = (b.generation > a.generation) == outward;
+            let start = a_along + if forward { along } else { 0.0 };
+            let end = b_along + if forward { 0.0 } else { along };
+            let middle = (start + end) / 2.0;
+            let (a_center, b_center) = (a_across + across / 2.0, b_across + across / 2.0);
+            let d = format!(
+                "M {} L {} L {} L {}",
+                point(start, a_center),
+                point(middle, a_center),
+                point(middle, b_center),
+                point(end, b_center)
+            );
+            paths = paths.with(Element::new("path").with_attr("d", d));
+        }
+        svg = svg.with(paths);
+        //roughly how many characters fit on a line
+        let fit = ((opts.box_width - 2.0 * padding) / (opts.font_size * 0.6)).max(1.0) as usize;
+        for b in &chart.boxes {
+            let p = &self.graph[b.n];
+            let (a, c) = corner(b);
+            let (x, y) = match vertical {
+                true => (c, a),
+                false => (a, c),
+            };
+            let fill = match p.sex {
+                Sex::Male => "#dbe8f6",
+                Sex::Female => "#f6dbe8",
+            };
+            let mut rect = Element::new("rect")
+                .with_attr("x", num(x))
+                .with_attr("y", num(y))
+                .with_attr("width", num(opts.box_width))
+                .with_attr("height", num(box_height))
+                .with_attr("rx", num(padding / 2.0))
+                .with_attr("fill", fill)
+                .with_attr("stroke", "#333");
+            if p.is_shadow {
+                rect = rect.with_attr("stroke-dasharray", "4 2");
+            }
+            if b.generation == 0 {
+                rect = rect.with_attr("stroke-width", "2");
+            }
+            let mut group = Element::new("g").with(rect);
+            for (i, line) in self.box_lines(p, opts).iter().enumerate() {
+                if line.is_empty() {
+                    continue;
+                }
+                let text = match line.chars().count() > fit {
+                    true => line.chars().take(fit - 1).collect::<String>() + "…",
+                    false => line.clone(),
+                };
+                let baseline = y + padding + i as f64 * line_height + opts.font_size;
+                let mut text = Element::text("text", &text)
+                    .with_attr("x", num(x + opts.box_width / 2.0))
+                    .with_attr("y", num(baseline))
+                    .with_attr("text-anchor", "middle");
+                if i == 0 {
+                    text = text.with_attr("font-weight", "bold");
+                }
+                group = group.with(text);
+            }
+            svg = svg.with(group);
+        }
+        let mut out = String::new();
+        svg.write(&mut out, 0);
+        out
+    }
+}
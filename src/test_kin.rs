@@ -5,6 +5,11 @@ use self::kin_dsl::parse_relations_from_dsl;
 
 use super::*;
 
+//the DOT files rendered by the tests are written to the temp dir, so that running them doesn't
+//change the examples in the repository
+fn dot_file(name: &str) -> std::fs::File {
+    std::fs::File::create(std::env::temp_dir().join(name)).unwrap()
+}
 fn setup_cousins() -> Result<KinGraph> {
     let mut kg = KinGraph::new();
     let p0 = kg.np(Sex::Male);
@@ -129,9 +134,8 @@ pub fn cousins() {
     let mut kg = KinGraph::new();
     let states = query_kin(&cousin_dsl, &mut kg).unwrap();
     tracing::warn!(relationships=?states);
-    use std::fs::File;
-    let mut f = File::create("cousins.dot").unwrap();
-    render_to(&mut f, &kg);
+    let mut f = dot_file("cousins.dot");
+    kg.write_dot(&mut f, &DotOptions::default()).unwrap();
 
     let res = states.iter().any(|s| {
         s.get_hash()
@@ -150,9 +154,8 @@ pub fn cousins() {
 pub fn half_siblings() {
     tracing_subscriber::fmt().init();
     let kg = setup_half_siblings().unwrap();
-    use std::fs::File;
-    let mut f = File::create("half_siblings.dot").unwrap();
-    render_to(&mut f, &kg);
+    let mut f = dot_file("half_siblings.dot");
+    kg.write_dot(&mut f, &DotOptions::default()).unwrap();
     let states = kg.get_canonical_relationships(kg.px(3), kg.px(4)).unwrap();
     tracing::info!(relations=?states);
     let res = states.iter().any(|s| {
@@ -168,17 +171,15 @@ pub fn half_siblings() {
 #[test]
 pub fn incest() {
     let kg = KinGraph::new();
-    use std::fs::File;
-    let mut f = File::create("incest.dot").unwrap();
-    render_to(&mut f, &kg);
+    let mut f = dot_file("incest.dot");
+    kg.write_dot(&mut f, &DotOptions::default()).unwrap();
 }
 ///Test nephew/niece and aunt/uncle relationship
 #[test]
 pub fn nn_au() {
     let kg = setup_nn_au().unwrap();
-    use std::fs::File;
-    let mut f = File::create("nn_au.dot").unwrap();
-    render_to(&mut f, &kg);
+    let mut f = dot_file("nn_au.dot");
+    kg.write_dot(&mut f, &DotOptions::default()).unwrap();
     let nn_states = kg.get_canonical_relationships(kg.px(2), kg.px(0)).unwrap();
     println!("NN_AU TEST: {:?}", nn_states);
     let res = nn_states.iter().any(|s| {
@@ -257,9 +258,8 @@ fn test_main() -> Result<()> {
     kg.add_relation(&p5, &p6, Kind::Parent).unwrap();
 
     println!("{:?}", kg.get_canonical_relationships(&p4, &p0).unwrap());
-    use std::fs::File;
-    let mut f = File::create("out.dot").unwrap();
-    render_to(&mut f, &kg);
+    let mut f = dot_file("out.dot");
+    kg.write_dot(&mut f, &DotOptions::default()).unwrap();
 
     Ok(())
 }
@@ -289,9 +289,8 @@ fn test_incest() -> Result<()> {
     //get rel between Izy and John
     let rel = kg.get_canonical_relationships(kg.px(0), kg.px(1));
     //render the graph to a file
-    use std::fs::File;
-    let mut f = File::create("incest.dot").unwrap();
-    render_to(&mut f, &kg);
+    let mut f = dot_file("incest.dot");
+    kg.write_dot(&mut f, &DotOptions::default()).unwrap();
 
     println!("Relationships are {:?}", rel);
     Ok(())
@@ -323,3 +322,2061 @@ fn find_multiple_paths() -> Result<()> {
     println!("{:?}", rels);
     Ok(())
 }
+fn relationship_names(kg: &KinGraph, p1: usize, p2: usize, opts: &QueryOptions) -> Vec<String> {
+    kg.get_canonical_relationships_with(kg.px(p1), kg.px(p2), opts)
+        .unwrap()
+        .iter()
+        .map(|s| s.print_canonical_name())
+        .collect()
+}
+#[test]
+fn adoptive_and_foster() -> Result<()> {
+    let dsl = r#"
+    Ann F ADOPTIVE_PARENT Bob M
+    Kid M FOSTER_CHILD Carer F
+    "#;
+    let mut kg = KinGraph::new();
+    parse_relations_from_dsl(dsl, &mut kg)?;
+    let opts = QueryOptions::default();
+    assert!(relationship_names(&kg, 0, 1, &opts).contains(&"adoptive mother".to_string()));
+    assert!(relationship_names(&kg, 1, 0, &opts).contains(&"adoptive son".to_string()));
+    assert!(relationship_names(&kg, 2, 3, &opts).contains(&"foster son".to_string()));
+    //adoptive links can be counted as blood
+    let blood = QueryOptions {
+        adoptive_as_blood: true,
+        ..Default::default()
+    };
+    assert!(relationship_names(&kg, 0, 1, &blood).contains(&"mother".to_string()));
+    //but fostering never is
+    assert!(relationship_names(&kg, 2, 3, &blood).contains(&"foster son".to_string()));
+    Ok(())
+}
+#[test]
+fn step_relations() -> Result<()> {
+    let dsl = r#"
+    Mom F PARENT Me M
+    Mom F SPOUSE Steve M
+    Steve M PARENT Sue F
+    "#;
+    let mut kg = KinGraph::new();
+    parse_relations_from_dsl(dsl, &mut kg)?;
+    let opts = QueryOptions::default();
+    //Mom 0, Me 1, Steve 2, Sue 3
+    assert!(relationship_names(&kg, 2, 1, &opts).contains(&"step-father".to_string()));
+    assert!(relationship_names(&kg, 3, 0, &opts).contains(&"step-daughter".to_string()));
+    assert!(relationship_names(&kg, 1, 3, &opts).contains(&"step-brother".to_string()));
+    //Mom is still just Me's mother
+    let names = relationship_names(&kg, 0, 1, &opts);
+    assert!(!names.iter().any(|n| n.starts_with("step-")));
+
+    //a reproductive partner of a parent who is not married to them is not a step-parent
+    let dsl = r#"
+    Mom F PARENT Me M
+    Mom F RP Steve M
+    Steve M PARENT Sue F
+    "#;
+    let mut kg = KinGraph::new();
+    parse_relations_from_dsl(dsl, &mut kg)?;
+    for (p1, p2) in [(2, 1), (3, 0), (1, 3), (0, 3), (1, 2)] {
+        let names = relationship_names(&kg, p1, p2, &opts);
+        assert!(!names.iter().any(|n| n.starts_with("step-")), "{:?}", names);
+    }
+    Ok(())
+}
+#[test]
+fn biological_and_legal_parentage() -> Result<()> {
+    let mut kg = KinGraph::new();
+    let mom = kg.np(Sex::Female);
+    let donor = kg.np(Sex::Male);
+    let dad = kg.np(Sex::Male);
+    let child = kg.np(Sex::Female);
+    let sibling = kg.np(Sex::Male);
+    kg.add_parent_with(&mom, &child, Parentage::default())?;
+    kg.add_parent_with(&donor, &child, Parentage::biological_only())?;
+    kg.add_parent_with(&dad, &child, Parentage::legal_only())?;
+    kg.make_child(&sibling, &mom, &donor)?;
+    //a third biological parent is not allowed
+    let other = kg.np(Sex::Male);
+    assert!(kg
+        .add_parent_with(&other, &child, Parentage::biological_only())
+        .is_err());
+    assert_eq!(
+        kg.get_parentage(&dad, &child),
+        Some(Parentage::legal_only())
+    );
+
+    assert!(kg.is_rbb(&donor, &child));
+    assert!(!kg.is_rbb(&dad, &child));
+    assert_eq!(kg.relationship_coefficient(&donor, &child), 0.5);
+    assert_eq!(kg.relationship_coefficient(&dad, &child), 0.0);
+    assert_eq!(kg.relationship_coefficient(&sibling, &child), 0.5);
+
+    let legal = QueryOptions {
+        parentage: ParentageView::Legal,
+        ..Default::default()
+    };
+    let biological = QueryOptions {
+        parentage: ParentageView::Biological,
+        ..Default::default()
+    };
+    assert!(relationship_names(&kg, 2, 3, &legal).contains(&"father".to_string()));
+    assert!(!relationship_names(&kg, 1, 3, &legal).contains(&"father".to_string()));
+    assert!(relationship_names(&kg, 1, 3, &biological).contains(&"father".to_string()));
+    assert!(!relationship_names(&kg, 2, 3, &biological).contains(&"father".to_string()));
+    Ok(())
+}
+#[test]
+fn half_sibling_coefficient() -> Result<()> {
+    let kg = setup_half_siblings()?;
+    assert_eq!(kg.relationship_coefficient(kg.px(3), kg.px(4)), 0.25);
+    assert_eq!(kg.relationship_coefficient(kg.px(0), kg.px(4)), 0.0);
+    Ok(())
+}
+#[test]
+fn spouses_and_in_laws() -> Result<()> {
+    let dsl = r#"
+    Ann F PARENT Bob M
+    Bob M SPOUSE Cat F
+    Cat F SIBLING Dee F
+    "#;
+    let mut kg = KinGraph::new();
+    parse_relations_from_dsl(dsl, &mut kg)?;
+    let opts = QueryOptions::default();
+    //Ann 0, Bob 1, Cat 2, Dee 3
+    assert!(relationship_names(&kg, 1, 2, &opts).contains(&"husband".to_string()));
+    assert!(relationship_names(&kg, 2, 1, &opts).contains(&"wife".to_string()));
+    assert!(relationship_names(&kg, 0, 2, &opts).contains(&"mother in law".to_string()));
+    assert!(relationship_names(&kg, 3, 1, &opts).contains(&"sister in law".to_string()));
+
+    let (bob, cat) = (kg.px(1).clone(), kg.px(2).clone());
+    kg.add_partnership(&bob, &cat, Partnership::new(MaritalStatus::Divorced))?;
+    assert!(relationship_names(&kg, 2, 1, &opts).contains(&"ex-wife".to_string()));
+
+    let dsl = r#"
+    Eve F PARENT Kid M
+    Fred M PARENT Kid M
+    Ann F PARENT Fred M
+    "#;
+    let mut kg = KinGraph::new();
+    parse_relations_from_dsl(dsl, &mut kg)?;
+    //Eve only had a child with Fred, so Ann is not her mother in law
+    let names = relationship_names(&kg, 3, 0, &opts);
+    assert!(!names.iter().any(|n| n.ends_with("in law")));
+    Ok(())
+}
+#[test]
+fn relationships_as_of() -> Result<()> {
+    let dsl = r#"
+    Bob M SPOUSE Cat F DIVORCED FROM 1900 UNTIL 1 Jun 1910
+    Cat F SIBLING Dee F
+    Bob M BORN abt 1870
+    Bob M DIED 3 Feb 1950
+    "#;
+    let mut kg = KinGraph::new();
+    parse_relations_from_dsl(dsl, &mut kg)?;
+    let (bob, cat, dee) = (kg.px(0).clone(), kg.px(1).clone(), kg.px(2).clone());
+    assert_eq!(
+        kg.get_partnership(&bob, &cat),
+        Some(&Partnership {
+            status: MaritalStatus::Divorced,
+            start: Some(Date::year(1900).into()),
+            end: Some(Date::ymd(1910, 6, 1).into()),
+        })
+    );
+    let names = |p1: &Person, p2: &Person, year: i32| -> Vec<String> {
+        kg.get_canonical_relationships_as_of(p1, p2, Date::year(year))
+            .unwrap()
+            .relationships
+            .iter()
+            .map(|s| s.print_canonical_name())
+            .collect()
+    };
+    assert!(names(&cat, &bob, 1890).is_empty());
+    assert!(names(&cat, &bob, 1905).contains(&"wife".to_string()));
+    assert!(names(&dee, &bob, 1905).contains(&"sister in law".to_string()));
+    assert!(names(&cat, &bob, 1915).contains(&"ex-wife".to_string()));
+    assert!(names(&dee, &bob, 1915).contains(&"former sister in law".to_string()));
+
+    let res = kg.get_canonical_relationships_as_of(&cat, &bob, Date::year(1860))?;
+    assert_eq!(res.p2_status, LifeStatus::Unborn);
+    let res = kg.get_canonical_relationships_as_of(&cat, &bob, Date::year(1960))?;
+    assert_eq!(res.p2_status, LifeStatus::Deceased);
+    assert_eq!(res.p1_status, LifeStatus::Living);
+    Ok(())
+}
+#[test]
+fn stale_handle_after_set_birth() -> Result<()> {
+    let mut kg = KinGraph::new();
+    parse_relations_from_dsl("Bob M PARENT Cat F", &mut kg)?;
+    let bob = kg.px(0).clone();
+    kg.set_birth(&bob, Some("abt 1870".parse()?))?;
+    //the handle no longer matches the stored person field for field, but is still the same person
+    let names = kg.get_canonical_relationships(&bob, kg.px(0))?;
+    assert_eq!(names.len(), 1);
+    assert_eq!(names[0].print_canonical_name(), "Stop");
+    let res = kg.get_canonical_relationships_as_of(kg.px(0), &bob, Date::year(1900))?;
+    assert_eq!(res.relationships[0].print_canonical_name(), "Stop");
+    assert_eq!(res.p1_status, LifeStatus::Living);
+    Ok(())
+}
+#[test]
+fn handle_from_another_graph() -> Result<()> {
+    let mut kg = KinGraph::new();
+    parse_relations_from_dsl("Bob M PARENT Cat F", &mut kg)?;
+    //a handle kept from a graph that has since been replaced by a smaller one
+    let mut old = KinGraph::new();
+    parse_relations_from_dsl("Ann F PARENT Bob M\nBob M PARENT Cat F", &mut old)?;
+    let cat = old.px(2).clone();
+    let missing = |res: Result<()>| {
+        matches!(
+            res.err().unwrap().downcast::<KinError>().unwrap(),
+            KinError::MissingNode { id: 2 }
+        )
+    };
+    assert!(missing(kg.set_birth(&cat, None)));
+    assert!(missing(kg.set_death(&cat, None)));
+    assert!(missing(kg.set_attr(&cat, "age", 3.0.into())));
+    assert!(missing(kg.set_external_ref(&cat, "novella", "cat")));
+    assert!(missing(kg.set_name(&cat, "Cat /Smith/".parse()?)));
+    assert!(missing(kg.add_alternate_name(&cat, "Kitty".parse()?)));
+    let bob = kg.px(0).clone();
+    assert!(missing(kg.add_relation(&bob, &cat, Kind::Sibling)));
+    assert!(missing(kg.get_canonical_relationships(&bob, &cat).map(|_| ())));
+    assert_eq!(kg.remove_attr(&cat, "age"), None);
+    Ok(())
+}
+#[test]
+fn custom_calendar_queries() -> Result<()> {
+    //thirteen months of 28 days, counted from the founding of the city
+    let months = (1..=13).map(|i| format!("Moon{}", i)).collect::<Vec<_>>();
+    let months = months.iter().map(|m| (m.as_str(), 28)).collect::<Vec<_>>();
+    let calendar = CustomCalendar::new("Lunar", &months)?.with_era("AF", 1, false);
+    let mut kg = KinGraph::new();
+    kg.set_calendar(Box::new(calendar));
+    let dsl = r#"
+    Ana F SPOUSE Ben M
+    Ana F BORN 3 Moon2 320 AF
+    Ben M BORN abt 318 AF
+    Ben M DIED 28 Moon13 360 AF
+    "#;
+    parse_relations_from_dsl(dsl, &mut kg)?;
+    let (ana, ben) = (kg.px(0).clone(), kg.px(1).clone());
+    let date = |s: &str| kg.parse_date(s).map(|d| d.date());
+    assert_eq!(kg.format_date(&ana.birth().unwrap()), "3 Moon2 320 AF");
+    assert_eq!(
+        kg.age_as_of(&ana, date("2 Moon2 340 AF")?),
+        Some((19, Some(19)))
+    );
+    assert_eq!(
+        kg.age_as_of(&ana, date("3 Moon2 340 AF")?),
+        Some((20, Some(20)))
+    );
+    assert_eq!(kg.age_as_of(&ana, date("1 Moon1 300 AF")?), None);
+    assert_eq!(kg.age_as_of(&ben, date("340 AF")?), Some((16, Some(27))));
+    //the thirteenth month does not exist in the Gregorian calendar
+    let res = kg.get_canonical_relationships_as_of(&ana, &ben, date("28 Moon13 360 AF")?)?;
+    assert_eq!(res.p2_status, LifeStatus::Deceased);
+    let res = kg.get_canonical_relationships_as_of(&ana, &ben, date("27 Moon13 360 AF")?)?;
+    assert_eq!(res.p2_status, LifeStatus::Living);
+    let names = res
+        .relationships
+        .iter()
+        .map(|s| s.print_canonical_name())
+        .collect::<Vec<_>>();
+    assert!(names.contains(&"wife".to_string()));
+    let by_birth = kg
+        .by_birth()
+        .iter()
+        .map(|p| p.name.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(by_birth, ["Ben", "Ana"]);
+
+    //the calendar is saved along with the graph, so its dates read back the same
+    let mut binary = Vec::new();
+    kg.write_binary(&mut binary)?;
+    for restored in [
+        KinGraph::from_json(&kg.to_json()?)?,
+        KinGraph::read_binary(&binary[..])?,
+    ] {
+        assert_eq!(restored.calendar().name(), "Lunar");
+        assert_eq!(restored.to_document(), kg.to_document());
+        assert_eq!(
+            restored.format_date(&ana.birth().unwrap()),
+            "3 Moon2 320 AF"
+        );
+    }
+    let mut bad = serde_json::to_value(kg.to_document())?;
+    bad["calendar"]["months"] = serde_json::json!([]);
+    assert!(KinGraph::from_json(&bad.to_string()).is_err());
+    Ok(())
+}
+#[test]
+fn attributes_and_external_refs() -> Result<()> {
+    let mut kg = KinGraph::new();
+    let mom = kg.np_with_name(Sex::Female, "Mom".to_string());
+    let kid = kg.np_with_name(Sex::Male, "Kid".to_string());
+    kg.add_parent(&mom, &kid)?;
+    kg.set_attr(&kid, "occupation", "scribe".into())?;
+    kg.set_attr(&kid, "age", 12.0.into())?;
+    kg.set_relation_attr(&kid, &mom, Kind::Child, "note", "estranged".into())?;
+    kg.set_external_ref(&kid, "novella", "c1")?;
+    assert!(kg.set_external_ref(&mom, "novella", "c1").is_err());
+    //a new id replaces the old one in the index
+    kg.set_external_ref(&kid, "novella", "c2")?;
+    assert!(kg.find_by_ref("novella", "c1").is_none());
+    assert_eq!(
+        kg.find_by_ref("novella", "c2").unwrap().get_id(),
+        kid.get_id()
+    );
+    assert_eq!(kg.get_attr(&kid, "age"), Some(&AttrValue::Number(12.0)));
+    assert_eq!(kg.remove_attr(&kid, "age"), Some(AttrValue::Number(12.0)));
+    let stranger = kg.np_with_name(Sex::Male, "Stranger".to_string());
+    assert!(kg
+        .set_relation_attr(&kid, &stranger, Kind::Child, "note", true.into())
+        .is_err());
+
+    let json = serde_json::to_value(kg.as_wasm_graph())?;
+    let node = &json["nodes"][1];
+    assert_eq!(node["attributes"]["occupation"]["value"], "scribe");
+    assert_eq!(node["refs"]["novella"], "c2");
+    assert_eq!(
+        node["relation_attributes"]["0"]["Child"]["note"]["value"],
+        "estranged"
+    );
+    assert!(json["nodes"][2].get("attributes").is_none());
+
+    let dot = kg.to_dot(&DotOptions::default());
+    assert!(dot.contains(r#"n1 [label="Kid\noccupation: scribe", shape=box];"#));
+    assert!(dot.contains(r#"n0 -> n1 [label="P\nnote: estranged"];"#));
+    assert!(dot.contains(r#"n2 [label="Stranger", shape=box];"#));
+    Ok(())
+}
+#[test]
+fn name_search() -> Result<()> {
+    let mut kg = KinGraph::new();
+    let anna = kg.np(Sex::Female);
+    let mut name: PersonName = "Anna /Bergman/".parse()?;
+    name.birth_surname = Some("Lind".to_string());
+    kg.set_name(&anna, name)?;
+    let erik = kg.np_with_name(Sex::Male, "Erik".to_string());
+    kg.add_alternate_name(&erik, PersonName::new(&["Erik"], Some("Lindqvist")))?;
+    let ids = |found: Vec<&Person>| found.iter().map(|p| p.get_id()).collect::<Vec<_>>();
+    assert!(kg.find_by_name("unkn").is_empty());
+    assert_eq!(ids(kg.find_by_name("lind")), [anna.get_id()]);
+    assert_eq!(
+        ids(kg.search_names("lind", NameMatch::Prefix)),
+        [anna.get_id(), erik.get_id()]
+    );
+    assert_eq!(
+        ids(kg.search_names("Bergmann", NameMatch::Fuzzy)),
+        [anna.get_id()]
+    );
+    //renaming drops the old name from the index
+    kg.set_name(&anna, PersonName::new(&["Anna"], Some("Berg")))?;
+    assert!(kg.find_by_name("bergman").is_empty());
+    assert_eq!(
+        kg.px(1).primary_name().unwrap().to_string(),
+        "Erik Lindqvist"
+    );
+    Ok(())
+}
+#[test]
+fn graph_serde_round_trip() -> Result<()> {
+    let dsl = r#"
+    Kid M CHILD Mom F
+    Kid M CHILD Dad M
+    Dad M SPOUSE Mom F
+    Kid M SIBLING Sis F
+    Kid M FOSTER_CHILD Carer F
+    Kid M BORN abt 1900
+    Kid M ATTR tags [a, 1, date(bef 1910)]
+    Kid M REF novella k1
+    Kid M NAME Karl "Kid" /Berg/
+    Dad M REL_ATTR PARENT Kid M note adopted later
+    Dad M REL_ATTR RP Mom F note met in York
+    Mom F REL_ATTR SPOUSE Dad M note wed in Leeds
+    "#;
+    let mut kg = KinGraph::new();
+    parse_relations_from_dsl(dsl, &mut kg)?;
+    let (sis, donor) = (kg.px(3).clone(), kg.np_with_name(Sex::Male, "Donor".into()));
+    kg.add_parent_with(&donor, &sis, Parentage::biological_only())?;
+    let (dad, mom) = (kg.px(2).clone(), kg.px(1).clone());
+    kg.add_partnership(
+        &dad,
+        &mom,
+        Partnership {
+            start: Some(Date::year(1895).into()),
+            ..Partnership::new(MaritalStatus::Partnered)
+        },
+    )?;
+    kg.graph[NodeIndex::new(3)].is_shadow = true;
+
+    let json = kg.to_json()?;
+    let restored = KinGraph::from_json(&json)?;
+    assert_eq!(restored.to_json()?, json);
+    assert_eq!(restored.to_document(), kg.to_document());
+    let doc = kg.to_document();
+    assert_eq!(doc.version, SCHEMA_VERSION);
+    //each relation is written once
+    assert_eq!(doc.relations.len() * 2, kg.graph.edge_count());
+    assert!(restored.px(3).is_shadow());
+    assert_eq!(restored.find_by_ref("novella", "k1").unwrap().name(), "Kid");
+    assert_eq!(restored.find_by_name("karl berg").len(), 1);
+    //the relations between the same two persons keep their own attributes
+    let note = |kind| {
+        restored
+            .relation_attrs(&dad, &mom, kind)
+            .map(|a| a["note"].to_string())
+    };
+    assert_eq!(note(Kind::RP).as_deref(), Some("met in York"));
+    assert_eq!(note(Kind::Spouse).as_deref(), Some("wed in Leeds"));
+    let names = |kg: &KinGraph, p1: usize, p2: usize| {
+        let mut names = relationship_names(kg, p1, p2, &QueryOptions::default());
+        names.sort();
+        names
+    };
+    for (p1, p2) in [(0, 1), (3, 0), (4, 0), (5, 3), (2, 1)] {
+        assert_eq!(names(&restored, p1, p2), names(&kg, p1, p2));
+    }
+
+    let mut bad = serde_json::to_value(&doc)?;
+    bad["version"] = 3.into();
+    assert!(KinGraph::from_json(&bad.to_string()).is_err());
+    let mut bad = serde_json::to_value(&doc)?;
+    bad["relations"][0]["to"] = 99.into();
+    assert!(KinGraph::from_json(&bad.to_string()).is_err());
+
+    //relations that could not have been added to a graph are rejected
+    let persons = (0..4).map(|_| Person::new(Sex::Female)).collect::<Vec<_>>();
+    let rel = |from, to, kind| RelationRecord {
+        from,
+        to,
+        kind,
+        parentage: None,
+        partnership: None,
+        attributes: Attributes::default(),
+    };
+    let doc = |relations| GraphDocument {
+        version: SCHEMA_VERSION,
+        persons: persons.clone(),
+        relations,
+        calendar: None,
+    };
+    assert!(
+        KinGraph::from_document(doc(vec![rel(1, 0, Kind::Parent), rel(2, 0, Kind::Parent)]))
+            .is_ok()
+    );
+    for relations in [
+        vec![
+            rel(1, 0, Kind::Parent),
+            rel(2, 0, Kind::Parent),
+            rel(3, 0, Kind::Parent),
+        ],
+        vec![rel(0, 1, Kind::Parent), rel(1, 0, Kind::Parent)],
+        vec![rel(0, 1, Kind::Parent), rel(0, 1, Kind::Child)],
+        vec![rel(0, 1, Kind::Guardian), rel(0, 1, Kind::Ward)],
+    ] {
+        assert!(KinGraph::from_document(doc(relations)).is_err());
+    }
+    Ok(())
+}
+#[test]
+fn migrate_wasm_graph_document() -> Result<()> {
+    let dsl = r#"
+    Kid M CHILD Mom F
+    Kid M CHILD Dad M
+    Kid M SIBLING Sis F
+    Mom F ATTR occupation baker
+    "#;
+    let mut kg = KinGraph::new();
+    parse_relations_from_dsl(dsl, &mut kg)?;
+    //the layout written by get_graph, which has no version
+    let v1 = serde_json::to_string(&kg.as_wasm_graph())?;
+    let restored = KinGraph::from_json(&v1)?;
+    assert_eq!(restored.graph.edge_count(), kg.graph.edge_count());
+    assert_eq!(
+        restored.get_attr(restored.px(1), "occupation"),
+        Some(&"baker".into())
+    );
+    assert_eq!(
+        relationship_names(&restored, 3, 0, &QueryOptions::default()),
+        ["sister"]
+    );
+    Ok(())
+}
+#[test]
+fn load_wasm_graph() -> Result<()> {
+    use kin_wasm::{KinWasmGraph, PersonNode, Relation, RelationKind};
+    let dsl = r#"
+    Kid M CHILD Mom F
+    Kid M CHILD Dad M
+    Mom F SPOUSE Dad M
+    "#;
+    let mut kg = KinGraph::new();
+    parse_relations_from_dsl(dsl, &mut kg)?;
+    let restored = KinGraph::from_wasm_graph(kg.as_wasm_graph())?;
+    assert_eq!(restored.graph.edge_count(), kg.graph.edge_count());
+    let sorted_names = |kg: &KinGraph| {
+        let mut names = relationship_names(kg, 1, 2, &QueryOptions::default());
+        names.sort();
+        names
+    };
+    assert_eq!(
+        sorted_names(&restored),
+        ["female reproductive partner", "wife"]
+    );
+    assert_eq!(sorted_names(&restored), sorted_names(&kg));
+
+    let node = |id, relations: &[(u32, RelationKind)]| {
+        let relations = relations
+            .iter()
+            .map(|&(id, kind)| Relation { id, kind })
+            .collect();
+        PersonNode::new(id, Sex::Female, format!("p{}", id), relations)
+    };
+    let error = |nodes| {
+        KinGraph::from_wasm_graph(KinWasmGraph::new(nodes))
+            .err()
+            .unwrap()
+            .downcast::<KinError>()
+            .unwrap()
+    };
+    assert!(matches!(
+        error(vec![
+            node(0, &[(1, RelationKind::Parent)]),
+            node(1, &[(0, RelationKind::Parent)]),
+        ]),
+        KinError::AsymmetricRelation { p1: 0, p2: 1 }
+    ));
+    assert!(matches!(
+        error(vec![node(0, &[(1, RelationKind::Sibling)]), node(1, &[])]),
+        KinError::AsymmetricRelation { p1: 0, p2: 1 }
+    ));
+    assert!(matches!(
+        error(vec![node(0, &[(7, RelationKind::Sibling)])]),
+        KinError::MissingNode { id: 7 }
+    ));
+    assert!(matches!(
+        error(vec![node(3, &[]), node(3, &[])]),
+        KinError::DuplicateNode { id: 3 }
+    ));
+    Ok(())
+}
+#[test]
+fn wasm_graph_keeps_family_details() -> Result<()> {
+    use kin_wasm::KinWasmGraph;
+    let dsl = r#"
+    Kid M CHILD Mom F
+    Mom F SPOUSE Dad M
+    "#;
+    let mut kg = KinGraph::new();
+    parse_relations_from_dsl(dsl, &mut kg)?;
+    kg.set_calendar(Box::new(CustomCalendar::new(
+        "Moons",
+        &[("One", 28), ("Two", 28)],
+    )?));
+    let (kid, mom, dad) = (kg.px(0).clone(), kg.px(1).clone(), kg.px(2).clone());
+    kg.add_partnership(
+        &mom,
+        &dad,
+        Partnership {
+            end: Some(Date::ym(1910, 2).into()),
+            ..Partnership::new(MaritalStatus::Divorced)
+        },
+    )?;
+    kg.add_parent_with(&dad, &kid, Parentage::biological_only())?;
+    let step = kg.np_with_name(Sex::Male, "Step".into());
+    kg.add_parent_with(&step, &kid, Parentage::legal_only())?;
+    kg.graph[NodeIndex::new(3)].is_shadow = true;
+
+    //through the JSON of get_graph and load_graph
+    let json = serde_json::to_string(&kg.as_wasm_graph())?;
+    let restored = KinGraph::from_wasm_graph(serde_json::from_str::<KinWasmGraph>(&json)?)?;
+    //the relations are listed by node, so may come back in another order
+    let document = |kg: &KinGraph| {
+        let mut doc = kg.to_document();
+        doc.relations.sort_by_key(|r| format!("{:?}", r));
+        doc
+    };
+    assert_eq!(document(&restored), document(&kg));
+    assert_eq!(
+        restored.get_partnership(&mom, &dad).map(|p| p.status),
+        Some(MaritalStatus::Divorced)
+    );
+    assert_eq!(
+        restored.get_parentage(&step, &kid),
+        Some(Parentage::legal_only())
+    );
+    assert!(restored.px(3).is_shadow());
+    assert_eq!(restored.calendar().name(), "Moons");
+    assert!(
+        relationship_names(&restored, 1, 2, &QueryOptions::default())
+            .contains(&"ex-wife".to_string())
+    );
+    Ok(())
+}
+#[test]
+fn binary_round_trip() -> Result<()> {
+    let dsl = r#"
+    Kid M CHILD Mom F
+    Kid M CHILD Dad M
+    Dad M SPOUSE Mom F
+    Kid M SIBLING Sis F
+    Kid M WARD Carer F
+    Kid M BORN bet 1899 and 1900
+    Mom F DIED 3 Mar 1710/11
+    Kid M ATTR tags [a, 1.5, true, date(bef 1910)]
+    Kid M REF novella k1
+    Kid M NAME Dr Karl "Kid" /Berg/
+    Kid M ALIAS Karl /Bergmann/
+    Dad M REL_ATTR PARENT Kid M note adopted later
+    "#;
+    let mut kg = KinGraph::new();
+    parse_relations_from_dsl(dsl, &mut kg)?;
+    let (sis, donor) = (kg.px(3).clone(), kg.np_with_name(Sex::Male, "Donor".into()));
+    kg.add_parent_with(&donor, &sis, Parentage::biological_only())?;
+    let (dad, mom) = (kg.px(2).clone(), kg.px(1).clone());
+    kg.add_partnership(
+        &dad,
+        &mom,
+        Partnership {
+            start: Some(GenDate::About(Date::year(1895))),
+            ..Partnership::new(MaritalStatus::Divorced)
+        },
+    )?;
+    kg.graph[NodeIndex::new(3)].is_shadow = true;
+
+    let mut bytes = Vec::new();
+    kg.write_binary(&mut bytes)?;
+    assert_eq!(&bytes[..4], b"KINB");
+    let restored = KinGraph::read_binary(&bytes[..])?;
+    let sorted_doc = |kg: &KinGraph| {
+        let mut doc = kg.to_document();
+        doc.relations
+            .sort_by_key(|r| (r.from, r.to, r.kind.to_string()));
+        doc
+    };
+    assert_eq!(sorted_doc(&restored), sorted_doc(&kg));
+    assert!(bytes.len() < kg.to_json()?.len() / 2);
+
+    //any change to the contents is caught by the checksum
+    for i in [5, bytes.len() / 2, bytes.len() - 1] {
+        let mut corrupt = bytes.clone();
+        corrupt[i] ^= 0x10;
+        assert!(KinGraph::read_binary(&corrupt[..]).is_err());
+    }
+    assert!(KinGraph::read_binary(&bytes[..bytes.len() - 3]).is_err());
+    assert!(KinGraph::read_binary(&b"KINJ"[..]).is_err());
+
+    //relations that could not have been added to a graph are rejected as they are read
+    let mut invalid = KinGraph::new();
+    let kid = invalid.np(Sex::Female);
+    for _ in 0..3 {
+        let parent = invalid.np(Sex::Female);
+        invalid.add_edges(
+            invalid.idx(&parent).unwrap(),
+            invalid.idx(&kid).unwrap(),
+            Kind::Parent,
+        );
+    }
+    let mut bytes = Vec::new();
+    invalid.write_binary(&mut bytes)?;
+    assert!(KinGraph::read_binary(&bytes[..]).is_err());
+    Ok(())
+}
+#[test]
+fn gedcom_import() {
+    let gedcom = "\
+0 HEAD
+1 GEDC
+2 VERS 5.5.1
+0 @I1@ INDI
+1 NAME John /Smith/
+2 NICK Jack
+1 SEX M
+1 BIRT
+2 DATE ABT 1850
+2 PLAC Leeds
+1 OCCU Miner
+1 FAMS @F1@
+0 @I2@ INDI
+1 NAME Mary /Jones/
+1 NAME Mary /Smith/
+2 TYPE married
+1 DEAT
+2 DATE 3 MAR 1901
+1 FAMS @F1@
+0 @I3@ INDI
+1 NAME Tom /Smith/
+1 SEX M
+1 FAMC @F1@
+0 @I4@ INDI
+1 NAME Ann /Smith/
+1 SEX F
+1 BIRT
+2 DATE @#DJULIAN@ 1880
+1 FAMC @F1@
+2 PEDI adopted
+0 @I5@ INDI
+1 SEX X
+0 @F1@ FAM
+1 HUSB @I1@
+1 WIFE @I2@
+1 CHIL @I3@
+1 CHIL @I4@
+1 CHIL @I9@
+1 MARR
+2 DATE 1875
+1 _MSTAT happy
+0 @F2@ FAM
+1 HUSB @I5@
+1 CHIL @I3@
+0 TRLR
+";
+    let GedcomImport {
+        graph: kg,
+        warnings,
+    } = KinGraph::from_gedcom(gedcom);
+    let find = |xref| kg.find_by_ref(GEDCOM_REF, xref).unwrap();
+    let (john, mary, tom, ann, x) = (find("I1"), find("I2"), find("I3"), find("I4"), find("I5"));
+    assert_eq!(john.name(), "John Smith");
+    assert_eq!(john.primary_name().unwrap().nicknames, ["Jack"]);
+    assert_eq!(john.birth(), Some(GenDate::About(Date::year(1850))));
+    assert_eq!(john.attr("BIRT.PLAC"), Some(&"Leeds".into()));
+    assert_eq!(john.attr("OCCU"), Some(&"Miner".into()));
+    //the sex of Mary is told from her being a wife
+    assert_eq!(mary.get_sex(), Sex::Female);
+    assert_eq!(mary.alternate_names().len(), 1);
+    assert_eq!(mary.attr("NAME.TYPE"), Some(&"married".into()));
+    assert_eq!(mary.death(), Some(Date::ymd(1901, 3, 3).into()));
+    assert_eq!(ann.birth(), None);
+    assert_eq!(ann.attr("BIRT.DATE"), Some(&"@#DJULIAN@ 1880".into()));
+    assert_eq!(x.attr("SEX"), Some(&"X".into()));
+    assert_eq!(kg.find_by_name("smith").len(), 4);
+
+    assert_eq!(kg.get_parentage(john, tom), Some(Parentage::default()));
+    assert_eq!(kg.get_parentage(mary, ann), None);
+    let kinds = |p1: &Person, p2: &Person| {
+        let (p1x, p2x) = (kg.idx(p1).unwrap(), kg.idx(p2).unwrap());
+        let mut kinds = kg
+            .graph
+            .edges_connecting(p1x, p2x)
+            .map(|e| e.weight().to_string())
+            .collect::<Vec<_>>();
+        kinds.sort();
+        kinds
+    };
+    assert_eq!(kinds(mary, ann), [Kind::AdoptiveParent.to_string()]);
+    assert_eq!(
+        kinds(john, mary),
+        [Kind::RP.to_string(), Kind::Spouse.to_string()]
+    );
+    assert_eq!(
+        kg.get_partnership(john, mary).unwrap().start,
+        Some(Date::year(1875).into())
+    );
+    assert_eq!(
+        kg.relation_attrs(john, mary, Kind::RP).unwrap()["_MSTAT"],
+        "happy".into()
+    );
+
+    let lines = warnings.iter().map(|w| w.line).collect::<Vec<_>>();
+    //the julian date, the missing child, and Tom's third parent
+    assert_eq!(lines, [28, 38, 44], "{:?}", warnings);
+    assert_eq!(
+        warnings[1].to_string(),
+        "line 38: Family refers to missing individual @I9@"
+    );
+    assert_eq!(x.get_sex(), Sex::Male);
+    //garbage is reported, not panicked on
+    let import = KinGraph::from_gedcom("hello\n1 x\n\n0 @I1@ INDI\n1 NAME /Bad\n3 SEX M");
+    assert_eq!(import.graph.graph.node_count(), 1);
+    let lines = import.warnings.iter().map(|w| w.line).collect::<Vec<_>>();
+    assert_eq!(lines, [1, 2, 6, 4, 5, 4]);
+    //as are malformed dates, even ones with letters that are more than a byte long
+    let import =
+        KinGraph::from_gedcom("0 @I1@ INDI\n1 BIRT\n2 DATE é\n1 DEAT\n2 DATE BET İ1820 AND 1825");
+    let lines = import.warnings.iter().map(|w| w.line).collect::<Vec<_>>();
+    assert_eq!(lines, [1, 3, 5, 1]);
+    assert_eq!(import.warnings[1].to_string(), "line 3: Invalid date \"é\"");
+    let p = import.graph.px(0);
+    assert_eq!((p.birth(), p.death()), (None, None));
+    assert_eq!(p.attr("BIRT.DATE"), Some(&"é".into()));
+}
+#[test]
+fn gedcom_export() -> Result<()> {
+    let dsl = r#"
+    Kid M CHILD Mom F
+    Kid M CHILD Dad M
+    Dad M SPOUSE Mom F
+    Kid M ADOPTIVE_CHILD Aunt F
+    Sis F FOSTER_CHILD Aunt F
+    Kid M NAME Dr Karl "Kid" /Berg/
+    Kid M ALIAS Charlie /Berg/
+    Kid M BORN 3 Mar 1710/11
+    Mom F DIED bet 1790 and 1795
+    Kid M ATTR occupation miner
+    Kid M ATTR BIRT.PLAC Leeds
+    Kid M REF novella k1
+    Dad M REL_ATTR RP Mom F _MSTAT happy
+    Loner M BORN abt 1700
+    "#;
+    let mut kg = KinGraph::new();
+    parse_relations_from_dsl(dsl, &mut kg)?;
+    let (dad, mom, kid) = (kg.px(2).clone(), kg.px(1).clone(), kg.px(0).clone());
+    kg.add_partnership(
+        &dad,
+        &mom,
+        Partnership {
+            start: Some(Date::year(1705).into()),
+            end: Some(Date::year(1720).into()),
+            ..Partnership::new(MaritalStatus::Divorced)
+        },
+    )?;
+    let note = format!("{}\nand @home", "a long note ".repeat(40));
+    kg.set_attr(&kid, "NOTE", note.as_str().into())?;
+    let shadow = kg.np_with_name(Sex::Female, "Shadow".into());
+    let shadow_x = kg.idx(&shadow).unwrap();
+    kg.graph[shadow_x].is_shadow = true;
+    kg.add_relation(&shadow, &kg.px(4).clone(), Kind::Parent)?;
+
+    for version in [GedcomVersion::V551, GedcomVersion::V70] {
+        let options = GedcomOptions {
+            version,
+            drop_shadows: false,
+        };
+        let gedcom = kg.to_gedcom(&options);
+        assert_eq!(validate_gedcom(&gedcom, version), [], "{}", gedcom);
+        assert_eq!(gedcom.contains("2 CONC "), version == GedcomVersion::V551);
+        let GedcomImport {
+            graph: restored,
+            warnings,
+        } = KinGraph::from_gedcom(&gedcom);
+        assert_eq!(warnings, []);
+        assert_eq!(restored.graph.node_count(), kg.graph.node_count());
+        let find = |p: &Person| restored.find_by_ref(GEDCOM_REF, &format!("I{}", p.get_id() + 1));
+        let kid2 = restored.find_by_ref("novella", "k1").unwrap();
+        assert_eq!(kid2.primary_name(), kid.primary_name());
+        assert_eq!(kid2.alternate_names(), kid.alternate_names());
+        assert_eq!(kid2.birth(), kid.birth());
+        assert_eq!(kid2.attr("occupation"), Some(&"miner".into()));
+        assert_eq!(kid2.attr("BIRT.PLAC"), Some(&"Leeds".into()));
+        assert_eq!(kid2.attr("NOTE"), Some(&note.as_str().into()));
+        let (dad2, mom2) = (find(&dad).unwrap(), find(&mom).unwrap());
+        assert_eq!(mom2.death(), mom.death());
+        assert_eq!(
+            restored.get_partnership(dad2, mom2),
+            kg.get_partnership(&dad, &mom)
+        );
+        assert_eq!(
+            restored.relation_attrs(dad2, mom2, Kind::RP),
+            kg.relation_attrs(&dad, &mom, Kind::RP)
+        );
+        assert!(find(&shadow).unwrap().is_shadow());
+        let names = |p1: usize, p2: usize| {
+            let mut names = relationship_names(&restored, p1, p2, &QueryOptions::default());
+            names.sort();
+            names
+        };
+        let original = |p1: usize, p2: usize| {
+            let mut names = relationship_names(&kg, p1, p2, &QueryOptions::default());
+            names.sort();
+            names
+        };
+        for (p1, p2) in [(0, 1), (3, 0), (4, 3), (2, 1), (6, 4)] {
+            assert_eq!(names(p1, p2), original(p1, p2));
+        }
+    }
+    //shadow persons can be left out instead
+    let gedcom = kg.to_gedcom(&GedcomOptions {
+        drop_shadows: true,
+        ..Default::default()
+    });
+    assert_eq!(validate_gedcom(&gedcom, GedcomVersion::V551), []);
+    let restored = KinGraph::from_gedcom(&gedcom).graph;
+    assert_eq!(restored.graph.node_count(), kg.graph.node_count() - 1);
+    Ok(())
+}
+#[test]
+fn gedcomx_samples() -> Result<()> {
+    let sorted_names = |kg: &KinGraph, p1: usize, p2: usize| {
+        let mut names = relationship_names(kg, p1, p2, &QueryOptions::default());
+        names.sort();
+        names
+    };
+    //adapted from the birth certificate example of the GEDCOM X specification
+    let birth = r##"{
+  "persons": [
+    {
+      "id": "P-1",
+      "gender": { "type": "http://gedcomx.org/Female" },
+      "names": [{ "nameForms": [{ "fullText": "Emma Bocock" }] }],
+      "facts": [{
+        "type": "http://gedcomx.org/Birth",
+        "date": { "original": "23 June 1843", "formal": "+1843-06-23" },
+        "place": { "original": "Broadfield Bar, Abbey Hey, Gorton, Lancashire, England" }
+      }]
+    },
+    {
+      "id": "P-2",
+      "gender": { "type": "http://gedcomx.org/Male" },
+      "names": [{ "nameForms": [{ "fullText": "William Bocock" }] }],
+      "facts": [{ "type": "http://gedcomx.org/Occupation", "value": "Toll Collector" }]
+    },
+    {
+      "id": "P-3",
+      "gender": { "type": "http://gedcomx.org/Female" },
+      "names": [{
+        "nameForms": [{
+          "fullText": "Sarah Bocock",
+          "parts": [
+            { "type": "http://gedcomx.org/Given", "value": "Sarah" },
+            { "type": "http://gedcomx.org/Surname", "value": "Bocock" }
+          ]
+        }]
+      }]
+    }
+  ],
+  "relationships": [
+    {
+      "type": "http://gedcomx.org/ParentChild",
+      "person1": { "resource": "#P-2" },
+      "person2": { "resource": "#P-1" }
+    },
+    {
+      "type": "http://gedcomx.org/ParentChild",
+      "person1": { "resource": "#P-3" },
+      "person2": { "resource": "#P-1" }
+    },
+    {
+      "type": "http://gedcomx.org/Couple",
+      "person1": { "resource": "#P-2" },
+      "person2": { "resource": "#P-3" }
+    }
+  ]
+}"##;
+    let kg = KinGraph::from_gedcomx(birth)?;
+    let find = |id| kg.find_by_ref(GEDCOMX_REF, id).unwrap();
+    let (emma, william, sarah) = (find("P-1"), find("P-2"), find("P-3"));
+    assert_eq!(emma.birth(), Some(Date::ymd(1843, 6, 23).into()));
+    assert_eq!(
+        emma.attr("Birth.place"),
+        Some(&"Broadfield Bar, Abbey Hey, Gorton, Lancashire, England".into())
+    );
+    assert_eq!(emma.primary_name().unwrap().given, ["Emma"]);
+    assert_eq!(william.get_sex(), Sex::Male);
+    assert_eq!(william.attr("Occupation"), Some(&"Toll Collector".into()));
+    assert_eq!(
+        sarah.primary_name().unwrap().surname.as_deref(),
+        Some("Bocock")
+    );
+    assert!(sorted_names(&kg, 1, 0).contains(&"father".to_string()));
+    assert!(sorted_names(&kg, 2, 0).contains(&"mother".to_string()));
+    assert!(kg.get_partnership(william, sarah).is_none());
+
+    //adapted from the marriage example of the GEDCOM X specification
+    let marriage = r##"{
+  "persons": [
+    {
+      "id": "P-1",
+      "gender": { "type": "http://gedcomx.org/Male" },
+      "names": [{ "nameForms": [{ "fullText": "Samuel Ham" }] }],
+      "facts": [
+        {
+          "type": "http://gedcomx.org/Residence",
+          "place": { "original": "Wilton St George, Wilton, Somerset, England" }
+        },
+        { "type": "data:,Marital%20age", "value": "25" }
+      ],
+      "identifiers": { "http://example.org/register": ["1829-12"] }
+    },
+    {
+      "id": "P-2",
+      "gender": { "type": "http://gedcomx.org/Unknown" },
+      "names": [{ "nameForms": [{ "fullText": "Elizabeth Spiller" }] }]
+    },
+    {
+      "id": "P-3",
+      "gender": { "type": "http://gedcomx.org/Male" },
+      "names": [{ "nameForms": [{ "fullText": "Jno. Pain" }] }]
+    }
+  ],
+  "relationships": [
+    {
+      "type": "http://gedcomx.org/Couple",
+      "person1": { "resource": "#P-1" },
+      "person2": { "resourceId": "P-2" },
+      "facts": [{
+        "type": "http://gedcomx.org/Marriage",
+        "date": { "original": "3 January 1829", "formal": "+1829-01-03" },
+        "place": { "original": "Wilton St George, Wilton, Somerset, England" }
+      }]
+    },
+    {
+      "type": "http://gedcomx.org/ParentChild",
+      "person1": { "resource": "#P-3" },
+      "person2": { "resource": "#P-2" },
+      "facts": [{ "type": "http://gedcomx.org/GuardianParent" }]
+    }
+  ]
+}"##;
+    let kg = KinGraph::from_gedcomx(marriage)?;
+    let find = |id| kg.find_by_ref(GEDCOMX_REF, id).unwrap();
+    let (samuel, elizabeth) = (find("P-1"), find("P-2"));
+    assert_eq!(samuel.attr("Marital age"), Some(&"25".into()));
+    assert_eq!(
+        samuel.external_ref("http://example.org/register"),
+        Some("1829-12")
+    );
+    assert!(samuel.attr("Residence.place").is_some());
+    //the unknown gender is kept, as persons are only ever male or female
+    assert_eq!(
+        elizabeth.attr("gender"),
+        Some(&"http://gedcomx.org/Unknown".into())
+    );
+    let partnership = kg.get_partnership(samuel, elizabeth).unwrap();
+    assert_eq!(partnership.status, MaritalStatus::Married);
+    assert_eq!(partnership.start, Some(Date::ymd(1829, 1, 3).into()));
+    let attrs = kg.relation_attrs(samuel, elizabeth, Kind::RP).unwrap();
+    assert!(attrs.contains_key("Marriage.place"));
+    assert!(sorted_names(&kg, 2, 1).contains(&"guardian".to_string()));
+
+    //writing the document and reading it back keeps what was read
+    let restored = KinGraph::from_gedcomx(&kg.to_gedcomx()?)?;
+    let find = |id| restored.find_by_ref(GEDCOMX_REF, id).unwrap();
+    let (samuel2, elizabeth2) = (find("P-1"), find("P-2"));
+    assert_eq!(samuel2.attributes(), samuel.attributes());
+    assert_eq!(samuel2.external_refs(), samuel.external_refs());
+    assert_eq!(elizabeth2.attributes(), elizabeth.attributes());
+    assert_eq!(elizabeth2.primary_name(), elizabeth.primary_name());
+    assert_eq!(
+        restored.get_partnership(samuel2, elizabeth2),
+        Some(partnership)
+    );
+    assert_eq!(
+        restored.relation_attrs(samuel2, elizabeth2, Kind::RP),
+        kg.relation_attrs(samuel, elizabeth, Kind::RP)
+    );
+    assert_eq!(sorted_names(&restored, 2, 1), sorted_names(&kg, 2, 1));
+
+    let missing = marriage.replace("\"resourceId\": \"P-2\"", "\"resourceId\": \"P-9\"");
+    assert!(KinGraph::from_gedcomx(&missing).is_err());
+    Ok(())
+}
+#[test]
+fn gedcomx_round_trip() -> Result<()> {
+    let dsl = r#"
+    Tom M CHILD Mary F
+    Tom M CHILD John M
+    Ann F ADOPTIVE_CHILD Mary F
+    Ann F FOSTER_CHILD Bob M
+    Tom M BORN 12 Mar 1710/11
+    Mary F DIED bet 1790 and 1795
+    John M NAME John "Jack" /Smith/
+    John M ATTR Occupation miner
+    John M ATTR eye colour blue
+    "#;
+    let mut kg = KinGraph::new();
+    parse_relations_from_dsl(dsl, &mut kg)?;
+    let (tom, mary, john, ann) = (
+        kg.px(0).clone(),
+        kg.px(1).clone(),
+        kg.px(2).clone(),
+        kg.px(3).clone(),
+    );
+    kg.add_partnership(
+        &mary,
+        &john,
+        Partnership {
+            start: Some(Date::year(1700).into()),
+            end: Some(Date::year(1720).into()),
+            ..Partnership::new(MaritalStatus::Divorced)
+        },
+    )?;
+    let step = kg.np_with_name(Sex::Male, "Step".into());
+    kg.add_parent_with(&step, &ann, Parentage::legal_only())?;
+    let restored = KinGraph::from_gedcomx(&kg.to_gedcomx()?)?;
+    assert_eq!(restored.graph.node_count(), kg.graph.node_count());
+    assert_eq!(restored.graph.edge_count(), kg.graph.edge_count());
+    let same = |p: &Person| restored.px(p.get_id());
+    assert_eq!(same(&tom).birth(), tom.birth());
+    assert_eq!(same(&mary).death(), mary.death());
+    assert_eq!(same(&john).primary_name(), john.primary_name());
+    assert_eq!(same(&john).attributes(), john.attributes());
+    assert_eq!(
+        restored.get_partnership(same(&mary), same(&john)),
+        kg.get_partnership(&mary, &john)
+    );
+    assert_eq!(
+        restored.get_parentage(same(&step), same(&ann)),
+        Some(Parentage::legal_only())
+    );
+    for (p1, p2) in [(3, 1), (3, 4), (2, 0)] {
+        let mut names = relationship_names(&restored, p1, p2, &QueryOptions::default());
+        let mut original = relationship_names(&kg, p1, p2, &QueryOptions::default());
+        names.sort();
+        original.sort();
+        assert_eq!(names, original);
+    }
+    Ok(())
+}
+//the relations of a graph, in an order that does not depend on the order they were added in
+fn gramps_edges(kg: &KinGraph) -> Vec<(usize, usize, String)> {
+    let mut edges = kg
+        .graph
+        .edge_references()
+        .map(|e| {
+            (
+                e.source().index(),
+                e.target().index(),
+                format!("{:?}", e.weight()),
+            )
+        })
+        .collect::<Vec<_>>();
+    edges.sort();
+    edges
+}
+#[test]
+fn gramps_round_trip() -> Result<()> {
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE database PUBLIC "-//Gramps//DTD Gramps XML 1.7.1//EN"
+"http://gramps-project.org/xml/1.7.1/grampsxml.dtd">
+<database xmlns="http://gramps-project.org/xml/1.7.1/">
+  <header>
+    <created date="2024-05-01" version="5.2.0"/>
+  </header>
+  <events>
+    <event handle="_e1" change="1714550000" id="E0001">
+      <type>Birth</type>
+      <dateval val="1850-03-12"/>
+      <place hlink="_pl1"/>
+    </event>
+    <event handle="_e2" change="1714550000" id="E0002">
+      <type>Marriage</type>
+      <dateval val="1875" type="about"/>
+      <place hlink="_pl1"/>
+    </event>
+    <event handle="_e3" change="1714550000" id="E0003">
+      <type>Occupation</type>
+      <datestr val="in the spring"/>
+      <description>Miner</description>
+    </event>
+  </events>
+  <people>
+    <person handle="_a1" change="1714550000" id="I0001">
+      <gender>M</gender>
+      <name type="Birth Name">
+        <first>John Henry</first>
+        <surname>Smith</surname>
+        <nick>Jack</nick>
+      </name>
+      <eventref hlink="_e1" role="Primary"/>
+      <eventref hlink="_e3" role="Primary"/>
+      <attribute type="Eye colour" value="blue"/>
+      <parentin hlink="_f1"/>
+    </person>
+    <person handle="_a2" change="1714550000" id="I0002">
+      <gender>F</gender>
+      <name type="Married Name">
+        <first>Mary</first>
+        <surname>Smith</surname>
+      </name>
+      <name alt="1" type="Birth Name">
+        <first>Mary</first>
+        <surname>Jones</surname>
+      </name>
+      <parentin hlink="_f1"/>
+    </person>
+    <person handle="_a3" change="1714550000" id="I0003">
+      <gender>M</gender>
+      <name type="Birth Name"><first>Tom</first><surname>Smith</surname></name>
+      <childof hlink="_f1"/>
+    </person>
+    <person handle="_a4" change="1714550000" id="I0004">
+      <gender>U</gender>
+      <name type="Birth Name"><first>Sam</first></name>
+      <childof hlink="_f1"/>
+      <personref hlink="_a5" rel="Guardian"/>
+    </person>
+    <person handle="_a5" change="1714550000" id="I0005">
+      <gender>F</gender>
+      <name type="Birth Name"><first>Ann</first></name>
+    </person>
+  </people>
+  <families>
+    <family handle="_f1" change="1714550000" id="F0001">
+      <rel type="Married"/>
+      <father hlink="_a1"/>
+      <mother hlink="_a2"/>
+      <eventref hlink="_e2" role="Family"/>
+      <childref hlink="_a3"/>
+      <childref hlink="_a4" frel="Adopted" mrel="Stepchild"/>
+      <attribute type="Witness" value="Bob"/>
+    </family>
+  </families>
+  <places>
+    <placeobj handle="_pl1" change="1714550000" id="P0001" type="City">
+      <ptitle>Leeds, England</ptitle>
+      <pname value="Leeds"/>
+    </placeobj>
+  </places>
+</database>
+"#;
+    let kg = KinGraph::read_gramps(xml.as_bytes())?;
+    let find = |handle| kg.find_by_ref(GRAMPS_REF, handle).unwrap();
+    let (john, mary, tom, sam, ann) = (
+        find("_a1"),
+        find("_a2"),
+        find("_a3"),
+        find("_a4"),
+        find("_a5"),
+    );
+    assert_eq!(john.external_ref(GRAMPS_ID_REF), Some("I0001"));
+    assert_eq!(john.name(), "John Henry Smith");
+    assert_eq!(john.primary_name().unwrap().nicknames, ["Jack"]);
+    assert_eq!(john.birth(), Some(Date::ymd(1850, 3, 12).into()));
+    assert_eq!(john.attr("Birth.place"), Some(&"Leeds".into()));
+    assert_eq!(john.attr("Occupation"), Some(&true.into()));
+    assert_eq!(john.attr("Occupation.description"), Some(&"Miner".into()));
+    assert_eq!(john.attr("Occupation.date"), Some(&"in the spring".into()));
+    assert_eq!(john.attr("Eye colour"), Some(&"blue".into()));
+    let mary_name = mary.primary_name().unwrap();
+    assert_eq!(mary_name.birth_surname.as_deref(), Some("Jones"));
+    assert!(mary.alternate_names().is_empty());
+    assert_eq!(sam.attr("gender"), Some(&"U".into()));
+    let partnership = kg.get_partnership(john, mary).unwrap();
+    assert_eq!(partnership.start, Some(GenDate::About(Date::year(1875))));
+    let family = kg.relation_attrs(john, mary, Kind::RP).unwrap();
+    assert_eq!(family.get("gramps.handle"), Some(&"_f1".into()));
+    assert_eq!(family.get("Marriage.place"), Some(&"Leeds".into()));
+    assert_eq!(family.get("Witness"), Some(&"Bob".into()));
+    assert_eq!(kg.get_parentage(mary, sam), Some(Parentage::legal_only()));
+    let names = |kg: &KinGraph, p1: usize, p2: usize| {
+        let mut names = relationship_names(kg, p1, p2, &QueryOptions::default());
+        names.sort();
+        names
+    };
+    assert!(names(&kg, 0, 2).contains(&"father".to_string()));
+    assert!(names(&kg, 0, 3).contains(&"adoptive father".to_string()));
+    assert!(names(&kg, 4, 3).contains(&"guardian".to_string()));
+    assert_eq!(tom.get_sex(), Sex::Male);
+    assert_eq!(ann.get_sex(), Sex::Female);
+
+    //handles, names, events and families come back the same, whether compressed or not
+    let mut plain = Vec::new();
+    kg.write_gramps(&mut plain, false)?;
+    let mut gzip = Vec::new();
+    kg.write_gramps(&mut gzip, true)?;
+    assert!(gzip.len() < plain.len());
+    for written in [&plain, &gzip] {
+        let restored = KinGraph::read_gramps(written.as_slice())?;
+        assert_eq!(restored.graph.node_count(), kg.graph.node_count());
+        assert_eq!(restored.graph.edge_count(), kg.graph.edge_count());
+        for i in 0..kg.graph.node_count() {
+            let (p, q) = (kg.px(i), restored.px(i));
+            assert_eq!(p.external_refs(), q.external_refs());
+            assert_eq!(p.primary_name(), q.primary_name());
+            assert_eq!((p.birth(), p.death()), (q.birth(), q.death()));
+            assert_eq!(p.attributes(), q.attributes());
+            assert_eq!(
+                restored.get_parentage(restored.px(1), q),
+                kg.get_parentage(kg.px(1), p)
+            );
+        }
+        assert_eq!(gramps_edges(&restored), gramps_edges(&kg));
+        let (john2, mary2) = (restored.px(0), restored.px(1));
+        assert_eq!(restored.get_partnership(john2, mary2), Some(partnership));
+        assert_eq!(
+            restored.relation_attrs(john2, mary2, Kind::RP),
+            Some(family)
+        );
+    }
+    //writing what was read gives the same document again
+    let mut again = Vec::new();
+    KinGraph::read_gramps(plain.as_slice())?.write_gramps(&mut again, false)?;
+    assert_eq!(String::from_utf8(again)?, String::from_utf8(plain)?);
+    let missing = xml.replace(r#"<childref hlink="_a3"/>"#, r#"<childref hlink="_a9"/>"#);
+    assert!(KinGraph::read_gramps(missing.as_bytes()).is_err());
+    Ok(())
+}
+#[test]
+fn gramps_export() -> Result<()> {
+    let dsl = r#"
+    Kid M CHILD Mom F
+    Kid M CHILD Dad M
+    Sis F FOSTER_CHILD Mom F
+    Kid M NAME Dr Karl /Berg/
+    Kid M BORN 3 Mar 1710/11
+    Mom F DIED bet 1790 and 1795
+    Kid M ATTR Baptism date(abt 1711)
+    Kid M ATTR tags [hero, brave]
+    Loner M BORN abt 1700
+    "#;
+    let mut kg = KinGraph::new();
+    parse_relations_from_dsl(dsl, &mut kg)?;
+    let (kid, mom, dad) = (kg.px(0).clone(), kg.px(1).clone(), kg.px(2).clone());
+    kg.add_partnership(
+        &dad,
+        &mom,
+        Partnership {
+            start: Some(Date::year(1705).into()),
+            end: Some(Date::year(1720).into()),
+            ..Partnership::new(MaritalStatus::Divorced)
+        },
+    )?;
+    let step = kg.np_with_name(Sex::Female, "Step".into());
+    kg.add_parent_with(&step, &kg.px(3).clone(), Parentage::legal_only())?;
+    let mut xml = Vec::new();
+    kg.write_gramps(&mut xml, false)?;
+    let xml = String::from_utf8(xml)?;
+    assert!(
+        xml.contains(r#"<dateval val="1711-03-03" dualdated="1"/>"#),
+        "{}",
+        xml
+    );
+    assert!(xml.contains(r#"<rel type="Married"/>"#));
+    assert!(xml.contains(r#"mrel="Foster""#));
+    let restored = KinGraph::read_gramps(xml.as_bytes())?;
+    assert_eq!(restored.graph.node_count(), kg.graph.node_count());
+    assert_eq!(gramps_edges(&restored), gramps_edges(&kg));
+    let kid2 = restored.px(0);
+    assert_eq!(kid2.primary_name(), kid.primary_name());
+    assert_eq!(kid2.birth(), kid.birth());
+    assert_eq!(kid2.attr("Baptism"), kid.attr("Baptism"));
+    //lists of text are written as repeated Gramps attributes
+    assert_eq!(kid2.attr("tags"), kid.attr("tags"));
+    assert_eq!(restored.px(1).death(), mom.death());
+    assert_eq!(
+        restored.get_partnership(restored.px(2), restored.px(1)),
+        kg.get_partnership(&dad, &mom)
+    );
+    assert_eq!(
+        restored.get_parentage(restored.px(5), restored.px(3)),
+        Some(Parentage::legal_only())
+    );
+    //persons without a handle get fresh ones, which are kept from then on
+    assert_eq!(kid2.external_ref(GRAMPS_REF), Some("_i0000"));
+    assert_eq!(restored.px(4).external_ref(GRAMPS_ID_REF), Some("I0004"));
+    Ok(())
+}
+#[test]
+fn csv_round_trip() -> Result<()> {
+    let mut kg = KinGraph::new();
+    let mike = kg.np_with_name(Sex::Male, "Mike, Jr.".into());
+    let izy = kg.np_with_name(Sex::Female, "Izy".into());
+    let ann = kg.np_with_name(Sex::Female, "Ann".into());
+    let tom = kg.np(Sex::Male);
+    kg.make_child(&ann, &mike, &izy)?;
+    kg.add_relation(&tom, &ann, Kind::Sibling)?;
+    kg.add_relation(&izy, &tom, Kind::AdoptiveParent)?;
+    kg.add_relation(&mike, &tom, Kind::Guardian)?;
+    kg.set_birth(&mike, Some(kg.parse_date("abt 1850")?))?;
+    kg.set_death(&ann, Some(kg.parse_date("1910/11")?))?;
+    kg.set_attr(&mike, "occupation", AttrValue::from("smith, farmer"))?;
+    kg.set_attr(&izy, "children", AttrValue::from("3"))?;
+    kg.set_external_ref(&izy, "wikitree", "Izy-1")?;
+    kg.set_external_ref(&tom, CSV_REF, "T")?;
+    kg.set_relation_attr(
+        &izy,
+        &tom,
+        Kind::AdoptiveParent,
+        "court",
+        AttrValue::from("Leeds"),
+    )?;
+
+    let (mut persons, mut relations) = (Vec::new(), Vec::new());
+    kg.to_csv(&mut persons, &mut relations)?;
+    let relations_csv = String::from_utf8(relations.clone())?;
+    //each symmetric pair is written once
+    assert_eq!(relations_csv.matches("SIBLING").count(), 1);
+    assert!(!relations_csv.contains("CHILD"));
+    assert!(!relations_csv.contains("WARD"));
+
+    let CsvImport { graph, errors } = KinGraph::from_csv(&persons[..], &relations[..])?;
+    assert!(errors.is_empty(), "{:?}", errors);
+    assert_eq!(gramps_edges(&graph), gramps_edges(&kg));
+    let read_tom = graph.find_by_ref(CSV_REF, "T").unwrap();
+    assert_eq!(graph.px(3).get_id(), read_tom.get_id());
+    let read_mike = graph.px(0);
+    assert_eq!(read_mike.name, "Mike, Jr.");
+    assert_eq!(read_mike.birth, kg.px(0).birth);
+    assert_eq!(graph.px(2).death, kg.px(2).death);
+    assert_eq!(
+        read_mike.attr("occupation"),
+        Some(&AttrValue::from("smith, farmer"))
+    );
+    assert_eq!(graph.px(1).attr("children"), Some(&AttrValue::from("3")));
+    assert_eq!(graph.px(1).external_ref("wikitree"), Some("Izy-1"));
+    let court = graph.relation_attrs(graph.px(1), read_tom, Kind::AdoptiveParent);
+    assert_eq!(
+        court.and_then(|attrs| attrs.get("court")),
+        Some(&AttrValue::from("Leeds"))
+    );
+
+    //and writing it again gives the same tables
+    let (mut persons2, mut relations2) = (Vec::new(), Vec::new());
+    graph.to_csv(&mut persons2, &mut relations2)?;
+    assert_eq!(persons2, persons);
+    assert_eq!(relations2, relations);
+    Ok(())
+}
+#[test]
+fn linked_data() -> Result<()> {
+    let dsl = r#"
+    Dad M PARENT Kid F
+    Mom F PARENT Kid F
+    Dad M PARENT Bro M
+    Mom F PARENT Bro M
+    Mom F ADOPTIVE_PARENT Ward M
+    "#;
+    let mut kg = KinGraph::new();
+    parse_relations_from_dsl(dsl, &mut kg)?;
+    let (dad, mom) = (kg.px(0).clone(), kg.px(2).clone());
+    let mut marriage = Partnership::new(MaritalStatus::Divorced);
+    marriage.start = Some(kg.parse_date("12 Jun 1870")?);
+    kg.add_partnership(&dad, &mom, marriage)?;
+    kg.set_birth(&dad, Some(kg.parse_date("abt 1845")?))?;
+    kg.add_alternate_name(&dad, PersonName::new(&["John", "\"Dad\""], Some("Smith")))?;
+
+    let opts = LinkedDataOptions::default();
+    let json: serde_json::Value = serde_json::from_str(&kg.to_json_ld(&opts)?)?;
+    let persons = json["@graph"].as_array().unwrap();
+    let iri = |i: usize| serde_json::json!({ "@id": format!("urn:kin:person:{}", i) });
+    let kid = &persons[1];
+    assert_eq!(kid["@type"], "Person");
+    assert_eq!(kid["parent"], serde_json::json!([iri(0), iri(2)]));
+    assert_eq!(kid["sibling"], serde_json::json!([iri(3)]));
+    assert_eq!(persons[2]["spouse"], serde_json::json!([iri(0)]));
+    assert_eq!(persons[2]["children"].as_array().unwrap().len(), 3);
+    assert_eq!(
+        persons[2]["kin:adoptiveParentOf"],
+        serde_json::json!([iri(4)])
+    );
+    assert_eq!(persons[0]["familyName"], "Smith");
+    assert_eq!(
+        persons[0]["birthDate"],
+        Gregorian.format(&kg.px(0).birth().unwrap())
+    );
+    assert!(kid.get("kin:relationship").is_none());
+
+    let turtle = kg.to_turtle(&opts)?;
+    assert!(turtle.contains("@prefix bio: <http://purl.org/vocab/bio/0.1/> ."));
+    assert!(turtle.contains("<urn:kin:person:1> a foaf:Person ;"));
+    assert!(
+        turtle.contains("    bio:father <urn:kin:person:0> ;\n    bio:mother <urn:kin:person:2> ;")
+    );
+    assert!(turtle.contains("    foaf:familyName \"Smith\" ;"));
+    assert!(turtle.contains("    kin:adoptiveChildOf <urn:kin:person:2> ."));
+    assert!(turtle.contains("    rel:siblingOf <urn:kin:person:1> ."));
+    assert!(turtle.contains(
+        "[] a bio:Marriage ;\n    bio:partner <urn:kin:person:0>, <urn:kin:person:2> ;\n    bio:date \"1870-06-12\"^^xsd:date ."
+    ));
+    assert!(turtle.contains("[] a bio:Divorce ;"));
+
+    //the canonical relationships can be added
+    let opts = LinkedDataOptions {
+        derived: Some(QueryOptions::default()),
+        ..Default::default()
+    };
+    let json: serde_json::Value = serde_json::from_str(&kg.to_json_ld(&opts)?)?;
+    let relationships = json["@graph"][1]["kin:relationship"].as_array().unwrap();
+    assert!(relationships.contains(&serde_json::json!({
+        "@type": "kin:Relationship", "kin:name": "daughter", "kin:of": iri(0)
+    })));
+    let turtle = kg.to_turtle(&opts)?;
+    assert!(turtle.contains(
+        "kin:relationship [ a kin:Relationship ; kin:name \"son\" ; kin:of <urn:kin:person:2> ]"
+    ));
+    Ok(())
+}
+#[test]
+fn dot_export() -> Result<()> {
+    let mut kg = setup_half_siblings()?;
+    kg.add_sibling(&kg.px(3).clone(), &kg.px(4).clone())?;
+    assert_eq!(kg.generations(), vec![0, 0, 0, 1, 1]);
+
+    let opts = DotOptions {
+        graph_id: "half \"sibs\"".into(),
+        ..Default::default()
+    };
+    let dot = kg.to_dot(&opts);
+    assert!(dot.starts_with("digraph \"half \\\"sibs\\\"\" {\n"));
+    assert!(dot.contains(", shape=box];\n    n1 [label="));
+    assert!(dot.contains(", shape=ellipse];\n    n2 [label="));
+    assert!(dot.contains("    n0 -> n3 [label=\"P\"];"));
+    assert!(!dot.contains("n3 -> n0"));
+    //each symmetric pair is drawn once, without a direction
+    assert_eq!(
+        dot.matches("n3 -> n4").count() + dot.matches("n4 -> n3").count(),
+        1
+    );
+    assert!(dot.contains("n3 -> n4 [label=\"S\", dir=none, style=dotted];"));
+    assert!(dot.contains("    { rank=same; n0; n1; n2; }\n"));
+    assert!(dot.contains("    { rank=same; n3; n4; }\n"));
+
+    let opts = DotOptions {
+        ranks: false,
+        ..Default::default()
+    };
+    assert!(!kg.to_dot(&opts).contains("rank=same"));
+    Ok(())
+}
+#[test]
+fn dot_families() -> Result<()> {
+    let mut kg = KinGraph::new();
+    let dad = kg.np(Sex::Male);
+    let mom = kg.np(Sex::Female);
+    let elder = kg.np(Sex::Female);
+    let younger = kg.np(Sex::Male);
+    let adopted = kg.np(Sex::Male);
+    let unknown = kg.np(Sex::Female);
+    for c in [&younger, &elder, &unknown] {
+        kg.make_child(c, &dad, &mom)?;
+    }
+    kg.add_sibling(&elder, &younger)?;
+    kg.add_relation(&mom, &adopted, Kind::AdoptiveParent)?;
+    kg.add_partnership(&dad, &mom, Partnership::default())?;
+    kg.set_birth(&elder, Some(kg.parse_date("1901")?))?;
+    kg.set_birth(&younger, Some(kg.parse_date("1905")?))?;
+
+    let opts = DotOptions {
+        layout: DotLayout::Families,
+        ..Default::default()
+    };
+    let dot = kg.to_dot(&opts);
+    assert!(
+        dot.contains("    n0 -> u0 [dir=none, style=bold];\n    u0 -> n1 [dir=none, style=bold];")
+    );
+    assert!(dot.contains("    { rank=same; n0; u0; n1; }\n"));
+    //siblings hang from the union, in order of birth when it is known
+    assert!(dot.contains("    u0 -> n2;\n    u0 -> n3;\n    u0 -> n5;\n"));
+    assert!(!dot.contains("n0 -> n2"));
+    assert!(!dot.contains("n2 -> n3"));
+    assert!(!dot.contains("n0 -> n1 ["));
+    //a single adoptive parent is drawn as a relation
+    assert!(dot.contains("    n1 -> n4 [label=\"AP\", style=dashed];"));
+    assert!(!dot.contains("u1"));
+    Ok(())
+}
+#[test]
+fn dot_import() -> Result<()> {
+    //written by the earlier exporter, with both edges of each relation
+    let kg = KinGraph::from_dot(include_str!("../half_sib.dot"))?;
+    let names = kg
+        .graph
+        .node_weights()
+        .map(Person::name)
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["Mike", "Izy", "John", "Kalob", "Mary"]);
+    assert_eq!(kg.graph.edge_count(), 12);
+    assert!(kg.is_parent(Nd::new(2), Nd::new(0)) && kg.is_parent(Nd::new(2), Nd::new(3)));
+    assert!(!kg.is_parent(Nd::new(1), Nd::new(3)));
+
+    //the other examples written by the earlier exporter, with the parent-child pairs of each
+    for (dot, persons, edges, parents) in [
+        (include_str!("../cousins.dot"), 4, 6, &[(1, 0), (2, 3)][..]),
+        (
+            include_str!("../half_siblings.dot"),
+            5,
+            12,
+            &[(0, 3), (1, 3), (1, 4), (2, 4)],
+        ),
+        (include_str!("../incest.dot"), 2, 4, &[(0, 1)]),
+        (include_str!("../nn_au.dot"), 5, 6, &[(1, 2), (2, 3)]),
+        (
+            include_str!("../out.dot"),
+            10,
+            20,
+            &[(0, 1), (2, 1), (1, 3), (4, 3), (3, 7), (7, 8), (5, 6)],
+        ),
+    ] {
+        let kg = KinGraph::from_dot(dot)?;
+        assert_eq!(kg.graph.node_count(), persons);
+        assert_eq!(kg.graph.edge_count(), edges);
+        for (p, c) in parents {
+            assert!(
+                kg.is_parent(Nd::new(*p), Nd::new(*c)) && kg.is_child(Nd::new(*c), Nd::new(*p))
+            );
+        }
+    }
+    let kg = KinGraph::from_dot(include_str!("../incest.dot"))?;
+    assert!(kg
+        .graph
+        .edges_connecting(Nd::new(1), Nd::new(0))
+        .any(|e| *e.weight() == Kind::RP));
+
+    //relations drawn in one direction get their inverse
+    let dot = r#"
+    digraph {
+        /* ids are read in order of their index */
+        a_1 -> b_0 [label=C];
+        b_0 -> c_2 -> d [label="S"];
+    }"#;
+    let kg = KinGraph::from_dot(dot)?;
+    let names = kg
+        .graph
+        .node_weights()
+        .map(Person::name)
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["b", "a", "c", "d"]);
+    assert!(kg.is_parent(Nd::new(0), Nd::new(1)) && kg.is_child(Nd::new(1), Nd::new(0)));
+    assert_eq!(kg.graph.edge_count(), 6);
+    assert!(KinGraph::from_dot("digraph { a -> b [label=X] }").is_err());
+    assert!(KinGraph::from_dot("digraph { a -> b [label=P]").is_err());
+    //a person can't be related to themselves
+    let err = KinGraph::from_dot("digraph {\n a -> b [label=P];\n a -> a [label=S] }")
+        .err()
+        .unwrap();
+    assert!(err.to_string().starts_with("Line 3"), "{}", err);
+
+    //default attributes apply to the nodes and edges after them, within their subgraph
+    let dot = r#"
+    digraph {
+        node [shape=box];
+        edge [label=P];
+        a_0 -> b_1;
+        subgraph {
+            node [shape=ellipse];
+            edge [label=S];
+            c_2 -> d_3;
+        }
+        b_1 -> e_4;
+        f_5;
+    }"#;
+    let kg = KinGraph::from_dot(dot)?;
+    let sexes = kg
+        .graph
+        .node_weights()
+        .map(Person::get_sex)
+        .collect::<Vec<_>>();
+    use Sex::{Female, Male};
+    assert_eq!(sexes, [Male, Male, Female, Female, Male, Male]);
+    assert!(kg.is_parent(Nd::new(0), Nd::new(1)) && kg.is_parent(Nd::new(1), Nd::new(4)));
+    assert!(kg
+        .graph
+        .edges_connecting(Nd::new(2), Nd::new(3))
+        .any(|e| *e.weight() == Kind::Sibling));
+    assert_eq!(kg.graph.edge_count(), 6);
+
+    //what the exporter writes reads back the same
+    let mut kg = KinGraph::new();
+    let dsl = r#"
+    Dad M SPOUSE Mom F
+    Kid M CHILD Dad M
+    Kid M CHILD Mom F
+    Ward F WARD Mom F
+    Kid M ATTR born date(abt 1850)
+    Kid M ATTR nick "the \"kid\""
+    Kid M REL_ATTR CHILD Dad M note estranged
+    "#;
+    parse_relations_from_dsl(dsl, &mut kg)?;
+    let opts = DotOptions::default();
+    let dot = kg.to_dot(&opts);
+    let read = KinGraph::from_dot(&dot)?;
+    assert_eq!(read.to_dot(&opts), dot);
+    assert_eq!(read.px(1).get_sex(), Sex::Female);
+    //line breaks within a name or value are not read as new lines of the label
+    let kid = kg.px(2).clone();
+    kg.np_with_name(Sex::Male, "Uncle\nBob".into());
+    kg.set_attr(&kid, "note", "two\nlines".into())?;
+    let dot = kg.to_dot(&opts);
+    assert!(dot.contains(r#"n4 [label="Uncle\lBob""#), "{}", dot);
+    let read = KinGraph::from_dot(&dot)?;
+    assert_eq!(read.px(4).name, "Uncle\nBob");
+    assert_eq!(read.px(2).attr("note"), Some(&"two\nlines".into()));
+    assert_eq!(read.to_dot(&opts), dot);
+    Ok(())
+}
+#[test]
+fn mermaid_and_plantuml() -> Result<()> {
+    let mut kg = KinGraph::new();
+    let dsl = r#"
+    Grandpa M PARENT Dad M
+    Dad M SPOUSE Mom F
+    Dad M PARENT Kid M
+    Mom F PARENT Kid M
+    Mom F ADOPTIVE_PARENT Ward F
+    Kid M BORN 1901
+    Kid M NAME Kid /Smith/
+    "#;
+    parse_relations_from_dsl(dsl, &mut kg)?;
+    kg.np_with_name(Sex::Male, "\"Doc\" Stranger".into());
+    kg.np_with_name(Sex::Female, "Anne\nMarie".into());
+
+    let mermaid = kg.to_mermaid(&DiagramOptions::default())?;
+    assert!(mermaid.starts_with("flowchart TD\n    n0[\"Grandpa\"]\n"));
+    assert!(mermaid.contains("    n2([\"Mom\"])\n"));
+    assert!(mermaid.contains("    n0 --> n1\n"));
+    assert!(mermaid.contains("    n1 === n2\n"));
+    assert!(mermaid.contains("    n2 -.->|adoptive| n4\n"));
+    assert!(!mermaid.contains("n3 --> n1"));
+    assert!(mermaid.contains("    n3[\"Kid Smith\"]\n"));
+    assert!(mermaid.contains("    n5[\"#quot;Doc#quot; Stranger\"]\n"));
+    //a line break in a name breaks the label rather than the diagram
+    assert!(mermaid.contains("    n6([\"Anne<br>Marie\"])\n"));
+    assert!(!mermaid.contains("classDef"));
+
+    //the parents of the kid, named by how they are related to the kid
+    let opts = DiagramOptions {
+        focus: Some(3),
+        depth: Some(1),
+        dates: true,
+        relationships: Some(QueryOptions::default()),
+    };
+    let mermaid = kg.to_mermaid(&opts)?;
+    assert!(mermaid.contains("    n1[\"Dad<br>father\"]\n"));
+    assert!(mermaid.contains("    n3[\"Kid Smith<br>b. 1901\"]\n"));
+    assert!(!mermaid.contains("Grandpa") && !mermaid.contains("Stranger"));
+    assert!(mermaid.contains("    class n3 focus\n"));
+    let plantuml = kg.to_plantuml(&opts)?;
+    assert!(plantuml.starts_with("@startuml\nrectangle \"Dad\\nfather\" as n1\n"));
+    assert!(plantuml.contains("usecase \"Mom\\nmother\" as n2\n"));
+    assert!(plantuml.contains("rectangle \"Kid Smith\\nb. 1901\" as n3 #line.bold\n"));
+    assert!(plantuml.contains("n1 == n2\nn1 --> n3\nn1 -- n2\n"));
+    assert!(plantuml.ends_with("n2 --> n3\n@enduml\n"));
+    let plantuml = kg.to_plantuml(&DiagramOptions::default())?;
+    assert!(plantuml.contains("n2 ..> n4 : adoptive\n"));
+    //a depth without a focus to count from draws everyone
+    let depth_only = DiagramOptions {
+        depth: Some(1),
+        ..Default::default()
+    };
+    assert_eq!(kg.to_plantuml(&depth_only)?, plantuml);
+    assert!(plantuml.contains("usecase \"Anne\\nMarie\" as n6\n"));
+    assert!(kg
+        .to_plantuml(&DiagramOptions {
+            focus: Some(9),
+            ..Default::default()
+        })
+        .is_err());
+    Ok(())
+}
+#[test]
+fn network_exports() -> Result<()> {
+    let mut kg = KinGraph::new();
+    let dsl = r#"
+    Dad M SPOUSE Mom F
+    Dad M PARENT Kid F
+    Mom F PARENT Kid F
+    Kid F BORN 1901
+    Kid F ATTR age 12
+    Kid F ATTR id k1
+    Dad M ATTR age unknown
+    Mom F ATTR alive true
+    Kid F REL_ATTR CHILD Dad M note "estranged & <far>"
+    "#;
+    parse_relations_from_dsl(dsl, &mut kg)?;
+    let opts = NetworkOptions { generations: true };
+
+    let graphml = kg.to_graphml(&opts);
+    assert!(graphml.contains(r#"<key id="n5" for="node" attr.name="generation" attr.type="int"/>"#));
+    //a key with both numbers and strings is a string one
+    assert!(graphml.contains(r#"<key id="n6" for="node" attr.name="age" attr.type="string"/>"#));
+    assert!(graphml.contains(r#"<key id="n8" for="node" attr.name="attr.id" attr.type="string"/>"#));
+    assert!(graphml.contains(r#"attr.name="alive" attr.type="boolean"/>"#));
+    assert!(graphml.contains(r#"<key id="e0" for="edge" attr.name="kind" attr.type="string"/>"#));
+    assert!(graphml.contains(r#"<data key="n3">1901</data>"#));
+    assert!(graphml.contains(r#"<data key="n5">1</data>"#));
+    assert!(graphml.contains(r#"<edge id="e0" source="n0" target="n1" directed="false">"#));
+    assert!(graphml.contains(r#"<data key="e0">SPOUSE</data>"#));
+    assert!(graphml.contains("estranged &amp; &lt;far&gt;"));
+    assert_eq!(graphml.matches("<edge ").count(), 4);
+
+    let gexf = kg.to_gexf(&opts);
+    assert!(gexf.contains(r#"<gexf xmlns="http://gexf.net/1.3" version="1.3">"#));
+    assert!(gexf.contains(r#"<attribute id="5" title="generation" type="integer"/>"#));
+    assert!(gexf.contains(r#"<node id="2" label="Kid">"#));
+    assert!(gexf.contains(r#"<attvalue for="2" value="false"/>"#));
+    assert!(gexf.contains(r#"<edge id="1" source="0" target="2" label="PARENT">"#));
+    assert!(gexf.contains(r#"type="undirected""#));
+
+    let json: serde_json::Value = serde_json::from_str(&kg.to_cytoscape(&opts)?)?;
+    let kid = &json["elements"]["nodes"][2]["data"];
+    assert_eq!(kid["id"], "n2");
+    assert_eq!(kid["attr.id"], "k1");
+    assert_eq!(kid["age"], "12");
+    assert_eq!(kid["generation"], 1);
+    assert_eq!(json["elements"]["nodes"][1]["data"]["alive"], true);
+    let edges = json["elements"]["edges"].as_array().unwrap();
+    assert_eq!(edges.len(), 4);
+    assert_eq!(edges[1]["data"]["kind"], "PARENT");
+    assert_eq!(edges[1]["data"]["directed"], true);
+    assert_eq!(edges[1]["data"]["note"], "estranged & <far>");
+
+    //generations are only written when asked for
+    let opts = NetworkOptions::default();
+    assert!(!kg.to_graphml(&opts).contains("generation"));
+    assert!(kg.to_cytoscape(&opts)?.contains("\"age\""));
+    Ok(())
+}
+#[test]
+fn svg_charts() -> Result<()> {
+    let mut kg = KinGraph::new();
+    let dsl = r#"
+    Grandpa M PARENT Dad M
+    Grandma F PARENT Dad M
+    Dad M PARENT Younger F
+    Mom F PARENT Younger F
+    Dad M PARENT Elder M
+    Mom F PARENT Elder M
+    Younger F PARENT Grandkid M
+    Younger F BORN 1901
+    Elder M BORN 1899
+    Elder M ATTR trade "smith & sons"
+    Grandkid M NAME Bartholomew Maximilian /Featherstonehaugh/
+    "#;
+    parse_relations_from_dsl(dsl, &mut kg)?;
+    let opts = ChartOptions::default();
+    //where the text of a line is drawn
+    let at = |svg: &str, text: &str| {
+        let line = svg
+            .lines()
+            .find(|l| l.contains(&format!(">{}<", text)))
+            .unwrap();
+        let coordinate = |name: &str| {
+            let start = line.find(&format!(" {}=\"", name)).unwrap() + name.len() + 3;
+            let len = line[start..].find('"').unwrap();
+            line[start..start + len].parse::<f64>().unwrap()
+        };
+        (coordinate("x"), coordinate("y"))
+    };
+
+    //ancestors from left to right, fathers above mothers
+    let svg = kg.pedigree_svg(kg.px(3), &opts)?;
+    assert!(
+        svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"580\" height=\"170\"")
+    );
+    assert_eq!(svg.matches("<rect").count(), 5);
+    assert_eq!(svg.matches("<path").count(), 4);
+    assert!(svg.contains(r##"<rect x="10" y="77.5" width="160" height="42" rx="3" fill="#f6dbe8" stroke="#333" stroke-width="2"/>"##));
+    assert!(svg.contains(r#"<text x="90" y="110.5" text-anchor="middle">b. 1901</text>"#));
+    assert!(svg.contains(r#"<path d="M 170 98.5 L 190 98.5 L 190 58 L 210 58"/>"#));
+    assert!(at(&svg, "Grandpa").1 < at(&svg, "Grandma").1);
+    assert!(at(&svg, "Grandma").1 < at(&svg, "Mom").1);
+    assert!(at(&svg, "Younger").0 < at(&svg, "Dad").0);
+    assert!(!svg.contains("Elder"));
+
+    //descendants down the page, from the first born
+    let opts = ChartOptions {
+        attributes: vec!["trade".into()],
+        generations: 2,
+        ..Default::default()
+    };
+    let svg = kg.descendants_svg(kg.px(0), &opts)?;
+    assert_eq!(svg.matches("<rect").count(), 4);
+    assert!(at(&svg, "Grandpa").1 < at(&svg, "Dad").1);
+    assert!(at(&svg, "Elder").0 < at(&svg, "Younger").0);
+    assert_eq!(at(&svg, "Elder").1, at(&svg, "Younger").1);
+    assert!(svg.contains(">trade: smith &amp; sons<"));
+    assert!(!svg.contains("Bartholomew"));
+
+    //ancestors above and descendants below, with long lines cut short
+    let svg = kg.hourglass_svg(kg.px(1), &ChartOptions::default())?;
+    assert_eq!(svg.matches("<rect").count(), 6);
+    assert_eq!(svg.matches("<path").count(), 5);
+    assert!(at(&svg, "Grandpa").1 < at(&svg, "Dad").1);
+    assert!(at(&svg, "Dad").1 < at(&svg, "Younger").1);
+    //the focus person is centered on both their parents and their children
+    let middle = |a: (f64, f64), b: (f64, f64)| (a.0 + b.0) / 2.0;
+    assert_eq!(
+        middle(at(&svg, "Grandpa"), at(&svg, "Grandma")),
+        at(&svg, "Dad").0
+    );
+    assert_eq!(
+        middle(at(&svg, "Elder"), at(&svg, "Younger")),
+        at(&svg, "Dad").0
+    );
+    assert!(svg.contains(">Bartholomew Maximil…<"));
+
+    //a person who is not in the graph has no chart
+    let stranger = Person::new(Sex::Male);
+    assert!(kg.pedigree_svg(&stranger, &opts).is_err());
+    assert!(kg.descendants_svg(&stranger, &opts).is_err());
+    assert!(kg.hourglass_svg(&stranger, &opts).is_err());
+    Ok(())
+}
+#[test]
+fn family_layout() -> Result<()> {
+    let mut kg = KinGraph::new();
+    let dsl = r#"
+    Ann F PARENT Wife F
+    Bob M PARENT Wife F
+    Ann F RP Bob M
+    Carl M PARENT Husband M
+    Dora F PARENT Husband M
+    Carl M RP Dora F
+    Carl M PARENT Uncle M
+    Dora F PARENT Uncle M
+    Husband M SPOUSE Wife F
+    Husband M PARENT Kid F
+    Wife F PARENT Kid F
+    Husband M PARENT Other M
+    Wife F PARENT Other M
+    "#;
+    parse_relations_from_dsl(dsl, &mut kg)?;
+    let person = |kg: &KinGraph, name: &str| kg.find_by_name(name)[0].clone();
+    let kid = person(&kg, "Kid");
+    assert_eq!(kg.location(&kid), None);
+    let layout = kg.layout().clone();
+    let at = |name: &str| layout[&kg.idx(&person(&kg, name)).unwrap()];
+    assert_eq!(
+        ["Ann", "Wife", "Kid"].map(|name| at(name).depth()),
+        [0, 1, 2]
+    );
+    //couples are side by side, men first unless their families are the other way round
+    assert_eq!(at("Ann").width() - at("Bob").width(), 2);
+    assert_eq!(at("Dora").width() - at("Carl").width(), 2);
+    assert!(at("Ann").width() < at("Carl").width());
+    assert_eq!(at("Husband").width() - at("Wife").width(), 2);
+    //children are centered under their parents
+    let middle = |a: &str, b: &str| at(a).width() + at(b).width();
+    assert_eq!(middle("Kid", "Other"), middle("Husband", "Wife"));
+    assert_eq!(middle("Husband", "Uncle"), middle("Carl", "Dora"));
+    let kid_at = at("Kid");
+    assert_eq!(kg.location(&kid), Some(kid_at));
+    //and kept, for handles from before, when only the details of a person change
+    kg.set_attr(&kid, "note", "youngest".into())?;
+    kg.set_birth(&kid, Some("abt 1950".parse()?))?;
+    kg.set_name(&kid, "Kit /Smith/".parse()?)?;
+    assert_eq!(kg.location(&kid), Some(kid_at));
+    assert_eq!(kg.location(kg.find_by_name("Kit")[0]), Some(kid_at));
+
+    //the layout is dropped when the graph changes
+    let (husband, uncle) = (person(&kg, "Husband"), person(&kg, "Uncle"));
+    kg.add_relation(&husband, &uncle, Kind::Sibling)?;
+    assert_eq!(kg.location(&kid), None);
+    Ok(())
+}
+#[test]
+fn pedigree_collapse_layout() -> Result<()> {
+    let mut kg = KinGraph::new();
+    //the parents of Kid are an uncle and his niece, a generation apart
+    let dsl = r#"
+    Gramps M PARENT Uncle M
+    Gramps M PARENT Mom F
+    Uncle M SIBLING Mom F
+    Uncle M RP Niece F
+    Mom F PARENT Niece F
+    Uncle M PARENT Kid M
+    Niece F PARENT Kid M
+    "#;
+    parse_relations_from_dsl(dsl, &mut kg)?;
+    let layout = kg.layout().clone();
+    let at = |name: &str| layout[&kg.idx(kg.find_by_name(name)[0]).unwrap()];
+    //every child is in a row below each of their parents
+    for n in kg.graph.node_indices() {
+        for c in kg.graph.node_indices().filter(|c| kg.is_parent(n, *c)) {
+            assert!(layout[&c].depth() > layout[&n].depth());
+        }
+    }
+    assert_eq!(
+        ["Gramps", "Mom", "Niece", "Kid"].map(|name| at(name).depth()),
+        [0, 1, 2, 3]
+    );
+    //the uncle moves down beside his partner, but his sister can't without going below her child
+    assert_eq!(at("Uncle").depth(), 2);
+    assert_eq!((at("Uncle").width() - at("Niece").width()).abs(), 2);
+    assert_eq!(
+        at("Kid").width() * 2,
+        at("Uncle").width() + at("Niece").width()
+    );
+    Ok(())
+}
+#[test]
+fn fan_charts() -> Result<()> {
+    let mut kg = KinGraph::new();
+    //the parents of Kid are cousins, so Great is reached twice
+    let dsl = r#"
+    Great M PARENT Uncle M
+    Great M PARENT Aunt F
+    Uncle M PARENT Dad M
+    Aunt F PARENT Mom F
+    Dad M PARENT Kid F
+    Mom F PARENT Kid F
+    Kid F BORN 1950
+    "#;
+    parse_relations_from_dsl(dsl, &mut kg)?;
+    let person = |name: &str| kg.find_by_name(name)[0].clone();
+    let index = |name: &str| Some(kg.idx(&person(name)).unwrap().index());
+    let opts = FanOptions {
+        generations: 3,
+        ..FanOptions::default()
+    };
+
+    let fan = kg.fan_chart(&person("Kid"), &opts)?;
+    let places = fan
+        .iter()
+        .map(|s| (s.generation, s.position, s.person))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        places,
+        [
+            (0, 0, index("Kid")),
+            (1, 0, index("Dad")),
+            (1, 1, index("Mom")),
+            (2, 0, index("Uncle")),
+            (2, 1, None),
+            (2, 2, None),
+            (2, 3, index("Aunt")),
+            (3, 0, index("Great")),
+            (3, 1, None),
+            (3, 6, index("Great")),
+            (3, 7, None),
+        ]
+    );
+    assert_eq!((fan[1].start_angle, fan[1].end_angle), (-180.0, 0.0));
+    assert_eq!((fan[9].start_angle, fan[9].end_angle), (90.0, 135.0));
+    assert_eq!((fan[9].inner_radius, fan[9].outer_radius), (220.0, 300.0));
+    assert_eq!(fan[9].repeat_of, Some(7));
+    assert!(fan.iter().all(|s| s.label_rotation.abs() <= 90.0));
+    //the label of the focus person is in the middle, and the others halfway across their ring
+    assert_eq!((fan[0].label_x, fan[0].label_y), (0.0, 0.0));
+    assert!((fan[1].label_x + 100.0).abs() < 1e-9 && fan[1].label_y.abs() < 1e-9);
+
+    let svg = kg.fan_svg(&person("Kid"), &opts)?;
+    assert!(
+        svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"620\" height=\"540\"")
+    );
+    assert!(svg.contains(
+        r##"<circle cx="0" cy="0" r="60" fill="#f6dbe8" stroke="#333" stroke-width="2"/>"##
+    ));
+    assert!(svg.contains(r##"<path d="M -212.1 212.1 A 300 300 0 0 1 -300 0 L -220 0 A 220 220 0 0 0 -155.6 155.6 Z" fill="#f4f4f4" stroke="#333" stroke-dasharray="4 2"/>"##));
+    assert_eq!(svg.matches("<path").count(), 10);
+    assert_eq!(svg.matches("fill-opacity=\"0.5\"").count(), 1);
+    assert!(svg.contains(">b. 1950</text>"));
+    //a half fan only takes the top half of the circle
+    let half = FanOptions {
+        sweep: 180.0,
+        ..opts.clone()
+    };
+    let svg = kg.fan_svg(&person("Kid"), &half)?;
+    assert!(svg.contains("width=\"620\" height=\"240\""));
+
+    //each child of a person takes as much of their sector as the lines they lead to
+    let radial = kg.radial_chart(&person("Great"), &opts)?;
+    let places = radial
+        .iter()
+        .map(|s| (s.person, s.start_angle, s.end_angle, s.repeat_of))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        places,
+        [
+            (index("Great"), -180.0, 180.0, None),
+            (index("Uncle"), -180.0, 0.0, None),
+            (index("Aunt"), 0.0, 180.0, None),
+            (index("Dad"), -180.0, 0.0, None),
+            (index("Mom"), 0.0, 180.0, None),
+            (index("Kid"), -180.0, 0.0, None),
+            (index("Kid"), 0.0, 180.0, Some(5)),
+        ]
+    );
+    assert_eq!(radial[6].position, 1);
+
+    //a person who is not in the graph has no chart
+    let stranger = Person::new(Sex::Female);
+    assert!(kg.fan_chart(&stranger, &opts).is_err());
+    assert!(kg.radial_chart(&stranger, &opts).is_err());
+    assert!(kg.fan_svg(&stranger, &opts).is_err());
+    assert!(kg.radial_svg(&stranger, &opts).is_err());
+    Ok(())
+}
@@ -0,0 +1,126 @@
+//! A tree of XML elements, as read from and written to the XML formats (Gramps, GraphML, GEXF
+//! and SVG).
+use anyhow::{bail, Result};
+use quick_xml::{escape::escape, events::BytesStart, events::Event, Reader};
+
+//an element of an XML document, with the text directly inside it
+#[derive(Default)]
+pub(crate) struct Element {
+    pub(crate) name: String,
+    pub(crate) attrs: Vec<(String, String)>,
+    pub(crate) children: Vec<Element>,
+    pub(crate) text: String,
+}
+impl Element {
+    pub(crate) fn new(name: &str) -> Self {
+        Element {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+    pub(crate) fn text(name: &str, text: &str) -> Self {
+        Element {
+            text: text.to_string(),
+            ..Element::new(name)
+        }
+    }
+    pub(crate) fn with_attr(mut self, key: &str, value: impl Into<String>) -> Self {
+        self.attrs.push((key.to_string(), value.into()));
+        self
+    }
+    pub(crate) fn with(mut self, child: Element) -> Self {
+        self.children.push(child);
+        self
+    }
+    pub(crate) fn attr(&self, key: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+    pub(crate) fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|c| c.name == name)
+    }
+    pub(crate) fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> + 'a {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+    //the elements of the given name in the first child of the section name, such as people/person
+    pub(crate) fn section<'a>(
+        &'a self,
+        section: &str,
+        name: &'a str,
+    ) -> impl Iterator<Item = &'a Element> {
+        self.child(section)
+            .into_iter()
+            .flat_map(move |s| s.children(name))
+    }
+    //the text of a child, if it has any
+    pub(crate) fn child_text(&self, name: &str) -> Option<&str> {
+        Some(self.child(name)?.text.as_str()).filter(|t| !t.is_empty())
+    }
+    pub(crate) fn write(&self, out: &mut String, depth: usize) {
+        let indent = "  ".repeat(depth);
+        out.push_str(&format!("{}<{}", indent, self.name));
+        for (key, value) in &self.attrs {
+            out.push_str(&format!(" {}=\"{}\"", key, escape(value)));
+        }
+        if self.children.is_empty() && self.text.is_empty() {
+            out.push_str("/>\n");
+        } else if self.children.is_empty() {
+            out.push_str(&format!(">{}</{}>\n", escape(&self.text), self.name));
+        } else {
+            out.push_str(">\n");
+            for child in &self.children {
+                child.write(out, depth + 1);
+            }
+            out.push_str(&format!("{}</{}>\n", indent, self.name));
+        }
+    }
+}
+
+//reads the elements of an XML document, without their namespaces, as the children of an unnamed
+//element
+pub(crate) fn parse(text: &str) -> Result<Element> {
+    let element_of = |e: &BytesStart| -> Result<Element> {
+        let mut element = Element::new(&String::from_utf8_lossy(e.local_name().as_ref()));
+        for attr in e.attributes() {
+            let attr = attr?;
+            let key = String::from_utf8_lossy(attr.key.local_name().as_ref()).into_owned();
+            element
+                .attrs
+                .push((key, attr.unescape_value()?.into_owned()));
+        }
+        Ok(element)
+    };
+    let mut reader = Reader::from_str(text);
+    reader.trim_text(true);
+    let mut open = vec![Element::default()];
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => open.push(element_of(&e)?),
+            Event::Empty(e) => {
+                let element = element_of(&e)?;
+                open.last_mut().unwrap().children.push(element);
+            }
+            Event::End(_) => {
+                let element = open.pop().unwrap();
+                match open.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => bail!("Unexpected end of element {:?}", element.name),
+                }
+            }
+            Event::Text(t) => open.last_mut().unwrap().text.push_str(&t.unescape()?),
+            Event::CData(t) => open
+                .last_mut()
+                .unwrap()
+                .text
+                .push_str(&String::from_utf8_lossy(&t)),
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    if open.len() != 1 {
+        bail!("The document ends inside of an element");
+    }
+    Ok(open.pop().unwrap())
+}